@@ -1,5 +1,12 @@
-use crate::error::Error as RetryError;
+use crate::error::{Error as RetryError, NotReady, RetryClass};
+use pin_project::pin_project;
 use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+use std::time::Duration;
+use tokio::time::Instant;
 
 /// An action can be run multiple times and produces a future.
 pub trait Action {
@@ -11,6 +18,10 @@ pub trait Action {
     type Error;
 
     fn run(&mut self) -> Self::Future;
+
+    /// Called by the retry loop with the back-off delay it is about to sleep before
+    /// running the action again. Does nothing by default.
+    fn before_retry(&mut self, _delay: Duration) {}
 }
 
 impl<R, E, T: Future<Output = Result<R, RetryError<E>>>, F: FnMut() -> T> Action for F {
@@ -22,3 +33,682 @@ impl<R, E, T: Future<Output = Result<R, RetryError<E>>>, F: FnMut() -> T> Action
         self()
     }
 }
+
+/// An action resolving to an `Option`, retried while it yields `None`,
+/// created by [`Retry::spawn_some`](crate::Retry::spawn_some).
+pub struct OptionAction<F>(pub(crate) F);
+
+impl<T, Fut: Future<Output = Option<T>>, F: FnMut() -> Fut> Action for OptionAction<F> {
+    type Item = T;
+    type Error = NotReady;
+    type Future = OptionFuture<Fut>;
+
+    fn run(&mut self) -> Self::Future {
+        OptionFuture { future: (self.0)() }
+    }
+}
+
+/// Future mapping `None` into a transient [`NotReady`] error.
+#[pin_project]
+pub struct OptionFuture<Fut> {
+    #[pin]
+    future: Fut,
+}
+
+impl<T, Fut: Future<Output = Option<T>>> Future for OptionFuture<Fut> {
+    type Output = Result<T, RetryError<NotReady>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        match ready!(self.project().future.poll(cx)) {
+            Some(item) => Poll::Ready(Ok(item)),
+            None => Poll::Ready(Err(RetryError::transient(NotReady))),
+        }
+    }
+}
+
+/// Details about the attempt a [`DeadlineAction`] is about to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AttemptContext {
+    /// The number of the attempt, starting at `1`.
+    pub attempt: u32,
+    /// The time left before the overall deadline, e.g. to fit the client timeout of
+    /// the attempt within it.
+    pub remaining: Duration,
+}
+
+/// An action receiving the time left before an overall deadline on every attempt,
+/// created by [`Retry::spawn_with_time_left`](crate::Retry::spawn_with_time_left).
+#[derive(Debug, Clone)]
+pub struct DeadlineAction<F> {
+    f: F,
+    deadline: Instant,
+    attempt: u32,
+}
+
+impl<F> DeadlineAction<F> {
+    pub(crate) fn new(f: F, deadline: Instant) -> Self {
+        DeadlineAction {
+            f,
+            deadline,
+            attempt: 0,
+        }
+    }
+}
+
+impl<R, E, Fut, F> Action for DeadlineAction<F>
+where
+    Fut: Future<Output = Result<R, RetryError<E>>>,
+    F: FnMut(AttemptContext) -> Fut,
+{
+    type Item = R;
+    type Error = E;
+    type Future = Fut;
+
+    fn run(&mut self) -> Self::Future {
+        self.attempt = self.attempt.saturating_add(1);
+        (self.f)(AttemptContext {
+            attempt: self.attempt,
+            remaining: self.deadline.saturating_duration_since(Instant::now()),
+        })
+    }
+}
+
+/// Details about the attempt a [`ContextAction`] is about to run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct RetryContext {
+    /// The number of the attempt, starting at `1`.
+    pub attempt: u32,
+    /// The time elapsed since the first attempt started.
+    pub elapsed: Duration,
+    /// The back-off delay before this attempt, or `None` for the first one.
+    pub last_delay: Option<Duration>,
+}
+
+/// An action receiving the [`RetryContext`] of every attempt,
+/// created by [`Retry::spawn_with_context`](crate::Retry::spawn_with_context).
+#[derive(Debug, Clone)]
+pub struct ContextAction<F> {
+    f: F,
+    started: Option<Instant>,
+    attempt: u32,
+    last_delay: Option<Duration>,
+}
+
+impl<F> ContextAction<F> {
+    pub(crate) fn new(f: F) -> Self {
+        ContextAction {
+            f,
+            started: None,
+            attempt: 0,
+            last_delay: None,
+        }
+    }
+}
+
+impl<R, E, Fut, F> Action for ContextAction<F>
+where
+    Fut: Future<Output = Result<R, RetryError<E>>>,
+    F: FnMut(RetryContext) -> Fut,
+{
+    type Item = R;
+    type Error = E;
+    type Future = Fut;
+
+    fn run(&mut self) -> Self::Future {
+        let now = Instant::now();
+        let started = *self.started.get_or_insert(now);
+        self.attempt = self.attempt.saturating_add(1);
+        (self.f)(RetryContext {
+            attempt: self.attempt,
+            elapsed: now.saturating_duration_since(started),
+            last_delay: self.last_delay.take(),
+        })
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.last_delay = Some(delay);
+    }
+}
+
+/// The outcome of a step of an action making incremental progress,
+/// see [`Retry::spawn_fold`](crate::Retry::spawn_fold).
+#[derive(Debug, PartialEq)]
+pub enum Step<T, Acc, E> {
+    /// The action is complete.
+    Done(T),
+    /// The step made progress. The next step runs right away from the new accumulator.
+    Progress(Acc),
+    /// The step failed. A transient error is retried from the last accumulator.
+    Err(RetryError<E>),
+}
+
+/// An action folding the progress of its steps into an accumulator kept across
+/// attempts, created by [`Retry::spawn_fold`](crate::Retry::spawn_fold).
+pub struct FoldAction<Acc, F> {
+    acc: Arc<Mutex<Acc>>,
+    step: Arc<Mutex<F>>,
+}
+
+impl<Acc, F> FoldAction<Acc, F> {
+    pub(crate) fn new(init: Acc, step: F) -> Self {
+        FoldAction {
+            acc: Arc::new(Mutex::new(init)),
+            step: Arc::new(Mutex::new(step)),
+        }
+    }
+}
+
+impl<T, Acc, E, Fut, F> Action for FoldAction<Acc, F>
+where
+    Acc: Clone,
+    Fut: Future<Output = Step<T, Acc, E>>,
+    F: FnMut(Acc) -> Fut,
+{
+    type Item = T;
+    type Error = E;
+    type Future = FoldFuture<Acc, F, Fut>;
+
+    fn run(&mut self) -> Self::Future {
+        let future = next_step(&self.step, &self.acc);
+        FoldFuture {
+            acc: Arc::clone(&self.acc),
+            step: Arc::clone(&self.step),
+            future,
+        }
+    }
+}
+
+/// Runs the step function on a clone of the current accumulator.
+fn next_step<Acc: Clone, F: FnMut(Acc) -> Fut, Fut>(step: &Mutex<F>, acc: &Mutex<Acc>) -> Fut {
+    let acc = acc
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    (step.lock().unwrap_or_else(|poisoned| poisoned.into_inner()))(acc)
+}
+
+/// Future running the steps of a [`FoldAction`] until one completes or fails.
+#[pin_project]
+pub struct FoldFuture<Acc, F, Fut> {
+    acc: Arc<Mutex<Acc>>,
+    step: Arc<Mutex<F>>,
+    #[pin]
+    future: Fut,
+}
+
+impl<T, Acc, E, Fut, F> Future for FoldFuture<Acc, F, Fut>
+where
+    Acc: Clone,
+    Fut: Future<Output = Step<T, Acc, E>>,
+    F: FnMut(Acc) -> Fut,
+{
+    type Output = Result<T, RetryError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            match ready!(this.future.as_mut().poll(cx)) {
+                Step::Done(item) => return Poll::Ready(Ok(item)),
+                Step::Err(err) => return Poll::Ready(Err(err)),
+                Step::Progress(acc) => {
+                    *this
+                        .acc
+                        .lock()
+                        .unwrap_or_else(|poisoned| poisoned.into_inner()) = acc;
+                    this.future.set(next_step(this.step, this.acc));
+                }
+            }
+        }
+    }
+}
+
+/// An action resolving to a plain `Result`, whose errors are classified by a closure,
+/// created by [`Retry::spawn_map`](crate::Retry::spawn_map).
+#[derive(Debug, Clone)]
+pub struct ClassifyAction<F, C> {
+    action: F,
+    classifier: C,
+}
+
+impl<F, C> ClassifyAction<F, C> {
+    pub(crate) const fn new(action: F, classifier: C) -> Self {
+        ClassifyAction { action, classifier }
+    }
+}
+
+impl<T, E, Fut, F, C> Action for ClassifyAction<F, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    F: FnMut() -> Fut,
+    C: Fn(&E) -> RetryClass + Clone,
+{
+    type Item = T;
+    type Error = E;
+    type Future = ClassifyFuture<Fut, C>;
+
+    fn run(&mut self) -> Self::Future {
+        ClassifyFuture {
+            future: (self.action)(),
+            classifier: self.classifier.clone(),
+        }
+    }
+}
+
+/// Future classifying the error of a plain `Result` into a [`RetryError`].
+#[pin_project]
+pub struct ClassifyFuture<Fut, C> {
+    #[pin]
+    future: Fut,
+    classifier: C,
+}
+
+impl<T, E, Fut, C> Future for ClassifyFuture<Fut, C>
+where
+    Fut: Future<Output = Result<T, E>>,
+    C: Fn(&E) -> RetryClass,
+{
+    type Output = Result<T, RetryError<E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        match ready!(this.future.poll(cx)) {
+            Ok(item) => Poll::Ready(Ok(item)),
+            Err(err) => Poll::Ready(Err((this.classifier)(&err).classify(err))),
+        }
+    }
+}
+
+/// Converts a closure taking arguments into an [`Action`], cloning `args` into
+/// every attempt.
+///
+/// Replaces the usual double clone of moving a clone into the closure and cloning it
+/// again into every `async move` block:
+///
+/// ```rust,no_run
+/// use tokio_retry2::{action, Retry, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// async fn send(url: String, body: Vec<u8>) -> Result<(), RetryError<std::io::Error>> {
+///     Ok(())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let url = String::from("https://example.com");
+/// let body = vec![1, 2, 3];
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// Retry::spawn(
+///     strategy,
+///     action::with_args((url, body), |(url, body)| send(url, body)),
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn with_args<T, F, Fut>(args: T, f: F) -> WithArgs<T, F>
+where
+    T: Clone,
+    F: FnMut(T) -> Fut,
+{
+    WithArgs { args, f }
+}
+
+/// Same as [`with_args`], but passes a shared reference to `args` to every attempt
+/// instead of a clone, so the arguments don't need to be `Clone`.
+pub fn with_args_ref<'a, T, F, Fut>(args: &'a T, f: F) -> WithArgs<&'a T, F>
+where
+    T: ?Sized,
+    F: FnMut(&'a T) -> Fut,
+{
+    WithArgs { args, f }
+}
+
+/// An action passing its arguments to every attempt,
+/// created by [`with_args`] or [`with_args_ref`].
+#[derive(Debug, Clone)]
+pub struct WithArgs<T, F> {
+    args: T,
+    f: F,
+}
+
+impl<T, F, Fut, R, E> Action for WithArgs<T, F>
+where
+    T: Clone,
+    F: FnMut(T) -> Fut,
+    Fut: Future<Output = Result<R, RetryError<E>>>,
+{
+    type Item = R;
+    type Error = E;
+    type Future = Fut;
+
+    fn run(&mut self) -> Self::Future {
+        (self.f)(self.args.clone())
+    }
+}
+
+/// Wraps every attempt of a retried action, e.g. to apply a timeout, hold a semaphore
+/// permit or inject faults, see [`layer`].
+///
+/// Closures taking the attempt future and returning a future with the same output
+/// implement it.
+pub trait RetryMiddleware<F: Future> {
+    /// The future wrapping an attempt.
+    type Future: Future<Output = F::Output>;
+
+    /// Wraps `attempt`, the future of a single attempt.
+    fn around(&self, attempt: F) -> Self::Future;
+}
+
+impl<F, G, Fut> RetryMiddleware<F> for G
+where
+    F: Future,
+    G: Fn(F) -> Fut,
+    Fut: Future<Output = F::Output>,
+{
+    type Future = Fut;
+
+    fn around(&self, attempt: F) -> Self::Future {
+        self(attempt)
+    }
+}
+
+/// Wraps every attempt of `action` with `middleware`. Layers stack by calling `layer`
+/// again, the last one added running outermost.
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+/// use tokio_retry2::{action, Retry, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// async fn fetch() -> Result<u64, RetryError<&'static str>> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), &'static str> {
+/// let with_timeout = |attempt| async move {
+///     tokio::time::timeout(Duration::from_secs(1), attempt)
+///         .await
+///         .unwrap_or(RetryError::to_transient("timed out"))
+/// };
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// Retry::spawn(strategy, action::layer(fetch, with_timeout)).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn layer<A, M>(action: A, middleware: M) -> Layered<A, M>
+where
+    A: Action,
+    M: RetryMiddleware<A::Future>,
+{
+    Layered { action, middleware }
+}
+
+/// An action wrapping every attempt with a [`RetryMiddleware`], created by [`layer`].
+#[derive(Debug, Clone)]
+pub struct Layered<A, M> {
+    action: A,
+    middleware: M,
+}
+
+impl<A, M> Action for Layered<A, M>
+where
+    A: Action,
+    M: RetryMiddleware<A::Future>,
+{
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = M::Future;
+
+    fn run(&mut self) -> Self::Future {
+        self.middleware.around(self.action.run())
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.action.before_retry(delay);
+    }
+}
+
+/// An action counting its attempts into `attempts`, for the hooks of a
+/// [`WithHooks`](crate::WithHooks) future.
+pub(crate) struct CountedAction<A> {
+    pub(crate) action: A,
+    pub(crate) attempts: Arc<AtomicU32>,
+}
+
+impl<A: Action> Action for CountedAction<A> {
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = A::Future;
+
+    fn run(&mut self) -> Self::Future {
+        self.attempts.fetch_add(1, Ordering::Relaxed);
+        self.action.run()
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.action.before_retry(delay);
+    }
+}
+
+/// Converts a `tower::Service` and a request builder into an [`Action`].
+///
+/// Every attempt clones `service`, waits for it to be ready, and calls it with a
+/// fresh request built by `request_factory`. All service errors are transient.
+#[cfg(feature = "tower")]
+pub fn from_service<S, Req, F>(service: S, request_factory: F) -> ServiceAction<S, F>
+where
+    S: tower_service::Service<Req> + Clone,
+    F: FnMut() -> Req,
+{
+    ServiceAction {
+        service,
+        request_factory,
+    }
+}
+
+/// An action calling a `tower::Service`, created by [`from_service`].
+#[cfg(feature = "tower")]
+#[derive(Debug, Clone)]
+pub struct ServiceAction<S, F> {
+    service: S,
+    request_factory: F,
+}
+
+#[cfg(feature = "tower")]
+impl<S, Req, F> Action for ServiceAction<S, F>
+where
+    S: tower_service::Service<Req> + Clone,
+    F: FnMut() -> Req,
+{
+    type Item = S::Response;
+    type Error = S::Error;
+    type Future = ServiceFuture<S, Req>;
+
+    fn run(&mut self) -> Self::Future {
+        ServiceFuture::Pending {
+            service: self.service.clone(),
+            request: Some((self.request_factory)()),
+        }
+    }
+}
+
+/// Future driving a call to a `tower::Service`, created by [`ServiceAction`].
+#[cfg(feature = "tower")]
+#[pin_project(project = ServiceFutureProj)]
+pub enum ServiceFuture<S, Req>
+where
+    S: tower_service::Service<Req>,
+{
+    Pending { service: S, request: Option<Req> },
+    Called(#[pin] S::Future),
+}
+
+#[cfg(feature = "tower")]
+impl<S, Req> Future for ServiceFuture<S, Req>
+where
+    S: tower_service::Service<Req>,
+{
+    type Output = Result<S::Response, RetryError<S::Error>>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        loop {
+            match self.as_mut().project() {
+                ServiceFutureProj::Pending { service, request } => {
+                    if let Err(err) = ready!(service.poll_ready(cx)) {
+                        return Poll::Ready(Err(RetryError::transient(err)));
+                    }
+                    let request = request.take().expect("ServiceFuture polled after call");
+                    let future = service.call(request);
+                    self.set(ServiceFuture::Called(future));
+                }
+                ServiceFutureProj::Called(future) => {
+                    return future.poll(cx).map_err(RetryError::transient);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::strategy::FixedInterval;
+    use crate::Retry;
+
+    #[tokio::test]
+    async fn clones_args_into_every_attempt() {
+        let calls = AtomicUsize::new(0);
+        let res = Retry::spawn(
+            FixedInterval::from_millis(1).take(3),
+            with_args(String::from("payload"), |payload: String| {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    future::ready(RetryError::to_transient("unavailable"))
+                } else {
+                    future::ready(Ok(payload.len()))
+                }
+            }),
+        )
+        .await;
+
+        assert_eq!(res, Ok(7));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn passes_shared_reference_to_every_attempt() {
+        struct NotClone(u64);
+
+        let args = NotClone(42);
+        let res = Retry::spawn(
+            FixedInterval::from_millis(1).take(3),
+            with_args_ref(&args, |args| async move { Ok::<_, RetryError<()>>(args.0) }),
+        )
+        .await;
+
+        assert_eq!(res, Ok(42));
+    }
+
+    #[tokio::test]
+    async fn middleware_wraps_every_attempt() {
+        let calls = AtomicUsize::new(0);
+        let wrapped = AtomicUsize::new(0);
+        let counting = |attempt| {
+            wrapped.fetch_add(1, Ordering::SeqCst);
+            attempt
+        };
+        let res = Retry::spawn(
+            FixedInterval::from_millis(1).take(3),
+            layer(
+                || {
+                    if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                        future::ready(RetryError::to_transient("unavailable"))
+                    } else {
+                        future::ready(Ok(42))
+                    }
+                },
+                counting,
+            ),
+        )
+        .await;
+
+        assert_eq!(res, Ok(42));
+        assert_eq!(wrapped.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn middleware_layers_stack() {
+        let order = std::sync::Mutex::new(Vec::new());
+        let inner = |attempt| {
+            order.lock().unwrap().push("inner");
+            attempt
+        };
+        let outer = |attempt| {
+            order.lock().unwrap().push("outer");
+            attempt
+        };
+        let res = Retry::spawn(
+            FixedInterval::from_millis(1).take(3),
+            layer(
+                layer(|| future::ready(Ok::<_, RetryError<()>>(42)), inner),
+                outer,
+            ),
+        )
+        .await;
+
+        assert_eq!(res, Ok(42));
+        assert_eq!(*order.lock().unwrap(), ["inner", "outer"]);
+    }
+
+    /// Service failing its first `failures` calls, echoing requests afterwards.
+    #[cfg(feature = "tower")]
+    #[derive(Clone)]
+    struct FlakyService {
+        calls: Arc<AtomicUsize>,
+        failures: usize,
+    }
+
+    #[cfg(feature = "tower")]
+    impl tower_service::Service<u64> for FlakyService {
+        type Response = u64;
+        type Error = &'static str;
+        type Future = future::Ready<Result<u64, &'static str>>;
+
+        fn poll_ready(&mut self, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: u64) -> Self::Future {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                future::ready(Err("unavailable"))
+            } else {
+                future::ready(Ok(request))
+            }
+        }
+    }
+
+    #[cfg(feature = "tower")]
+    #[tokio::test]
+    async fn retries_service_calls() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let service = FlakyService {
+            calls: calls.clone(),
+            failures: 2,
+        };
+        let res = Retry::spawn(
+            FixedInterval::from_millis(1).take(3),
+            from_service(service, || 42),
+        )
+        .await;
+
+        assert_eq!(res, Ok(42));
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
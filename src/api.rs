@@ -0,0 +1,227 @@
+//! Mapping of the outcome of a retry loop that gave up to API error shapes.
+//!
+//! A server retrying an upstream dependency on behalf of its own callers should tell
+//! them when retrying later is worthwhile. [`Exhausted`] turns the [`StopReason`]
+//! a retry future resolves with after
+//! [`with_stop_reason`](crate::RetryIf::with_stop_reason) into an `http` response (`503 Service Unavailable` with a `Retry-After` header), behind
+//! the `http` feature, or a `tonic::Status` (`UNAVAILABLE` with a
+//! `grpc-retry-pushback-ms` trailer), behind the `tonic` feature.
+//!
+//! ```rust
+//! # #[cfg(feature = "http")] {
+//! use std::time::Duration;
+//! use tokio_retry2::api::Exhausted;
+//! use tokio_retry2::strategy::RetryBudget;
+//! use tokio_retry2::StopReason;
+//!
+//! // one retry is deposited back into the budget every 5 seconds
+//! let budget = RetryBudget::new(0).refill_every(Duration::from_secs(5));
+//! let exhausted = Exhausted::new(StopReason::BudgetExhausted).retry_after_budget(&budget);
+//!
+//! let response = exhausted.into_response(());
+//! assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+//! assert_eq!(response.headers()[http::header::RETRY_AFTER], "5");
+//! # }
+//! ```
+use std::fmt;
+
+use tokio::time::Duration;
+
+use crate::notify::StopReason;
+use crate::strategy::RetryBudget;
+
+/// The final outcome of a retry loop that stopped without succeeding, along with a
+/// hint of when the caller may try again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhausted {
+    reason: StopReason,
+    retry_after: Option<Duration>,
+}
+
+impl Exhausted {
+    /// Constructs the outcome of a retry loop stopped for `reason`, without hint.
+    pub const fn new(reason: StopReason) -> Exhausted {
+        Exhausted {
+            reason,
+            retry_after: None,
+        }
+    }
+
+    /// Hints the caller to try again after `duration`.
+    pub const fn retry_after(mut self, duration: Duration) -> Exhausted {
+        self.retry_after = Some(duration);
+        self
+    }
+
+    /// Computes the hint from the refill state of `budget`. Once the loop stopped
+    /// because the budget is spent, the caller is hinted to come back at its next
+    /// [`refill`](RetryBudget::refill_every). Otherwise, or if the budget is never
+    /// refilled, the hint is left untouched.
+    pub fn retry_after_budget(self, budget: &RetryBudget) -> Exhausted {
+        let spent = self.reason == StopReason::BudgetExhausted || budget.remaining() == 0;
+        match budget.refill_in() {
+            Some(refill_in) if spent => self.retry_after(refill_in),
+            _ => self,
+        }
+    }
+
+    /// The reason the retry loop stopped.
+    pub const fn reason(&self) -> StopReason {
+        self.reason
+    }
+
+    /// The hint of when the caller may try again, if any.
+    pub const fn retry_after_hint(&self) -> Option<Duration> {
+        self.retry_after
+    }
+
+    /// Whether trying again later may succeed, i.e. the loop didn't stop because of
+    /// a permanent error.
+    pub const fn is_transient(&self) -> bool {
+        !matches!(
+            self.reason,
+            StopReason::PermanentError | StopReason::ConditionRejected
+        )
+    }
+
+    /// The hint, if any and the outcome is transient.
+    fn transient_hint(&self) -> Option<Duration> {
+        self.retry_after.filter(|_| self.is_transient())
+    }
+}
+
+impl From<StopReason> for Exhausted {
+    fn from(reason: StopReason) -> Exhausted {
+        Exhausted::new(reason)
+    }
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "retries stopped: {}", self.reason.as_str())
+    }
+}
+
+#[cfg(feature = "http")]
+impl Exhausted {
+    /// The status code of the outcome: `503 Service Unavailable` when trying again
+    /// later may succeed, `502 Bad Gateway` when the upstream failed permanently.
+    pub fn status_code(&self) -> http::StatusCode {
+        if self.is_transient() {
+            http::StatusCode::SERVICE_UNAVAILABLE
+        } else {
+            http::StatusCode::BAD_GATEWAY
+        }
+    }
+
+    /// The value of the `Retry-After` header, in whole seconds rounded up, if the
+    /// outcome is transient and has a hint.
+    pub fn retry_after_header(&self) -> Option<http::HeaderValue> {
+        let hint = self.transient_hint()?;
+        let secs = hint.as_secs() + u64::from(hint.subsec_nanos() > 0);
+        Some(http::HeaderValue::from(secs))
+    }
+
+    /// Builds a response with [`status_code`](Exhausted::status_code), the
+    /// `Retry-After` header if any, and `body`.
+    pub fn into_response<B>(self, body: B) -> http::Response<B> {
+        let mut response = http::Response::new(body);
+        *response.status_mut() = self.status_code();
+        if let Some(retry_after) = self.retry_after_header() {
+            response
+                .headers_mut()
+                .insert(http::header::RETRY_AFTER, retry_after);
+        }
+        response
+    }
+}
+
+#[cfg(feature = "tonic")]
+impl From<Exhausted> for tonic::Status {
+    /// `UNAVAILABLE` when trying again later may succeed, `CANCELLED` when the retry
+    /// was cancelled, and `INTERNAL` when the upstream failed permanently. The hint
+    /// is sent in the `grpc-retry-pushback-ms` trailer honored by gRPC clients.
+    fn from(exhausted: Exhausted) -> tonic::Status {
+        let code = match exhausted.reason {
+            StopReason::Cancelled => tonic::Code::Cancelled,
+            _ if exhausted.is_transient() => tonic::Code::Unavailable,
+            _ => tonic::Code::Internal,
+        };
+        let mut metadata = tonic::metadata::MetadataMap::new();
+        if let Some(hint) = exhausted.transient_hint() {
+            let millis = u64::try_from(hint.as_millis()).unwrap_or(u64::MAX);
+            metadata.insert("grpc-retry-pushback-ms", millis.into());
+        }
+        tonic::Status::with_metadata(code, exhausted.to_string(), metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "http")]
+    #[test]
+    fn maps_to_http_responses() {
+        let response = Exhausted::new(StopReason::MaxRetriesExceeded)
+            .retry_after(Duration::from_millis(1_500))
+            .into_response(());
+        assert_eq!(response.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(response.headers()[http::header::RETRY_AFTER], "2");
+
+        let response = Exhausted::new(StopReason::PermanentError)
+            .retry_after(Duration::from_secs(1))
+            .into_response(());
+        assert_eq!(response.status(), http::StatusCode::BAD_GATEWAY);
+        assert!(response.headers().get(http::header::RETRY_AFTER).is_none());
+    }
+
+    #[cfg(feature = "tonic")]
+    #[test]
+    fn maps_to_tonic_statuses() {
+        let status = tonic::Status::from(
+            Exhausted::new(StopReason::Throttled).retry_after(Duration::from_millis(250)),
+        );
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+        assert_eq!(status.message(), "retries stopped: throttled");
+        assert_eq!(
+            status.metadata().get("grpc-retry-pushback-ms").unwrap(),
+            "250"
+        );
+
+        let status = tonic::Status::from(Exhausted::new(StopReason::ConditionRejected));
+        assert_eq!(status.code(), tonic::Code::Internal);
+        assert!(status.metadata().get("grpc-retry-pushback-ms").is_none());
+    }
+
+    #[test]
+    fn hints_from_the_budget_refill() {
+        let refill = Duration::from_secs(5);
+        let budget = RetryBudget::new(1).refill_every(refill);
+        let exhausted = Exhausted::new(StopReason::MaxRetriesExceeded);
+
+        assert_eq!(
+            exhausted.retry_after_budget(&budget).retry_after_hint(),
+            None
+        );
+        let hint = Exhausted::new(StopReason::BudgetExhausted)
+            .retry_after_budget(&budget)
+            .retry_after_hint()
+            .unwrap();
+        assert!(hint > Duration::ZERO && hint <= refill);
+
+        assert!(budget.try_withdraw());
+        assert!(exhausted
+            .retry_after_budget(&budget)
+            .retry_after_hint()
+            .is_some());
+
+        let never_refilled = RetryBudget::new(0);
+        assert_eq!(
+            exhausted
+                .retry_after_budget(&never_refilled)
+                .retry_after_hint(),
+            None
+        );
+    }
+}
@@ -0,0 +1,163 @@
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::Duration;
+
+use crate::action::Action;
+use crate::error::Error as RetryError;
+use crate::future::RetryIf;
+use crate::notify::{AttemptInfo, Notify};
+use crate::strategy::Strategy;
+
+/// Same as [`Notify`](crate::Notify), but awaited before the next attempt, e.g. to
+/// push a metric to an async client, write to a channel or persist the retry state,
+/// see [`Retry::spawn_notify_async`](crate::Retry::spawn_notify_async).
+///
+/// Closures taking the error and the delay before the next attempt, and returning a
+/// future, implement it. The future can't borrow the error, so clone what it needs.
+pub trait AsyncNotify<E> {
+    /// The future awaited before the next attempt.
+    type Future: Future<Output = ()>;
+
+    /// Called on every retried error, with the delay before the next attempt.
+    fn notify(&mut self, err: &E, duration: Duration) -> Self::Future;
+}
+
+impl<E, F, Fut> AsyncNotify<E> for F
+where
+    F: FnMut(&E, Duration) -> Fut,
+    Fut: Future<Output = ()>,
+{
+    type Future = Fut;
+
+    fn notify(&mut self, err: &E, duration: Duration) -> Self::Future {
+        self(err, duration)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// The future returned by the last [`AsyncNotify::notify`] call, until the next
+/// attempt awaits it.
+type Pending<F> = Arc<Mutex<Option<F>>>;
+
+/// Calls an [`AsyncNotify`] from the retry loop on every retried error, leaving its
+/// future to the next attempt of the [`NotifiedAction`] sharing `pending`.
+pub(crate) struct AsyncNotifier<N, F> {
+    notify: N,
+    pending: Pending<F>,
+}
+
+impl<E, N: AsyncNotify<E>> Notify<E> for AsyncNotifier<N, N::Future> {
+    fn notify(&mut self, err: &E, duration: Duration) {
+        *lock(&self.pending) = Some(self.notify.notify(err, duration));
+    }
+
+    fn notify_attempt(&mut self, err: &E, _duration: Duration, attempt: &AttemptInfo) {
+        // the final error has no next attempt to await the future
+        if let Some(delay) = attempt.next_delay {
+            Notify::notify(self, err, delay);
+        }
+    }
+}
+
+/// An action awaiting the future left by an [`AsyncNotifier`], if any, before
+/// running the wrapped action.
+pub(crate) struct NotifiedAction<A, F> {
+    action: Arc<Mutex<A>>,
+    pending: Pending<F>,
+}
+
+impl<A, F> Action for NotifiedAction<A, F>
+where
+    A: Action,
+    F: Future<Output = ()>,
+{
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = NotifiedFuture<A, F>;
+
+    fn run(&mut self) -> Self::Future {
+        NotifiedFuture {
+            action: Arc::clone(&self.action),
+            notified: lock(&self.pending).take(),
+            attempt: None,
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        lock(&self.action).before_retry(delay);
+    }
+}
+
+/// Future awaiting a notification, then running the attempt of a [`NotifiedAction`].
+#[pin_project]
+pub(crate) struct NotifiedFuture<A: Action, F> {
+    action: Arc<Mutex<A>>,
+    #[pin]
+    notified: Option<F>,
+    #[pin]
+    attempt: Option<A::Future>,
+}
+
+impl<A, F> Future for NotifiedFuture<A, F>
+where
+    A: Action,
+    F: Future<Output = ()>,
+{
+    type Output = Result<A::Item, RetryError<A::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(notified) = this.notified.as_mut().as_pin_mut() {
+            ready!(notified.poll(cx));
+            this.notified.set(None);
+        }
+        if this.attempt.is_none() {
+            let attempt = lock(this.action).run();
+            this.attempt.set(Some(attempt));
+        }
+        this.attempt
+            .as_pin_mut()
+            .expect("the attempt was just started")
+            .poll(cx)
+    }
+}
+
+/// Drives `action` via `strategy` on a [`RetryIf`], awaiting `notify` before every
+/// retry, see [`Retry::spawn_notify_async`](crate::Retry::spawn_notify_async).
+pub(crate) fn retry_notify_async<T, A, N>(
+    strategy: T,
+    action: A,
+    notify: N,
+) -> RetryIf<
+    T::IntoIter,
+    NotifiedAction<A, N::Future>,
+    fn(&A::Error) -> bool,
+    AsyncNotifier<N, N::Future>,
+>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+    N: AsyncNotify<A::Error>,
+{
+    let pending = Arc::new(Mutex::new(None));
+    RetryIf::spawn(
+        strategy,
+        NotifiedAction {
+            action: Arc::new(Mutex::new(action)),
+            pending: Arc::clone(&pending),
+        },
+        (|_| true) as fn(&A::Error) -> bool,
+        AsyncNotifier { notify, pending },
+    )
+}
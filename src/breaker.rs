@@ -0,0 +1,554 @@
+//! A circuit breaker short-circuiting retries while a dependency is down.
+//!
+//! Once `failure_threshold` attempts in a row failed, a [`CircuitBreaker`] opens: for
+//! `open_for`, retry loops attached to it fail right away with
+//! [`BreakerError::Open`] instead of sleeping and running their action again. It then
+//! lets a few probe attempts through, and closes again once they all succeed, or
+//! opens again on the first failing probe.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tokio_retry2::{BreakerError, Retry, RetryError};
+//! use tokio_retry2::breaker::CircuitBreaker;
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! async fn call_payments() -> Result<u64, RetryError<String>> {
+//!     RetryError::to_transient("unavailable".to_owned())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! // shared by every call to the payments service
+//! let breaker = CircuitBreaker::new(5, Duration::from_secs(30)).half_open_probes(2);
+//!
+//! let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+//! match Retry::spawn_with_breaker(strategy, &breaker, call_payments).await {
+//!     Ok(receipt) => println!("paid: {receipt}"),
+//!     Err(BreakerError::Open { retry_in, .. }) => println!("payments down, retry in {retry_in:?}"),
+//!     Err(BreakerError::Action(err)) => println!("payment failed: {err}"),
+//! }
+//! # }
+//! ```
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::{pin, Pin};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::{Duration, Instant};
+
+use crate::action::Action;
+use crate::error::{BreakerError, Error as RetryError};
+use crate::history::ErrorHistory;
+use crate::notify::StopReason;
+use crate::strategy::Strategy;
+use crate::RetryIf;
+
+/// The state of a [`CircuitBreaker`], returned by [`CircuitBreaker::state`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BreakerState {
+    /// Attempts run normally.
+    Closed,
+    /// Attempts are short-circuited, until the probes start in `retry_in`.
+    Open {
+        /// The time left until the breaker lets probes through.
+        retry_in: Duration,
+    },
+    /// A limited number of probe attempts run, deciding whether to close again.
+    HalfOpen,
+}
+
+#[derive(Debug)]
+enum State {
+    Closed { failures: u32 },
+    Open { until: Instant },
+    HalfOpen { probing: u32, succeeded: u32 },
+}
+
+/// A circuit breaker shared by the retry loops calling the same dependency, see the
+/// [module documentation](self).
+///
+/// Clones share the same state.
+#[derive(Debug, Clone)]
+pub struct CircuitBreaker {
+    state: Arc<Mutex<State>>,
+    failure_threshold: u32,
+    open_for: Duration,
+    half_open_probes: u32,
+}
+
+impl CircuitBreaker {
+    /// Constructs a closed breaker, opening for `open_for` once `failure_threshold`
+    /// attempts in a row failed. A threshold of `0` behaves like `1`.
+    pub fn new(failure_threshold: u32, open_for: Duration) -> CircuitBreaker {
+        CircuitBreaker {
+            state: Arc::new(Mutex::new(State::Closed { failures: 0 })),
+            failure_threshold: failure_threshold.max(1),
+            open_for,
+            half_open_probes: 1,
+        }
+    }
+
+    /// Sets how many probe attempts may run at once once `open_for` elapsed, all of
+    /// which must succeed to close the breaker. Default is `1`, and `0` behaves like `1`.
+    pub fn half_open_probes(mut self, probes: u32) -> CircuitBreaker {
+        self.half_open_probes = probes.max(1);
+        self
+    }
+
+    /// The current state of the breaker.
+    pub fn state(&self) -> BreakerState {
+        match *self.lock() {
+            State::Closed { .. } => BreakerState::Closed,
+            State::Open { until } => match until.checked_duration_since(Instant::now()) {
+                Some(retry_in) if !retry_in.is_zero() => BreakerState::Open { retry_in },
+                _ => BreakerState::HalfOpen,
+            },
+            State::HalfOpen { .. } => BreakerState::HalfOpen,
+        }
+    }
+
+    /// How long attempts are short-circuited for, if they are: the time left while
+    /// open, or `open_for` while half-open with every probe running already, as a
+    /// failing probe opens it again for that long.
+    fn blocked(&self) -> Option<Duration> {
+        match *self.lock() {
+            State::Closed { .. } => None,
+            State::Open { until } => until
+                .checked_duration_since(Instant::now())
+                .filter(|retry_in| !retry_in.is_zero()),
+            State::HalfOpen { probing, succeeded } => (probing.saturating_add(succeeded)
+                >= self.half_open_probes)
+                .then_some(self.open_for),
+        }
+    }
+
+    /// Lets an attempt through, or returns how long the breaker stays open.
+    fn acquire(&self) -> Result<Permit, Duration> {
+        let mut state = self.lock();
+        if let State::Open { until } = *state {
+            match until.checked_duration_since(Instant::now()) {
+                Some(retry_in) if !retry_in.is_zero() => return Err(retry_in),
+                _ => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("circuit breaker half-open, probing");
+                    *state = State::HalfOpen {
+                        probing: 0,
+                        succeeded: 0,
+                    };
+                }
+            }
+        }
+        let probe = match &mut *state {
+            State::HalfOpen { probing, succeeded } => {
+                if probing.saturating_add(*succeeded) >= self.half_open_probes {
+                    // enough probes are running already
+                    return Err(self.open_for);
+                }
+                *probing += 1;
+                true
+            }
+            _ => false,
+        };
+        Ok(Permit {
+            breaker: self.clone(),
+            probe,
+            settled: false,
+        })
+    }
+
+    fn open(&self, state: &mut State) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(open_for = ?self.open_for, "circuit breaker opened");
+        *state = State::Open {
+            until: Instant::now() + self.open_for,
+        };
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An attempt let through by a breaker, reporting its outcome.
+struct Permit {
+    breaker: CircuitBreaker,
+    probe: bool,
+    settled: bool,
+}
+
+impl Permit {
+    fn success(mut self) {
+        self.settled = true;
+        let mut state = self.breaker.lock();
+        match &mut *state {
+            State::Closed { failures } => *failures = 0,
+            State::HalfOpen { probing, succeeded } if self.probe => {
+                *probing = probing.saturating_sub(1);
+                *succeeded += 1;
+                if *succeeded >= self.breaker.half_open_probes {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!("circuit breaker closed");
+                    *state = State::Closed { failures: 0 };
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn failure(mut self) {
+        self.settled = true;
+        let mut state = self.breaker.lock();
+        match &mut *state {
+            State::Closed { failures } => {
+                *failures += 1;
+                if *failures >= self.breaker.failure_threshold {
+                    self.breaker.open(&mut state);
+                }
+            }
+            State::HalfOpen { .. } if self.probe => self.breaker.open(&mut state),
+            _ => {}
+        }
+    }
+}
+
+impl Drop for Permit {
+    /// Frees the slot of a probe which neither succeeded nor failed, e.g. cancelled.
+    fn drop(&mut self) {
+        if self.probe && !self.settled {
+            if let State::HalfOpen { probing, .. } = &mut *self.breaker.lock() {
+                *probing = probing.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Runs every attempt of an action with a permit of a breaker, reporting its outcome.
+struct BreakerAction<A> {
+    action: A,
+    breaker: CircuitBreaker,
+    /// How long the breaker stays open, once it stopped the loop.
+    open: Arc<Mutex<Option<Duration>>>,
+}
+
+impl<A: Action> Action for BreakerAction<A> {
+    type Item = A::Item;
+    type Error = BreakerError<A::Error>;
+    type Future = BreakerAttempt<A::Future>;
+
+    fn run(&mut self) -> Self::Future {
+        match self.breaker.acquire() {
+            Ok(permit) => BreakerAttempt {
+                permit: Some(permit),
+                future: Some(self.action.run()),
+            },
+            Err(retry_in) => {
+                *lock(&self.open) = Some(retry_in);
+                BreakerAttempt {
+                    permit: None,
+                    future: None,
+                }
+            }
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.action.before_retry(delay);
+    }
+}
+
+/// Future running an attempt of a [`BreakerAction`], or failing right away without a
+/// permit.
+#[pin_project]
+struct BreakerAttempt<F> {
+    permit: Option<Permit>,
+    #[pin]
+    future: Option<F>,
+}
+
+impl<T, E, F> Future for BreakerAttempt<F>
+where
+    F: Future<Output = Result<T, RetryError<E>>>,
+{
+    type Output = Result<T, RetryError<BreakerError<E>>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let Some(future) = this.future.as_pin_mut() else {
+            // the breaker is open, which the strategy stops on
+            return Poll::Ready(Err(RetryError::transient(BreakerError::Open {
+                last_error: None,
+                retry_in: Duration::ZERO,
+            })));
+        };
+        let output = ready!(future.poll(cx));
+        let permit = this.permit.take().expect("an attempt only completes once");
+        Poll::Ready(match output {
+            Ok(item) => {
+                permit.success();
+                Ok(item)
+            }
+            // a permanent error isn't a sign of the dependency being down
+            Err(err @ RetryError::Permanent(..)) => Err(err.map(BreakerError::Action)),
+            Err(err) => {
+                permit.failure();
+                Err(err.map(BreakerError::Action))
+            }
+        })
+    }
+}
+
+/// Strategy stopping once the breaker is open, instead of sleeping for nothing.
+struct WhileClosed<I> {
+    iter: I,
+    breaker: CircuitBreaker,
+    open: Arc<Mutex<Option<Duration>>>,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for WhileClosed<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.iter.next()?;
+        let mut open = lock(&self.open);
+        // the attempt may have opened the breaker, or found it open
+        if open.is_none() {
+            *open = self.breaker.blocked();
+        }
+        open.is_none().then_some(delay)
+    }
+}
+
+impl<I: Strategy> Strategy for WhileClosed<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        match *lock(&self.open) {
+            Some(_) => Some(StopReason::CircuitOpen),
+            None => self.iter.stop_reason(),
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+/// Drives `action` via `strategy` through `breaker` on a [`RetryIf`],
+/// see [`Retry::spawn_with_breaker`](crate::Retry::spawn_with_breaker).
+pub(crate) async fn retry_with_breaker<T, A>(
+    strategy: T,
+    breaker: CircuitBreaker,
+    action: A,
+) -> Result<A::Item, BreakerError<A::Error>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+{
+    let open = Arc::new(Mutex::new(None));
+    let strategy = WhileClosed {
+        iter: strategy.into_iter(),
+        breaker: breaker.clone(),
+        open: Arc::clone(&open),
+    };
+    let action = BreakerAction {
+        action,
+        breaker,
+        open: Arc::clone(&open),
+    };
+    let retry = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&BreakerError<A::Error>) -> bool,
+        (|_, _| {}) as fn(&BreakerError<A::Error>, Duration),
+    )
+    // keeps the error of the last retried attempt, for when the next one finds the
+    // breaker open
+    .record_history(ErrorHistory::sampled(1));
+    let mut retry = pin!(retry);
+
+    let result = retry.as_mut().await;
+    let retry_in = *lock(&open);
+    match (result, retry_in) {
+        (Err(BreakerError::Action(err)), Some(retry_in)) => Err(BreakerError::Open {
+            last_error: Some(err),
+            retry_in,
+        }),
+        (Err(BreakerError::Open { .. }), Some(retry_in)) => {
+            let last_error = retry
+                .take_history()
+                .and_then(|mut history| history.pop_last())
+                .and_then(|err| match err {
+                    BreakerError::Action(err) => Some(err),
+                    BreakerError::Open { .. } => None,
+                });
+            Err(BreakerError::Open {
+                last_error,
+                retry_in,
+            })
+        }
+        (result, _) => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use tokio::time::sleep;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+
+    #[tokio::test(start_paused = true)]
+    async fn opens_after_consecutive_failures_and_short_circuits() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(50));
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let failing = || {
+            let attempts = attempts.clone();
+            move || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<()>(503))
+            }
+        };
+
+        let res = retry_with_breaker(
+            FixedInterval::from_millis(1).max_retries(10),
+            breaker.clone(),
+            failing(),
+        )
+        .await;
+        assert!(matches!(
+            res,
+            Err(BreakerError::Open {
+                last_error: Some(503),
+                ..
+            })
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+
+        // other loops fail right away, without running their action
+        let res =
+            retry_with_breaker(FixedInterval::from_millis(1), breaker.clone(), failing()).await;
+        assert!(matches!(
+            res,
+            Err(BreakerError::Open {
+                last_error: None,
+                ..
+            })
+        ));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn closes_once_the_probes_succeed() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        let _ = retry_with_breaker(
+            FixedInterval::from_millis(1).take(1),
+            breaker.clone(),
+            || future::ready(RetryError::to_transient::<()>(503)),
+        )
+        .await;
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+
+        sleep(Duration::from_millis(30)).await;
+        assert_eq!(breaker.state(), BreakerState::HalfOpen);
+        let res = retry_with_breaker(FixedInterval::from_millis(1), breaker.clone(), || {
+            future::ready(Ok::<_, RetryError<u16>>(200))
+        })
+        .await;
+
+        assert_eq!(res, Ok(200));
+        assert_eq!(breaker.state(), BreakerState::Closed);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn reopens_on_a_failing_probe() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20)).half_open_probes(2);
+        let failing = || future::ready(RetryError::to_transient::<()>(503));
+        let _ = retry_with_breaker(
+            FixedInterval::from_millis(1).take(1),
+            breaker.clone(),
+            failing,
+        )
+        .await;
+
+        sleep(Duration::from_millis(30)).await;
+        let res = retry_with_breaker(
+            FixedInterval::from_millis(1).take(3),
+            breaker.clone(),
+            failing,
+        )
+        .await;
+
+        assert!(matches!(
+            res,
+            Err(BreakerError::Open {
+                last_error: Some(503),
+                ..
+            })
+        ));
+        assert!(matches!(breaker.state(), BreakerState::Open { .. }));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn half_open_with_every_probe_running_reports_open_for() {
+        let breaker = CircuitBreaker::new(1, Duration::from_millis(20));
+        let _ = retry_with_breaker(
+            FixedInterval::from_millis(1).take(1),
+            breaker.clone(),
+            || future::ready(RetryError::to_transient::<()>(503)),
+        )
+        .await;
+        sleep(Duration::from_millis(30)).await;
+
+        // the only probe is held by another loop
+        let probe = breaker.acquire();
+        assert!(probe.is_ok());
+        let res = retry_with_breaker(FixedInterval::from_millis(1), breaker.clone(), || {
+            future::ready(Ok::<_, RetryError<u16>>(200))
+        })
+        .await;
+
+        assert_eq!(
+            res,
+            Err(BreakerError::Open {
+                last_error: None,
+                retry_in: Duration::from_millis(20),
+            })
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn keeps_the_last_error_when_opened_during_the_sleep() {
+        let breaker = CircuitBreaker::new(2, Duration::from_secs(1));
+        let retry = retry_with_breaker(
+            FixedInterval::from_millis(10).max_retries(3),
+            breaker.clone(),
+            || future::ready(RetryError::to_transient::<()>(503)),
+        );
+        // another loop fails while this one sleeps, opening the breaker
+        let other = async {
+            sleep(Duration::from_millis(5)).await;
+            breaker.acquire().map(Permit::failure).unwrap();
+        };
+        let (res, ()) = tokio::join!(retry, other);
+
+        assert!(matches!(
+            res,
+            Err(BreakerError::Open {
+                last_error: Some(503),
+                ..
+            })
+        ));
+    }
+}
@@ -0,0 +1,368 @@
+//! Fire-and-forget sends, delivered with retries by a background task.
+//!
+//! Telemetry or audit-log shippers shouldn't make their callers wait for, nor fail
+//! with, a flaky collector. [`spawn`] returns a [`Sender`] whose [`Sender::send`]
+//! enqueues an item into a bounded buffer and returns right away, while a spawned
+//! task delivers the buffered items one at a time, retrying each one with its own
+//! strategy. Once the buffer is full, the [`Overflow`] policy decides which item is
+//! dropped, and [`Worker::drain`] delivers what is left on shutdown.
+//!
+//! ```rust,no_run
+//! use tokio_retry2::RetryError;
+//! use tokio_retry2::buffered_retry::{self, Overflow};
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! async fn ship(line: String) -> Result<(), RetryError<std::io::Error>> {
+//!     Ok(())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let (sender, worker) = buffered_retry::spawn(
+//!     1024,
+//!     Overflow::DropOldest,
+//!     || ExponentialBackoff::from_millis(10).max_retries(5),
+//!     ship,
+//! );
+//!
+//! let _ = sender.send("user 42 logged in".to_owned());
+//!
+//! // on shutdown
+//! drop(sender);
+//! let stats = worker.drain().await;
+//! println!("delivered {}, dropped {}", stats.delivered, stats.dropped);
+//! # }
+//! ```
+use std::collections::VecDeque;
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::error::Error as RetryError;
+use crate::future::Retry;
+use crate::strategy::Strategy;
+
+/// What [`Sender::send`] does once the buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overflow {
+    /// Rejects the new item, returning it in [`SendError::Full`].
+    #[default]
+    DropNewest,
+    /// Drops the oldest buffered item to make room for the new one.
+    DropOldest,
+}
+
+/// Error returned by [`Sender::send`], handing the item back.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError<T> {
+    /// The buffer is full, and the overflow policy is [`Overflow::DropNewest`].
+    Full(T),
+    /// The buffer was closed by [`Worker::drain`].
+    Closed(T),
+}
+
+impl<T> SendError<T> {
+    /// Returns the item which couldn't be sent.
+    pub fn into_inner(self) -> T {
+        match self {
+            SendError::Full(item) | SendError::Closed(item) => item,
+        }
+    }
+}
+
+impl<T> fmt::Display for SendError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SendError::Full(_) => write!(f, "retry buffer is full"),
+            SendError::Closed(_) => write!(f, "retry buffer is closed"),
+        }
+    }
+}
+
+impl<T: fmt::Debug> error::Error for SendError<T> {}
+
+/// Counters of a buffered retry, returned by [`Worker::stats`] and [`Worker::drain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub struct DeliveryStats {
+    /// Items delivered, possibly after retries.
+    pub delivered: u64,
+    /// Items given up on, after a permanent error or once their strategy was exhausted.
+    pub failed: u64,
+    /// Items dropped because the buffer was full.
+    pub dropped: u64,
+}
+
+#[derive(Debug)]
+struct Buffer<T> {
+    items: VecDeque<T>,
+    closed: bool,
+}
+
+#[derive(Debug)]
+struct Shared<T> {
+    buffer: Mutex<Buffer<T>>,
+    capacity: usize,
+    overflow: Overflow,
+    wakeup: Notify,
+    senders: AtomicUsize,
+    delivered: AtomicU64,
+    failed: AtomicU64,
+    dropped: AtomicU64,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, Buffer<T>> {
+        self.buffer
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.wakeup.notify_one();
+    }
+
+    fn stats(&self) -> DeliveryStats {
+        DeliveryStats {
+            delivered: self.delivered.load(Ordering::Relaxed),
+            failed: self.failed.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Enqueues items for the background task of a buffered retry, see the
+/// [module documentation](self).
+///
+/// The buffer is closed once every sender is dropped, after which the task delivers
+/// the items left and completes.
+#[derive(Debug)]
+pub struct Sender<T> {
+    shared: Arc<Shared<T>>,
+}
+
+impl<T> Sender<T> {
+    /// Enqueues `item` without waiting for it to be delivered.
+    ///
+    /// With [`Overflow::DropOldest`], a full buffer drops its oldest item instead of
+    /// failing.
+    pub fn send(&self, item: T) -> Result<(), SendError<T>> {
+        let mut buffer = self.shared.lock();
+        if buffer.closed {
+            return Err(SendError::Closed(item));
+        }
+        if buffer.items.len() >= self.shared.capacity {
+            self.shared.dropped.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(overflow = ?self.shared.overflow, "retry buffer is full, dropping an item");
+            match self.shared.overflow {
+                Overflow::DropNewest => return Err(SendError::Full(item)),
+                Overflow::DropOldest => drop(buffer.items.pop_front()),
+            }
+        }
+        buffer.items.push_back(item);
+        drop(buffer);
+        self.shared.wakeup.notify_one();
+        Ok(())
+    }
+
+    /// The number of items waiting in the buffer, not counting the one being delivered.
+    pub fn len(&self) -> usize {
+        self.shared.lock().items.len()
+    }
+
+    /// Whether no item is waiting in the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<T> Clone for Sender<T> {
+    fn clone(&self) -> Self {
+        self.shared.senders.fetch_add(1, Ordering::Relaxed);
+        Sender {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<T> Drop for Sender<T> {
+    fn drop(&mut self) {
+        if self.shared.senders.fetch_sub(1, Ordering::AcqRel) == 1 {
+            self.shared.close();
+        }
+    }
+}
+
+/// The background task of a buffered retry, see the [module documentation](self).
+///
+/// Dropping it doesn't cancel the task.
+#[derive(Debug)]
+pub struct Worker<T> {
+    shared: Arc<Shared<T>>,
+    handle: JoinHandle<()>,
+}
+
+impl<T> Worker<T> {
+    /// The counters of the buffered retry so far.
+    pub fn stats(&self) -> DeliveryStats {
+        self.shared.stats()
+    }
+
+    /// Closes the buffer, so further sends fail with [`SendError::Closed`], and
+    /// resolves with the final counters once the items left were delivered or given
+    /// up on.
+    ///
+    /// Wrap it in [`tokio::time::timeout`] to bound the shutdown.
+    pub async fn drain(self) -> DeliveryStats {
+        self.shared.close();
+        // the task only ends by draining the buffer, or by panicking in `deliver`
+        let _ = self.handle.await;
+        self.shared.stats()
+    }
+}
+
+/// Spawns a task delivering the items sent to the returned [`Sender`] with
+/// `deliver`, retrying each of them via a fresh strategy from `strategy_factory`.
+///
+/// The buffer holds up to `capacity` items besides the one being delivered, and a
+/// capacity of `0` behaves like `1`. Items given up on are counted in
+/// [`DeliveryStats::failed`] and, with the `tracing` feature, logged.
+pub fn spawn<T, G, P, D, Fut, R, E>(
+    capacity: usize,
+    overflow: Overflow,
+    mut strategy_factory: G,
+    mut deliver: D,
+) -> (Sender<T>, Worker<T>)
+where
+    T: Clone + Send + 'static,
+    G: FnMut() -> P + Send + 'static,
+    P: IntoIterator<Item = Duration>,
+    P::IntoIter: Strategy + Send,
+    D: FnMut(T) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<R, RetryError<E>>> + Send,
+    E: Send,
+{
+    let capacity = capacity.max(1);
+    let shared = Arc::new(Shared {
+        buffer: Mutex::new(Buffer {
+            items: VecDeque::<T>::with_capacity(capacity),
+            closed: false,
+        }),
+        capacity,
+        overflow,
+        wakeup: Notify::new(),
+        senders: AtomicUsize::new(1),
+        delivered: AtomicU64::new(0),
+        failed: AtomicU64::new(0),
+        dropped: AtomicU64::new(0),
+    });
+
+    let worker = shared.clone();
+    let handle = tokio::spawn(async move {
+        loop {
+            let next = {
+                let mut buffer = worker.lock();
+                match buffer.items.pop_front() {
+                    Some(item) => Some(item),
+                    None if buffer.closed => return,
+                    None => None,
+                }
+            };
+            let Some(item) = next else {
+                // `notify_one` stores a permit, so a send in between isn't missed
+                worker.wakeup.notified().await;
+                continue;
+            };
+            let deliver = &mut deliver;
+            match Retry::spawn(strategy_factory(), move || deliver(item.clone())).await {
+                Ok(_) => worker.delivered.fetch_add(1, Ordering::Relaxed),
+                Err(_) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("giving up on a buffered item");
+                    worker.failed.fetch_add(1, Ordering::Relaxed)
+                }
+            };
+        }
+    });
+
+    (
+        Sender {
+            shared: shared.clone(),
+        },
+        Worker { shared, handle },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn delivers_every_item_with_retries_and_drains() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let failures = Arc::new(AtomicUsize::new(0));
+        let (sender, worker) = spawn(
+            8,
+            Overflow::DropNewest,
+            || FixedInterval::from_millis(1).take(3),
+            {
+                let received = received.clone();
+                move |item: u32| {
+                    // every other attempt fails, and item 9 never goes through
+                    if item == 9 || failures.fetch_add(1, Ordering::SeqCst) % 2 == 0 {
+                        return future::ready(RetryError::to_transient("unavailable"));
+                    }
+                    received.lock().unwrap().push(item);
+                    future::ready(Ok(()))
+                }
+            },
+        );
+
+        for item in [1, 2, 9, 3] {
+            sender.send(item).unwrap();
+        }
+        let stats = worker.drain().await;
+
+        assert_eq!(*received.lock().unwrap(), vec![1, 2, 3]);
+        assert_eq!(stats.delivered, 3);
+        assert_eq!(stats.failed, 1);
+        assert_eq!(sender.send(4), Err(SendError::Closed(4)));
+    }
+
+    #[tokio::test]
+    async fn overflow_policies_pick_the_dropped_item() {
+        let deliver = |_: u32| future::ready(Ok::<_, RetryError<()>>(()));
+        let strategy = || FixedInterval::from_millis(1).take(1);
+
+        // the task doesn't run before the first await
+        let (sender, worker) = spawn(2, Overflow::DropNewest, strategy, deliver);
+        assert_eq!(sender.send(1), Ok(()));
+        assert_eq!(sender.send(2), Ok(()));
+        assert_eq!(sender.send(3), Err(SendError::Full(3)));
+        assert_eq!(worker.stats().dropped, 1);
+
+        let (oldest, oldest_worker) = spawn(2, Overflow::DropOldest, strategy, deliver);
+        for item in 1..=4 {
+            assert_eq!(oldest.send(item), Ok(()));
+        }
+        assert_eq!(oldest.shared.lock().items, [3, 4]);
+
+        drop((sender, oldest));
+        assert_eq!(worker.drain().await.delivered, 2);
+        let stats = oldest_worker.drain().await;
+        assert_eq!((stats.delivered, stats.dropped), (2, 2));
+    }
+}
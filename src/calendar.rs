@@ -0,0 +1,364 @@
+//! Blackout periods during which retries are suppressed or slowed.
+//!
+//! Some dependencies are known to be unavailable, or must not be disturbed, at
+//! predictable times: the nightly maintenance of a partner, a freeze during trading
+//! hours. A [`Calendar`] lists these blackouts per named operation, in the time zone
+//! they are defined in, and turns them into a [`LoadGate`] which holds retries until
+//! the blackout ends, or slows them down meanwhile. Any `chrono` time zone works,
+//! e.g. `chrono::Utc`, `chrono::FixedOffset`, or the `Tz` of the `chrono-tz` crate,
+//! which follows daylight saving time.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use chrono::{FixedOffset, NaiveTime, Weekday};
+//! use tokio_retry2::{Retry, RetryError};
+//! use tokio_retry2::calendar::{BlackoutEffect, Calendar};
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! async fn settle() -> Result<u64, RetryError<()>> {
+//!     Ok(42)
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), ()> {
+//! let new_york = FixedOffset::west_opt(5 * 3600).unwrap();
+//! let calendar = Calendar::new(new_york)
+//!     // the partner is down for maintenance every night
+//!     .daily(
+//!         "settlement",
+//!         NaiveTime::from_hms_opt(23, 30, 0).unwrap(),
+//!         NaiveTime::from_hms_opt(1, 0, 0).unwrap(),
+//!         BlackoutEffect::Suppress,
+//!     )
+//!     // and only takes a retry per minute during the Monday batch
+//!     .weekly(
+//!         "settlement",
+//!         Weekday::Mon,
+//!         NaiveTime::from_hms_opt(9, 0, 0).unwrap(),
+//!         NaiveTime::from_hms_opt(10, 0, 0).unwrap(),
+//!         BlackoutEffect::Slow(Duration::from_secs(60)),
+//!     );
+//!
+//! Retry::spawn(ExponentialBackoff::from_millis(100).max_retries(5), settle)
+//!     .load_gated(calendar.gate("settlement"))
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::Arc;
+
+use chrono::{DateTime, Datelike, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday};
+use tokio::time::Duration;
+
+use crate::load_gate::{GateDecision, LoadGate};
+
+/// What happens to the retries of an operation during a blackout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlackoutEffect {
+    /// Retries are held until the blackout ends.
+    Suppress,
+    /// Every retry is postponed once by the given duration on top of its back-off
+    /// delay, see [`GateDecision::Delay`].
+    Slow(Duration),
+}
+
+#[derive(Debug, Clone)]
+enum Window {
+    /// Every day, or every given weekday, from `start` to `end` in local time. Windows
+    /// ending at or before their start end on the next day.
+    Recurring {
+        weekday: Option<Weekday>,
+        start: NaiveTime,
+        end: NaiveTime,
+    },
+    /// Once, from `start` to `end`.
+    Once {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Blackout {
+    operation: String,
+    window: Window,
+    effect: BlackoutEffect,
+}
+
+/// Blackout periods of named operations, in the time zone `Tz`, see the
+/// [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Calendar<Tz: TimeZone> {
+    tz: Tz,
+    blackouts: Vec<Blackout>,
+}
+
+impl<Tz: TimeZone> Calendar<Tz> {
+    /// Constructs a calendar without blackouts, whose times of day are in `tz`.
+    pub fn new(tz: Tz) -> Calendar<Tz> {
+        Calendar {
+            tz,
+            blackouts: Vec::new(),
+        }
+    }
+
+    /// Adds a blackout of `operation` every day from `start` to `end`. A blackout
+    /// ending at or before its start ends on the next day, e.g. from 23:30 to 01:00.
+    pub fn daily(
+        self,
+        operation: impl Into<String>,
+        start: NaiveTime,
+        end: NaiveTime,
+        effect: BlackoutEffect,
+    ) -> Self {
+        self.blackout(operation, None, start, end, effect)
+    }
+
+    /// Adds a blackout of `operation` every `weekday` from `start` to `end`. A
+    /// blackout ending at or before its start ends on the next day.
+    pub fn weekly(
+        self,
+        operation: impl Into<String>,
+        weekday: Weekday,
+        start: NaiveTime,
+        end: NaiveTime,
+        effect: BlackoutEffect,
+    ) -> Self {
+        self.blackout(operation, Some(weekday), start, end, effect)
+    }
+
+    /// Adds a one-off blackout of `operation` from `start` to `end`, e.g. a
+    /// maintenance announced by a partner.
+    pub fn between(
+        mut self,
+        operation: impl Into<String>,
+        start: DateTime<Tz>,
+        end: DateTime<Tz>,
+        effect: BlackoutEffect,
+    ) -> Self {
+        self.blackouts.push(Blackout {
+            operation: operation.into(),
+            window: Window::Once {
+                start: start.with_timezone(&Utc),
+                end: end.with_timezone(&Utc),
+            },
+            effect,
+        });
+        self
+    }
+
+    fn blackout(
+        mut self,
+        operation: impl Into<String>,
+        weekday: Option<Weekday>,
+        start: NaiveTime,
+        end: NaiveTime,
+        effect: BlackoutEffect,
+    ) -> Self {
+        self.blackouts.push(Blackout {
+            operation: operation.into(),
+            window: Window::Recurring {
+                weekday,
+                start,
+                end,
+            },
+            effect,
+        });
+        self
+    }
+
+    /// Whether a retry of `operation` may run at `now`, or how long to postpone it.
+    ///
+    /// Overlapping blackouts postpone retries until the longest suppressing one ends,
+    /// or else delay them by the longest slow down.
+    pub fn decision_at(&self, operation: &str, now: DateTime<Utc>) -> GateDecision {
+        let local = now.with_timezone(&self.tz);
+        let mut suppress: Option<Duration> = None;
+        let mut slow: Option<Duration> = None;
+        for blackout in self.blackouts.iter().filter(|b| b.operation == operation) {
+            let Some(remaining) = self.remaining(&blackout.window, now, &local) else {
+                continue;
+            };
+            match blackout.effect {
+                BlackoutEffect::Suppress => {
+                    suppress = Some(suppress.map_or(remaining, |s| s.max(remaining)));
+                }
+                BlackoutEffect::Slow(delay) => {
+                    slow = Some(slow.map_or(delay, |s| s.max(delay)));
+                }
+            }
+        }
+        match (suppress, slow) {
+            (Some(wait), _) => GateDecision::Postpone(wait),
+            (None, Some(delay)) => GateDecision::Delay(delay),
+            (None, None) => GateDecision::Proceed,
+        }
+    }
+
+    /// Same as [`Calendar::decision_at`], at the current time.
+    pub fn decision(&self, operation: &str) -> GateDecision {
+        self.decision_at(operation, Utc::now())
+    }
+
+    /// A gate holding or slowing the retries of `operation` during its blackouts,
+    /// to attach with [`Retry::load_gated`](crate::Retry::load_gated).
+    pub fn gate(&self, operation: &str) -> LoadGate
+    where
+        Tz: Send + Sync + 'static,
+        Tz::Offset: Send + Sync,
+    {
+        let calendar = Arc::new(self.clone());
+        let operation = operation.to_owned();
+        LoadGate::new(move || {
+            let decision = calendar.decision(&operation);
+            #[cfg(feature = "tracing")]
+            if let GateDecision::Postpone(wait) | GateDecision::Delay(wait) = decision {
+                tracing::debug!(operation, ?wait, "retry held by a blackout");
+            }
+            decision
+        })
+    }
+
+    /// The time left in `window` if it contains `now`.
+    fn remaining(
+        &self,
+        window: &Window,
+        now: DateTime<Utc>,
+        local: &DateTime<Tz>,
+    ) -> Option<Duration> {
+        match *window {
+            Window::Once { start, end } => {
+                (start <= now && now < end).then(|| (end - now).to_std().unwrap_or_default())
+            }
+            Window::Recurring {
+                weekday,
+                start,
+                end,
+            } => {
+                let today = local.date_naive();
+                let time = local.time();
+                let on = |day: chrono::NaiveDate| weekday.is_none_or(|w| day.weekday() == w);
+                let end_at = if start < end {
+                    (on(today) && start <= time && time < end).then(|| today.and_time(end))
+                } else if time >= start && on(today) {
+                    // started today, ends tomorrow
+                    today.succ_opt().map(|tomorrow| tomorrow.and_time(end))
+                } else {
+                    // started yesterday, ends today
+                    let yesterday = today.pred_opt()?;
+                    (time < end && on(yesterday)).then(|| today.and_time(end))
+                }?;
+                Some(self.until(local, end_at))
+            }
+        }
+    }
+
+    /// The time from `local` until the local time `end_at`, which may fall in a gap
+    /// of the time zone, e.g. when clocks are put forward.
+    fn until(&self, local: &DateTime<Tz>, end_at: NaiveDateTime) -> Duration {
+        let delta = match self.tz.from_local_datetime(&end_at).earliest() {
+            Some(end_at) => end_at.with_timezone(&Utc) - local.with_timezone(&Utc),
+            None => end_at - local.naive_local(),
+        };
+        delta.to_std().unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::FixedOffset;
+
+    fn at(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn suppresses_retries_until_the_blackout_ends() {
+        // UTC+2, so 23:30 local is 21:30 UTC
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        let calendar =
+            Calendar::new(tz).daily("sync", at(23, 30), at(1, 0), BlackoutEffect::Suppress);
+
+        let before = Utc.with_ymd_and_hms(2024, 3, 4, 21, 0, 0).unwrap();
+        let during = Utc.with_ymd_and_hms(2024, 3, 4, 22, 0, 0).unwrap();
+        let after_midnight = Utc.with_ymd_and_hms(2024, 3, 4, 22, 45, 0).unwrap();
+
+        assert_eq!(calendar.decision_at("sync", before), GateDecision::Proceed);
+        assert_eq!(
+            calendar.decision_at("sync", during),
+            GateDecision::Postpone(Duration::from_secs(3600))
+        );
+        assert_eq!(
+            calendar.decision_at("sync", after_midnight),
+            GateDecision::Postpone(Duration::from_secs(15 * 60))
+        );
+        // other operations aren't affected
+        assert_eq!(
+            calendar.decision_at("report", during),
+            GateDecision::Proceed
+        );
+    }
+
+    #[test]
+    fn slows_retries_on_weekdays_and_one_off_blackouts() {
+        let slow = BlackoutEffect::Slow(Duration::from_secs(60));
+        let calendar = Calendar::new(Utc)
+            .weekly("trade", Weekday::Mon, at(9, 0), at(10, 0), slow)
+            .between(
+                "trade",
+                Utc.with_ymd_and_hms(2024, 3, 5, 9, 0, 0).unwrap(),
+                Utc.with_ymd_and_hms(2024, 3, 5, 9, 30, 0).unwrap(),
+                BlackoutEffect::Suppress,
+            );
+
+        // 2024-03-04 is a Monday
+        let monday = Utc.with_ymd_and_hms(2024, 3, 4, 9, 15, 0).unwrap();
+        let tuesday = Utc.with_ymd_and_hms(2024, 3, 5, 9, 15, 0).unwrap();
+        let wednesday = Utc.with_ymd_and_hms(2024, 3, 6, 9, 15, 0).unwrap();
+
+        assert_eq!(
+            calendar.decision_at("trade", monday),
+            GateDecision::Delay(Duration::from_secs(60))
+        );
+        assert_eq!(
+            calendar.decision_at("trade", tuesday),
+            GateDecision::Postpone(Duration::from_secs(15 * 60))
+        );
+        assert_eq!(
+            calendar.decision_at("trade", wednesday),
+            GateDecision::Proceed
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn slow_blackouts_delay_each_retry_once() {
+        use std::future;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        use crate::strategy::FixedInterval;
+        use crate::{Retry, RetryError};
+
+        // the blackout spans the whole test, which barely advances the wall clock
+        let now = Utc::now();
+        let calendar = Calendar::new(Utc).between(
+            "sync",
+            now - chrono::Duration::hours(1),
+            now + chrono::Duration::hours(1),
+            BlackoutEffect::Slow(Duration::from_secs(60)),
+        );
+        let attempts = AtomicUsize::new(0);
+        let start = tokio::time::Instant::now();
+        let res = Retry::spawn(FixedInterval::from_millis(10).take(2), || {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            future::ready(RetryError::to_transient::<()>(42u64))
+        })
+        .load_gated(calendar.gate("sync"))
+        .await;
+
+        assert_eq!(res, Err(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(start.elapsed(), Duration::from_millis(2 * 60_010));
+    }
+}
@@ -1,6 +1,17 @@
+use core::time::Duration;
+
 /// Specifies under which conditions a retry is attempted.
 pub trait Condition<E> {
     fn should_retry(&mut self, error: &E) -> bool;
+
+    /// Decides how the `retry_after` hint of a transient error accepted by
+    /// [`should_retry`](Condition::should_retry) is honored.
+    ///
+    /// Returns the hint to sleep for, or `None` to veto it and fall back to the
+    /// strategy delay. Defaults to keeping the hint.
+    fn retry_after(&mut self, error: &E, retry_after: Option<Duration>) -> Option<Duration> {
+        retry_after
+    }
 }
 
 impl<E, F: FnMut(&E) -> bool> Condition<E> for F {
@@ -0,0 +1,217 @@
+use std::iter::IntoIterator;
+
+use tokio::time::Duration;
+
+use crate::action::Action;
+use crate::condition::Condition;
+use crate::future::RetryIf;
+use crate::notify::Notify;
+use crate::strategy::Strategy;
+
+/// A reusable retry configuration, bundling a strategy, a condition and a notify,
+/// that can be applied to many actions.
+///
+/// Every call to [`RetryConfig::run`] starts from a fresh clone of the configuration,
+/// so services can build it once and share it between call sites instead of repeating
+/// the same chain everywhere. For per-attempt timeouts and deadlines, see
+/// [`Retry::spawn_with_timeouts`](crate::Retry::spawn_with_timeouts).
+///
+/// ```rust,no_run
+/// use tokio_retry2::{RetryConfig, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// async fn fetch(id: u64) -> Result<u64, RetryError<std::io::Error>> {
+///     Ok(id)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let config = RetryConfig::new(ExponentialBackoff::from_millis(10).max_retries(3))
+///     .notify(|err: &std::io::Error, duration| println!("retrying in {duration:?}: {err}"));
+///
+/// let first = config.run(|| fetch(1)).await?;
+/// let second = config.run(|| fetch(2)).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryConfig<S, C, N> {
+    strategy: S,
+    condition: C,
+    notify: N,
+    unbounded_ok: bool,
+    #[cfg(feature = "jitter-std")]
+    retry_after_jitter: Option<(f64, f64)>,
+    #[cfg(feature = "tracing")]
+    attempt_span: crate::future::AttemptSpan,
+}
+
+/// The default condition of a [`RetryConfig`], retrying every error.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AlwaysRetry;
+
+impl<E> Condition<E> for AlwaysRetry {
+    fn should_retry(&mut self, _error: &E) -> bool {
+        true
+    }
+}
+
+/// The default notify of a [`RetryConfig`], doing nothing.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NoopNotify;
+
+impl<E> Notify<E> for NoopNotify {
+    fn notify(&mut self, _err: &E, _duration: Duration) {}
+}
+
+impl<S> RetryConfig<S, AlwaysRetry, NoopNotify>
+where
+    S: IntoIterator<Item = Duration> + Clone,
+{
+    /// Constructs a configuration retrying every error with `strategy`.
+    pub fn new(strategy: S) -> Self {
+        RetryConfig {
+            strategy,
+            condition: AlwaysRetry,
+            notify: NoopNotify,
+            unbounded_ok: false,
+            #[cfg(feature = "jitter-std")]
+            retry_after_jitter: None,
+            #[cfg(feature = "tracing")]
+            attempt_span: crate::future::AttemptSpan::Inherit,
+        }
+    }
+}
+
+impl<S, C, N> RetryConfig<S, C, N>
+where
+    S: IntoIterator<Item = Duration> + Clone,
+    S::IntoIter: Strategy,
+{
+    /// Only retries errors satisfying `condition`.
+    pub fn condition<C2>(self, condition: C2) -> RetryConfig<S, C2, N> {
+        RetryConfig {
+            strategy: self.strategy,
+            condition,
+            notify: self.notify,
+            unbounded_ok: self.unbounded_ok,
+            #[cfg(feature = "jitter-std")]
+            retry_after_jitter: self.retry_after_jitter,
+            #[cfg(feature = "tracing")]
+            attempt_span: self.attempt_span,
+        }
+    }
+
+    /// Notifies `notify` of every retried error.
+    pub fn notify<N2>(self, notify: N2) -> RetryConfig<S, C, N2> {
+        RetryConfig {
+            strategy: self.strategy,
+            condition: self.condition,
+            notify,
+            unbounded_ok: self.unbounded_ok,
+            #[cfg(feature = "jitter-std")]
+            retry_after_jitter: self.retry_after_jitter,
+            #[cfg(feature = "tracing")]
+            attempt_span: self.attempt_span,
+        }
+    }
+
+    /// See [`RetryIf::jitter_retry_after`].
+    #[cfg(feature = "jitter-std")]
+    pub fn jitter_retry_after(mut self, min: f64, max: f64) -> Self {
+        self.retry_after_jitter = Some((min, max));
+        self
+    }
+
+    /// See [`RetryIf::attempt_span`].
+    #[cfg(feature = "tracing")]
+    pub fn attempt_span(mut self, attempt_span: crate::future::AttemptSpan) -> Self {
+        self.attempt_span = attempt_span;
+        self
+    }
+
+    /// See [`RetryIf::unbounded_ok`].
+    pub fn unbounded_ok(mut self) -> Self {
+        self.unbounded_ok = true;
+        self
+    }
+
+    /// Retries `action` with a fresh clone of this configuration.
+    pub fn run<A>(&self, action: A) -> RetryIf<S::IntoIter, A, C, N>
+    where
+        A: Action,
+        C: Condition<A::Error> + Clone,
+        N: Notify<A::Error> + Clone,
+    {
+        let retry = RetryIf::spawn(
+            self.strategy.clone(),
+            action,
+            self.condition.clone(),
+            self.notify.clone(),
+        );
+        #[cfg(feature = "jitter-std")]
+        let retry = match self.retry_after_jitter {
+            Some((min, max)) => retry.jitter_retry_after(min, max),
+            None => retry,
+        };
+        #[cfg(feature = "tracing")]
+        let retry = retry.attempt_span(self.attempt_span);
+        if self.unbounded_ok {
+            retry.unbounded_ok()
+        } else {
+            retry
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::error::Error as RetryError;
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn applies_the_same_config_to_many_actions() {
+        let config = RetryConfig::new(FixedInterval::from_millis(1).take(2));
+
+        for _ in 0..2 {
+            let counter = AtomicUsize::new(0);
+            let res = config
+                .run(|| {
+                    counter.fetch_add(1, Ordering::SeqCst);
+                    future::ready(RetryError::to_transient::<()>(42))
+                })
+                .await;
+
+            assert_eq!(res, Err(42));
+            assert_eq!(counter.load(Ordering::SeqCst), 3);
+        }
+    }
+
+    #[tokio::test]
+    async fn applies_condition_and_notify() {
+        let notified = std::sync::Arc::new(AtomicUsize::new(0));
+        let cloned = notified.clone();
+        let config = RetryConfig::new(FixedInterval::from_millis(1).take(5))
+            .condition(|err: &u64| *err < 2)
+            .notify(move |_: &u64, _| {
+                cloned.fetch_add(1, Ordering::SeqCst);
+            });
+
+        let counter = AtomicUsize::new(0);
+        let res = config
+            .clone()
+            .run(|| {
+                let i = counter.fetch_add(1, Ordering::SeqCst) as u64;
+                future::ready(RetryError::to_transient::<()>(i))
+            })
+            .await;
+
+        assert_eq!(res, Err(2));
+        assert_eq!(notified.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,330 @@
+use std::iter::Iterator;
+
+use tokio::time::Duration;
+
+use crate::condition::Condition;
+use crate::error::Error as RetryError;
+use crate::notify::{DelaySource, StopReason};
+use crate::strategy::{RetryBudget, Strategy};
+
+/// A delay function, see [`RetryIf::delay_fn`](crate::RetryIf::delay_fn).
+pub(crate) type DelayFn<E> = Box<dyn FnMut(&E, Duration) -> Duration + Send + Sync>;
+
+/// Resolves how long to wait before retrying after `error`, or `None` to stop, with
+/// the same precedence rules as [`Retry::spawn`](crate::Retry::spawn), for custom
+/// retry loops, e.g. `select!` or stream based ones.
+///
+/// - a permanent error stops right away, without advancing `strategy`;
+/// - disabled [`retries`](crate::retries) stop right away too;
+/// - otherwise `strategy` is advanced, and stops the loop once exhausted, even when
+///   the error carries a `retry_after` hint;
+/// - the `retry_after` hint of a transient error, if any, replaces the strategy delay,
+///   which is still consumed, so hints count against the retry limit;
+/// - otherwise the strategy delay is used as is, already capped by any `max_delay`.
+///
+/// `Retry` resolves its delays with the same function, along with the options it
+/// was configured with, such as a condition vetoing hints, a delay function or a
+/// retry budget, which don't exist here.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_retry2::{next_delay, RetryError};
+/// use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+///
+/// let mut strategy = FixedInterval::from_millis(100).max_retries(2);
+///
+/// let hinted = RetryError::retry_after("busy", Duration::from_secs(1));
+/// assert_eq!(next_delay(&mut strategy, &hinted), Some(Duration::from_secs(1)));
+/// assert_eq!(
+///     next_delay(&mut strategy, &RetryError::transient("down")),
+///     Some(Duration::from_millis(100))
+/// );
+/// // the hint doesn't extend an exhausted strategy
+/// assert_eq!(next_delay(&mut strategy, &hinted), None);
+/// ```
+pub fn next_delay<I, E>(strategy: &mut I, error: &RetryError<E>) -> Option<Duration>
+where
+    I: Iterator<Item = Duration> + ?Sized,
+{
+    let RetryError::Transient {
+        err, retry_after, ..
+    } = error
+    else {
+        return None;
+    };
+    plain_delay(strategy, err, *retry_after)
+}
+
+/// Resolves the delay before retrying after the transient error `err` for the loops
+/// configured with nothing but a strategy, such as the stream, sink and I/O wrappers,
+/// see [`resolve`].
+pub(crate) fn plain_delay<I, E>(
+    strategy: &mut I,
+    err: &E,
+    retry_after: Option<Duration>,
+) -> Option<Duration>
+where
+    I: Iterator<Item = Duration> + ?Sized,
+{
+    let mut always = |_: &E| true;
+    resolve(
+        || Planned::plain(strategy.next()),
+        err,
+        retry_after,
+        &mut always,
+        DelayRules::default(),
+    )
+    .next
+    .ok()
+}
+
+/// The options of a retry loop shaping its delays besides its strategy, see
+/// [`resolve`].
+pub(crate) struct DelayRules<'a, E> {
+    /// See [`RetryIf::jitter_retry_after`](crate::RetryIf::jitter_retry_after).
+    pub(crate) retry_after_jitter: Option<(f64, f64)>,
+    /// See [`RetryIf::delay_fn`](crate::RetryIf::delay_fn), along with its cap.
+    pub(crate) delay_fn: Option<(&'a mut DelayFn<E>, Duration)>,
+    pub(crate) retry_budget: Option<&'a RetryBudget>,
+    /// The floor of [`RetryIf::compensate_overrun`](crate::RetryIf::compensate_overrun)
+    /// and how long the failed attempt took.
+    pub(crate) overrun: Option<(Duration, Duration)>,
+    /// Whether the delays come from a policy override instead of the strategy.
+    pub(crate) overridden: bool,
+}
+
+impl<E> Default for DelayRules<'_, E> {
+    fn default() -> Self {
+        DelayRules {
+            retry_after_jitter: None,
+            delay_fn: None,
+            retry_budget: None,
+            overrun: None,
+            overridden: false,
+        }
+    }
+}
+
+/// The delay a strategy planned, along with what it reported about it.
+pub(crate) struct Planned {
+    pub(crate) delay: Option<Duration>,
+    /// See [`Strategy::stop_reason`].
+    pub(crate) stop: Option<StopReason>,
+    /// See [`Strategy::clamped`].
+    pub(crate) clamped: bool,
+}
+
+impl Planned {
+    /// The delay `strategy` just yielded, or `None` if it stopped.
+    pub(crate) fn of<S: Strategy + ?Sized>(strategy: &S, delay: Option<Duration>) -> Planned {
+        Planned {
+            delay,
+            stop: delay.is_none().then(|| strategy.stop_reason()).flatten(),
+            clamped: delay.is_some() && strategy.clamped(),
+        }
+    }
+
+    /// A delay from a strategy reporting nothing.
+    pub(crate) fn plain(delay: Option<Duration>) -> Planned {
+        Planned {
+            delay,
+            stop: None,
+            clamped: false,
+        }
+    }
+}
+
+/// What follows a failed attempt, see [`resolve`].
+pub(crate) struct Resolution {
+    /// The delay before the next attempt, or why the loop stops.
+    pub(crate) next: Result<Duration, StopReason>,
+    /// The `retry_after` hint as honored by the condition.
+    pub(crate) retry_after: Option<Duration>,
+    /// Where the delay comes from.
+    pub(crate) source: DelaySource,
+}
+
+/// Resolves the delay before retrying after the transient error `err`, taking the
+/// planned delay from `next` unless the loop stops first. Every retry loop goes
+/// through here, so they all apply the same rules in the same order; the loops
+/// without a condition or any [`DelayRules`] go through [`plain_delay`].
+pub(crate) fn resolve<E, C>(
+    next: impl FnOnce() -> Planned,
+    err: &E,
+    retry_after: Option<Duration>,
+    condition: &mut C,
+    rules: DelayRules<'_, E>,
+) -> Resolution
+where
+    C: Condition<E> + ?Sized,
+{
+    if !condition.should_retry(err) {
+        return Resolution {
+            next: Err(StopReason::ConditionRejected),
+            retry_after,
+            source: DelaySource::Strategy,
+        };
+    }
+    let retry_after = condition
+        .retry_after(err, retry_after)
+        .map(|hint| jittered(hint, rules.retry_after_jitter));
+
+    let enabled = crate::retries::is_enabled();
+    let planned = if enabled {
+        next()
+    } else {
+        Planned::plain(None)
+    };
+    let mut source = if retry_after.is_some() {
+        DelaySource::RetryAfter
+    } else if rules.overridden {
+        DelaySource::Override
+    } else if planned.clamped {
+        DelaySource::Clamped
+    } else {
+        DelaySource::Strategy
+    };
+
+    let Some(delay) = planned.delay else {
+        let reason = if enabled {
+            planned.stop.unwrap_or(StopReason::StrategyExhausted)
+        } else {
+            StopReason::RetriesDisabled
+        };
+        return Resolution {
+            next: Err(reason),
+            retry_after,
+            source,
+        };
+    };
+    let delay = match (retry_after, rules.delay_fn) {
+        (Some(hint), _) => hint,
+        (None, Some((delay_fn, max))) => {
+            source = DelaySource::Computed;
+            delay_fn(err, delay).min(max)
+        }
+        (None, None) => delay,
+    };
+    if rules
+        .retry_budget
+        .is_some_and(|budget| !budget.try_withdraw())
+    {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("retry budget spent, cancelling retry");
+        return Resolution {
+            next: Err(StopReason::BudgetExhausted),
+            retry_after,
+            source,
+        };
+    }
+    let delay = match (retry_after, rules.overrun) {
+        (None, Some((floor, attempt_duration))) => {
+            delay.saturating_sub(attempt_duration).max(floor.min(delay))
+        }
+        _ => delay,
+    };
+    Resolution {
+        next: Ok(delay),
+        retry_after,
+        source,
+    }
+}
+
+fn jittered(retry_after: Duration, jitter: Option<(f64, f64)>) -> Duration {
+    match jitter {
+        #[cfg(feature = "jitter-std")]
+        Some((min, max)) => crate::strategy::jitter_range(min, max)(retry_after),
+        _ => retry_after,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{ExponentialBackoff, FixedInterval};
+
+    #[test]
+    fn permanent_errors_do_not_advance_the_strategy() {
+        let mut strategy = FixedInterval::from_millis(10).take(1);
+
+        assert_eq!(next_delay(&mut strategy, &RetryError::permanent(())), None);
+        assert_eq!(
+            next_delay(&mut strategy, &RetryError::transient(())),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(next_delay(&mut strategy, &RetryError::transient(())), None);
+    }
+
+    #[test]
+    fn retry_after_replaces_the_strategy_delay() {
+        let mut strategy = ExponentialBackoff::from_millis(10);
+        let hinted = RetryError::retry_after((), Duration::from_secs(3));
+
+        assert_eq!(
+            next_delay(&mut strategy, &hinted),
+            Some(Duration::from_secs(3))
+        );
+        // the first delay was consumed by the hint
+        assert_eq!(
+            next_delay(&mut strategy, &RetryError::transient(())),
+            Some(Duration::from_millis(100))
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn retry_loops_resolve_the_same_delays() {
+        use std::sync::Mutex;
+
+        use tokio::time::Instant;
+
+        use crate::strategy::MaxRetries;
+        use crate::Retry;
+
+        let strategy = || {
+            ExponentialBackoff::from_millis(10)
+                .max_delay_millis(500)
+                .max_retries(4)
+        };
+        let errors = || {
+            [
+                RetryError::transient(()),
+                RetryError::retry_after((), Duration::from_secs(3)),
+                RetryError::transient(()),
+                RetryError::transient(()),
+                RetryError::transient(()),
+            ]
+        };
+
+        let mut manual = strategy();
+        let expected: Vec<_> = errors()
+            .iter()
+            .map_while(|error| next_delay(&mut manual, error))
+            .collect();
+
+        let starts = Mutex::new(Vec::new());
+        let mut pending = errors().into_iter();
+        let res = Retry::spawn(strategy(), || {
+            starts.lock().unwrap().push(Instant::now());
+            std::future::ready(Err::<(), _>(pending.next().unwrap()))
+        })
+        .await;
+        let starts = starts.into_inner().unwrap();
+        let slept: Vec<_> = starts.windows(2).map(|w| w[1] - w[0]).collect();
+
+        assert_eq!(res, Err(()));
+        assert_eq!(slept, expected);
+        assert_eq!(expected, [10, 3_000, 500, 500].map(Duration::from_millis));
+    }
+
+    #[test]
+    fn works_with_dyn_strategies() {
+        let mut strategy: Box<dyn Iterator<Item = Duration>> =
+            Box::new(FixedInterval::from_millis(5));
+
+        assert_eq!(
+            next_delay(strategy.as_mut(), &RetryError::transient(())),
+            Some(Duration::from_millis(5))
+        );
+    }
+}
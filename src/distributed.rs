@@ -0,0 +1,471 @@
+//! Retry coordination shared between process instances.
+//!
+//! Every replica of a service retrying the same dependency on its own escalates its
+//! back-off independently, so 50 replicas keep hammering a struggling dependency 50
+//! times as hard as one would. A [`Coordinator`] keeps the back-off level and the
+//! circuit state of every key, e.g. a dependency or tenant, in a
+//! [`CoordinationBackend`] shared by all replicas, such as `RedisBackend` with the
+//! `redis` feature:
+//!
+//! - retry loops resume the back-off schedule at the level escalated by every replica
+//!   before them, like [`BackoffRegistry`](crate::strategy::BackoffRegistry) within one
+//!   process;
+//! - once the level reaches a threshold, the circuit of the key opens and every replica
+//!   holds its attempts until it closes.
+//!
+//! The backend is only an optimization: when it fails, the loop carries on as if the
+//! key had no shared state, so an outage of the backend doesn't stop retries.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tokio_retry2::RetryError;
+//! use tokio_retry2::distributed::{Coordinator, InMemoryBackend};
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! async fn charge() -> Result<u64, RetryError<std::io::Error>> {
+//!     Ok(42)
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), std::io::Error> {
+//! let coordinator = Coordinator::new(InMemoryBackend::new(), Duration::from_secs(60))
+//!     .open_circuit_at(8, Duration::from_secs(30));
+//!
+//! let strategy = ExponentialBackoff::from_millis(10).max_retries(10);
+//! let payment = coordinator.retry("payments-api", strategy, charge).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::error;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+
+use tokio::time::{sleep, Duration, Instant};
+
+use crate::action::Action;
+use crate::error::Error as RetryError;
+
+/// Whether attempts for a key are currently held by every replica.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Attempts run normally.
+    Closed,
+    /// Attempts are held until the circuit closes, in `retry_in`.
+    Open {
+        /// The time left until the circuit closes.
+        retry_in: Duration,
+    },
+}
+
+/// Storage of the back-off levels and circuit states shared by the replicas of a
+/// [`Coordinator`].
+pub trait CoordinationBackend {
+    /// The error of the backend, e.g. a lost connection.
+    type Error: error::Error;
+
+    /// Increments the back-off level of `key`, returning its level before this
+    /// failure. The level is forgotten `decay` after the last failure.
+    fn escalate(
+        &self,
+        key: &str,
+        decay: Duration,
+    ) -> impl Future<Output = Result<u32, Self::Error>>;
+
+    /// Forgets the back-off level of `key`, e.g. after an attempt succeeded.
+    fn reset(&self, key: &str) -> impl Future<Output = Result<(), Self::Error>>;
+
+    /// The circuit state of `key`.
+    fn circuit(&self, key: &str) -> impl Future<Output = Result<CircuitState, Self::Error>>;
+
+    /// Opens the circuit of `key` for `open_for`, unless it is already open.
+    fn open_circuit(
+        &self,
+        key: &str,
+        open_for: Duration,
+    ) -> impl Future<Output = Result<(), Self::Error>>;
+}
+
+/// Retries actions with back-off levels and circuit states shared through a
+/// [`CoordinationBackend`], see the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Coordinator<B> {
+    backend: B,
+    decay: Duration,
+    circuit: Option<(u32, Duration)>,
+}
+
+impl<B: CoordinationBackend> Coordinator<B> {
+    /// Constructs a coordinator sharing state through `backend`, where the back-off
+    /// level of a key is forgotten `decay` after its last failure.
+    pub fn new(backend: B, decay: Duration) -> Coordinator<B> {
+        Coordinator {
+            backend,
+            decay,
+            circuit: None,
+        }
+    }
+
+    /// Opens the circuit of a key for `open_for` once its back-off level reaches
+    /// `level`, holding the attempts of every replica meanwhile.
+    pub fn open_circuit_at(mut self, level: u32, open_for: Duration) -> Self {
+        self.circuit = Some((level, open_for));
+        self
+    }
+
+    /// The backend the state is shared through.
+    pub fn backend(&self) -> &B {
+        &self.backend
+    }
+
+    /// Retries `action` via `strategy`, resuming the schedule at the shared back-off
+    /// level of `key` on the first failure, and escalating it with every failure.
+    ///
+    /// Attempts, including the first one, wait for an open circuit of `key` to close.
+    /// A success resets the level of `key`. Permanent errors are returned right away.
+    pub async fn retry<S, A>(
+        &self,
+        key: &str,
+        strategy: S,
+        mut action: A,
+    ) -> Result<A::Item, A::Error>
+    where
+        S: IntoIterator<Item = Duration>,
+        A: Action,
+    {
+        let mut strategy = strategy.into_iter();
+        let mut resumed = false;
+
+        loop {
+            if let CircuitState::Open { retry_in } = self
+                .shared(self.backend.circuit(key).await)
+                .unwrap_or(CircuitState::Closed)
+            {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(key, ?retry_in, "waiting for the shared circuit to close");
+                sleep(retry_in).await;
+            }
+
+            let (err, retry_after) = match action.run().await {
+                Ok(item) => {
+                    self.shared(self.backend.reset(key).await);
+                    return Ok(item);
+                }
+                Err(RetryError::Permanent(err, ..)) => return Err(err),
+                Err(RetryError::Transient {
+                    err, retry_after, ..
+                }) => (err, retry_after),
+            };
+
+            let level = self
+                .shared(self.backend.escalate(key, self.decay).await)
+                .unwrap_or(0);
+            if !resumed {
+                resumed = true;
+                // skip the delays already taken by every replica for this key
+                for _ in 0..level {
+                    if strategy.next().is_none() {
+                        break;
+                    }
+                }
+            }
+            if let Some((threshold, open_for)) = self.circuit {
+                if level.saturating_add(1) >= threshold {
+                    self.shared(self.backend.open_circuit(key, open_for).await);
+                }
+            }
+
+            match crate::delay::plain_delay(&mut strategy, &err, retry_after) {
+                Some(duration) => sleep(duration).await,
+                None => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("ending retry: strategy reached its limit");
+                    return Err(err);
+                }
+            }
+        }
+    }
+
+    /// Discards failures of the backend, carrying on without the shared state.
+    fn shared<T>(&self, result: Result<T, B::Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(_err) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_err, "retry coordination backend failed");
+                None
+            }
+        }
+    }
+}
+
+/// A [`CoordinationBackend`] keeping the state in memory, shared between clones.
+///
+/// It only coordinates the retry loops of one process, e.g. in tests or before a
+/// shared backend is deployed.
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryBackend {
+    state: Arc<Mutex<HashMap<String, KeyState>>>,
+}
+
+#[derive(Debug, Default)]
+struct KeyState {
+    level: u32,
+    expires: Option<Instant>,
+    open_until: Option<Instant>,
+}
+
+impl InMemoryBackend {
+    /// Constructs a backend without any state.
+    pub fn new() -> InMemoryBackend {
+        InMemoryBackend::default()
+    }
+
+    fn with_key<T>(&self, key: &str, f: impl FnOnce(&mut KeyState, Instant) -> T) -> T {
+        // the map stays consistent even if a thread panicked while holding the lock
+        let mut state = self
+            .state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        let key = state.entry(key.to_owned()).or_default();
+        if key.expires.is_some_and(|expires| expires <= now) {
+            key.level = 0;
+            key.expires = None;
+        }
+        f(key, now)
+    }
+}
+
+impl CoordinationBackend for InMemoryBackend {
+    type Error = Infallible;
+
+    async fn escalate(&self, key: &str, decay: Duration) -> Result<u32, Infallible> {
+        Ok(self.with_key(key, |state, now| {
+            let level = state.level;
+            state.level = level.saturating_add(1);
+            state.expires = Some(now + decay);
+            level
+        }))
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), Infallible> {
+        self.with_key(key, |state, _| {
+            state.level = 0;
+            state.expires = None;
+        });
+        Ok(())
+    }
+
+    async fn circuit(&self, key: &str) -> Result<CircuitState, Infallible> {
+        Ok(self.with_key(key, |state, now| match state.open_until {
+            Some(open_until) if open_until > now => CircuitState::Open {
+                retry_in: open_until - now,
+            },
+            _ => CircuitState::Closed,
+        }))
+    }
+
+    async fn open_circuit(&self, key: &str, open_for: Duration) -> Result<(), Infallible> {
+        self.with_key(key, |state, now| {
+            if !state.open_until.is_some_and(|open_until| open_until > now) {
+                state.open_until = Some(now + open_for);
+            }
+        });
+        Ok(())
+    }
+}
+
+/// A [`CoordinationBackend`] keeping the state in Redis, so it is shared by every
+/// process connected to the same server.
+///
+/// The level of a key is stored in `{prefix}{key}:level`, expiring `decay` after the
+/// last failure, and its circuit in `{prefix}{key}:circuit`, expiring when it closes.
+#[cfg(feature = "redis")]
+#[derive(Clone)]
+pub struct RedisBackend {
+    connection: redis::aio::ConnectionManager,
+    prefix: String,
+}
+
+#[cfg(feature = "redis")]
+impl RedisBackend {
+    /// Constructs a backend storing the state through `connection`, under keys
+    /// prefixed with `tokio-retry2:`.
+    pub fn new(connection: redis::aio::ConnectionManager) -> RedisBackend {
+        RedisBackend {
+            connection,
+            prefix: String::from("tokio-retry2:"),
+        }
+    }
+
+    /// Prefixes the Redis keys with `prefix`, e.g. to separate environments sharing
+    /// a server.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = prefix.into();
+        self
+    }
+
+    fn key(&self, key: &str, suffix: &str) -> String {
+        format!("{}{key}:{suffix}", self.prefix)
+    }
+}
+
+#[cfg(feature = "redis")]
+impl std::fmt::Debug for RedisBackend {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RedisBackend")
+            .field("prefix", &self.prefix)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "redis")]
+fn millis(duration: Duration) -> u64 {
+    // Redis rejects a zero expiry
+    u64::try_from(duration.as_millis())
+        .unwrap_or(u64::MAX)
+        .max(1)
+}
+
+/// Decodes the reply of the escalation pipeline, in which only the `INCR` isn't
+/// ignored, into the level before the increment.
+#[cfg(feature = "redis")]
+fn escalated_level(reply: &redis::Value) -> Result<u32, redis::RedisError> {
+    let (level,): (u32,) = redis::from_redis_value(reply)?;
+    Ok(level.saturating_sub(1))
+}
+
+#[cfg(feature = "redis")]
+impl CoordinationBackend for RedisBackend {
+    type Error = redis::RedisError;
+
+    async fn escalate(&self, key: &str, decay: Duration) -> Result<u32, redis::RedisError> {
+        let key = self.key(key, "level");
+        let reply: redis::Value = redis::pipe()
+            .atomic()
+            .incr(&key, 1)
+            .cmd("PEXPIRE")
+            .arg(&key)
+            .arg(millis(decay))
+            .ignore()
+            .query_async(&mut self.connection.clone())
+            .await?;
+        escalated_level(&reply)
+    }
+
+    async fn reset(&self, key: &str) -> Result<(), redis::RedisError> {
+        redis::cmd("DEL")
+            .arg(self.key(key, "level"))
+            .query_async(&mut self.connection.clone())
+            .await
+    }
+
+    async fn circuit(&self, key: &str) -> Result<CircuitState, redis::RedisError> {
+        // negative when the key doesn't exist or has no expiry
+        let ttl: i64 = redis::cmd("PTTL")
+            .arg(self.key(key, "circuit"))
+            .query_async(&mut self.connection.clone())
+            .await?;
+        Ok(match u64::try_from(ttl) {
+            Ok(ttl) if ttl > 0 => CircuitState::Open {
+                retry_in: Duration::from_millis(ttl),
+            },
+            _ => CircuitState::Closed,
+        })
+    }
+
+    async fn open_circuit(&self, key: &str, open_for: Duration) -> Result<(), redis::RedisError> {
+        let _: Option<String> = redis::cmd("SET")
+            .arg(self.key(key, "circuit"))
+            .arg(1)
+            .arg("PX")
+            .arg(millis(open_for))
+            .arg("NX")
+            .query_async(&mut self.connection.clone())
+            .await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    #[cfg(feature = "redis")]
+    fn decodes_the_escalation_reply() {
+        let reply = redis::Value::Array(vec![redis::Value::Int(3)]);
+        assert_eq!(escalated_level(&reply).unwrap(), 2);
+
+        let first = redis::Value::Array(vec![redis::Value::Int(1)]);
+        assert_eq!(escalated_level(&first).unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn replicas_share_the_back_off_level() {
+        let backend = InMemoryBackend::new();
+        let first = Coordinator::new(backend.clone(), Duration::from_secs(60));
+        let second = Coordinator::new(backend, Duration::from_secs(60));
+        let strategy = || [1, 2, 3, 4].map(Duration::from_millis);
+
+        let res = first
+            .retry("dep", strategy(), || {
+                future::ready(RetryError::to_transient::<()>(42))
+            })
+            .await;
+        assert_eq!(res, Err(42));
+
+        // the second replica resumes past the delays taken by the first one
+        let attempts = AtomicUsize::new(0);
+        let res = second
+            .retry("dep", strategy(), || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<()>(42))
+            })
+            .await;
+        assert_eq!(res, Err(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        // a success resets the level
+        let res = second
+            .retry("dep", strategy(), || {
+                future::ready(Ok::<_, RetryError<()>>(1))
+            })
+            .await;
+        assert_eq!(res, Ok(1));
+        assert_eq!(
+            first
+                .backend()
+                .escalate("dep", Duration::from_secs(60))
+                .await,
+            Ok(0)
+        );
+    }
+
+    #[tokio::test]
+    async fn open_circuit_holds_attempts() {
+        let coordinator = Coordinator::new(InMemoryBackend::new(), Duration::from_secs(60))
+            .open_circuit_at(1, Duration::from_millis(50));
+
+        let start = Instant::now();
+        let attempts = AtomicUsize::new(0);
+        let res = coordinator
+            .retry("dep", FixedInterval::from_millis(1).take(1), || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<()>(42))
+            })
+            .await;
+
+        assert_eq!(res, Err(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        // the retry waited for the circuit opened by the first failure to close
+        assert!(start.elapsed() >= Duration::from_millis(50));
+    }
+}
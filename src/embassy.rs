@@ -0,0 +1,150 @@
+//! Retry loops sleeping on an [`embassy_time::Timer`], for embedded async firmware.
+//!
+//! The [`Retry`](crate::Retry) futures sleep on the tokio timer, which isn't available
+//! on embedded targets. [`retry`] and [`retry_if`] drive the same strategies and
+//! [`RetryError`](crate::RetryError) classification on the embassy time driver instead,
+//! and don't need the `std` feature.
+//!
+//! ```rust,ignore
+//! use tokio_retry2::RetryError;
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! let strategy = ExponentialBackoff::from_millis(10).max_retries(5);
+//! let reading = tokio_retry2::embassy::retry(strategy, || async {
+//!     sensor.read().await.map_err(RetryError::transient)
+//! })
+//! .await;
+//! ```
+use core::future::Future;
+use core::time::Duration;
+
+use embassy_time::Timer;
+
+use crate::condition::Condition;
+use crate::error::Error as RetryError;
+use crate::notify::{AttemptInfo, DelaySource, Notify, StopReason};
+
+/// Runs `action` until it succeeds, fails permanently or `strategy` is exhausted,
+/// sleeping on an [`embassy_time::Timer`] between attempts.
+///
+/// Transient errors honor their `retry_after` hint.
+pub async fn retry<I, F, Fut, T, E>(strategy: I, action: F) -> Result<T, E>
+where
+    I: IntoIterator<Item = Duration>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RetryError<E>>>,
+{
+    retry_if(strategy, action, |_: &E| true, |_: &E, _| {}).await
+}
+
+/// Like [`retry`], but only retries transient errors accepted by `condition`, and
+/// calls `notify` before every retry and once the loop stops without succeeding.
+pub async fn retry_if<I, F, Fut, T, E, C, N>(
+    strategy: I,
+    mut action: F,
+    mut condition: C,
+    mut notify: N,
+) -> Result<T, E>
+where
+    I: IntoIterator<Item = Duration>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, RetryError<E>>>,
+    C: Condition<E>,
+    N: Notify<E>,
+{
+    let mut strategy = strategy.into_iter();
+
+    loop {
+        let start = embassy_time::Instant::now();
+        let (err, retry_after) = match action().await {
+            Ok(item) => return Ok(item),
+            Err(RetryError::Permanent(err, ..)) => {
+                notify.notify_stop(StopReason::PermanentError);
+                return Err(err);
+            }
+            Err(RetryError::Transient {
+                err, retry_after, ..
+            }) => (err, retry_after),
+        };
+        if !condition.should_retry(&err) {
+            notify.notify_stop(StopReason::ConditionRejected);
+            return Err(err);
+        }
+        let retry_after = condition.retry_after(&err, retry_after);
+
+        match strategy.next() {
+            Some(duration) => {
+                let duration = retry_after.unwrap_or(duration);
+                let attempt = AttemptInfo {
+                    attempt_duration: start.elapsed().into(),
+                    retry_after,
+                    delay_source: if retry_after.is_some() {
+                        DelaySource::RetryAfter
+                    } else {
+                        DelaySource::Strategy
+                    },
+                    next_delay: Some(duration),
+                };
+                notify.notify_attempt(&err, duration, &attempt);
+                Timer::after(duration.try_into().unwrap_or(embassy_time::Duration::MAX)).await;
+            }
+            None => {
+                notify.notify_stop(StopReason::StrategyExhausted);
+                return Err(err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::future;
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    fn retries_until_success() {
+        let attempts = Cell::new(0);
+        let result =
+            futures::executor::block_on(retry(FixedInterval::from_millis(1).take(5), || {
+                attempts.set(attempts.get() + 1);
+                future::ready(if attempts.get() < 3 {
+                    Err(RetryError::transient(()))
+                } else {
+                    Ok(attempts.get())
+                })
+            }));
+
+        assert_eq!(result, Ok(3));
+    }
+
+    #[test]
+    fn reports_why_it_stopped() {
+        let delays = Cell::new(0);
+        let stop = Cell::new(None);
+        struct Recorder<'a>(&'a Cell<usize>, &'a Cell<Option<StopReason>>);
+        impl Notify<u8> for Recorder<'_> {
+            fn notify(&mut self, _: &u8, _: Duration) {
+                self.0.set(self.0.get() + 1);
+            }
+
+            fn notify_stop(&mut self, reason: StopReason) {
+                self.1.set(Some(reason));
+            }
+        }
+
+        let result: Result<(), u8> = futures::executor::block_on(retry_if(
+            FixedInterval::from_millis(1).take(2),
+            || future::ready(Err(RetryError::transient(7))),
+            |_: &u8| true,
+            Recorder(&delays, &stop),
+        ));
+
+        assert_eq!(result, Err(7));
+        assert_eq!(delays.get(), 2);
+        assert_eq!(stop.get(), Some(StopReason::StrategyExhausted));
+    }
+}
@@ -1,38 +1,51 @@
-use std::error;
-use std::fmt;
-
-use std::time::Duration;
+use core::error;
+use core::fmt;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::io;
 
 const TRANSIENT_ERROR: &str = "transient error";
 const PERMANENT_ERROR: &str = "permanent error";
+const NOT_READY_ERROR: &str = "value not ready";
+const ATTEMPT_TIMED_OUT_ERROR: &str = "attempt timed out";
+const DEADLINE_EXCEEDED_ERROR: &str = "deadline exceeded";
+const CIRCUIT_OPEN_ERROR: &str = "circuit breaker is open";
 
 /// `Error` is the error value in an actions's retry result.
 ///
 /// Based on the two possible values, the operation
 /// may be retried.
+///
+/// Both variants carry a hidden site where the error was classified, which records a
+/// backtrace with the `backtrace` feature, so errors have to be constructed with the
+/// functions below or `?`, and matched with `..`, e.g. `Error::Permanent(err, ..)`.
 pub enum Error<E> {
     /// `Permanent` means that it's impossible to execute the operation
     /// successfully. This error is an early return from the retry operation.
-    Permanent(E),
+    #[non_exhaustive]
+    Permanent(E, #[doc(hidden)] ClassificationSite),
 
     /// `Transient` means that the error is temporary. If the `retry_after` is `None`
     /// the operation should be retried according to the defined strategy policy, else after
     /// the specified duration. Useful for handling ratelimits like a HTTP 429 response.
+    #[non_exhaustive]
     Transient {
         err: E,
         retry_after: Option<Duration>,
+        #[doc(hidden)]
+        site: ClassificationSite,
     },
 }
 
 impl<E> Error<E> {
     // Creates an permanent error.
     pub fn permanent(err: E) -> Self {
-        Error::Permanent(err)
+        Error::Permanent(err, ClassificationSite::capture())
     }
 
     // Creates a Result::Err container with an permanent error.
     pub fn to_permanent<T>(err: E) -> Result<T, Self> {
-        Err(Error::Permanent(err))
+        Err(Error::permanent(err))
     }
 
     // Creates an transient error which is retried according to the defined strategy
@@ -41,26 +54,21 @@ impl<E> Error<E> {
         Error::Transient {
             err,
             retry_after: None,
+            site: ClassificationSite::capture(),
         }
     }
 
     // Creates a Result::Err container with an transient error which
     // is retried according to the defined strategy policy.
     pub fn to_transient<T>(err: E) -> Result<T, Self> {
-        Err(Error::Transient {
-            err,
-            retry_after: None,
-        })
+        Err(Error::transient(err))
     }
 
     /// Creates a Result::Err container with a transient error which
     /// is retried after the specified duration.
     /// Useful for handling ratelimits like a HTTP 429 response.
     pub fn to_retry_after<T>(err: E, duration: Duration) -> Result<T, Self> {
-        Err(Error::Transient {
-            err,
-            retry_after: Some(duration),
-        })
+        Err(Error::retry_after(err, duration))
     }
 
     /// Creates a transient error which is retried after the specified duration.
@@ -69,21 +77,138 @@ impl<E> Error<E> {
         Error::Transient {
             err,
             retry_after: Some(duration),
+            site: ClassificationSite::capture(),
+        }
+    }
+
+    /// Maps the inner error with `f`, keeping its classification.
+    #[cfg(feature = "std")]
+    pub(crate) fn map<F>(self, f: impl FnOnce(E) -> F) -> Error<F> {
+        match self {
+            Error::Permanent(err, site) => Error::Permanent(f(err), site),
+            Error::Transient {
+                err,
+                retry_after,
+                site,
+            } => Error::Transient {
+                err: f(err),
+                retry_after,
+                site,
+            },
+        }
+    }
+
+    /// Converts the error into an `std::io::Error` of kind `Other`, for io-centric APIs.
+    ///
+    /// The inner error is a [`ClassifiedError`] carrying the classification and, with the
+    /// `backtrace` feature, the backtrace captured when the error was classified.
+    #[cfg(feature = "std")]
+    pub fn into_io_error(self) -> io::Error
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        io::Error::other(ClassifiedError::new(self))
+    }
+}
+
+/// Unwraps the inner `std::io::Error`, keeping its kind.
+///
+/// Use [`Error::into_io_error`] to keep the classification instead.
+#[cfg(feature = "std")]
+impl From<Error<io::Error>> for io::Error {
+    fn from(err: Error<io::Error>) -> io::Error {
+        match err {
+            Error::Permanent(err, ..) | Error::Transient { err, .. } => err,
         }
     }
 }
 
+/// Where an [`Error`] was classified, carried by both variants. Only records a
+/// backtrace with the `backtrace` feature, and is zero-sized otherwise.
+#[doc(hidden)]
+#[derive(Debug, Clone)]
+pub struct ClassificationSite {
+    #[cfg(feature = "backtrace")]
+    backtrace: std::sync::Arc<std::backtrace::Backtrace>,
+}
+
+impl ClassificationSite {
+    fn capture() -> Self {
+        ClassificationSite {
+            #[cfg(feature = "backtrace")]
+            backtrace: std::sync::Arc::new(std::backtrace::Backtrace::capture()),
+        }
+    }
+}
+
+/// The inner error of an `std::io::Error` converted by [`Error::into_io_error`],
+/// recording whether the error was permanent and, with the `backtrace` feature, where
+/// it was classified.
+///
+/// Retrieve it with `io::Error::get_ref` and `downcast_ref`.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct ClassifiedError {
+    source: Box<dyn error::Error + Send + Sync>,
+    permanent: bool,
+    site: ClassificationSite,
+}
+
+#[cfg(feature = "std")]
+impl ClassifiedError {
+    fn new<E>(err: Error<E>) -> Self
+    where
+        E: Into<Box<dyn error::Error + Send + Sync>>,
+    {
+        let (source, permanent, site) = match err {
+            Error::Permanent(err, site) => (err.into(), true, site),
+            Error::Transient { err, site, .. } => (err.into(), false, site),
+        };
+        ClassifiedError {
+            source,
+            permanent,
+            site,
+        }
+    }
+
+    /// Whether the error was classified as permanent.
+    pub fn is_permanent(&self) -> bool {
+        self.permanent
+    }
+
+    /// The backtrace captured when the error was classified, e.g. by
+    /// [`Error::transient`] or `?`, or `None` without the `backtrace` feature. Like
+    /// `std::backtrace::Backtrace::capture`, it is only resolved if `RUST_BACKTRACE` or
+    /// `RUST_LIB_BACKTRACE` is set.
+    pub fn backtrace(&self) -> Option<&std::backtrace::Backtrace> {
+        #[cfg(feature = "backtrace")]
+        return Some(&self.site.backtrace);
+        #[cfg(not(feature = "backtrace"))]
+        None
+    }
+}
+
+#[cfg(feature = "std")]
+impl fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.source.fmt(f)
+    }
+}
+
+#[cfg(feature = "std")]
+impl error::Error for ClassifiedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
 impl<E> fmt::Display for Error<E>
 where
     E: fmt::Display,
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         match *self {
-            Error::Permanent(ref err)
-            | Error::Transient {
-                ref err,
-                retry_after: _,
-            } => err.fmt(f),
+            Error::Permanent(ref err, ..) | Error::Transient { ref err, .. } => err.fmt(f),
         }
     }
 }
@@ -94,11 +219,8 @@ where
 {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
         let (name, err) = match *self {
-            Error::Permanent(ref err) => ("Permanent", err as &dyn fmt::Debug),
-            Error::Transient {
-                ref err,
-                retry_after: _,
-            } => ("Transient", err as &dyn fmt::Debug),
+            Error::Permanent(ref err, ..) => ("Permanent", err as &dyn fmt::Debug),
+            Error::Transient { ref err, .. } => ("Transient", err as &dyn fmt::Debug),
         };
         f.debug_tuple(name).field(err).finish()
     }
@@ -110,18 +232,14 @@ where
 {
     fn description(&self) -> &str {
         match *self {
-            Error::Permanent(_) => PERMANENT_ERROR,
+            Error::Permanent(..) => PERMANENT_ERROR,
             Error::Transient { .. } => TRANSIENT_ERROR,
         }
     }
 
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match *self {
-            Error::Permanent(ref err)
-            | Error::Transient {
-                ref err,
-                retry_after: _,
-            } => err.source(),
+            Error::Permanent(ref err, ..) | Error::Transient { ref err, .. } => err.source(),
         }
     }
 
@@ -133,12 +251,13 @@ where
 /// By default all errors are transient. Permanent errors can
 /// be constructed explicitly. This implementation is for making
 /// the question mark operator (?) and the `try!` macro to work.
+///
+/// Removed by the `no-implicit-transient` feature, so that every error site has
+/// to be classified explicitly.
+#[cfg(not(feature = "no-implicit-transient"))]
 impl<E> From<E> for Error<E> {
     fn from(err: E) -> Error<E> {
-        Error::Transient {
-            err,
-            retry_after: None,
-        }
+        Error::transient(err)
     }
 }
 
@@ -148,17 +267,19 @@ where
 {
     fn eq(&self, other: &Self) -> bool {
         match (self, other) {
-            (Error::Permanent(ref self_err), Error::Permanent(ref other_err)) => {
+            (Error::Permanent(ref self_err, ..), Error::Permanent(ref other_err, ..)) => {
                 self_err == other_err
             }
             (
                 Error::Transient {
                     err: self_err,
                     retry_after: self_retry_after,
+                    ..
                 },
                 Error::Transient {
                     err: other_err,
                     retry_after: other_retry_after,
+                    ..
                 },
             ) => self_err == other_err && self_retry_after == other_retry_after,
             _ => false,
@@ -166,6 +287,165 @@ where
     }
 }
 
+/// How an error should be handled, as decided by the classifier of
+/// [`Retry::spawn_map`](crate::Retry::spawn_map).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RetryClass {
+    /// Retry according to the strategy.
+    Transient,
+    /// Retry after the given duration instead of the strategy delay.
+    RetryAfter(Duration),
+    /// Stop retrying.
+    Permanent,
+}
+
+impl RetryClass {
+    /// Wraps `err` into the matching [`Error`].
+    pub fn classify<E>(self, err: E) -> Error<E> {
+        match self {
+            RetryClass::Transient => Error::transient(err),
+            RetryClass::RetryAfter(duration) => Error::retry_after(err, duration),
+            RetryClass::Permanent => Error::permanent(err),
+        }
+    }
+}
+
+/// `NotReady` is returned by [`Retry::spawn_some`](crate::Retry::spawn_some) when the
+/// strategy is exhausted and the action still yields `None`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NotReady;
+
+impl fmt::Display for NotReady {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.write_str(NOT_READY_ERROR)
+    }
+}
+
+impl error::Error for NotReady {}
+
+/// `BreakerError` is returned by [`Retry::spawn_with_breaker`](crate::Retry::spawn_with_breaker).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BreakerError<E> {
+    /// The last attempt failed with this error.
+    Action(E),
+    /// The circuit breaker is open, so no more attempts were made.
+    Open {
+        /// The error of the last attempt, or `None` if the breaker was already open
+        /// before the first one.
+        last_error: Option<E>,
+        /// The time left until the breaker lets probe attempts through, or its
+        /// `open_for` while it is half-open with every probe running already, as a
+        /// failing probe opens it again for that long.
+        retry_in: Duration,
+    },
+}
+
+impl<E> fmt::Display for BreakerError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            BreakerError::Action(err) => err.fmt(f),
+            BreakerError::Open {
+                last_error: Some(last_error),
+                retry_in,
+            } => write!(
+                f,
+                "{last_error} ({CIRCUIT_OPEN_ERROR}, retry in {retry_in:?})"
+            ),
+            BreakerError::Open {
+                last_error: None,
+                retry_in,
+            } => write!(f, "{CIRCUIT_OPEN_ERROR}, retry in {retry_in:?}"),
+        }
+    }
+}
+
+impl<E> error::Error for BreakerError<E>
+where
+    E: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            BreakerError::Action(err)
+            | BreakerError::Open {
+                last_error: Some(err),
+                ..
+            } => Some(err),
+            BreakerError::Open {
+                last_error: None, ..
+            } => None,
+        }
+    }
+}
+
+/// `TimeoutError` is returned by [`Retry::spawn_with_timeouts`](crate::Retry::spawn_with_timeouts).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeoutError<E> {
+    /// The last attempt failed with this error.
+    Action(E),
+    /// The last attempt did not complete within the per-attempt timeout.
+    AttemptTimedOut,
+    /// The overall deadline was reached while an attempt was running.
+    DeadlineExceeded,
+    /// The last attempt failed with a `retry_after` hint ending past the overall
+    /// deadline, and the retry failed fast, see [`RetryAfterVsDeadline::FailFast`].
+    ///
+    /// [`RetryAfterVsDeadline::FailFast`]: crate::RetryAfterVsDeadline::FailFast
+    RetryAfterPastDeadline {
+        /// The error of the last attempt.
+        last_error: E,
+        /// The hint of the last attempt, to propagate to callers.
+        retry_after: Duration,
+    },
+}
+
+impl<E> TimeoutError<E> {
+    /// The `retry_after` hint of the last attempt, if it ended past the deadline.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            TimeoutError::RetryAfterPastDeadline { retry_after, .. } => Some(*retry_after),
+            _ => None,
+        }
+    }
+}
+
+impl<E> fmt::Display for TimeoutError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            TimeoutError::Action(err) => err.fmt(f),
+            TimeoutError::AttemptTimedOut => f.write_str(ATTEMPT_TIMED_OUT_ERROR),
+            TimeoutError::DeadlineExceeded => f.write_str(DEADLINE_EXCEEDED_ERROR),
+            TimeoutError::RetryAfterPastDeadline {
+                last_error,
+                retry_after,
+            } => write!(
+                f,
+                "{last_error} (retry after {retry_after:?}, past the deadline)"
+            ),
+        }
+    }
+}
+
+impl<E> error::Error for TimeoutError<E>
+where
+    E: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TimeoutError::Action(err)
+            | TimeoutError::RetryAfterPastDeadline {
+                last_error: err, ..
+            } => Some(err),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(feature = "implicit_results")]
 #[derive(Debug, PartialEq)]
 pub enum RetryResult<T, E> {
@@ -212,14 +492,13 @@ impl<T: PartialEq, E: PartialEq> PartialEq<RetryResult<T, E>> for Result<T, Erro
 }
 
 #[cfg(feature = "implicit_results")]
-impl<T> From<Option<T>> for RetryResult<T, String> {
-    fn from(r: Option<T>) -> RetryResult<T, String> {
+impl<T> From<Option<T>> for RetryResult<T, alloc::string::String> {
+    fn from(r: Option<T>) -> RetryResult<T, alloc::string::String> {
         match r {
             Some(t) => Self::Ok(t),
-            None => Self::Err(Error::Transient {
-                err: String::from(TRANSIENT_ERROR),
-                retry_after: None,
-            }),
+            None => Self::Err(Error::transient(alloc::string::String::from(
+                TRANSIENT_ERROR,
+            ))),
         }
     }
 }
@@ -256,32 +535,34 @@ mod test {
     #[test]
     fn create_permanent_error() {
         let e = Error::permanent("err");
-        assert_eq!(e, Error::Permanent("err"));
+        assert!(matches!(e, Error::Permanent("err", ..)));
     }
 
     #[test]
     fn create_transient_error() {
         let e = Error::transient("err");
-        assert_eq!(
+        assert!(matches!(
             e,
             Error::Transient {
                 err: "err",
-                retry_after: None
+                retry_after: None,
+                ..
             }
-        );
+        ));
     }
 
     #[test]
     fn create_transient_error_with_retry_after() {
         let retry_after = Duration::from_secs(42);
         let e = Error::retry_after("err", retry_after);
-        assert_eq!(
+        assert!(matches!(
             e,
             Error::Transient {
                 err: "err",
-                retry_after: Some(retry_after),
-            }
-        );
+                retry_after: Some(after),
+                ..
+            } if after == retry_after
+        ));
     }
 
     #[test]
@@ -293,13 +574,7 @@ mod test {
     #[test]
     fn map_transient_maps_err() {
         let result: Result<(), Error<&str>> = Err("err").map_transient_err();
-        assert_eq!(
-            result,
-            Err::<(), Error<&str>>(Error::Transient {
-                err: "err",
-                retry_after: None
-            })
-        );
+        assert_eq!(result, Err::<(), Error<&str>>(Error::transient("err")));
     }
 
     #[test]
@@ -311,43 +586,92 @@ mod test {
     #[test]
     fn map_permanent_maps_err() {
         let result: Result<(), Error<&str>> = Err("err").map_permanent_err();
-        assert_eq!(result, Err(Error::Permanent("err")));
+        assert_eq!(result, Err(Error::permanent("err")));
     }
 
     #[test]
     fn fmt_permanent_error() {
-        let error = Error::Permanent(PERMANENT_ERROR);
+        let error = Error::permanent(PERMANENT_ERROR);
         let formatted = format!("{}", error);
         assert_eq!(formatted, PERMANENT_ERROR);
     }
 
+    #[test]
+    fn fmt_not_ready_error() {
+        let formatted = format!("{}", NotReady);
+        assert_eq!(formatted, NOT_READY_ERROR);
+    }
+
     #[test]
     fn fmt_transient_error() {
-        let error = Error::Transient {
-            err: TRANSIENT_ERROR,
-            retry_after: None,
-        };
+        let error = Error::transient(TRANSIENT_ERROR);
         let formatted = format!("{}", error);
         assert_eq!(formatted, TRANSIENT_ERROR);
     }
 
     #[test]
     fn debug_permanent_error() {
-        let error = Error::Permanent(PERMANENT_ERROR);
+        let error = Error::permanent(PERMANENT_ERROR);
         let debug = format!("{:?}", error);
         assert_eq!(debug, "Permanent(\"permanent error\")");
     }
 
     #[test]
     fn debug_transient_error() {
-        let error = Error::Transient {
-            err: TRANSIENT_ERROR,
-            retry_after: None,
-        };
+        let error = Error::transient(TRANSIENT_ERROR);
         let debug = format!("{:?}", error);
         assert_eq!(debug, "Transient(\"transient error\")");
     }
 
+    #[test]
+    #[cfg(feature = "std")]
+    fn io_error_keeps_kind() {
+        let error = Error::permanent(io::Error::from(io::ErrorKind::NotFound));
+        let io_error: io::Error = error.into();
+        assert_eq!(io_error.kind(), io::ErrorKind::NotFound);
+
+        // whatever the features, the original error is handed back as is
+        let error = Error::transient(io::Error::other(MyError(TRANSIENT_ERROR)));
+        let io_error: io::Error = error.into();
+        assert!(io_error.get_ref().unwrap().is::<MyError>());
+
+        let io_error = Error::transient(MyError(TRANSIENT_ERROR)).into_io_error();
+        assert_eq!(io_error.kind(), io::ErrorKind::Other);
+        assert_eq!(io_error.to_string(), TRANSIENT_ERROR);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn io_error_carries_classification() {
+        let io_error = Error::permanent(MyError(PERMANENT_ERROR)).into_io_error();
+        let classified = io_error
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<ClassifiedError>())
+            .unwrap();
+        assert!(classified.is_permanent());
+        assert_eq!(
+            classified.backtrace().is_some(),
+            cfg!(feature = "backtrace")
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "backtrace")]
+    fn backtrace_is_captured_at_classification() {
+        let error = Error::transient(MyError(TRANSIENT_ERROR));
+        let Error::Transient { ref site, .. } = error else {
+            unreachable!()
+        };
+        let captured = std::sync::Arc::as_ptr(&site.backtrace);
+        let io_error = error.into_io_error();
+        let classified = io_error
+            .get_ref()
+            .and_then(|err| err.downcast_ref::<ClassifiedError>())
+            .unwrap();
+        assert!(!classified.is_permanent());
+        assert!(std::ptr::eq(classified.backtrace().unwrap(), captured));
+    }
+
     #[test]
     fn description_permanent_error() {
         let error = Error::permanent(MyError(PERMANENT_ERROR));
@@ -417,29 +741,19 @@ mod test {
     #[test]
     #[cfg(feature = "implicit_results")]
     fn from_err_permanent() {
-        let error = Error::Permanent(PERMANENT_ERROR);
+        let error = Error::permanent(PERMANENT_ERROR);
         let result: Result<i32, Error<&str>> = Err(error);
         let retry_result: RetryResult<i32, &str> = result.into();
-        assert_eq!(retry_result, Err(Error::Permanent(PERMANENT_ERROR)).into());
+        assert_eq!(retry_result, Err(Error::permanent(PERMANENT_ERROR)).into());
     }
 
     #[test]
     #[cfg(feature = "implicit_results")]
     fn from_err_transient() {
-        let error = Error::Transient {
-            err: TRANSIENT_ERROR,
-            retry_after: None,
-        };
+        let error = Error::transient(TRANSIENT_ERROR);
         let result: Result<i32, Error<&str>> = Err(error);
         let retry_result: RetryResult<i32, &str> = result.into();
-        assert_eq!(
-            retry_result,
-            Err(Error::Transient {
-                err: TRANSIENT_ERROR,
-                retry_after: None
-            })
-            .into()
-        );
+        assert_eq!(retry_result, Err(Error::transient(TRANSIENT_ERROR)).into());
     }
 
     #[test]
@@ -450,11 +764,7 @@ mod test {
         let retry_result: RetryResult<i32, MyError> = result.into();
         assert_eq!(
             retry_result,
-            Err(Error::Transient {
-                err: MyError("my error"),
-                retry_after: None
-            })
-            .into()
+            Err(Error::transient(MyError("my error"))).into()
         );
     }
 
@@ -466,11 +776,7 @@ mod test {
         let retry_result: RetryResult<i32, MyError> = result.into();
         assert_eq!(
             retry_result,
-            Err(Error::Transient {
-                err: MyError("my transient error"),
-                retry_after: None
-            })
-            .into()
+            Err(Error::transient(MyError("my transient error"))).into()
         );
     }
 
@@ -489,11 +795,7 @@ mod test {
         let retry_result: RetryResult<i32, String> = option.into();
         assert_eq!(
             retry_result,
-            Err(Error::Transient {
-                err: String::from(TRANSIENT_ERROR),
-                retry_after: None
-            })
-            .into()
+            Err(Error::transient(String::from(TRANSIENT_ERROR))).into()
         );
     }
 
@@ -4,15 +4,30 @@ use std::fmt;
 use std::future::Future;
 use std::iter::{IntoIterator, Iterator};
 use std::pin::Pin;
-use std::task::{Context, Poll};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::task::{ready, Context, Poll};
 
-use pin_project::pin_project;
+use pin_project::{pin_project, pinned_drop};
+use tokio::sync::futures::OwnedNotified;
 use tokio::time::{sleep_until, Duration, Instant, Sleep};
 
-use crate::error::Error as RetryError;
-use crate::notify::Notify;
+use crate::async_notify::AsyncNotify;
+use crate::breaker::CircuitBreaker;
+use crate::delay::{self, DelayFn, DelayRules, Planned, Resolution};
+use crate::error::{BreakerError, Error as RetryError, TimeoutError};
+use crate::history::ErrorHistory;
+use crate::load_gate::{GateDecision, LoadGate};
+use crate::notify::{AttemptInfo, DelaySource, Notify, StopReason, Watchdog, WatchdogAlert};
+use crate::pacer::Pacer;
+use crate::report::RetryReport;
+use crate::singleflight::SingleFlight;
+use crate::strategy::{RetryBudget, Strategy};
+use crate::timeout::{RetryAfterVsDeadline, Timer};
 
-use super::action::Action;
+use super::action::{
+    Action, ClassifyAction, ContextAction, CountedAction, DeadlineAction, FoldAction, OptionAction,
+};
 use super::condition::Condition;
 
 #[pin_project(project = RetryStateProj)]
@@ -22,6 +37,11 @@ where
 {
     Running(#[pin] A::Future),
     Sleeping(#[pin] Sleep),
+    /// A boxed attempt which can outlive the retry future, see [`RetryIf::cancel_grace`].
+    /// Only `None` once detached on drop.
+    Detachable(Option<DetachableAttempt<A>>),
+    /// A zero delay, which only waits for the task's coop budget instead of a timer tick.
+    Yielding,
 }
 
 impl<A: Action> RetryState<A> {
@@ -29,6 +49,14 @@ impl<A: Action> RetryState<A> {
         match self.project() {
             RetryStateProj::Running(future) => RetryFuturePoll::Running(future.poll(cx)),
             RetryStateProj::Sleeping(future) => RetryFuturePoll::Sleeping(future.poll(cx)),
+            RetryStateProj::Detachable(future) => RetryFuturePoll::Running(
+                future
+                    .as_mut()
+                    .map_or(Poll::Pending, |future| future.as_mut().poll(cx)),
+            ),
+            RetryStateProj::Yielding => RetryFuturePoll::Sleeping(
+                tokio::task::coop::poll_proceed(cx).map(|coop| coop.made_progress()),
+            ),
         }
     }
 }
@@ -41,11 +69,43 @@ where
     Sleeping(Poll<()>),
 }
 
+/// Whether the first attempt runs when the strategy yields no delay at all.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FirstAttemptPolicy {
+    /// The action always runs at least once, even with an empty strategy.
+    /// This is the behavior of [`Retry::spawn`] and [`RetryIf::spawn`].
+    #[default]
+    Always,
+    /// The action doesn't run at all when the strategy is empty, e.g. when a scheduler
+    /// computed that no retries are allowed right now.
+    OnlyIfStrategyNonEmpty,
+}
+
+/// The tracing span every attempt runs in, see [`RetryIf::attempt_span`].
+///
+/// `retry.attempt` spans carry a `retry.id` field, unique to each retry future and
+/// shared by all of its attempts, and an `attempt` field, so traces can correlate all
+/// attempts of one logical operation.
+#[cfg(feature = "tracing")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AttemptSpan {
+    /// Attempts run in the span of the caller polling the retry future.
+    #[default]
+    Inherit,
+    /// Every attempt runs in a new `retry.attempt` span, a child of the caller's span.
+    Child,
+    /// Every attempt runs in a new `retry.attempt` root span, detached from the caller's.
+    Root,
+}
+
+#[cfg(feature = "tracing")]
+static NEXT_RETRY_ID: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(1);
+
 /// Future that drives multiple attempts at an action via a retry strategy.
 #[pin_project]
 pub struct Retry<I, A>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
 {
     #[pin]
@@ -54,9 +114,11 @@ where
 
 impl<I, A> Retry<I, A>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
 {
+    /// Retries `action` via `strategy`. The action runs at least once, even with an
+    /// empty strategy, see [`FirstAttemptPolicy::Always`].
     pub fn spawn<T: IntoIterator<IntoIter = I, Item = Duration>>(
         strategy: T,
         action: A,
@@ -71,6 +133,25 @@ where
         }
     }
 
+    /// Same as [`Retry::spawn`], but returns `None` without running `action` when
+    /// `first_attempt` is [`FirstAttemptPolicy::OnlyIfStrategyNonEmpty`] and the
+    /// strategy is empty.
+    pub fn spawn_with_first_attempt<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+        first_attempt: FirstAttemptPolicy,
+    ) -> Option<Retry<I, A>> {
+        Some(Retry {
+            retry_if: RetryIf::spawn_with_first_attempt(
+                strategy,
+                action,
+                (|_| true) as fn(&A::Error) -> bool,
+                (|_, _| {}) as fn(&A::Error, std::time::Duration),
+                first_attempt,
+            )?,
+        })
+    }
+
     pub fn spawn_notify<T: IntoIterator<IntoIter = I, Item = Duration>>(
         strategy: T,
         action: A,
@@ -85,11 +166,617 @@ where
             ),
         }
     }
+
+    /// Same as [`Retry::spawn_notify`], but awaits `notify` before every retry, so it
+    /// can do async work, e.g. push a metric or write to a channel, without blocking
+    /// the runtime. `notify` is awaited once the delay elapsed, right before the next
+    /// attempt. The returned [`RetryIf`] takes the same builders as [`Retry`], e.g.
+    /// [`RetryIf::on_success`].
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tokio::sync::mpsc;
+    /// use tokio_retry2::{Retry, RetryError};
+    /// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    ///
+    /// async fn fetch() -> Result<String, RetryError<String>> {
+    ///     RetryError::to_transient("unavailable".to_owned())
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let (events, _receiver) = mpsc::channel(16);
+    /// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+    /// let res = Retry::spawn_notify_async(strategy, fetch, |err: &String, delay: Duration| {
+    ///     let (events, err) = (events.clone(), err.clone());
+    ///     async move {
+    ///         let _ = events.send((err, delay)).await;
+    ///     }
+    /// })
+    /// .await;
+    /// # }
+    /// ```
+    pub fn spawn_notify_async<T, N>(
+        strategy: T,
+        action: A,
+        notify: N,
+    ) -> RetryIf<
+        I,
+        impl Action<Item = A::Item, Error = A::Error>,
+        fn(&A::Error) -> bool,
+        impl Notify<A::Error>,
+    >
+    where
+        T: IntoIterator<IntoIter = I, Item = Duration>,
+        N: AsyncNotify<A::Error>,
+    {
+        crate::async_notify::retry_notify_async(strategy, action, notify)
+    }
+
+    /// Same as [`Retry::spawn`], but the delay before every retry is computed by
+    /// `delay_fn` from the error and the delay planned by the strategy, capped at
+    /// `max`, see [`RetryIf::delay_fn`].
+    pub fn spawn_with_delay_fn<T, F>(
+        strategy: T,
+        action: A,
+        delay_fn: F,
+        max: Duration,
+    ) -> Retry<I, A>
+    where
+        T: IntoIterator<IntoIter = I, Item = Duration>,
+        F: FnMut(&A::Error, Duration) -> Duration + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: Retry::spawn(strategy, action)
+                .retry_if
+                .delay_fn(delay_fn, max),
+        }
+    }
+
+    /// Same as [`Retry::spawn`], but the final error is returned along with the
+    /// number of attempts made.
+    pub fn spawn_counted<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+    ) -> RetryCounted<I, A> {
+        RetryCounted {
+            retry: Retry::spawn(strategy, action),
+        }
+    }
+
+    /// Spawns the retry loop as a task of `scope`, so it is tracked by its parent: it is
+    /// awaited with `JoinSet::join_next` and cancelled by `JoinSet::shutdown` or when
+    /// `scope` is dropped, instead of sleeping on after the parent is gone.
+    ///
+    /// Must be called from within a tokio runtime.
+    #[cfg(feature = "rt")]
+    pub fn spawn_scoped<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        scope: &mut tokio::task::JoinSet<Result<A::Item, A::Error>>,
+        strategy: T,
+        action: A,
+    ) -> tokio::task::AbortHandle
+    where
+        I: Send + 'static,
+        A: Send + 'static,
+        A::Future: Send + 'static,
+        A::Item: Send + 'static,
+        A::Error: Send + 'static,
+    {
+        scope.spawn(Retry::spawn(strategy, action))
+    }
+
+    /// Returns a stream yielding the outcome of every attempt as it happens, for
+    /// progress reporting. The stream completes after the first success, a permanent
+    /// error, or once the strategy is exhausted.
+    #[cfg(feature = "stream")]
+    pub fn attempts<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+    ) -> crate::stream::Attempts<I, A> {
+        crate::stream::Attempts::new(strategy.into_iter(), action)
+    }
+
+    /// Retries `action`, recording the error of every failed attempt into `history`.
+    ///
+    /// Resolves with the recorded history if the retries are exhausted or an error
+    /// is permanent. Use [`ErrorHistory::sampled`] to bound its memory usage.
+    pub fn spawn_with_history<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+        history: ErrorHistory<A::Error>,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, ErrorHistory<A::Error>>>> {
+        WithHooks::run(action, |action| {
+            crate::history::retry_with_history(strategy, action, history)
+        })
+    }
+
+    /// Retries `action`, recording the start, duration, error and following delay of
+    /// every attempt into a [`RetryReport`](crate::RetryReport), returned along with
+    /// the result. Errors are recorded formatted with `Display`.
+    pub fn spawn_with_report<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+    ) -> WithHooks<impl Future<Output = (Result<A::Item, A::Error>, RetryReport)>>
+    where
+        A::Error: fmt::Display,
+    {
+        WithHooks::run(action, |action| {
+            crate::report::retry_with_report(strategy, action)
+        })
+    }
+
+    /// Retries `action`, unless a retry loop for `key` is already in flight in `group`,
+    /// in which case every caller receives a clone of the result of that loop. See
+    /// [`singleflight`](crate::singleflight).
+    pub fn spawn_keyed<K, T: IntoIterator<IntoIter = I, Item = Duration>>(
+        group: &SingleFlight<K, A::Item, A::Error>,
+        key: K,
+        strategy: T,
+        action: A,
+    ) -> impl Future<Output = Result<A::Item, A::Error>>
+    where
+        K: Eq + std::hash::Hash + Clone,
+        A::Item: Clone,
+        A::Error: Clone,
+    {
+        let group = group.clone();
+        async move { group.retry(key, strategy, action).await }
+    }
+
+    /// Retries `action` through `breaker`, shared by the retry loops calling the same
+    /// dependency. While it is open, the loop fails right away with
+    /// [`BreakerError::Open`] instead of sleeping and running `action` again. See
+    /// [`breaker`](crate::breaker).
+    ///
+    /// Transient errors count as failures of the dependency, permanent ones don't. When
+    /// the breaker stops the loop, its [`StopReason`] is [`StopReason::CircuitOpen`].
+    pub fn spawn_with_breaker<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        breaker: &CircuitBreaker,
+        action: A,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, BreakerError<A::Error>>>> {
+        WithHooks::run(action, |action| {
+            crate::breaker::retry_with_breaker(strategy, breaker.clone(), action)
+        })
+    }
+
+    /// Retries `action` with both a timeout for every attempt and an overall deadline.
+    ///
+    /// Every attempt is bounded by `per_attempt`, or by the time left before the
+    /// `overall` deadline if that is shorter. An attempt timing out is retried like a
+    /// transient error. No sleep runs past the deadline: when the next attempt would
+    /// start after it, the last error is returned right away. When that is because of
+    /// a `retry_after` hint, the error is [`TimeoutError::RetryAfterPastDeadline`].
+    pub fn spawn_with_timeouts<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+        per_attempt: Duration,
+        overall: Duration,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, TimeoutError<A::Error>>>> {
+        Retry::spawn_with_timeouts_policy(
+            strategy,
+            action,
+            per_attempt,
+            overall,
+            RetryAfterVsDeadline::FailFast,
+        )
+    }
+
+    /// Retries `action` until `deadline` elapses, cancelling the attempt in flight at
+    /// that point, unlike [`MaxInterval`](crate::strategy::MaxInterval), which only
+    /// stops issuing new retries.
+    ///
+    /// Resolves with [`TimeoutError::DeadlineExceeded`] when an attempt is cancelled.
+    /// When the next attempt would start past the deadline, the last error is returned
+    /// right away instead of sleeping. Same as [`Retry::spawn_with_timeouts`] with a
+    /// per-attempt timeout of `deadline`.
+    pub fn spawn_with_deadline<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        deadline: Duration,
+        action: A,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, TimeoutError<A::Error>>>> {
+        Retry::spawn_with_timeouts(strategy, action, deadline, deadline)
+    }
+
+    /// Same as [`Retry::spawn_with_timeouts`], with `on_retry_after` choosing what
+    /// happens when a `retry_after` hint would delay the next attempt past the deadline,
+    /// e.g. when a server says to retry in 60s with 10s left.
+    pub fn spawn_with_timeouts_policy<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+        per_attempt: Duration,
+        overall: Duration,
+        on_retry_after: RetryAfterVsDeadline,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, TimeoutError<A::Error>>>> {
+        WithHooks::run(action, |action| {
+            crate::timeout::retry_with_timeouts(
+                strategy,
+                action,
+                per_attempt,
+                overall,
+                on_retry_after,
+            )
+        })
+    }
+
+    /// Same as [`Retry::spawn_with_timeouts`], with the timeouts, deadline and sleeps
+    /// driven by `timer` instead of `tokio::time`, e.g. to run on async-std, smol or
+    /// wasm. See [`Timer`].
+    pub fn spawn_with_timeouts_on<T, K>(
+        strategy: T,
+        action: A,
+        per_attempt: Duration,
+        overall: Duration,
+        timer: K,
+    ) -> WithHooks<impl Future<Output = Result<A::Item, TimeoutError<A::Error>>>>
+    where
+        T: IntoIterator<IntoIter = I, Item = Duration>,
+        K: Timer,
+    {
+        WithHooks::run(action, |action| {
+            crate::timeout::retry_with_timeouts_on(
+                strategy,
+                action,
+                per_attempt,
+                overall,
+                RetryAfterVsDeadline::FailFast,
+                timer,
+            )
+        })
+    }
+
+    /// Applies jitter ranging between `min * retry_after` and `max * retry_after` to
+    /// `retry_after` hints, so clients told to come back at the same time don't all
+    /// retry at the same instant.
+    #[cfg(feature = "jitter-std")]
+    pub fn jitter_retry_after(self, min: f64, max: f64) -> Self {
+        Retry {
+            retry_if: self.retry_if.jitter_retry_after(min, max),
+        }
+    }
+
+    /// Marks an unbounded strategy as intentional, silencing the warning emitted
+    /// with the `tracing` feature when retrying with a strategy reporting itself as
+    /// infinite, i.e. whose `size_hint` has a lower bound of `usize::MAX`, as the
+    /// built-in strategies do until bounded, e.g. with `max_retries`.
+    pub fn unbounded_ok(self) -> Self {
+        Retry {
+            retry_if: self.retry_if.unbounded_ok(),
+        }
+    }
+
+    /// Waits for a slot of `pacer` before every retry, after the back-off delay.
+    pub fn paced(self, pacer: Pacer) -> Self {
+        Retry {
+            retry_if: self.retry_if.paced(pacer),
+        }
+    }
+
+    /// See [`RetryIf::load_gated`].
+    pub fn load_gated(self, gate: LoadGate) -> Self {
+        Retry {
+            retry_if: self.retry_if.load_gated(gate),
+        }
+    }
+
+    /// See [`RetryIf::rate_limited`].
+    #[cfg(feature = "governor")]
+    pub fn rate_limited(self, limiter: Arc<governor::DefaultDirectRateLimiter>) -> Self {
+        Retry {
+            retry_if: self.retry_if.rate_limited(limiter),
+        }
+    }
+
+    /// See [`RetryIf::compensate_overrun`].
+    pub fn compensate_overrun(self, floor: Duration) -> Self {
+        Retry {
+            retry_if: self.retry_if.compensate_overrun(floor),
+        }
+    }
+
+    /// See [`RetryIf::triggered_by`].
+    pub fn triggered_by(self, trigger: Arc<tokio::sync::Notify>) -> Self {
+        Retry {
+            retry_if: self.retry_if.triggered_by(trigger),
+        }
+    }
+
+    /// See [`RetryIf::watchdog`].
+    pub fn watchdog<F>(self, watchdog: Watchdog, hook: F) -> Self
+    where
+        F: FnOnce(&A::Error, &WatchdogAlert) + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.watchdog(watchdog, hook),
+        }
+    }
+
+    /// See [`RetryIf::retry_budget`].
+    pub fn retry_budget(self, budget: RetryBudget) -> Self {
+        Retry {
+            retry_if: self.retry_if.retry_budget(budget),
+        }
+    }
+
+    /// See [`RetryIf::on_cancelled`].
+    pub fn on_cancelled<F>(self, hook: F) -> Self
+    where
+        F: FnOnce(Option<&A::Error>) + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.on_cancelled(hook),
+        }
+    }
+
+    /// See [`RetryIf::on_success`].
+    pub fn on_success<F>(self, hook: F) -> Self
+    where
+        F: FnOnce(&A::Item, u32, Duration) + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.on_success(hook),
+        }
+    }
+
+    /// See [`RetryIf::on_exhausted`].
+    pub fn on_exhausted<F>(self, hook: F) -> Self
+    where
+        F: FnOnce(&A::Error, u32) + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.on_exhausted(hook),
+        }
+    }
+
+    /// See [`RetryIf::on_retry`].
+    pub fn on_retry<F>(self, hook: F) -> Self
+    where
+        F: FnMut(u32, Duration) + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.on_retry(hook),
+        }
+    }
+
+    /// See [`RetryIf::cancel_grace`].
+    pub fn cancel_grace(self, grace: Duration) -> Self
+    where
+        A::Future: Send + 'static,
+        A::Item: Send + 'static,
+        A::Error: Send + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.cancel_grace(grace),
+        }
+    }
+
+    /// See [`RetryIf::stats_label`].
+    #[cfg(feature = "stats")]
+    pub fn stats_label<F>(self, label_fn: F) -> Self
+    where
+        F: Fn(&A::Error) -> &'static str + Send + Sync + 'static,
+    {
+        Retry {
+            retry_if: self.retry_if.stats_label(label_fn),
+        }
+    }
+
+    /// See [`RetryIf::attempt_span`].
+    #[cfg(feature = "tracing")]
+    pub fn attempt_span(self, attempt_span: AttemptSpan) -> Self {
+        Retry {
+            retry_if: self.retry_if.attempt_span(attempt_span),
+        }
+    }
+
+    /// See [`RetryIf::with_stop_reason`].
+    pub fn with_stop_reason(
+        self,
+    ) -> WithStopReason<I, A, fn(&A::Error) -> bool, fn(&A::Error, Duration)> {
+        self.retry_if.with_stop_reason()
+    }
+}
+
+impl<I, F, C> Retry<I, ClassifyAction<F, C>>
+where
+    I: Strategy,
+    ClassifyAction<F, C>: Action,
+{
+    /// Retries an existing action resolving to a plain `Result<T, E>`, with
+    /// `classifier` deciding whether each error is retried, so neither a wrapper
+    /// function nor a change of error type is needed.
+    ///
+    /// ```rust,no_run
+    /// use tokio_retry2::{Retry, RetryClass};
+    /// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    ///
+    /// async fn fetch() -> Result<String, std::io::Error> {
+    ///     Ok(String::new())
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), std::io::Error> {
+    /// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+    /// let body = Retry::spawn_map(strategy, fetch, |err: &std::io::Error| {
+    ///     match err.kind() {
+    ///         std::io::ErrorKind::NotFound => RetryClass::Permanent,
+    ///         _ => RetryClass::Transient,
+    ///     }
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_map<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: F,
+        classifier: C,
+    ) -> Retry<I, ClassifyAction<F, C>> {
+        Retry::spawn(strategy, ClassifyAction::new(action, classifier))
+    }
+}
+
+impl<I, Acc, F> Retry<I, FoldAction<Acc, F>>
+where
+    I: Strategy,
+    FoldAction<Acc, F>: Action,
+{
+    /// Retries an action making incremental progress, e.g. a paginated sync, keeping
+    /// its progress across attempts.
+    ///
+    /// `step` runs on a clone of the accumulator, starting from `init`. A
+    /// [`Step::Progress`] replaces the accumulator and runs the next step right away,
+    /// without sleeping or consuming the strategy. A [`Step::Err`] ends the attempt:
+    /// a transient error is retried after the next delay, from the last accumulator.
+    /// The strategy thus only bounds the failed steps, across the whole fold.
+    ///
+    /// ```rust,no_run
+    /// use tokio_retry2::{Retry, RetryError, Step};
+    /// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    ///
+    /// async fn fetch_page(cursor: u32) -> Result<(Vec<u64>, Option<u32>), std::io::Error> {
+    ///     Ok((vec![], None))
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), std::io::Error> {
+    /// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+    /// let items = Retry::spawn_fold(strategy, (0, Vec::new()), |(cursor, mut items)| async move {
+    ///     match fetch_page(cursor).await {
+    ///         Ok((page, Some(next))) => {
+    ///             items.extend(page);
+    ///             Step::Progress((next, items))
+    ///         }
+    ///         Ok((page, None)) => {
+    ///             items.extend(page);
+    ///             Step::Done(items)
+    ///         }
+    ///         Err(err) => Step::Err(RetryError::transient(err)),
+    ///     }
+    /// })
+    /// .await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// [`Step::Progress`]: crate::Step::Progress
+    /// [`Step::Err`]: crate::Step::Err
+    pub fn spawn_fold<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        init: Acc,
+        step: F,
+    ) -> Retry<I, FoldAction<Acc, F>> {
+        Retry::spawn(strategy, FoldAction::new(init, step))
+    }
+}
+
+impl<I, F> Retry<I, OptionAction<F>>
+where
+    I: Strategy,
+    OptionAction<F>: Action,
+{
+    /// Retries an action resolving to an `Option` while it yields `None`, and
+    /// resolves with the first `Some` value. Resolves with [`NotReady`](crate::NotReady)
+    /// once the strategy is exhausted.
+    pub fn spawn_some<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: F,
+    ) -> Retry<I, OptionAction<F>> {
+        Retry::spawn(strategy, OptionAction(action))
+    }
+}
+
+impl<I, F> Retry<I, ContextAction<F>>
+where
+    I: Strategy,
+    ContextAction<F>: Action,
+{
+    /// Retries an action receiving the [`RetryContext`](crate::RetryContext) of every
+    /// attempt, with its number, the time elapsed since the first one and the back-off
+    /// delay before it, e.g. to log it or to send different headers on retries.
+    ///
+    /// ```rust,no_run
+    /// use tokio_retry2::{Retry, RetryContext, RetryError};
+    /// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    ///
+    /// async fn fetch(is_retry: bool) -> Result<String, RetryError<String>> {
+    ///     Ok(String::new())
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> Result<(), String> {
+    /// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+    /// let body = Retry::spawn_with_context(strategy, |ctx: RetryContext| fetch(ctx.attempt > 1)).await?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn spawn_with_context<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: F,
+    ) -> Retry<I, ContextAction<F>> {
+        Retry::spawn(strategy, ContextAction::new(action))
+    }
+}
+
+impl<I, F> Retry<I, DeadlineAction<F>>
+where
+    I: Strategy,
+    DeadlineAction<F>: Action,
+{
+    /// Retries `action` until the `overall` deadline, passing it the time left on
+    /// every attempt, so it can fit its own client timeout within it instead of using
+    /// a fixed per-attempt timeout which may exceed what's left.
+    ///
+    /// Attempts are still cancelled at the deadline, and no sleep runs past it, like
+    /// with [`Retry::spawn_with_deadline`].
+    ///
+    /// ```rust,no_run
+    /// use std::time::Duration;
+    /// use tokio_retry2::{AttemptContext, Retry, RetryError};
+    /// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    ///
+    /// async fn fetch(timeout: Duration) -> Result<String, RetryError<String>> {
+    ///     Ok(String::new())
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+    /// let body = Retry::spawn_with_time_left(strategy, Duration::from_secs(5), |ctx: AttemptContext| {
+    ///     fetch(ctx.remaining.min(Duration::from_secs(2)))
+    /// })
+    /// .await;
+    /// # }
+    /// ```
+    pub fn spawn_with_time_left<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        overall: Duration,
+        action: F,
+    ) -> WithHooks<
+        impl Future<
+            Output = Result<
+                <DeadlineAction<F> as Action>::Item,
+                TimeoutError<<DeadlineAction<F> as Action>::Error>,
+            >,
+        >,
+    > {
+        let action = DeadlineAction::new(action, Instant::now() + overall);
+        // attempts are bounded by the time left, set before the loop's own deadline
+        WithHooks::run(action, |action| {
+            crate::timeout::retry_with_timeouts(
+                strategy,
+                action,
+                overall,
+                overall,
+                RetryAfterVsDeadline::FailFast,
+            )
+        })
+    }
 }
 
 impl<I, A> Future for Retry<I, A>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
 {
     type Output = Result<A::Item, A::Error>;
@@ -100,12 +787,289 @@ where
     }
 }
 
+/// Future that drives multiple attempts at an action via a retry strategy, and resolves
+/// with the number of attempts along with the final error,
+/// created by [`Retry::spawn_counted`].
+#[pin_project]
+pub struct RetryCounted<I, A>
+where
+    I: Strategy,
+    A: Action,
+{
+    #[pin]
+    retry: Retry<I, A>,
+}
+
+impl<I, A> Future for RetryCounted<I, A>
+where
+    I: Strategy,
+    A: Action,
+{
+    type Output = Result<A::Item, (A::Error, u32)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut retry = self.project().retry;
+        match retry.as_mut().poll(cx) {
+            Poll::Ready(Err(err)) => Poll::Ready(Err((err, retry.retry_if.attempts()))),
+            Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future that drives multiple attempts at an action via a retry strategy, and resolves
+/// with the reason it stopped along with the final error,
+/// created by [`RetryIf::with_stop_reason`].
+#[pin_project]
+pub struct WithStopReason<I, A, C, N>
+where
+    I: Strategy,
+    A: Action,
+    C: Condition<A::Error>,
+    N: Notify<A::Error>,
+{
+    #[pin]
+    retry_if: RetryIf<I, A, C, N>,
+}
+
+impl<I, A, C, N> Future for WithStopReason<I, A, C, N>
+where
+    I: Strategy,
+    A: Action,
+    C: Condition<A::Error>,
+    N: Notify<A::Error>,
+{
+    type Output = Result<A::Item, (A::Error, StopReason)>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut retry_if = self.project().retry_if;
+        match retry_if.as_mut().poll(cx) {
+            Poll::Ready(Err(err)) => {
+                let reason = retry_if
+                    .stop_reason
+                    .expect("a retry loop only gives up once it stopped");
+                Poll::Ready(Err((err, reason)))
+            }
+            Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok)),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Future of a retry loop running on its own, or mapping the outcome of a [`RetryIf`],
+/// which still takes the [`on_success`](WithHooks::on_success) and
+/// [`on_exhausted`](WithHooks::on_exhausted) hooks, e.g. as returned by
+/// [`Retry::spawn_with_breaker`] or [`with_reauth`](crate::with_reauth).
+#[pin_project]
+pub struct WithHooks<F>
+where
+    F: Future,
+    F::Output: outcome::Outcome,
+{
+    #[pin]
+    future: F,
+    attempts: Arc<AtomicU32>,
+    started: Option<Instant>,
+    on_success: Option<SuccessHook<outcome::Item<F>>>,
+    on_exhausted: Option<ExhaustedHook<outcome::Error<F>>>,
+}
+
+impl<F> WithHooks<F>
+where
+    F: Future,
+    F::Output: outcome::Outcome,
+{
+    /// Runs the loop built by `retry` around `action`, counting its attempts.
+    pub(crate) fn run<A>(action: A, retry: impl FnOnce(CountedAction<A>) -> F) -> Self {
+        let attempts = Arc::default();
+        let future = retry(CountedAction {
+            action,
+            attempts: Arc::clone(&attempts),
+        });
+        WithHooks::counted(future, attempts)
+    }
+
+    /// Wraps the loop `future`, whose attempts are counted into `attempts`.
+    pub(crate) fn counted(future: F, attempts: Arc<AtomicU32>) -> Self {
+        WithHooks {
+            future,
+            attempts,
+            started: None,
+            on_success: None,
+            on_exhausted: None,
+        }
+    }
+
+    /// Same as [`RetryIf::on_success`], with the item the future resolves with and the
+    /// time elapsed since it was first polled.
+    pub fn on_success<H>(mut self, hook: H) -> Self
+    where
+        H: FnOnce(&outcome::Item<F>, u32, Duration) + Send + Sync + 'static,
+    {
+        self.on_success = Some(Box::new(hook));
+        self
+    }
+
+    /// Same as [`RetryIf::on_exhausted`], with the error the future resolves with.
+    pub fn on_exhausted<H>(mut self, hook: H) -> Self
+    where
+        H: FnOnce(&outcome::Error<F>, u32) + Send + Sync + 'static,
+    {
+        self.on_exhausted = Some(Box::new(hook));
+        self
+    }
+}
+
+impl<F> Future for WithHooks<F>
+where
+    F: Future,
+    F::Output: outcome::Outcome,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let started = *this.started.get_or_insert_with(Instant::now);
+        let output = ready!(this.future.poll(cx));
+        let attempts = this.attempts.load(Ordering::Relaxed);
+        match outcome::Outcome::result(&output) {
+            Ok(item) => {
+                if let Some(hook) = this.on_success.take() {
+                    hook(item, attempts, started.elapsed());
+                }
+            }
+            Err(err) => {
+                if let Some(hook) = this.on_exhausted.take() {
+                    hook(err, attempts);
+                }
+            }
+        }
+        Poll::Ready(output)
+    }
+}
+
+mod outcome {
+    use std::future::Future;
+
+    use crate::report::RetryReport;
+
+    /// What a loop wrapped in a [`WithHooks`](super::WithHooks) resolves with.
+    pub trait Outcome {
+        /// The item the loop succeeds with.
+        type Item;
+        /// The error the loop gives up with.
+        type Error;
+
+        /// Whether the loop succeeded or gave up.
+        fn result(&self) -> Result<&Self::Item, &Self::Error>;
+    }
+
+    /// The item the loop `F` succeeds with.
+    pub type Item<F> = <<F as Future>::Output as Outcome>::Item;
+
+    /// The error the loop `F` gives up with.
+    pub type Error<F> = <<F as Future>::Output as Outcome>::Error;
+
+    impl<T, E> Outcome for Result<T, E> {
+        type Item = T;
+        type Error = E;
+
+        fn result(&self) -> Result<&T, &E> {
+            self.as_ref()
+        }
+    }
+
+    impl<T, E> Outcome for (Result<T, E>, RetryReport) {
+        type Item = T;
+        type Error = E;
+
+        fn result(&self) -> Result<&T, &E> {
+            self.0.as_ref()
+        }
+    }
+}
+
+/// A hook fired by [`RetryIf::watchdog`].
+type WatchdogHook<E> = Box<dyn FnOnce(&E, &WatchdogAlert) + Send + Sync>;
+
+/// A hook fired by [`RetryIf::on_cancelled`].
+type CancelHook<E> = Box<dyn FnOnce(Option<&E>) + Send + Sync>;
+
+/// A hook fired by [`RetryIf::on_retry`].
+type RetryHook = Box<dyn FnMut(u32, Duration) + Send + Sync>;
+
+/// A hook fired by [`RetryIf::on_success`].
+type SuccessHook<T> = Box<dyn FnOnce(&T, u32, Duration) + Send + Sync>;
+
+/// A hook fired by [`RetryIf::on_exhausted`].
+type ExhaustedHook<E> = Box<dyn FnOnce(&E, u32) + Send + Sync>;
+
+/// An attempt boxed so it can be handed over to a detached task.
+type DetachableAttempt<A> = Pin<
+    Box<dyn Future<Output = Result<<A as Action>::Item, RetryError<<A as Action>::Error>>> + Send>,
+>;
+
+/// How the in-flight attempt is detached on drop, see [`RetryIf::cancel_grace`].
+struct CancelGrace<A: Action> {
+    grace: Duration,
+    boxed: fn(A::Future) -> DetachableAttempt<A>,
+    detach: fn(DetachableAttempt<A>, Duration),
+}
+
+fn box_attempt<A>(future: A::Future) -> DetachableAttempt<A>
+where
+    A: Action,
+    A::Future: Send + 'static,
+{
+    Box::pin(future)
+}
+
+/// Runs `attempt` to completion in a task of its own, for at most `grace`.
+fn detach_attempt<A>(attempt: DetachableAttempt<A>, grace: Duration)
+where
+    A: Action,
+    A::Item: Send + 'static,
+    A::Error: Send + 'static,
+{
+    let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("dropping the cancelled attempt: no runtime to detach it onto");
+        return;
+    };
+    runtime.spawn(async move {
+        if tokio::time::timeout(grace, attempt).await.is_err() {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                ?grace,
+                "cancelled attempt didn't complete within its grace period"
+            );
+        }
+    });
+}
+
 /// Future that drives multiple attempts at an action via a retry strategy. Retries are only attempted if
 /// the `Error` returned by the future satisfies a given condition.
-#[pin_project]
+///
+/// When the loop stops without succeeding, the reason is reported to [`Notify::notify_stop`]
+/// and, with the `tracing` feature, logged. Dropping the future before it completes
+/// reports [`StopReason::Cancelled`].
+///
+/// Dropping the future also drops the attempt in flight, if any, at whichever await
+/// point it reached, so a side-effectful action may or may not have taken effect. No
+/// further attempt is made. Use [`RetryIf::on_cancelled`] to learn about it, and
+/// [`RetryIf::cancel_grace`] to let the attempt in flight complete on its own instead.
+///
+/// Zero delays skip the timer, but every retry consumes tokio's cooperative task budget,
+/// so an always-ready failing action yields to other tasks once the budget is spent
+/// instead of monopolizing its worker.
+///
+/// [`Retry`] and the other entry points of the crate resolving with a future run on
+/// this loop, so they all log their [`StopReason`], count in the `stats` feature and
+/// honor `test_util::with_policy_override`.
+#[pin_project(PinnedDrop)]
 pub struct RetryIf<I, A, C, N>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
     C: Condition<A::Error>,
     N: Notify<A::Error>,
@@ -117,63 +1081,565 @@ where
     condition: C,
     duration: Duration,
     notify: N,
+    started: Instant,
+    attempt_start: Instant,
+    attempts: u32,
+    finished: bool,
+    stop_reason: Option<StopReason>,
+    warn_unbounded: bool,
+    retry_after_jitter: Option<(f64, f64)>,
+    peeked: Option<Duration>,
+    pacer: Option<Pacer>,
+    paced: bool,
+    load_gate: Option<LoadGate>,
+    gated: bool,
+    trigger: Option<Arc<tokio::sync::Notify>>,
+    triggered: Option<Pin<Box<OwnedNotified>>>,
+    overrun_floor: Option<Duration>,
+    watchdog: Option<(Watchdog, WatchdogHook<A::Error>)>,
+    delay_fn: Option<(DelayFn<A::Error>, Duration)>,
+    retry_budget: Option<RetryBudget>,
+    on_cancelled: Option<CancelHook<A::Error>>,
+    last_error: Option<A::Error>,
+    history: Option<ErrorHistory<A::Error>>,
+    on_success: Option<SuccessHook<A::Item>>,
+    on_exhausted: Option<ExhaustedHook<A::Error>>,
+    on_retry: Option<RetryHook>,
+    cancel_grace: Option<CancelGrace<A>>,
+    action_sleeps: bool,
+    #[cfg(feature = "governor")]
+    rate_limiter: Option<Arc<governor::DefaultDirectRateLimiter>>,
+    #[cfg(feature = "tracing")]
+    span_mode: AttemptSpan,
+    #[cfg(feature = "tracing")]
+    span: Option<tracing::Span>,
+    #[cfg(feature = "tracing")]
+    retry_id: u64,
+    #[cfg(feature = "stats")]
+    stats_label: Option<Box<dyn Fn(&A::Error) -> &'static str + Send + Sync>>,
+    #[cfg(feature = "test-util")]
+    policy_override: Option<Box<dyn Strategy + Send + Sync>>,
 }
 
 impl<I, A, C, N> RetryIf<I, A, C, N>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
     C: Condition<A::Error>,
     N: Notify<A::Error>,
 {
+    /// Retries `action` via `strategy` while `condition` holds. The action runs at
+    /// least once, even with an empty strategy, see [`FirstAttemptPolicy::Always`].
     pub fn spawn<T: IntoIterator<IntoIter = I, Item = Duration>>(
         strategy: T,
+        action: A,
+        condition: C,
+        notify: N,
+    ) -> RetryIf<I, A, C, N> {
+        RetryIf::start(strategy.into_iter(), None, action, condition, notify)
+    }
+
+    /// Same as [`RetryIf::spawn`], but returns `None` without running `action` when
+    /// `first_attempt` is [`FirstAttemptPolicy::OnlyIfStrategyNonEmpty`] and the
+    /// strategy is empty.
+    pub fn spawn_with_first_attempt<T: IntoIterator<IntoIter = I, Item = Duration>>(
+        strategy: T,
+        action: A,
+        condition: C,
+        notify: N,
+        first_attempt: FirstAttemptPolicy,
+    ) -> Option<RetryIf<I, A, C, N>> {
+        let mut strategy = strategy.into_iter();
+        let peeked = match first_attempt {
+            FirstAttemptPolicy::Always => None,
+            // the delay is kept for the first retry
+            FirstAttemptPolicy::OnlyIfStrategyNonEmpty => Some(strategy.next()?),
+        };
+        Some(RetryIf::start(strategy, peeked, action, condition, notify))
+    }
+
+    fn start(
+        strategy: I,
+        peeked: Option<Duration>,
         mut action: A,
         condition: C,
         notify: N,
     ) -> RetryIf<I, A, C, N> {
+        #[cfg(feature = "stats")]
+        {
+            crate::stats::retry_started();
+            crate::stats::attempt_started();
+        }
         RetryIf {
-            strategy: strategy.into_iter(),
+            strategy,
             state: RetryState::Running(action.run()),
             action,
             condition,
             duration: Duration::from_millis(0),
             notify,
+            started: Instant::now(),
+            attempt_start: Instant::now(),
+            attempts: 1,
+            finished: false,
+            stop_reason: None,
+            warn_unbounded: true,
+            retry_after_jitter: None,
+            peeked,
+            pacer: None,
+            paced: false,
+            load_gate: None,
+            gated: false,
+            trigger: None,
+            triggered: None,
+            overrun_floor: None,
+            watchdog: None,
+            delay_fn: None,
+            retry_budget: None,
+            on_cancelled: None,
+            last_error: None,
+            history: None,
+            on_success: None,
+            on_exhausted: None,
+            on_retry: None,
+            cancel_grace: None,
+            action_sleeps: false,
+            #[cfg(feature = "governor")]
+            rate_limiter: None,
+            #[cfg(feature = "tracing")]
+            span_mode: AttemptSpan::Inherit,
+            #[cfg(feature = "tracing")]
+            span: None,
+            #[cfg(feature = "tracing")]
+            retry_id: NEXT_RETRY_ID.fetch_add(1, std::sync::atomic::Ordering::Relaxed),
+            #[cfg(feature = "stats")]
+            stats_label: None,
+            #[cfg(feature = "test-util")]
+            policy_override: crate::test_util::policy_override(),
         }
     }
 
-    fn attempt(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<A::Item, A::Error>> {
+    /// Leaves the delays to the action, which gets them through [`Action::before_retry`]
+    /// and sleeps them itself, on a timer of its own, before its next attempt.
+    pub(crate) fn action_sleeps(mut self) -> Self {
+        self.action_sleeps = true;
+        self
+    }
+
+    /// Applies jitter ranging between `min * retry_after` and `max * retry_after` to
+    /// `retry_after` hints, so clients told to come back at the same time don't all
+    /// retry at the same instant.
+    #[cfg(feature = "jitter-std")]
+    pub fn jitter_retry_after(mut self, min: f64, max: f64) -> Self {
+        self.retry_after_jitter = Some((min, max));
+        self
+    }
+
+    /// The number of attempts made so far, including the running one.
+    pub(crate) const fn attempts(&self) -> u32 {
+        self.attempts
+    }
+
+    /// Records the error of every retried attempt into `history` instead of dropping
+    /// it, see [`Retry::spawn_with_history`].
+    pub(crate) fn record_history(mut self, history: ErrorHistory<A::Error>) -> Self {
+        self.history = Some(history);
+        self
+    }
+
+    /// Takes the history recorded so far, see [`RetryIf::record_history`].
+    pub(crate) fn take_history(self: Pin<&mut Self>) -> Option<ErrorHistory<A::Error>> {
+        self.project().history.take()
+    }
+
+    /// Marks an unbounded strategy as intentional, silencing the warning emitted
+    /// with the `tracing` feature when retrying with a strategy reporting itself as
+    /// infinite, i.e. whose `size_hint` has a lower bound of `usize::MAX`, as the
+    /// built-in strategies do until bounded, e.g. with `max_retries`.
+    pub fn unbounded_ok(mut self) -> Self {
+        self.warn_unbounded = false;
+        self
+    }
+
+    /// Waits for a slot of `pacer` before every retry, after the back-off delay.
+    pub fn paced(mut self, pacer: Pacer) -> Self {
+        self.pacer = Some(pacer);
+        self
+    }
+
+    /// Asks `gate` before every retry, after the back-off delay and before any pacer
+    /// slot or rate limiter cell, whether to postpone it, e.g. while the CPU of the
+    /// host is saturated. See [`load_gate`](crate::load_gate).
+    pub fn load_gated(mut self, gate: LoadGate) -> Self {
+        self.load_gate = Some(gate);
+        self
+    }
+
+    /// Waits for a cell of `limiter` before every retry, after the back-off delay and
+    /// any pacer slot, so retries count against the same client-side rate limit as the
+    /// first attempts made through it.
+    #[cfg(feature = "governor")]
+    pub fn rate_limited(mut self, limiter: Arc<governor::DefaultDirectRateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Subtracts the execution time of the failed attempt from the next strategy delay,
+    /// without going below `floor`, so attempts start at the cadence the strategy
+    /// intends even when they run long. `retry_after` hints are kept as is.
+    pub fn compensate_overrun(mut self, floor: Duration) -> Self {
+        self.overrun_floor = Some(floor);
+        self
+    }
+
+    /// Cuts the back-off delay short when `trigger` is notified, e.g. on a config push
+    /// or a connectivity event, so the next attempt runs right away. The strategy delay
+    /// then only bounds how long to wait without a notification.
+    ///
+    /// A permit stored by [`Notify::notify_one`](tokio::sync::Notify::notify_one) before
+    /// the back-off started also ends it.
+    pub fn triggered_by(mut self, trigger: Arc<tokio::sync::Notify>) -> Self {
+        self.trigger = Some(trigger);
+        self
+    }
+
+    /// Calls `hook` once, with the error of the failed attempt, when the loop is about
+    /// to retry past a threshold of `watchdog`, e.g. to page someone about an operation
+    /// still failing after 10 minutes. The hook is never called when the loop stops.
+    pub fn watchdog<F>(mut self, watchdog: Watchdog, hook: F) -> Self
+    where
+        F: FnOnce(&A::Error, &WatchdogAlert) + Send + Sync + 'static,
+    {
+        self.watchdog = Some((watchdog, Box::new(hook)));
+        self
+    }
+
+    /// Computes the delay before every retry with `delay_fn`, from the error of the
+    /// failed attempt and the delay planned by the strategy, e.g. to honor a "slow
+    /// down" hint found in an error payload. The computed delay is capped at `max`,
+    /// as the error may ask for any delay, while the `max_delay` of the strategy only
+    /// caps the delays it yields.
+    ///
+    /// The strategy still decides when to stop, and a `retry_after` hint still takes
+    /// precedence, in which case `delay_fn` isn't called.
+    pub fn delay_fn<F>(mut self, delay_fn: F, max: Duration) -> Self
+    where
+        F: FnMut(&A::Error, Duration) -> Duration + Send + Sync + 'static,
+    {
+        self.delay_fn = Some((Box::new(delay_fn), max));
+        self
+    }
+
+    /// Spends one retry from `budget` before every retry, stopping with
+    /// [`StopReason::BudgetExhausted`] once it is spent, and records a success in it
+    /// when an attempt succeeds, so many retry loops share a cap on their retries. See
+    /// [`RetryBudget`].
+    ///
+    /// Unlike [`WhileBudget::while_budget`](crate::strategy::WhileBudget::while_budget),
+    /// which only spends the budget, this also refills it from successful calls.
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_budget = Some(budget);
+        self
+    }
+
+    /// Calls `hook` if the future is dropped before it completes, with the error of the
+    /// last failed attempt, or `None` if the first attempt was still running, e.g. to
+    /// record that a side-effectful operation was abandoned.
+    ///
+    /// The error of every retried attempt is kept until the next one fails, instead of
+    /// being dropped right away.
+    pub fn on_cancelled<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(Option<&A::Error>) + Send + Sync + 'static,
+    {
+        self.on_cancelled = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` when an attempt succeeds, with its result, the number of attempts
+    /// made and the time elapsed since the future was created, e.g. to emit a single
+    /// summary log or metric per operation, or to log which replica finally answered
+    /// once more than one attempt was made.
+    pub fn on_success<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&A::Item, u32, Duration) + Send + Sync + 'static,
+    {
+        self.on_success = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` when the loop gives up, with the final error and the number of
+    /// attempts made, right after [`Notify::notify_stop`] reported why. It isn't
+    /// called if the future is dropped, see [`RetryIf::on_cancelled`].
+    pub fn on_exhausted<F>(mut self, hook: F) -> Self
+    where
+        F: FnOnce(&A::Error, u32) + Send + Sync + 'static,
+    {
+        self.on_exhausted = Some(Box::new(hook));
+        self
+    }
+
+    /// Calls `hook` before sleeping ahead of every retry, with the retry number,
+    /// starting at `1`, and the delay actually slept, e.g. a `retry_after` hint rather
+    /// than the strategy delay. Retries refused by a retry budget aren't reported.
+    pub fn on_retry<F>(mut self, hook: F) -> Self
+    where
+        F: FnMut(u32, Duration) + Send + Sync + 'static,
+    {
+        self.on_retry = Some(Box::new(hook));
+        self
+    }
+
+    /// Lets the attempt in flight when the future is dropped run on in a detached task
+    /// for at most `grace`, instead of being dropped mid-way. Its outcome is discarded
+    /// and it is never retried. A back-off in progress is simply cancelled.
+    ///
+    /// Every attempt is boxed to make this possible. Without a tokio runtime at drop
+    /// time, the attempt is dropped as usual.
+    pub fn cancel_grace(mut self, grace: Duration) -> Self
+    where
+        A::Future: Send + 'static,
+        A::Item: Send + 'static,
+        A::Error: Send + 'static,
+    {
+        let boxed = box_attempt::<A>;
+        self.state = match std::mem::replace(&mut self.state, RetryState::Yielding) {
+            RetryState::Running(future) => RetryState::Detachable(Some(boxed(future))),
+            state => state,
+        };
+        self.cancel_grace = Some(CancelGrace {
+            grace,
+            boxed,
+            detach: detach_attempt::<A>,
+        });
+        self
+    }
+
+    /// Counts every retried error under the label `label_fn` returns for it, e.g.
+    /// `"timeout"` or `"5xx"`, in [`stats::retries_by_label`](crate::stats::retries_by_label).
+    #[cfg(feature = "stats")]
+    pub fn stats_label<F>(mut self, label_fn: F) -> Self
+    where
+        F: Fn(&A::Error) -> &'static str + Send + Sync + 'static,
+    {
+        self.stats_label = Some(Box::new(label_fn));
+        self
+    }
+
+    /// Chooses whether attempts inherit the caller's tracing span, which is the
+    /// default, or each run in a new `retry.attempt` span carrying the `retry.id`
+    /// of this future, see [`AttemptSpan`].
+    #[cfg(feature = "tracing")]
+    pub fn attempt_span(mut self, attempt_span: AttemptSpan) -> Self {
+        self.span_mode = attempt_span;
+        self
+    }
+
+    /// Resolves with the [`StopReason`] along with the final error, e.g. to map the
+    /// outcome to an API error with [`api::Exhausted`](crate::api), without
+    /// implementing [`Notify::notify_stop`].
+    pub fn with_stop_reason(self) -> WithStopReason<I, A, C, N> {
+        WithStopReason { retry_if: self }
+    }
+
+    /// The span the running attempt is polled in, created on first use.
+    #[cfg(feature = "tracing")]
+    fn span(self: Pin<&mut Self>) -> Option<tracing::Span> {
+        let this = self.project();
+        if !matches!(
+            this.state.project(),
+            RetryStateProj::Running(_) | RetryStateProj::Detachable(_)
+        ) {
+            return None;
+        }
+        let (retry_id, attempt) = (*this.retry_id, *this.attempts);
+        let span = match this.span_mode {
+            AttemptSpan::Inherit => return None,
+            AttemptSpan::Child => this.span.get_or_insert_with(|| {
+                tracing::info_span!("retry.attempt", retry.id = retry_id, attempt)
+            }),
+            AttemptSpan::Root => this.span.get_or_insert_with(
+                || tracing::info_span!(parent: None, "retry.attempt", retry.id = retry_id, attempt),
+            ),
+        };
+        Some(span.clone())
+    }
+
+    fn attempt(mut self: Pin<&mut Self>) {
         let future = {
             let mut this = self.as_mut().project();
-            this.action.run()
+            *this.attempt_start = Instant::now();
+            *this.attempts = this.attempts.saturating_add(1);
+            *this.paced = false;
+            *this.gated = false;
+            #[cfg(feature = "tracing")]
+            {
+                *this.span = None;
+            }
+            #[cfg(feature = "stats")]
+            crate::stats::attempt_started();
+            let future = this.action.run();
+            match this.cancel_grace {
+                Some(cancel_grace) => RetryState::Detachable(Some((cancel_grace.boxed)(future))),
+                None => RetryState::Running(future),
+            }
+        };
+        self.as_mut().project().state.set(future);
+    }
+
+    /// Resolves the delay before retrying after `err`, see [`delay::resolve`].
+    fn resolve_delay(
+        self: Pin<&mut Self>,
+        err: &A::Error,
+        retry_after: Option<Duration>,
+    ) -> Resolution {
+        let this = self.project();
+        if *this.warn_unbounded {
+            *this.warn_unbounded = false;
+            #[cfg(feature = "tracing")]
+            if this.strategy.size_hint().0 == usize::MAX {
+                tracing::warn!(
+                    "retrying with an unbounded strategy: bound it with `max_retries` or `max_duration`, or call `unbounded_ok`"
+                );
+            }
+        }
+        #[cfg(feature = "test-util")]
+        let overridden = this.policy_override.is_some();
+        #[cfg(not(feature = "test-util"))]
+        let overridden = false;
+        let rules = DelayRules {
+            retry_after_jitter: *this.retry_after_jitter,
+            delay_fn: this
+                .delay_fn
+                .as_mut()
+                .map(|(delay_fn, max)| (delay_fn, *max)),
+            retry_budget: this.retry_budget.as_ref(),
+            overrun: this
+                .overrun_floor
+                .map(|floor| (floor, this.attempt_start.elapsed())),
+            overridden,
+        };
+        let (strategy, peeked) = (this.strategy, this.peeked);
+        #[cfg(feature = "test-util")]
+        let policy_override = this.policy_override;
+        let next = move || {
+            #[cfg(feature = "test-util")]
+            if let Some(policy_override) = policy_override {
+                let delay = policy_override.next();
+                return Planned::of(policy_override, delay);
+            }
+            let delay = peeked.take().or_else(|| strategy.next());
+            Planned::of(strategy, delay)
         };
-        self.as_mut()
-            .project()
-            .state
-            .set(RetryState::Running(future));
-        self.poll(cx)
+        delay::resolve(next, err, retry_after, this.condition, rules)
+    }
+
+    /// How long to wait for a cell of the rate limiter, if any, before the next attempt.
+    /// Returns `None` once a cell was taken, or without a rate limiter.
+    fn rate_limit_wait(&self) -> Option<Duration> {
+        #[cfg(feature = "governor")]
+        if let Some(limiter) = &self.rate_limiter {
+            use governor::clock::Clock;
+            if let Err(not_until) = limiter.check() {
+                return Some(not_until.wait_time_from(limiter.clock().now()));
+            }
+        }
+        None
+    }
+
+    /// Fires the watchdog, at most once, if a threshold is crossed.
+    fn check_watchdog(self: Pin<&mut Self>, err: &A::Error) {
+        let this = self.project();
+        let alert = WatchdogAlert {
+            attempts: *this.attempts,
+            elapsed: this.started.elapsed(),
+        };
+        if this
+            .watchdog
+            .as_ref()
+            .is_some_and(|(watchdog, _)| watchdog.is_exceeded(alert.attempts, alert.elapsed))
+        {
+            if let Some((_, hook)) = this.watchdog.take() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(
+                    attempts = alert.attempts,
+                    elapsed = ?alert.elapsed,
+                    "retry loop is still failing past its watchdog threshold"
+                );
+                hook(err, &alert);
+            }
+        }
+    }
+
+    fn stop(self: Pin<&mut Self>, reason: StopReason) {
+        let this = self.project();
+        *this.finished = true;
+        *this.stop_reason = Some(reason);
+        #[cfg(feature = "stats")]
+        crate::stats::retry_finished(Some(reason));
+        #[cfg(feature = "tracing")]
+        match reason {
+            // expected outcomes rather than limits being hit
+            StopReason::PermanentError
+            | StopReason::ConditionRejected
+            | StopReason::RetriesDisabled
+            | StopReason::Cancelled => tracing::debug!(
+                reason = reason.as_str(),
+                attempts = *this.attempts,
+                "ending retry"
+            ),
+            _ => tracing::warn!(
+                reason = reason.as_str(),
+                attempts = *this.attempts,
+                "ending retry"
+            ),
+        }
+        this.notify.notify_stop(reason);
+    }
+
+    /// Reports the final error once the loop stopped, and resolves with it.
+    fn give_up(self: Pin<&mut Self>, err: A::Error) -> Poll<Result<A::Item, A::Error>> {
+        let this = self.project();
+        if let Some(hook) = this.on_exhausted.take() {
+            hook(&err, *this.attempts);
+        }
+        Poll::Ready(Err(err))
     }
 
     fn retry(
         mut self: Pin<&mut Self>,
         err: A::Error,
-        cx: &mut Context,
-    ) -> Result<Poll<Result<A::Item, A::Error>>, A::Error> {
-        match self.as_mut().project().strategy.next() {
-            None => {
-                #[cfg(feature = "tracing")]
-                tracing::warn!("ending retry: strategy reached its limit");
+        next: Result<Duration, StopReason>,
+    ) -> Result<(), A::Error> {
+        match next {
+            Err(reason) => {
+                self.stop(reason);
                 Err(err)
             }
-            Some(duration) => {
-                *self.as_mut().project().duration += duration;
-                let deadline = Instant::now() + duration;
-                let future = sleep_until(deadline);
-                self.as_mut()
-                    .project()
-                    .state
-                    .set(RetryState::Sleeping(future));
-                Ok(self.poll(cx))
+            Ok(duration) => {
+                let this = self.as_mut().project();
+                *this.duration += duration;
+                this.action.before_retry(duration);
+                if let Some(hook) = this.on_retry {
+                    hook(*this.attempts, duration);
+                }
+                let state = if duration.is_zero() || *this.action_sleeps {
+                    RetryState::Yielding
+                } else {
+                    RetryState::Sleeping(sleep_until(Instant::now() + duration))
+                };
+                let mut this = self.as_mut().project();
+                this.state.set(state);
+                match this.history {
+                    Some(history) => history.push(err),
+                    // kept for the cancellation hook
+                    None if this.on_cancelled.is_some() => *this.last_error = Some(err),
+                    None => {}
+                }
+                *this.triggered = this
+                    .trigger
+                    .clone()
+                    .map(|trigger| Box::pin(trigger.notified_owned()));
+                Ok(())
             }
         }
     }
@@ -181,7 +1647,7 @@ where
 
 impl<I, A, C, N> Future for RetryIf<I, A, C, N>
 where
-    I: Iterator<Item = Duration>,
+    I: Strategy,
     A: Action,
     C: Condition<A::Error>,
     N: Notify<A::Error>,
@@ -189,32 +1655,163 @@ where
     type Output = Result<A::Item, A::Error>;
 
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
-        match self.as_mut().project().state.poll(cx) {
-            RetryFuturePoll::Running(poll_result) => match poll_result {
-                Poll::Ready(Ok(ok)) => Poll::Ready(Ok(ok)),
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(Err(error)) => match error {
-                    RetryError::Permanent(err) => Poll::Ready(Err(err)),
-                    RetryError::Transient { err, retry_after } => {
-                        if self.as_mut().project().condition.should_retry(&err) {
+        // loops instead of recursing, so a long run of zero delays can't grow the stack
+        loop {
+            let state = {
+                #[cfg(feature = "tracing")]
+                let _entered = self.as_mut().span().map(tracing::Span::entered);
+                self.as_mut().project().state.poll(cx)
+            };
+            match state {
+                RetryFuturePoll::Running(poll_result) => match poll_result {
+                    Poll::Ready(Ok(ok)) => {
+                        let this = self.as_mut().project();
+                        *this.finished = true;
+                        if let Some(budget) = this.retry_budget {
+                            budget.record_success();
+                        }
+                        if let Some(hook) = this.on_success.take() {
+                            hook(&ok, *this.attempts, this.started.elapsed());
+                        }
+                        #[cfg(feature = "stats")]
+                        crate::stats::retry_finished(None);
+                        return Poll::Ready(Ok(ok));
+                    }
+                    Poll::Pending => return Poll::Pending,
+                    Poll::Ready(Err(error)) => match error {
+                        RetryError::Permanent(err, ..) => {
+                            self.as_mut().stop(StopReason::PermanentError);
+                            return self.give_up(err);
+                        }
+                        RetryError::Transient {
+                            err, retry_after, ..
+                        } => {
+                            let Resolution {
+                                next,
+                                retry_after,
+                                source: delay_source,
+                            } = self.as_mut().resolve_delay(&err, retry_after);
+                            if next == Err(StopReason::ConditionRejected) {
+                                self.as_mut().stop(StopReason::ConditionRejected);
+                                return self.give_up(err);
+                            }
                             let duration =
                                 retry_after.unwrap_or(self.as_ref().project_ref().duration.clone());
-                            self.as_mut().project().notify.notify(&err, duration);
+                            let attempt = AttemptInfo {
+                                attempt_duration: self.attempt_start.elapsed(),
+                                retry_after,
+                                delay_source,
+                                next_delay: next.ok(),
+                            };
+                            self.as_mut()
+                                .project()
+                                .notify
+                                .notify_attempt(&err, duration, &attempt);
                             *self.as_mut().project().duration = duration;
-                            match self.retry(err, cx) {
-                                Ok(poll) => poll,
-                                Err(err) => Poll::Ready(Err(err)),
+                            if next.is_ok() {
+                                self.as_mut().check_watchdog(&err);
+                            }
+                            #[cfg(feature = "stats")]
+                            let label = self.stats_label.as_ref().map(|label_fn| label_fn(&err));
+                            if let Err(err) = self.as_mut().retry(err, next) {
+                                return self.give_up(err);
+                            }
+                            #[cfg(feature = "stats")]
+                            if let Some(label) = label {
+                                crate::stats::retried(label);
                             }
-                        } else {
-                            Poll::Ready(Err(err))
                         }
-                    }
+                    },
                 },
-            },
-            RetryFuturePoll::Sleeping(poll_result) => match poll_result {
-                Poll::Pending => Poll::Pending,
-                Poll::Ready(_) => self.attempt(cx),
-            },
+                RetryFuturePoll::Sleeping(poll_result) => {
+                    match poll_result {
+                        Poll::Pending => {
+                            let mut this = self.as_mut().project();
+                            let fired = this
+                                .triggered
+                                .as_mut()
+                                .is_some_and(|triggered| triggered.as_mut().poll(cx).is_ready());
+                            if !fired {
+                                return Poll::Pending;
+                            }
+                            // an external event cuts the back-off short
+                            *this.triggered = None;
+                            this.state.set(RetryState::Yielding);
+                        }
+                        Poll::Ready(_) => {
+                            let mut this = self.as_mut().project();
+                            *this.triggered = None;
+                            let decision = match this.load_gate {
+                                Some(gate) if !*this.gated => gate.check(),
+                                _ => GateDecision::Proceed,
+                            };
+                            *this.gated = true;
+                            if let GateDecision::Postpone(wait) | GateDecision::Delay(wait) =
+                                decision
+                            {
+                                #[cfg(feature = "tracing")]
+                                tracing::debug!(?wait, "retry postponed by the load gate");
+                                // the process is overloaded, ask the gate again later,
+                                // unless it only delays this retry
+                                *this.gated = matches!(decision, GateDecision::Delay(_));
+                                this.state.set(if wait.is_zero() {
+                                    RetryState::Yielding
+                                } else {
+                                    RetryState::Sleeping(sleep_until(Instant::now() + wait))
+                                });
+                                continue;
+                            }
+                            match this.pacer {
+                                // the back-off is over, now wait for a slot of the pacer
+                                Some(pacer) if !*this.paced => {
+                                    *this.paced = true;
+                                    let slot = pacer.reserve();
+                                    this.state.set(RetryState::Sleeping(sleep_until(slot)));
+                                }
+                                _ => match self.rate_limit_wait() {
+                                    // then for a cell of the rate limiter
+                                    Some(wait) => self.as_mut().project().state.set(
+                                        RetryState::Sleeping(sleep_until(Instant::now() + wait)),
+                                    ),
+                                    None => self.as_mut().attempt(),
+                                },
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[pinned_drop]
+impl<I, A, C, N> PinnedDrop for RetryIf<I, A, C, N>
+where
+    I: Strategy,
+    A: Action,
+    C: Condition<A::Error>,
+    N: Notify<A::Error>,
+{
+    fn drop(mut self: Pin<&mut Self>) {
+        if self.finished {
+            return;
+        }
+        let this = self.as_mut().project();
+        if let (Some(cancel_grace), RetryStateProj::Detachable(attempt)) =
+            (this.cancel_grace.as_ref(), this.state.project())
+        {
+            if let Some(attempt) = attempt.take() {
+                (cancel_grace.detach)(attempt, cancel_grace.grace);
+            }
+        }
+        if let Some(hook) = this.on_cancelled.take() {
+            let history = this.history.as_ref();
+            hook(
+                this.last_error
+                    .as_ref()
+                    .or_else(|| history.and_then(ErrorHistory::last_error)),
+            );
         }
+        self.stop(StopReason::Cancelled);
     }
 }
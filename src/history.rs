@@ -0,0 +1,162 @@
+use std::collections::VecDeque;
+use std::iter::IntoIterator;
+use std::pin::pin;
+
+use tokio::time::Duration;
+
+use crate::future::RetryIf;
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// The errors returned by the attempts of a retry loop,
+/// collected by [`Retry::spawn_with_history`](crate::Retry::spawn_with_history).
+///
+/// A history created with [`ErrorHistory::sampled`] is memory-bounded: it keeps the
+/// first and last `k` errors and only counts the ones in between, so long-running
+/// retry loops still tell how they started and how they ended.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ErrorHistory<E> {
+    sample: usize,
+    first: Vec<E>,
+    last: VecDeque<E>,
+    dropped: usize,
+}
+
+impl<E> ErrorHistory<E> {
+    /// Creates a history keeping every error.
+    pub fn unbounded() -> Self {
+        Self::sampled(usize::MAX)
+    }
+
+    /// Creates a history keeping only the first `k` and the last `k` errors,
+    /// plus a count of the dropped ones.
+    pub fn sampled(k: usize) -> Self {
+        ErrorHistory {
+            sample: k,
+            first: Vec::new(),
+            last: VecDeque::new(),
+            dropped: 0,
+        }
+    }
+
+    /// Records an error.
+    pub fn push(&mut self, err: E) {
+        if self.first.len() < self.sample {
+            self.first.push(err);
+            return;
+        }
+        self.last.push_back(err);
+        if self.last.len() > self.sample {
+            self.last.pop_front();
+            self.dropped += 1;
+        }
+    }
+
+    /// The first errors recorded, up to `k` of them in a sampled history.
+    pub fn first(&self) -> &[E] {
+        &self.first
+    }
+
+    /// The last errors recorded after [`first`](ErrorHistory::first), up to `k` of them.
+    pub fn last(&self) -> impl Iterator<Item = &E> {
+        self.last.iter()
+    }
+
+    /// The number of errors dropped between [`first`](ErrorHistory::first) and
+    /// [`last`](ErrorHistory::last).
+    pub const fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// The number of errors recorded, including the dropped ones.
+    pub fn total(&self) -> usize {
+        self.first.len() + self.last.len() + self.dropped
+    }
+
+    /// The kept errors, from the oldest to the most recent.
+    pub fn iter(&self) -> impl Iterator<Item = &E> {
+        self.first.iter().chain(self.last.iter())
+    }
+
+    /// The most recent error.
+    pub fn last_error(&self) -> Option<&E> {
+        self.last.back().or_else(|| self.first.last())
+    }
+
+    /// Removes the most recent error, see [`ErrorHistory::last_error`].
+    pub(crate) fn pop_last(&mut self) -> Option<E> {
+        self.last.pop_back().or_else(|| self.first.pop())
+    }
+}
+
+/// Drives `action` on a [`RetryIf`], recording every error into `history`,
+/// see [`Retry::spawn_with_history`](crate::Retry::spawn_with_history).
+pub(crate) async fn retry_with_history<T, A>(
+    strategy: T,
+    action: A,
+    history: ErrorHistory<A::Error>,
+) -> Result<A::Item, ErrorHistory<A::Error>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+{
+    let retry = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&A::Error) -> bool,
+        (|_, _| {}) as fn(&A::Error, Duration),
+    )
+    .record_history(history);
+    let mut retry = pin!(retry);
+
+    match retry.as_mut().await {
+        Ok(item) => Ok(item),
+        Err(err) => {
+            let mut history = retry
+                .take_history()
+                .expect("the history is only taken once the loop ended");
+            history.push(err);
+            Err(history)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unbounded_keeps_every_error() {
+        let mut history = ErrorHistory::unbounded();
+        (0..100).for_each(|err| history.push(err));
+
+        assert_eq!(history.total(), 100);
+        assert_eq!(history.dropped(), 0);
+        assert_eq!(history.iter().count(), 100);
+        assert_eq!(history.last_error(), Some(&99));
+    }
+
+    #[test]
+    fn sampled_keeps_first_and_last_errors() {
+        let mut history = ErrorHistory::sampled(2);
+        (0..10).for_each(|err| history.push(err));
+
+        assert_eq!(history.first(), &[0, 1]);
+        assert_eq!(history.last().copied().collect::<Vec<_>>(), vec![8, 9]);
+        assert_eq!(history.dropped(), 6);
+        assert_eq!(history.total(), 10);
+        assert_eq!(history.last_error(), Some(&9));
+    }
+
+    #[test]
+    fn sampled_with_few_errors_drops_nothing() {
+        let mut history = ErrorHistory::sampled(2);
+        (0..3).for_each(|err| history.push(err));
+
+        assert_eq!(history.iter().copied().collect::<Vec<_>>(), vec![0, 1, 2]);
+        assert_eq!(history.dropped(), 0);
+        assert_eq!(history.last_error(), Some(&2));
+    }
+}
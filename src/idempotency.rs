@@ -0,0 +1,220 @@
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::time::Duration;
+
+use crate::error::Error as RetryError;
+use crate::future::{RetryIf, WithHooks};
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// Tells whether a failed attempt actually succeeded server-side, consulted by
+/// [`with_idempotency`] before every retry.
+///
+/// Typically looks up the idempotency key sent along with the request, e.g. in the
+/// database the write goes to, or through a status endpoint of the remote service.
+pub trait IdempotencyStore<T> {
+    /// Returns the result of an earlier attempt that landed even though it appeared
+    /// to fail, e.g. because the response was lost, or `None` to execute the action again.
+    fn completed(&mut self) -> impl Future<Output = Option<T>>;
+}
+
+impl<T, S: IdempotencyStore<T> + ?Sized> IdempotencyStore<T> for &mut S {
+    fn completed(&mut self) -> impl Future<Output = Option<T>> {
+        (**self).completed()
+    }
+}
+
+/// Retries `action` via a retry strategy, unless `store` reports that a failed attempt
+/// did succeed, preventing duplicate side effects such as a write applied twice.
+///
+/// After every transient error and its back-off delay, `store` is consulted before
+/// the action runs again. If it holds a result, the loop resolves with it instead of
+/// re-executing the action. Permanent errors are returned right away.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{with_idempotency, IdempotencyStore, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// struct Payments {
+///     idempotency_key: String,
+/// }
+///
+/// impl IdempotencyStore<u64> for Payments {
+///     async fn completed(&mut self) -> Option<u64> {
+///         // look up the payment created with `self.idempotency_key`
+///         None
+///     }
+/// }
+///
+/// async fn charge(idempotency_key: &str) -> Result<u64, RetryError<std::io::Error>> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let idempotency_key = String::from("order-1234");
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// let payment = with_idempotency(
+///     strategy,
+///     || charge(&idempotency_key),
+///     Payments { idempotency_key: idempotency_key.clone() },
+/// )
+/// .await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn with_idempotency<S, A, St>(
+    strategy: S,
+    action: A,
+    store: St,
+) -> WithHooks<impl Future<Output = Result<A::Item, A::Error>>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+    St: IdempotencyStore<A::Item>,
+{
+    WithHooks::run(action, |action| idempotent(strategy, action, store))
+}
+
+fn idempotent<S, A, St>(
+    strategy: S,
+    action: A,
+    store: St,
+) -> impl Future<Output = Result<A::Item, A::Error>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+    St: IdempotencyStore<A::Item>,
+{
+    let action = Arc::new(Mutex::new(action));
+    let store = Arc::new(Mutex::new(Some(store)));
+    let mut retrying = false;
+    let attempt = {
+        let action = Arc::clone(&action);
+        move || {
+            let action = Arc::clone(&action);
+            let store = Arc::clone(&store);
+            let lookup = std::mem::replace(&mut retrying, true);
+            async move {
+                if lookup {
+                    // the store is put back once done, for the next retry
+                    let mut taken = lock(&store)
+                        .take()
+                        .expect("the store is put back after every lookup");
+                    let completed = taken.completed().await;
+                    *lock(&store) = Some(taken);
+                    if let Some(item) = completed {
+                        #[cfg(feature = "tracing")]
+                        tracing::debug!("a failed attempt succeeded server-side, not retrying");
+                        return Ok(item);
+                    }
+                }
+                let attempt = lock(&action).run();
+                attempt.await
+            }
+        }
+    };
+    RetryIf::spawn(
+        strategy,
+        IdempotentAction { action, attempt },
+        (|_| true) as fn(&A::Error) -> bool,
+        (|_, _| {}) as fn(&A::Error, Duration),
+    )
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An action consulting the store of [`with_idempotency`] before every retry, and
+/// only running the wrapped action if it holds no result.
+struct IdempotentAction<A, G> {
+    action: Arc<Mutex<A>>,
+    attempt: G,
+}
+
+impl<A, G, Fut> Action for IdempotentAction<A, G>
+where
+    A: Action,
+    G: FnMut() -> Fut,
+    Fut: Future<Output = Result<A::Item, RetryError<A::Error>>>,
+{
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = Fut;
+
+    fn run(&mut self) -> Self::Future {
+        (self.attempt)()
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        lock(&self.action).before_retry(delay);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::FixedInterval;
+
+    /// Reports the write as landed after `landed_after` lookups.
+    struct Store {
+        lookups: usize,
+        landed_after: usize,
+    }
+
+    impl IdempotencyStore<u64> for Store {
+        async fn completed(&mut self) -> Option<u64> {
+            self.lookups += 1;
+            (self.lookups >= self.landed_after).then_some(42)
+        }
+    }
+
+    #[tokio::test]
+    async fn short_circuits_once_the_write_landed() {
+        let attempts = AtomicUsize::new(0);
+        let res = with_idempotency(
+            FixedInterval::from_millis(1).take(5),
+            || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<u64>("response lost"))
+            },
+            Store {
+                lookups: 0,
+                landed_after: 2,
+            },
+        )
+        .await;
+
+        assert_eq!(res, Ok(42));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn skips_the_store_on_permanent_errors() {
+        let mut store = Store {
+            lookups: 0,
+            landed_after: 1,
+        };
+        let res = with_idempotency(
+            FixedInterval::from_millis(1).take(5),
+            || future::ready(RetryError::to_permanent::<u64>("rejected")),
+            &mut store,
+        )
+        .await;
+
+        assert_eq!(res, Err("rejected"));
+        assert_eq!(store.lookups, 0);
+    }
+}
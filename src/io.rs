@@ -0,0 +1,609 @@
+//! Retry adapters for `AsyncRead` and `AsyncWrite` streams.
+//!
+//! [`retry_reader`] and [`retry_writer`] wrap a stream together with a reconnect
+//! function. When the stream fails mid-way with a transient error, e.g. a reset
+//! connection, they back off, reconnect at the offset reached so far, and carry on as
+//! if nothing happened, so resumable downloads and uploads don't have to restart.
+//!
+//! ```rust,no_run
+//! use std::pin::pin;
+//! use tokio::io::AsyncReadExt;
+//! use tokio::net::TcpStream;
+//! use tokio_retry2::io::retry_reader;
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! /// Opens the download at `offset`, e.g. with an HTTP `Range` header.
+//! async fn open(offset: u64) -> std::io::Result<TcpStream> {
+//!     TcpStream::connect("127.0.0.1:8080").await
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> std::io::Result<()> {
+//! let mut download = pin!(retry_reader(open(0).await?, open, || {
+//!     ExponentialBackoff::from_millis(10).max_retries(5)
+//! }));
+//! let mut bytes = Vec::new();
+//! download.read_to_end(&mut bytes).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::time::{sleep, Duration, Sleep};
+
+use crate::condition::Condition;
+
+/// How a [`RetryReader`] picks up where it left off after reconnecting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Resume {
+    /// The reconnect function opens the stream at the offset it is given, e.g. with
+    /// an HTTP `Range` header.
+    #[default]
+    AtOffset,
+    /// The reconnect function is always given `0` and opens the stream from the
+    /// start, and the bytes read before the failure are skipped.
+    FromStart,
+    /// Only errors before the first byte is read are retried, for protocols which
+    /// can't resume at all.
+    Never,
+}
+
+/// The default condition of the IO adapters, retrying the errors of connections
+/// being lost, reset, refused or timing out.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TransientIo;
+
+impl Condition<io::Error> for TransientIo {
+    fn should_retry(&mut self, error: &io::Error) -> bool {
+        matches!(
+            error.kind(),
+            io::ErrorKind::ConnectionRefused
+                | io::ErrorKind::ConnectionReset
+                | io::ErrorKind::ConnectionAborted
+                | io::ErrorKind::NotConnected
+                | io::ErrorKind::BrokenPipe
+                | io::ErrorKind::TimedOut
+                | io::ErrorKind::Interrupted
+                | io::ErrorKind::UnexpectedEof
+        )
+    }
+}
+
+/// Wraps `reader` so that transient errors are retried by reconnecting with
+/// `reconnect`, given the offset to resume at, after a backoff.
+///
+/// Failures of `reconnect` are retried the same way. `strategy_factory` builds a
+/// fresh strategy after every successful read, so the strategy caps the retries
+/// between two reads that make progress. Once it is exhausted, the last error is
+/// returned.
+pub fn retry_reader<R, F, Fut, S, P>(
+    reader: R,
+    reconnect: F,
+    strategy_factory: S,
+) -> RetryReader<R, F, Fut, S, P>
+where
+    R: AsyncRead,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<R>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    RetryReader {
+        reader: Some(reader),
+        reconnect,
+        connecting: None,
+        strategy_factory,
+        strategy: None,
+        sleep: None,
+        condition: TransientIo,
+        resume: Resume::AtOffset,
+        offset: 0,
+        skip: 0,
+    }
+}
+
+/// Reader that reconnects and resumes the underlying reader on transient errors,
+/// created by the [`retry_reader`] function.
+#[pin_project]
+pub struct RetryReader<R, F, Fut, S, P, C = TransientIo>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    #[pin]
+    reader: Option<R>,
+    reconnect: F,
+    #[pin]
+    connecting: Option<Fut>,
+    strategy_factory: S,
+    strategy: Option<P::IntoIter>,
+    #[pin]
+    sleep: Option<Sleep>,
+    condition: C,
+    resume: Resume,
+    offset: u64,
+    skip: u64,
+}
+
+impl<R, F, Fut, S, P, C> RetryReader<R, F, Fut, S, P, C>
+where
+    R: AsyncRead,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<R>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: Condition<io::Error>,
+{
+    /// Sets how the reader resumes after reconnecting, [`Resume::AtOffset`] by default.
+    pub fn resume(mut self, resume: Resume) -> Self {
+        self.resume = resume;
+        self
+    }
+
+    /// Only retries errors satisfying `condition`, instead of [`TransientIo`].
+    pub fn condition<C2>(self, condition: C2) -> RetryReader<R, F, Fut, S, P, C2>
+    where
+        C2: Condition<io::Error>,
+    {
+        RetryReader {
+            reader: self.reader,
+            reconnect: self.reconnect,
+            connecting: self.connecting,
+            strategy_factory: self.strategy_factory,
+            strategy: self.strategy,
+            sleep: self.sleep,
+            condition,
+            resume: self.resume,
+            offset: self.offset,
+            skip: self.skip,
+        }
+    }
+
+    /// The number of bytes read so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl<R, F, Fut, S, P, C> AsyncRead for RetryReader<R, F, Fut, S, P, C>
+where
+    R: AsyncRead,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<R>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: Condition<io::Error>,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut().as_pin_mut() {
+                ready!(sleep.poll(cx));
+                this.sleep.set(None);
+                let offset = match this.resume {
+                    Resume::AtOffset => *this.offset,
+                    Resume::FromStart | Resume::Never => 0,
+                };
+                this.connecting.set(Some((this.reconnect)(offset)));
+            }
+
+            let result = if let Some(connecting) = this.connecting.as_mut().as_pin_mut() {
+                let connected = ready!(connecting.poll(cx));
+                this.connecting.set(None);
+                connected.map(|reader| {
+                    this.reader.set(Some(reader));
+                    if *this.resume == Resume::FromStart {
+                        *this.skip = *this.offset;
+                    }
+                })
+            } else if let Some(reader) = this.reader.as_mut().as_pin_mut() {
+                if *this.skip > 0 {
+                    // discards the bytes already read before the stream restarted
+                    let mut scratch = [0; 8192];
+                    let len = scratch
+                        .len()
+                        .min(usize::try_from(*this.skip).unwrap_or(usize::MAX));
+                    let mut scratch = ReadBuf::new(&mut scratch[..len]);
+                    ready!(reader.poll_read(cx, &mut scratch)).and_then(|()| {
+                        match scratch.filled().len() {
+                            0 => Err(io::ErrorKind::UnexpectedEof.into()),
+                            skipped => {
+                                *this.skip -= skipped as u64;
+                                Ok(())
+                            }
+                        }
+                    })
+                } else {
+                    let filled = buf.filled().len();
+                    let result = ready!(reader.poll_read(cx, buf));
+                    if result.is_ok() {
+                        let read = buf.filled().len() - filled;
+                        *this.offset += read as u64;
+                        if read > 0 {
+                            *this.strategy = None;
+                        }
+                        return Poll::Ready(Ok(()));
+                    }
+                    result
+                }
+            } else {
+                Err(io::ErrorKind::NotConnected.into())
+            };
+
+            if let Err(err) = result {
+                let resumable = *this.resume != Resume::Never || *this.offset == 0;
+                if !resumable || !this.condition.should_retry(&err) {
+                    return Poll::Ready(Err(err));
+                }
+                this.reader.set(None);
+                let strategy_factory = &mut *this.strategy_factory;
+                let strategy = this
+                    .strategy
+                    .get_or_insert_with(|| strategy_factory().into_iter());
+                match crate::delay::plain_delay(strategy, &err, None) {
+                    Some(duration) => this.sleep.set(Some(sleep(duration))),
+                    None => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!("ending read retry: strategy reached its limit");
+                        *this.strategy = None;
+                        return Poll::Ready(Err(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Wraps `writer` so that transient errors are retried by reconnecting with
+/// `reconnect`, given the offset to resume at, after a backoff.
+///
+/// Failures of `reconnect` are retried the same way, and it may fail with an error
+/// rejected by the condition if the protocol can't resume. `strategy_factory` builds
+/// a fresh strategy after every successful write, so the strategy caps the retries
+/// between two writes that make progress. Once it is exhausted, the last error is
+/// returned.
+///
+/// Only `poll_write` is retried. Bytes buffered by the underlying writer when its
+/// connection fails are counted as written but lost, so wrap an unbuffered writer, or
+/// one flushing what it accepts, for the offset to match what the peer received.
+pub fn retry_writer<W, F, Fut, S, P>(
+    writer: W,
+    reconnect: F,
+    strategy_factory: S,
+) -> RetryWriter<W, F, Fut, S, P>
+where
+    W: AsyncWrite,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<W>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    RetryWriter {
+        writer: Some(writer),
+        reconnect,
+        connecting: None,
+        strategy_factory,
+        strategy: None,
+        sleep: None,
+        condition: TransientIo,
+        offset: 0,
+    }
+}
+
+/// Writer that reconnects and resumes the underlying writer on transient errors,
+/// created by the [`retry_writer`] function.
+#[pin_project]
+pub struct RetryWriter<W, F, Fut, S, P, C = TransientIo>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    #[pin]
+    writer: Option<W>,
+    reconnect: F,
+    #[pin]
+    connecting: Option<Fut>,
+    strategy_factory: S,
+    strategy: Option<P::IntoIter>,
+    #[pin]
+    sleep: Option<Sleep>,
+    condition: C,
+    offset: u64,
+}
+
+impl<W, F, Fut, S, P, C> RetryWriter<W, F, Fut, S, P, C>
+where
+    W: AsyncWrite,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<W>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: Condition<io::Error>,
+{
+    /// Only retries errors satisfying `condition`, instead of [`TransientIo`].
+    pub fn condition<C2>(self, condition: C2) -> RetryWriter<W, F, Fut, S, P, C2>
+    where
+        C2: Condition<io::Error>,
+    {
+        RetryWriter {
+            writer: self.writer,
+            reconnect: self.reconnect,
+            connecting: self.connecting,
+            strategy_factory: self.strategy_factory,
+            strategy: self.strategy,
+            sleep: self.sleep,
+            condition,
+            offset: self.offset,
+        }
+    }
+
+    /// The number of bytes written so far.
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+
+    /// Runs `op` on the underlying writer once any reconnection in progress is done,
+    /// backing off and reconnecting after its failures if `retried`.
+    fn poll_op<T>(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        retried: bool,
+        mut op: impl FnMut(Pin<&mut W>, &mut Context<'_>) -> Poll<io::Result<T>>,
+    ) -> Poll<io::Result<T>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut().as_pin_mut() {
+                ready!(sleep.poll(cx));
+                this.sleep.set(None);
+                this.connecting.set(Some((this.reconnect)(*this.offset)));
+            }
+
+            let err = if let Some(connecting) = this.connecting.as_mut().as_pin_mut() {
+                let connected = ready!(connecting.poll(cx));
+                this.connecting.set(None);
+                match connected {
+                    Ok(writer) => {
+                        this.writer.set(Some(writer));
+                        continue;
+                    }
+                    Err(err) => err,
+                }
+            } else if let Some(writer) = this.writer.as_mut().as_pin_mut() {
+                match ready!(op(writer, cx)) {
+                    Ok(value) => return Poll::Ready(Ok(value)),
+                    Err(err) if !retried => return Poll::Ready(Err(err)),
+                    Err(err) => err,
+                }
+            } else {
+                io::ErrorKind::NotConnected.into()
+            };
+
+            if !this.condition.should_retry(&err) {
+                return Poll::Ready(Err(err));
+            }
+            this.writer.set(None);
+            let strategy_factory = &mut *this.strategy_factory;
+            let strategy = this
+                .strategy
+                .get_or_insert_with(|| strategy_factory().into_iter());
+            match crate::delay::plain_delay(strategy, &err, None) {
+                Some(duration) => this.sleep.set(Some(sleep(duration))),
+                None => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("ending write retry: strategy reached its limit");
+                    *this.strategy = None;
+                    return Poll::Ready(Err(err));
+                }
+            }
+        }
+    }
+}
+
+impl<W, F, Fut, S, P, C> AsyncWrite for RetryWriter<W, F, Fut, S, P, C>
+where
+    W: AsyncWrite,
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = io::Result<W>>,
+    S: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: Condition<io::Error>,
+{
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let written = ready!(self
+            .as_mut()
+            .poll_op(cx, true, |writer, cx| writer.poll_write(cx, buf)))?;
+        let this = self.project();
+        *this.offset += written as u64;
+        if written > 0 {
+            *this.strategy = None;
+        }
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_op(cx, false, AsyncWrite::poll_flush)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.poll_op(cx, false, AsyncWrite::poll_shutdown)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::pin::pin;
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use crate::strategy::FixedInterval;
+
+    const DATA: &[u8] = b"the quick brown fox jumps over the lazy dog";
+
+    /// Reader serving `DATA` from `pos`, resetting the connection at `fail_at`.
+    struct FlakyReader {
+        pos: usize,
+        fail_at: Option<usize>,
+    }
+
+    impl AsyncRead for FlakyReader {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &mut ReadBuf<'_>,
+        ) -> Poll<io::Result<()>> {
+            if self.fail_at == Some(self.pos) {
+                return Poll::Ready(Err(io::ErrorKind::ConnectionReset.into()));
+            }
+            let end = self.fail_at.unwrap_or(DATA.len()).min(self.pos + 5);
+            let len = (end - self.pos).min(buf.remaining());
+            buf.put_slice(&DATA[self.pos..self.pos + len]);
+            self.pos += len;
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_reading_at_offset() {
+        let offsets = Arc::new(Mutex::new(Vec::new()));
+        let cloned = offsets.clone();
+        let reader = FlakyReader {
+            pos: 0,
+            fail_at: Some(12),
+        };
+        let mut reader = pin!(retry_reader(
+            reader,
+            move |offset| {
+                cloned.lock().unwrap().push(offset);
+                future::ready(Ok(FlakyReader {
+                    pos: offset as usize,
+                    fail_at: None,
+                }))
+            },
+            || FixedInterval::from_millis(1).take(1),
+        ));
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.unwrap();
+        assert_eq!(bytes, DATA);
+        assert_eq!(reader.offset(), DATA.len() as u64);
+        assert_eq!(*offsets.lock().unwrap(), vec![12]);
+    }
+
+    #[tokio::test]
+    async fn skips_bytes_already_read_when_restarting() {
+        let reader = FlakyReader {
+            pos: 0,
+            fail_at: Some(12),
+        };
+        let mut reader = pin!(retry_reader(
+            reader,
+            |offset| {
+                assert_eq!(offset, 0);
+                future::ready(Ok(FlakyReader {
+                    pos: 0,
+                    fail_at: None,
+                }))
+            },
+            || FixedInterval::from_millis(1).take(1),
+        )
+        .resume(Resume::FromStart));
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await.unwrap();
+        assert_eq!(bytes, DATA);
+    }
+
+    #[tokio::test]
+    async fn returns_error_mid_stream_when_not_resumable() {
+        let reader = FlakyReader {
+            pos: 0,
+            fail_at: Some(12),
+        };
+        let mut reader = pin!(retry_reader(
+            reader,
+            |_| {
+                future::ready(Ok(FlakyReader {
+                    pos: 0,
+                    fail_at: None,
+                }))
+            },
+            || FixedInterval::from_millis(1).take(1),
+        )
+        .resume(Resume::Never));
+
+        let mut bytes = Vec::new();
+        let err = reader.read_to_end(&mut bytes).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::ConnectionReset);
+    }
+
+    /// Writer appending to a shared buffer, breaking the pipe after `budget` bytes.
+    struct FlakyWriter {
+        written: Arc<Mutex<Vec<u8>>>,
+        budget: usize,
+    }
+
+    impl AsyncWrite for FlakyWriter {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            _: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            if self.budget == 0 {
+                return Poll::Ready(Err(io::ErrorKind::BrokenPipe.into()));
+            }
+            let len = buf.len().min(self.budget);
+            self.budget -= len;
+            self.written.lock().unwrap().extend_from_slice(&buf[..len]);
+            Poll::Ready(Ok(len))
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    #[tokio::test]
+    async fn resumes_writing_at_offset() {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let cloned = written.clone();
+        let writer = FlakyWriter {
+            written: written.clone(),
+            budget: 10,
+        };
+        let mut writer = pin!(retry_writer(
+            writer,
+            move |offset| {
+                assert_eq!(cloned.lock().unwrap().len() as u64, offset);
+                future::ready(Ok(FlakyWriter {
+                    written: cloned.clone(),
+                    budget: 20,
+                }))
+            },
+            || FixedInterval::from_millis(1).take(1),
+        ));
+
+        writer.write_all(DATA).await.unwrap();
+        writer.shutdown().await.unwrap();
+        assert_eq!(*written.lock().unwrap(), DATA);
+        assert_eq!(writer.offset(), DATA.len() as u64);
+    }
+}
@@ -37,7 +37,7 @@
 //!
 //! ```toml
 //! [dependencies]
-//! tokio-retry2 = "0.5"
+//! tokio-retry2 = "0.6"
 //! ```
 //!
 //! # Example
@@ -45,7 +45,7 @@
 //! ```rust,no_run
 
 //! use tokio_retry2::{Retry, RetryError};
-//! use tokio_retry2::strategy::{ExponentialBackoff, MaxInterval};
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxInterval, MaxRetries};
 //!
 //! async fn action() -> Result<u64, RetryError<()>> {
 //!     // do some real-world stuff here...
@@ -53,12 +53,12 @@
 //! }
 //!
 //! # #[tokio::main]
-//! # async fn main() -> Result<(), RetryError<()>> {
+//! # async fn main() -> Result<(), ()> {
 //! let retry_strategy = ExponentialBackoff::from_millis(10)
 //!     .factor(1) // multiplication factor applied to deplay
 //!     .max_delay_millis(100) // set max delay between retries to 500ms
 //!     .max_interval(1000) // set max interval to 1 second for all retries
-//!     .take(3);    // limit to 3 retries
+//!     .max_retries(3); // limit to 3 retries
 //!
 //! let result = Retry::spawn(retry_strategy, action).await?;
 //! // First retry in 10ms, second in 100ms, third in 100ms
@@ -107,12 +107,13 @@
 //! `[jitter]`
 //! - `jitter` ranges between 50% and 150% of the strategy delay.
 //! - `jitter_range(min: f64, max: f64)` ranges between `min * Duration` and `max * Duration`.
+//! - `jitter_range_duration(min: Duration, max: Duration)` adds between `min` and `max` to the strategy delay.
 //!
 //! To use jitter, add this to your Cargo.toml
 //!
 //! ```toml
 //! [dependencies]
-//! tokio-retry2 = { version = "0.5", features = ["jitter"] }
+//! tokio-retry2 = { version = "0.6", features = ["jitter"] }
 //! ```
 //!
 //! # Example
@@ -120,6 +121,7 @@
 //! ## `jitter`
 //!
 //! ```rust,no_run
+//! # #[cfg(feature = "jitter-std")] {
 //! use tokio_retry2::Retry;
 //! use tokio_retry2::strategy::{ExponentialBackoff, jitter, MaxInterval};
 //!
@@ -127,11 +129,13 @@
 //!    .max_interval(10000) // set max interval to 10 seconds
 //!    .map(jitter) // add jitter to the retry interval
 //!    .take(3);    // limit to 3 retries
+//! # }
 //!````
 //!
 //! ## `jitter_range`
 //!
 //! ```rust,no_run
+//! # #[cfg(feature = "jitter-std")] {
 //! use tokio_retry2::Retry;
 //! use tokio_retry2::strategy::{ExponentialFactorBackoff, jitter_range, MaxInterval};
 //!
@@ -139,6 +143,7 @@
 //!    .max_interval(10000) // set max interval to 10 seconds
 //!    .map(jitter_range(0.5, 1.2)) // add jitter ranging between 50% and 120% to the retry interval
 //!    .take(3);    // limit to 3 retries
+//! # }
 //!````
 //!
 //! ### NOTE:
@@ -147,16 +152,128 @@
 //! to place an upper bound on the strategy execution time.
 
 #![allow(warnings)]
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
+
+extern crate alloc;
 
-mod action;
+#[cfg(feature = "std")]
+pub mod action;
+#[cfg(any(feature = "http", feature = "tonic"))]
+pub mod api;
+#[cfg(feature = "std")]
+mod async_notify;
+#[cfg(feature = "std")]
+pub mod breaker;
+#[cfg(feature = "std")]
+pub mod buffered_retry;
+#[cfg(feature = "chrono")]
+pub mod calendar;
 mod condition;
+#[cfg(feature = "std")]
+mod config;
+#[cfg(feature = "std")]
+mod delay;
+#[cfg(feature = "distributed")]
+pub mod distributed;
+#[cfg(feature = "embassy")]
+pub mod embassy;
 pub(crate) mod error;
+#[cfg(feature = "std")]
 mod future;
+#[cfg(feature = "std")]
+mod history;
+#[cfg(feature = "std")]
+mod idempotency;
+#[cfg(feature = "io")]
+pub mod io;
+#[cfg(feature = "std")]
+pub mod load_gate;
+#[cfg(feature = "std")]
+pub mod lock;
+#[cfg(feature = "std")]
+mod mutable;
 mod notify;
+#[cfg(feature = "std")]
+pub mod pacer;
+#[cfg(feature = "std")]
+pub mod policy;
+#[cfg(feature = "std")]
+mod reauth;
+#[cfg(feature = "std")]
+mod refresh;
+#[cfg(feature = "std")]
+pub mod replay;
+#[cfg(feature = "std")]
+mod report;
+#[cfg(feature = "std")]
+pub mod retries;
+#[cfg(feature = "std")]
+mod scoped;
+#[cfg(feature = "sentry")]
+mod sentry;
+pub mod simulation;
+#[cfg(feature = "std")]
+pub mod singleflight;
+/// Retry adapter for sinks.
+#[cfg(feature = "sink")]
+pub mod sink;
+#[cfg(feature = "std")]
+mod stable;
+#[cfg(feature = "std")]
+mod startup;
+#[cfg(feature = "stats")]
+pub mod stats;
 /// Assorted retry strategies including fixed interval and exponential back-off.
 pub mod strategy;
+/// Retry adapters for fallible streams.
+#[cfg(feature = "stream")]
+pub mod stream;
+#[cfg(feature = "test-util")]
+pub mod test_util;
+#[cfg(feature = "std")]
+pub mod throttle;
+#[cfg(feature = "std")]
+mod timeout;
 
-pub use action::Action;
+#[cfg(feature = "std")]
+pub use action::{
+    Action, AttemptContext, ClassifyAction, ClassifyFuture, ContextAction, DeadlineAction,
+    FoldAction, FoldFuture, OptionAction, OptionFuture, RetryContext, RetryMiddleware, Step,
+};
+#[cfg(feature = "std")]
+pub use async_notify::AsyncNotify;
 pub use condition::Condition;
-pub use error::{Error as RetryError, MapErr};
-pub use future::{Retry, RetryIf};
+#[cfg(feature = "std")]
+pub use config::{AlwaysRetry, NoopNotify, RetryConfig};
+#[cfg(feature = "std")]
+pub use delay::next_delay;
+#[cfg(feature = "std")]
+pub use error::ClassifiedError;
+pub use error::{BreakerError, Error as RetryError, MapErr, NotReady, RetryClass, TimeoutError};
+#[cfg(feature = "tracing")]
+pub use future::AttemptSpan;
+#[cfg(feature = "std")]
+pub use future::{FirstAttemptPolicy, Retry, RetryCounted, RetryIf, WithHooks, WithStopReason};
+#[cfg(feature = "std")]
+pub use history::ErrorHistory;
+#[cfg(feature = "std")]
+pub use idempotency::{with_idempotency, IdempotencyStore};
+#[cfg(feature = "std")]
+pub use mutable::{spawn_mut, spawn_mut_between_attempts, ActionMutFuture};
+pub use notify::{AttemptInfo, DelaySource, Notify, StopReason, Watchdog, WatchdogAlert};
+#[cfg(feature = "std")]
+pub use reauth::with_reauth;
+#[cfg(feature = "std")]
+pub use refresh::refresh_with_retry;
+#[cfg(feature = "std")]
+pub use report::{AttemptRecord, RetryReport};
+#[cfg(feature = "std")]
+pub use scoped::ScopedPolicies;
+#[cfg(feature = "sentry")]
+pub use sentry::SentryNotify;
+#[cfg(feature = "std")]
+pub use stable::{poll_until_stable, StableError};
+#[cfg(feature = "std")]
+pub use startup::{init_with_retry, init_with_retry_until, StartupError};
+#[cfg(feature = "std")]
+pub use timeout::{RetryAfterVsDeadline, Timer, TokioTimer};
@@ -0,0 +1,154 @@
+//! Postponing retries while the local process is overloaded.
+//!
+//! Retries multiply the work a process does exactly when it is struggling, e.g. a
+//! dependency is slow and requests pile up. A [`LoadGate`] is asked before every retry,
+//! after the back-off delay, whether the attempt may run now or should be postponed,
+//! based on the CPU or memory of the host or on a signal of the application such as
+//! its queue depth. Attach it with [`Retry::load_gated`](crate::Retry::load_gated) or
+//! [`RetryIf::load_gated`](crate::RetryIf::load_gated); the first attempt isn't gated.
+//!
+//! ```rust,no_run
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::time::Duration;
+//! use tokio_retry2::{Retry, RetryError};
+//! use tokio_retry2::load_gate::{GateDecision, LoadGate};
+//! use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+//!
+//! static QUEUE_DEPTH: AtomicUsize = AtomicUsize::new(0);
+//!
+//! async fn action() -> Result<u64, RetryError<()>> {
+//!     RetryError::to_transient(())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), ()> {
+//! let gate = LoadGate::new(|| {
+//!     if QUEUE_DEPTH.load(Ordering::Relaxed) > 1_000 {
+//!         GateDecision::Postpone(Duration::from_millis(500))
+//!     } else {
+//!         GateDecision::Proceed
+//!     }
+//! });
+//!
+//! Retry::spawn(FixedInterval::from_millis(100).max_retries(3), action)
+//!     .load_gated(gate)
+//!     .await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt;
+use std::sync::Arc;
+
+use tokio::time::Duration;
+
+/// Whether a retry may run now, returned by the check of a [`LoadGate`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateDecision {
+    /// The retry runs now.
+    Proceed,
+    /// The retry waits for the given duration, after which the gate is asked again.
+    Postpone(Duration),
+    /// The retry waits for the given duration, after which it runs without asking the
+    /// gate again.
+    Delay(Duration),
+}
+
+/// A check run before every retry, postponing it while the process is overloaded.
+///
+/// Clones share the same check. A retry is postponed for as long as the gate says
+/// so, which doesn't count as an attempt nor consumes the strategy, so cap the wait
+/// with a timeout if the load may never go down.
+#[derive(Clone)]
+pub struct LoadGate {
+    check: Arc<dyn Fn() -> GateDecision + Send + Sync>,
+}
+
+impl LoadGate {
+    /// Constructs a gate from `check`, called before every retry.
+    pub fn new<F>(check: F) -> LoadGate
+    where
+        F: Fn() -> GateDecision + Send + Sync + 'static,
+    {
+        LoadGate {
+            check: Arc::new(check),
+        }
+    }
+
+    /// Postpones retries by `postpone` while the one-minute load average per CPU is
+    /// above `max_load`, e.g. `1.0` when every CPU is busy on average.
+    ///
+    /// The load average is read from `/proc/loadavg`, so this gate only ever postpones
+    /// retries on Linux, and proceeds when it can't be read.
+    pub fn cpu_load_above(max_load: f64, postpone: Duration) -> LoadGate {
+        LoadGate::new(move || match cpu_load() {
+            Some(load) if load > max_load => GateDecision::Postpone(postpone),
+            _ => GateDecision::Proceed,
+        })
+    }
+
+    /// Postpones retries by `postpone` while the share of memory in use is above
+    /// `max_usage`, between `0.0` and `1.0`.
+    ///
+    /// The memory usage is read from `/proc/meminfo`, so this gate only ever postpones
+    /// retries on Linux, and proceeds when it can't be read.
+    pub fn memory_usage_above(max_usage: f64, postpone: Duration) -> LoadGate {
+        LoadGate::new(move || match memory_usage() {
+            Some(usage) if usage > max_usage => GateDecision::Postpone(postpone),
+            _ => GateDecision::Proceed,
+        })
+    }
+
+    /// Runs the check.
+    pub fn check(&self) -> GateDecision {
+        (self.check)()
+    }
+}
+
+impl fmt::Debug for LoadGate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoadGate").finish_non_exhaustive()
+    }
+}
+
+/// The one-minute load average divided by the number of CPUs.
+fn cpu_load() -> Option<f64> {
+    let loadavg = std::fs::read_to_string("/proc/loadavg").ok()?;
+    let load: f64 = loadavg.split_whitespace().next()?.parse().ok()?;
+    let cpus = std::thread::available_parallelism().map_or(1, usize::from);
+    Some(load / cpus as f64)
+}
+
+/// The share of memory which isn't available to start new applications.
+fn memory_usage() -> Option<f64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    parse_memory_usage(&meminfo)
+}
+
+fn parse_memory_usage(meminfo: &str) -> Option<f64> {
+    let field = |name: &str| -> Option<f64> {
+        let line = meminfo.lines().find(|line| line.starts_with(name))?;
+        line[name.len()..].split_whitespace().next()?.parse().ok()
+    };
+    let total = field("MemTotal:")?;
+    let available = field("MemAvailable:")?;
+    (total > 0.0).then(|| 1.0 - available / total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_memory_usage() {
+        let meminfo = "MemTotal:       16000000 kB\n\
+                       MemFree:         1000000 kB\n\
+                       MemAvailable:    4000000 kB\n";
+
+        assert_eq!(parse_memory_usage(meminfo), Some(0.75));
+        assert_eq!(
+            parse_memory_usage("MemTotal: 0 kB\nMemAvailable: 0 kB\n"),
+            None
+        );
+        assert_eq!(parse_memory_usage(""), None);
+    }
+}
@@ -0,0 +1,88 @@
+//! Spinning on distributed locks and leases with back-off.
+//!
+//! When many contenders race for the same lock, a plain exponential back-off keeps
+//! them in lockstep, colliding again and again. [`acquire_with_backoff`] paired with a
+//! [`CollisionBackoff`](crate::strategy::CollisionBackoff) spreads them over random
+//! slots instead, and [`contender_id`](crate::strategy::CollisionBackoff::contender_id)
+//! de-synchronizes contenders that start at the same instant.
+//!
+//! ```rust,no_run
+//! use tokio_retry2::lock::acquire_with_backoff;
+//! use tokio_retry2::strategy::{CollisionBackoff, MaxRetries};
+//!
+//! struct Lease;
+//!
+//! /// Resolves to `None` while another node holds the lease.
+//! async fn try_acquire_lease() -> Option<Lease> {
+//!     None
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let strategy = CollisionBackoff::from_millis(20)
+//!     .contender_id("node-1")
+//!     .max_retries(10);
+//! let lease = acquire_with_backoff(strategy, try_acquire_lease).await;
+//! # }
+//! ```
+use std::future::Future;
+use std::iter::IntoIterator;
+
+use tokio::time::Duration;
+
+use crate::action::OptionAction;
+use crate::future::Retry;
+use crate::strategy::Strategy;
+
+/// Calls `try_acquire` until it resolves to `Some` guard, backing off via `strategy`
+/// while the lock is held by someone else, i.e. while it resolves to `None`.
+///
+/// Resolves with [`NotReady`](crate::NotReady) once the strategy is exhausted. This is
+/// [`Retry::spawn_some`] under a name stating the intent.
+pub fn acquire_with_backoff<S, F, Fut, G>(
+    strategy: S,
+    try_acquire: F,
+) -> Retry<S::IntoIter, OptionAction<F>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Option<G>>,
+{
+    Retry::spawn_some(strategy, try_acquire)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::{CollisionBackoff, MaxRetries};
+    use crate::NotReady;
+
+    #[tokio::test]
+    async fn acquires_once_released() {
+        let attempts = AtomicUsize::new(0);
+        let strategy = CollisionBackoff::from_millis(1)
+            .contender_id("node-1")
+            .max_retries(10);
+        let res = acquire_with_backoff(strategy, || {
+            let held = attempts.fetch_add(1, Ordering::SeqCst) < 3;
+            future::ready((!held).then_some("guard"))
+        })
+        .await;
+
+        assert_eq!(res, Ok("guard"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn gives_up_when_exhausted() {
+        let strategy = CollisionBackoff::from_millis(1).max_retries(2);
+        let res = acquire_with_backoff(strategy, || future::ready(None::<()>)).await;
+
+        assert_eq!(res, Err(NotReady));
+    }
+}
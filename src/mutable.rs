@@ -0,0 +1,207 @@
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::time::Duration;
+
+use crate::error::Error as RetryError;
+use crate::future::{RetryIf, WithHooks};
+use crate::notify::{AttemptInfo, Notify};
+use crate::strategy::Strategy;
+
+/// Future returned by the actions of [`spawn_mut`], borrowing the target for `'a`.
+pub type ActionMutFuture<'a, T, E> =
+    Pin<Box<dyn Future<Output = Result<T, RetryError<E>>> + Send + 'a>>;
+
+/// Retries a method of a long-lived `target` taking `&mut self`, such as
+/// `client.reconnect()`, via a retry strategy.
+///
+/// Every attempt mutably borrows `target` for as long as its future runs, so the
+/// client doesn't need interior mutability or to be moved into the action.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{spawn_mut, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// struct Client;
+///
+/// impl Client {
+///     async fn reconnect(&mut self) -> Result<(), RetryError<std::io::Error>> {
+///         Ok(())
+///     }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let mut client = Client;
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// spawn_mut(strategy, &mut client, |client| Box::pin(client.reconnect())).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn spawn_mut<S, C, F, T, E>(
+    strategy: S,
+    target: &mut C,
+    action: F,
+) -> WithHooks<impl Future<Output = Result<T, E>> + use<'_, S, C, F, T, E>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    C: ?Sized,
+    F: for<'a> FnMut(&'a mut C) -> ActionMutFuture<'a, T, E>,
+{
+    spawn_mut_between_attempts(strategy, target, action, |_, _| {})
+}
+
+/// Same as [`spawn_mut`], but calls `between_attempts` with mutable access to `target`
+/// after every failed attempt that will be retried, before sleeping.
+///
+/// Useful to rotate credentials, pick a new endpoint or rebuild a client before the
+/// next attempt, without smuggling an `Arc<Mutex<_>>` into the action.
+pub fn spawn_mut_between_attempts<S, C, F, B, T, E>(
+    strategy: S,
+    target: &mut C,
+    action: F,
+    between_attempts: B,
+) -> WithHooks<impl Future<Output = Result<T, E>> + use<'_, S, C, F, B, T, E>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    C: ?Sized,
+    F: for<'a> FnMut(&'a mut C) -> ActionMutFuture<'a, T, E>,
+    B: FnMut(&mut C, &E),
+{
+    let target = Arc::new(Mutex::new(Some(target)));
+    let action = Arc::new(Mutex::new(action));
+    let notify = BetweenAttempts {
+        target: Arc::clone(&target),
+        between_attempts,
+    };
+    // every attempt takes the target out of its slot, and puts it back once done
+    let attempt = move || {
+        let target = Arc::clone(&target);
+        let action = Arc::clone(&action);
+        async move {
+            let mut borrowed = lock(&target)
+                .take()
+                .expect("the target is put back after every attempt");
+            let attempt = lock(&action)(&mut *borrowed);
+            let result = attempt.await;
+            *lock(&target) = Some(borrowed);
+            result
+        }
+    };
+    WithHooks::run(attempt, |attempt| {
+        RetryIf::spawn(strategy, attempt, (|_| true) as fn(&E) -> bool, notify)
+    })
+}
+
+/// Calls `between_attempts` with the target of [`spawn_mut_between_attempts`] once an
+/// attempt failed, if it is retried.
+struct BetweenAttempts<'t, C: ?Sized, B> {
+    target: Arc<Mutex<Option<&'t mut C>>>,
+    between_attempts: B,
+}
+
+impl<C, B, E> Notify<E> for BetweenAttempts<'_, C, B>
+where
+    C: ?Sized,
+    B: FnMut(&mut C, &E),
+{
+    fn notify(&mut self, _err: &E, _duration: Duration) {}
+
+    fn notify_attempt(&mut self, err: &E, _duration: Duration, attempt: &AttemptInfo) {
+        if attempt.next_delay.is_none() {
+            return;
+        }
+        if let Some(target) = lock(&self.target).as_deref_mut() {
+            (self.between_attempts)(target, err);
+        }
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::FixedInterval;
+
+    struct Client {
+        failures: u32,
+        connects: u32,
+    }
+
+    impl Client {
+        async fn reconnect(&mut self) -> Result<u32, RetryError<&'static str>> {
+            self.connects += 1;
+            if self.failures > 0 {
+                self.failures -= 1;
+                return RetryError::to_transient("connection refused");
+            }
+            Ok(self.connects)
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_method_on_mutable_target() {
+        let mut client = Client {
+            failures: 2,
+            connects: 0,
+        };
+        let res = spawn_mut(
+            FixedInterval::from_millis(1).take(3),
+            &mut client,
+            |client| Box::pin(client.reconnect()),
+        )
+        .await;
+
+        assert_eq!(res, Ok(3));
+        assert_eq!(client.connects, 3);
+    }
+
+    #[tokio::test]
+    async fn returns_error_when_exhausted() {
+        let mut client = Client {
+            failures: 5,
+            connects: 0,
+        };
+        let res = spawn_mut(
+            FixedInterval::from_millis(1).take(1),
+            &mut client,
+            |client| Box::pin(client.reconnect()),
+        )
+        .await;
+
+        assert_eq!(res, Err("connection refused"));
+        assert_eq!(client.connects, 2);
+    }
+
+    #[tokio::test]
+    async fn mutates_target_between_attempts() {
+        let mut client = Client {
+            failures: 5,
+            connects: 0,
+        };
+        let mut errors = Vec::new();
+        let res = spawn_mut_between_attempts(
+            FixedInterval::from_millis(1).take(3),
+            &mut client,
+            |client| Box::pin(client.reconnect()),
+            |client, err| {
+                errors.push(*err);
+                client.failures = 0;
+            },
+        )
+        .await;
+
+        assert_eq!(res, Ok(2));
+        assert_eq!(errors, vec!["connection refused"]);
+    }
+}
@@ -1,7 +1,147 @@
-use std::time::Duration;
+use core::time::Duration;
+
+/// Details about a failed attempt, passed to [`Notify::notify_attempt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct AttemptInfo {
+    /// How long the failed attempt took to execute.
+    pub attempt_duration: Duration,
+    /// The `retry_after` hint driving the next delay, after the condition and jitter
+    /// were applied, or `None` if the strategy delay is used.
+    pub retry_after: Option<Duration>,
+    /// Where the delay before the next attempt comes from, or [`DelaySource::Strategy`]
+    /// if the strategy is exhausted.
+    pub delay_source: DelaySource,
+    /// The delay actually slept before the next attempt, or `None` if the loop gives up
+    /// with this error.
+    pub next_delay: Option<Duration>,
+}
+
+/// Where the delay before the next attempt comes from, see [`AttemptInfo::delay_source`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DelaySource {
+    /// The retry strategy.
+    Strategy,
+    /// The `retry_after` hint of the error.
+    RetryAfter,
+    /// The retry strategy, capped by its `max_delay`.
+    Clamped,
+    /// A policy override, e.g. `test_util::with_policy_override`.
+    Override,
+    /// The delay function of the retry loop, e.g. `Retry::spawn_with_delay_fn`.
+    Computed,
+}
+
+/// Thresholds of a retry loop watchdog, set with `RetryIf::watchdog`, which fires
+/// once when the loop is still retrying past either of them.
+///
+/// Unlike [`Notify`], which hears about every attempt, a watchdog is meant to page a
+/// human about an operation stuck within an intentionally long policy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Watchdog {
+    attempts: Option<u32>,
+    elapsed: Option<Duration>,
+}
+
+impl Watchdog {
+    /// Constructs a watchdog without thresholds, which never fires.
+    pub const fn new() -> Watchdog {
+        Watchdog {
+            attempts: None,
+            elapsed: None,
+        }
+    }
+
+    /// Fires once `attempts` attempts have failed.
+    pub const fn attempts(mut self, attempts: u32) -> Watchdog {
+        self.attempts = Some(attempts);
+        self
+    }
+
+    /// Fires once an attempt fails `elapsed` after the first attempt started,
+    /// e.g. "still failing after 10 minutes".
+    pub const fn elapsed(mut self, elapsed: Duration) -> Watchdog {
+        self.elapsed = Some(elapsed);
+        self
+    }
+
+    /// Whether a loop that made `attempts` attempts over `elapsed` crossed a threshold.
+    pub(crate) fn is_exceeded(&self, attempts: u32, elapsed: Duration) -> bool {
+        self.attempts.is_some_and(|threshold| attempts >= threshold)
+            || self.elapsed.is_some_and(|threshold| elapsed >= threshold)
+    }
+}
+
+/// Details about a retry loop that crossed a [`Watchdog`] threshold.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct WatchdogAlert {
+    /// The number of failed attempts so far.
+    pub attempts: u32,
+    /// The time since the first attempt started.
+    pub elapsed: Duration,
+}
+
+/// Why a retry loop stopped without succeeding, passed to [`Notify::notify_stop`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum StopReason {
+    /// A `max_retries` bound was reached.
+    MaxRetriesExceeded,
+    /// A `max_duration`/`max_interval` bound was reached.
+    MaxDurationExceeded,
+    /// A shared [`RetryBudget`](crate::strategy::RetryBudget) was spent.
+    BudgetExhausted,
+    /// A gRPC retry throttle disallowed retries.
+    Throttled,
+    /// A [`CircuitBreaker`](crate::breaker::CircuitBreaker) was open.
+    CircuitOpen,
+    /// The strategy yielded no more delays for another reason, e.g. a `take` bound.
+    StrategyExhausted,
+    /// The action returned a permanent error.
+    PermanentError,
+    /// The retry condition rejected the error.
+    ConditionRejected,
+    /// Retries are disabled by the [`retries`](crate::retries) kill switch.
+    RetriesDisabled,
+    /// The retry future was dropped before completing.
+    Cancelled,
+}
+
+impl StopReason {
+    /// A stable, `snake_case` name for the reason, suitable for logs and metrics.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::MaxRetriesExceeded => "max_retries_exceeded",
+            StopReason::MaxDurationExceeded => "max_duration_exceeded",
+            StopReason::BudgetExhausted => "budget_exhausted",
+            StopReason::Throttled => "throttled",
+            StopReason::CircuitOpen => "circuit_open",
+            StopReason::StrategyExhausted => "strategy_exhausted",
+            StopReason::PermanentError => "permanent_error",
+            StopReason::ConditionRejected => "condition_rejected",
+            StopReason::RetriesDisabled => "retries_disabled",
+            StopReason::Cancelled => "cancelled",
+        }
+    }
+}
 
 pub trait Notify<E> {
     fn notify(&mut self, err: &E, duration: Duration);
+
+    /// Called on every retried error with details about the failed attempt, such as how
+    /// long it took to execute. Distinguishes slow-but-failing attempts from fast rejections.
+    ///
+    /// Defaults to calling [`Notify::notify`].
+    fn notify_attempt(&mut self, err: &E, duration: Duration, attempt: &AttemptInfo) {
+        self.notify(err, duration)
+    }
+
+    /// Called once when the retry loop stops without succeeding, with the reason why.
+    ///
+    /// Defaults to doing nothing.
+    fn notify_stop(&mut self, reason: StopReason) {}
 }
 
 impl<E, F> Notify<E> for F
@@ -0,0 +1,102 @@
+//! Global pacing of retry attempts.
+//!
+//! When thousands of retry futures wake up at the same time, e.g. because they share
+//! a back-off bucket, their attempts stampede the recovering service. A [`Pacer`]
+//! shared between them spaces the attempts out evenly over time, independently of any
+//! per-future jitter. Attach it with [`Retry::paced`](crate::Retry::paced) or
+//! [`RetryIf::paced`](crate::RetryIf::paced), which await a slot before every retry;
+//! the first attempt isn't paced.
+//!
+//! ```rust,no_run
+//! use tokio_retry2::{Retry, RetryError};
+//! use tokio_retry2::pacer::Pacer;
+//! use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+//!
+//! async fn action() -> Result<u64, RetryError<()>> {
+//!     RetryError::to_transient(())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! // at most 100 retries per second across every future sharing the pacer
+//! let pacer = Pacer::new(100);
+//! for _ in 0..1_000 {
+//!     let retry = Retry::spawn(FixedInterval::from_millis(500).max_retries(3), action)
+//!         .paced(pacer.clone());
+//!     tokio::spawn(retry);
+//! }
+//! # }
+//! ```
+use std::sync::{Arc, Mutex};
+
+use tokio::time::{sleep_until, Duration, Instant};
+
+/// Hands out evenly spaced slots for attempts, shared between clones.
+#[derive(Debug, Clone)]
+pub struct Pacer {
+    interval: Duration,
+    next_slot: Arc<Mutex<Option<Instant>>>,
+}
+
+impl Pacer {
+    /// Constructs a pacer allowing at most `rate` attempts per second.
+    /// A `rate` of `0` is treated as `1`.
+    pub fn new(rate: u32) -> Pacer {
+        Pacer::from_interval(Duration::from_secs(1) / rate.max(1))
+    }
+
+    /// Constructs a pacer spacing attempts at least `interval` apart.
+    pub fn from_interval(interval: Duration) -> Pacer {
+        Pacer {
+            interval,
+            next_slot: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Reserves the next free slot, returning the instant the attempt may run at.
+    pub fn reserve(&self) -> Instant {
+        let now = Instant::now();
+        let mut next_slot = self
+            .next_slot
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let slot = next_slot.map_or(now, |next| next.max(now));
+        *next_slot = Some(slot + self.interval);
+        slot
+    }
+
+    /// Waits for the next free slot.
+    pub async fn acquire(&self) {
+        sleep_until(self.reserve()).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn spaces_out_slots() {
+        let pacer = Pacer::new(10);
+
+        let first = pacer.reserve();
+        assert_eq!(pacer.clone().reserve(), first + Duration::from_millis(100));
+        assert_eq!(pacer.reserve(), first + Duration::from_millis(200));
+
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        let now = Instant::now();
+        assert!(pacer.reserve() >= now);
+    }
+
+    #[tokio::test]
+    async fn acquire_waits_for_the_slot() {
+        let pacer = Pacer::from_interval(Duration::from_millis(50));
+        let start = Instant::now();
+
+        pacer.acquire().await;
+        pacer.acquire().await;
+        pacer.acquire().await;
+
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,394 @@
+//! Typestate builder for retry policies.
+//!
+//! [`Builder::build`] only exists once a termination condition has been chosen,
+//! using [`Builder::max_retries`], [`Builder::max_duration`] or, for intentional
+//! forever-retries, [`Builder::unbounded`]. This turns an accidental infinite
+//! retry into a compile error:
+//!
+//! ```rust,compile_fail
+//! use tokio_retry2::policy::Builder;
+//! use tokio_retry2::strategy::FixedInterval;
+//!
+//! let policy = Builder::new(FixedInterval::from_millis(100)).build();
+//! ```
+//!
+//! ```rust
+//! use tokio_retry2::policy::Builder;
+//! use tokio_retry2::strategy::ExponentialBackoff;
+//! use std::time::Duration;
+//!
+//! let policy = Builder::new(ExponentialBackoff::from_millis(10))
+//!     .max_retries(5)
+//!     .max_duration(Duration::from_secs(10))
+//!     .build();
+//! ```
+//!
+//! A terminated builder is also a reusable [`RetryPolicy`], which can be stored in
+//! config structs and shared by clones: every retry loop passed a reference builds
+//! a fresh policy, whose `max_duration` clock starts with the loop.
+//!
+//! ```rust,no_run
+//! use std::time::Duration;
+//! use tokio_retry2::{Retry, RetryError};
+//! use tokio_retry2::policy::{Builder, RetryPolicy};
+//!
+//! struct Config {
+//!     retry: RetryPolicy,
+//! }
+//!
+//! async fn fetch() -> Result<u64, RetryError<()>> {
+//!     Ok(42)
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), ()> {
+//! let config = Config {
+//!     retry: Builder::exponential(10)
+//!         .max_delay(Duration::from_secs(1))
+//!         .max_retries(5)
+//!         .max_duration(Duration::from_secs(10)),
+//! };
+//!
+//! let value = Retry::spawn(&config.retry, fetch).await?;
+//! # Ok(())
+//! # }
+//! ```
+use std::iter::FusedIterator;
+use std::marker::PhantomData;
+use std::time::Instant;
+
+use tokio::time::Duration;
+
+use crate::notify::StopReason;
+use crate::strategy::{
+    BoundedPolicy, ExponentialBackoff, FixedInterval, Fused, FusedStrategy, Strategy,
+};
+use crate::throttle::GrpcRetryThrottle;
+
+/// Typestate of a [`Builder`] without a termination condition.
+#[derive(Debug, Clone, Copy)]
+pub struct Unterminated;
+
+/// Typestate of a [`Builder`] with a termination condition.
+#[derive(Debug, Clone, Copy)]
+pub struct Terminated;
+
+/// Builder of a [`Policy`], which can only be built once a termination condition is set.
+#[derive(Debug, Clone)]
+pub struct Builder<I, S = Unterminated> {
+    strategy: I,
+    max_retries: Option<usize>,
+    max_duration: Option<Duration>,
+    throttle: Option<GrpcRetryThrottle>,
+    jitter: bool,
+    max_delay: Option<Duration>,
+    state: PhantomData<S>,
+}
+
+/// A reusable policy, i.e. a terminated [`Builder`], see the
+/// [module documentation](self).
+pub type RetryPolicy<I = ExponentialBackoff> = Builder<I, Terminated>;
+
+impl Builder<ExponentialBackoff, Unterminated> {
+    /// Starts building a policy backing off exponentially from `millis`,
+    /// see [`ExponentialBackoff::from_millis`].
+    pub fn exponential(millis: u64) -> Self {
+        Builder::new(ExponentialBackoff::from_millis(millis))
+    }
+}
+
+impl Builder<FixedInterval, Unterminated> {
+    /// Starts building a policy retrying every `millis` milliseconds,
+    /// see [`FixedInterval::from_millis`].
+    pub fn fixed(millis: u64) -> Self {
+        Builder::new(FixedInterval::from_millis(millis))
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Builder<I, Unterminated> {
+    /// Starts building a policy out of a strategy.
+    pub const fn new(strategy: I) -> Self {
+        Builder {
+            strategy,
+            max_retries: None,
+            max_duration: None,
+            throttle: None,
+            jitter: false,
+            max_delay: None,
+            state: PhantomData,
+        }
+    }
+
+    /// Explicitly allows the policy to retry forever.
+    pub fn unbounded(self) -> Builder<I, Terminated> {
+        self.terminated()
+    }
+}
+
+impl<I: Iterator<Item = Duration>, S> Builder<I, S> {
+    /// Limits the policy to `max_retries` retries.
+    pub fn max_retries(mut self, max_retries: usize) -> Builder<I, Terminated> {
+        self.max_retries = Some(max_retries);
+        self.terminated()
+    }
+
+    /// Stops retrying once `max_duration` has elapsed since the policy was built.
+    pub fn max_duration(mut self, max_duration: Duration) -> Builder<I, Terminated> {
+        self.max_duration = Some(max_duration);
+        self.terminated()
+    }
+
+    /// Attaches a gRPC retry throttle, shared with every other policy on the same channel.
+    ///
+    /// Every delay taken from the policy records a failed attempt on the throttle,
+    /// and the policy stops retrying while the throttle disallows retries.
+    pub fn throttle(mut self, throttle: GrpcRetryThrottle) -> Self {
+        self.throttle = Some(throttle);
+        self
+    }
+
+    /// Jitters every delay by a random factor between `0.5` and `1.5`,
+    /// see [`jitter`](crate::strategy::jitter).
+    #[cfg(feature = "jitter-std")]
+    pub fn with_jitter(mut self) -> Self {
+        self.jitter = true;
+        self
+    }
+
+    /// Caps every delay, jittered or not, at `max_delay`.
+    pub fn max_delay(mut self, max_delay: Duration) -> Self {
+        self.max_delay = Some(max_delay);
+        self
+    }
+
+    fn terminated(self) -> Builder<I, Terminated> {
+        Builder {
+            strategy: self.strategy,
+            max_retries: self.max_retries,
+            max_duration: self.max_duration,
+            throttle: self.throttle,
+            jitter: self.jitter,
+            max_delay: self.max_delay,
+            state: PhantomData,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Builder<I, Terminated> {
+    /// Builds the policy. The `max_duration` clock starts now.
+    pub fn build(self) -> Policy<I> {
+        Policy {
+            strategy: self.strategy.fused(),
+            remaining: self.max_retries,
+            max_duration: self.max_duration,
+            throttle: self.throttle,
+            jitter: self.jitter,
+            max_delay: self.max_delay,
+            start: Instant::now(),
+            stop: None,
+            clamped: false,
+        }
+    }
+}
+
+/// Builds a fresh policy for every retry loop, so a single [`RetryPolicy`] can drive
+/// any number of them.
+impl<I: Iterator<Item = Duration> + Clone> IntoIterator for &Builder<I, Terminated> {
+    type Item = Duration;
+    type IntoIter = Policy<I>;
+
+    fn into_iter(self) -> Policy<I> {
+        self.clone().build()
+    }
+}
+
+/// A strategy with a termination condition, created by [`Builder::build`].
+///
+/// As a policy may stop at any time, e.g. once throttled, its `size_hint` has a lower
+/// bound of `0`, so policies built with [`Builder::unbounded`] aren't reported as
+/// accidentally unbounded.
+#[derive(Debug, Clone)]
+pub struct Policy<I> {
+    strategy: FusedStrategy<I>,
+    remaining: Option<usize>,
+    max_duration: Option<Duration>,
+    throttle: Option<GrpcRetryThrottle>,
+    jitter: bool,
+    max_delay: Option<Duration>,
+    start: Instant,
+    stop: Option<StopReason>,
+    clamped: bool,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Policy<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // stays stopped even if the throttle allows retries again meanwhile
+        if self.stop.is_some() {
+            return None;
+        }
+        if let Some(throttle) = &self.throttle {
+            throttle.record_failure();
+        }
+        if self.remaining == Some(0) {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("`max_retries` reached, cancelling retry");
+            self.stop = Some(StopReason::MaxRetriesExceeded);
+            return None;
+        }
+        if let Some(max_duration) = self.max_duration {
+            if self.start.elapsed() > max_duration {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("`max_duration` reached, cancelling retry");
+                self.stop = Some(StopReason::MaxDurationExceeded);
+                return None;
+            }
+        }
+        if let Some(throttle) = &self.throttle {
+            if !throttle.is_retry_allowed() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("retry throttled, cancelling retry");
+                self.stop = Some(StopReason::Throttled);
+                return None;
+            }
+        }
+        if let Some(remaining) = self.remaining.as_mut() {
+            *remaining -= 1;
+        }
+        let delay = self.strategy.next()?;
+        #[cfg(feature = "jitter-std")]
+        let delay = match self.jitter {
+            true => crate::strategy::jitter(delay),
+            false => delay,
+        };
+        // jitter may push a delay past `max_delay`
+        self.clamped = self.max_delay.is_some_and(|max_delay| delay > max_delay);
+        match self.max_delay {
+            Some(max_delay) if self.clamped => Some(max_delay),
+            _ => Some(delay),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (_, upper) = self.strategy.size_hint();
+        let upper = match (self.remaining, upper) {
+            (Some(remaining), Some(upper)) => Some(remaining.min(upper)),
+            (remaining, upper) => remaining.or(upper),
+        };
+        (0, upper)
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for Policy<I> {}
+
+impl<I: Strategy> Strategy for Policy<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.stop.or_else(|| self.strategy.stop_reason())
+    }
+
+    fn clamped(&self) -> bool {
+        self.clamped || self.strategy.clamped()
+    }
+}
+
+impl<I> BoundedPolicy for Policy<I> {
+    fn remaining_retries(&self) -> Option<usize> {
+        self.remaining
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    fn stops_after_max_retries() {
+        let mut policy = Builder::new(FixedInterval::from_millis(10))
+            .max_retries(2)
+            .build();
+
+        assert_eq!(policy.remaining_retries(), Some(2));
+        assert_eq!(policy.next(), Some(Duration::from_millis(10)));
+        assert_eq!(policy.next(), Some(Duration::from_millis(10)));
+        assert_eq!(policy.next(), None);
+    }
+
+    #[tokio::test]
+    async fn stops_after_max_duration() {
+        let mut policy = Builder::new(FixedInterval::from_millis(10))
+            .max_duration(Duration::from_millis(50))
+            .build();
+
+        assert_eq!(policy.next(), Some(Duration::from_millis(10)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(policy.next(), None);
+    }
+
+    #[test]
+    fn unbounded_keeps_retrying() {
+        let mut policy = Builder::new(FixedInterval::from_millis(10))
+            .unbounded()
+            .build();
+
+        assert_eq!(policy.remaining_retries(), None);
+        assert_eq!(policy.size_hint(), (0, None));
+        assert_eq!(policy.nth(1_000), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn stops_while_throttled() {
+        let throttle = GrpcRetryThrottle::new(4, 1.);
+        let mut policy = Builder::new(FixedInterval::from_millis(10))
+            .max_retries(5)
+            .throttle(throttle.clone())
+            .build();
+
+        assert_eq!(policy.next(), Some(Duration::from_millis(10)));
+        assert_eq!(policy.next(), None);
+
+        // a throttled policy stays stopped, new ones retry once the throttle recovers
+        throttle.record_success();
+        throttle.record_success();
+        assert_eq!(policy.next(), None);
+        let mut policy = Builder::new(FixedInterval::from_millis(10))
+            .max_retries(5)
+            .throttle(throttle.clone())
+            .build();
+        assert_eq!(policy.next(), Some(Duration::from_millis(10)));
+    }
+
+    #[tokio::test]
+    async fn retry_policy_is_reusable_across_loops() {
+        let policy: RetryPolicy = Builder::exponential(10)
+            .max_delay(Duration::from_millis(500))
+            .max_retries(3)
+            .max_duration(Duration::from_millis(50));
+
+        let delays = |policy: &RetryPolicy| policy.into_iter().collect::<Vec<_>>();
+        let expected = [10, 100, 500].map(Duration::from_millis).to_vec();
+        assert_eq!(delays(&policy), expected);
+        // the `max_duration` clock starts with every loop
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(delays(&policy.clone()), expected);
+    }
+
+    #[cfg(feature = "jitter-std")]
+    #[test]
+    fn jitter_stays_under_max_delay() {
+        let policy = Builder::fixed(100)
+            .with_jitter()
+            .max_delay(Duration::from_millis(120))
+            .unbounded()
+            .build();
+
+        assert!(
+            policy
+                .take(64)
+                .all(|delay| delay >= Duration::from_millis(50)
+                    && delay <= Duration::from_millis(120))
+        );
+    }
+}
@@ -0,0 +1,263 @@
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::Duration;
+
+use crate::error::Error as RetryError;
+use crate::future::{RetryIf, WithHooks};
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// Retries `action` via a retry strategy, refreshing credentials when it fails
+/// because they expired.
+///
+/// The first time an attempt fails with an error for which `is_auth_expired` returns
+/// `true`, whether transient or permanent, `refresh` runs and the action is retried
+/// right away, without consuming a delay from `strategy`. Any later failure, including
+/// another expired authentication, is handled as usual: permanent errors are returned
+/// and transient ones are retried with back-off. An error returned by `refresh` is
+/// handled like an error of the action.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{with_reauth, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// #[derive(Debug, PartialEq)]
+/// enum ApiError {
+///     Unauthorized,
+///     Unavailable,
+/// }
+///
+/// async fn fetch() -> Result<String, RetryError<ApiError>> {
+///     RetryError::to_permanent(ApiError::Unauthorized)
+/// }
+///
+/// async fn refresh_token() -> Result<(), RetryError<ApiError>> {
+///     Ok(())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// let body = with_reauth(
+///     strategy,
+///     fetch,
+///     |err: &ApiError| *err == ApiError::Unauthorized,
+///     refresh_token,
+/// )
+/// .await;
+/// # }
+/// ```
+pub fn with_reauth<S, A, P, R, Fut>(
+    strategy: S,
+    action: A,
+    is_auth_expired: P,
+    refresh: R,
+) -> WithHooks<impl Future<Output = Result<A::Item, A::Error>>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+    P: FnMut(&A::Error) -> bool,
+    R: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), RetryError<A::Error>>>,
+{
+    WithHooks::run(action, |action| {
+        reauthenticating(strategy, action, is_auth_expired, refresh)
+    })
+}
+
+fn reauthenticating<S, A, P, R, Fut>(
+    strategy: S,
+    action: A,
+    is_auth_expired: P,
+    refresh: R,
+) -> RetryIf<S::IntoIter, ReauthAction<A, P, R>, fn(&A::Error) -> bool, fn(&A::Error, Duration)>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+    P: FnMut(&A::Error) -> bool,
+    R: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), RetryError<A::Error>>>,
+{
+    let action = ReauthAction {
+        reauth: Arc::new(Mutex::new(Reauth {
+            action,
+            is_auth_expired,
+            refresh,
+            refreshed: false,
+        })),
+    };
+    RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&A::Error) -> bool,
+        (|_, _| {}) as fn(&A::Error, Duration),
+    )
+}
+
+/// What the attempts of a [`ReauthAction`] share.
+struct Reauth<A, P, R> {
+    action: A,
+    is_auth_expired: P,
+    refresh: R,
+    /// Whether the credentials were refreshed already, which only happens once.
+    refreshed: bool,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An action refreshing the credentials and running again right away, within the same
+/// attempt, the first time it fails because they expired.
+struct ReauthAction<A, P, R> {
+    reauth: Arc<Mutex<Reauth<A, P, R>>>,
+}
+
+impl<A, P, R, Fut> Action for ReauthAction<A, P, R>
+where
+    A: Action,
+    P: FnMut(&A::Error) -> bool,
+    R: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), RetryError<A::Error>>>,
+{
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = ReauthFuture<A, P, R, Fut>;
+
+    fn run(&mut self) -> Self::Future {
+        ReauthFuture {
+            attempt: lock(&self.reauth).action.run(),
+            reauth: Arc::clone(&self.reauth),
+            refresh: None,
+            retry: None,
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        lock(&self.reauth).action.before_retry(delay);
+    }
+}
+
+/// Future running an attempt of a [`ReauthAction`], then the refresh and the attempt
+/// following it, if the credentials expired.
+#[pin_project]
+struct ReauthFuture<A: Action, P, R, Fut> {
+    reauth: Arc<Mutex<Reauth<A, P, R>>>,
+    #[pin]
+    attempt: A::Future,
+    #[pin]
+    refresh: Option<Fut>,
+    #[pin]
+    retry: Option<A::Future>,
+}
+
+impl<A, P, R, Fut> Future for ReauthFuture<A, P, R, Fut>
+where
+    A: Action,
+    P: FnMut(&A::Error) -> bool,
+    R: FnMut() -> Fut,
+    Fut: Future<Output = Result<(), RetryError<A::Error>>>,
+{
+    type Output = Result<A::Item, RetryError<A::Error>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+        loop {
+            if let Some(retry) = this.retry.as_mut().as_pin_mut() {
+                return retry.poll(cx);
+            }
+            if let Some(refresh) = this.refresh.as_mut().as_pin_mut() {
+                ready!(refresh.poll(cx))?;
+                this.refresh.set(None);
+                let retry = lock(this.reauth).action.run();
+                this.retry.set(Some(retry));
+                continue;
+            }
+
+            let result = ready!(this.attempt.as_mut().poll(cx));
+            let Err(RetryError::Permanent(err, ..) | RetryError::Transient { err, .. }) = &result
+            else {
+                return Poll::Ready(result);
+            };
+            let mut reauth = lock(this.reauth);
+            if reauth.refreshed || !(reauth.is_auth_expired)(err) {
+                return Poll::Ready(result);
+            }
+            reauth.refreshed = true;
+            #[cfg(feature = "tracing")]
+            tracing::debug!("authentication expired, refreshing before retrying");
+            let refresh = (reauth.refresh)();
+            drop(reauth);
+            this.refresh.set(Some(refresh));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn refreshes_once_and_retries_immediately() {
+        let attempts = AtomicUsize::new(0);
+        let refreshes = AtomicUsize::new(0);
+        let res = with_reauth(
+            FixedInterval::from_millis(1).take(0),
+            || {
+                if refreshes.load(Ordering::SeqCst) == 0 {
+                    attempts.fetch_add(1, Ordering::SeqCst);
+                    future::ready(RetryError::to_permanent("expired"))
+                } else {
+                    future::ready(Ok(attempts.fetch_add(1, Ordering::SeqCst) + 1))
+                }
+            },
+            |err: &&str| *err == "expired",
+            || {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                future::ready(Ok(()))
+            },
+        )
+        .await;
+
+        assert_eq!(res, Ok(2));
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_backoff_after_refreshing() {
+        let attempts = AtomicUsize::new(0);
+        let refreshes = AtomicUsize::new(0);
+        let res = with_reauth(
+            FixedInterval::from_millis(1).take(2),
+            || {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<()>("expired"))
+            },
+            |err: &&str| *err == "expired",
+            || {
+                refreshes.fetch_add(1, Ordering::SeqCst);
+                future::ready(Ok(()))
+            },
+        )
+        .await;
+
+        assert_eq!(res, Err("expired"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 4);
+        assert_eq!(refreshes.load(Ordering::SeqCst), 1);
+    }
+}
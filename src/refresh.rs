@@ -0,0 +1,136 @@
+use std::iter::IntoIterator;
+use std::sync::{Arc, RwLock};
+
+use tokio::task::JoinHandle;
+use tokio::time::Duration;
+
+use crate::config::AlwaysRetry;
+use crate::future::RetryIf;
+use crate::notify::Notify;
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// Serves the current, possibly stale, value of `cache_entry` right away while
+/// refreshing it in the background ("stale-while-revalidate" with back-off).
+///
+/// The refresh runs `action` in a spawned task, retrying every transient error via
+/// `strategy`. On success, the new value replaces the cached one. When the refresh
+/// stops without succeeding, the entry keeps its stale value and `notify` receives
+/// the [`StopReason`](crate::StopReason) through [`Notify::notify_stop`].
+///
+/// Returns the stale value along with the handle of the refresh task. Dropping the
+/// handle doesn't cancel the refresh.
+///
+/// ```rust,no_run
+/// use std::sync::{Arc, RwLock};
+/// use tokio_retry2::{refresh_with_retry, NoopNotify, RetryError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// async fn fetch_config() -> Result<String, RetryError<std::io::Error>> {
+///     Ok("fresh".to_string())
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let cache_entry = Arc::new(RwLock::new("stale".to_string()));
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// let (config, _refresh) = refresh_with_retry(&cache_entry, strategy, fetch_config, NoopNotify);
+/// assert_eq!(config, "stale");
+/// # }
+/// ```
+pub fn refresh_with_retry<T, S, A, N>(
+    cache_entry: &Arc<RwLock<T>>,
+    strategy: S,
+    action: A,
+    notify: N,
+) -> (T, JoinHandle<()>)
+where
+    T: Clone + Send + Sync + 'static,
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy + Send + 'static,
+    A: Action<Item = T> + Send + 'static,
+    A::Future: Send,
+    A::Error: Send,
+    N: Notify<A::Error> + Send + 'static,
+{
+    let stale = cache_entry
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let refresh = RetryIf::spawn(strategy, action, AlwaysRetry, notify);
+    let cache_entry = Arc::clone(cache_entry);
+    let handle = tokio::spawn(async move {
+        if let Ok(fresh) = refresh.await {
+            *cache_entry
+                .write()
+                .unwrap_or_else(|poisoned| poisoned.into_inner()) = fresh;
+        }
+    });
+    (stale, handle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Mutex;
+
+    use crate::error::Error as RetryError;
+    use crate::notify::StopReason;
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn serves_stale_value_then_refreshes() {
+        let cache_entry = Arc::new(RwLock::new(1));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cloned_counter = counter.clone();
+
+        let (stale, refresh) = refresh_with_retry(
+            &cache_entry,
+            FixedInterval::from_millis(1).take(5),
+            move || {
+                if cloned_counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                    future::ready(RetryError::to_transient(()))
+                } else {
+                    future::ready(Ok(2))
+                }
+            },
+            |_: &(), _| {},
+        );
+
+        assert_eq!(stale, 1);
+        refresh.await.unwrap();
+        assert_eq!(*cache_entry.read().unwrap(), 2);
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn keeps_stale_value_and_reports_exhaustion() {
+        struct StopRecorder(Arc<Mutex<Option<StopReason>>>);
+        impl Notify<()> for StopRecorder {
+            fn notify(&mut self, _: &(), _: Duration) {}
+
+            fn notify_stop(&mut self, reason: StopReason) {
+                *self.0.lock().unwrap() = Some(reason);
+            }
+        }
+
+        let cache_entry = Arc::new(RwLock::new(1));
+        let stop = Arc::new(Mutex::new(None));
+
+        let (stale, refresh) = refresh_with_retry(
+            &cache_entry,
+            FixedInterval::from_millis(1).take(2),
+            || future::ready(RetryError::to_transient(())),
+            StopRecorder(stop.clone()),
+        );
+        refresh.await.unwrap();
+
+        assert_eq!(stale, 1);
+        assert_eq!(*cache_entry.read().unwrap(), 1);
+        assert_eq!(*stop.lock().unwrap(), Some(StopReason::StrategyExhausted));
+    }
+}
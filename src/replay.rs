@@ -0,0 +1,151 @@
+//! Recording and deterministic replay of retry delays.
+//!
+//! Timing bugs, e.g. with jittered strategies, are hard to reproduce. Wrapping the
+//! strategy with [`record`] captures every delay it yields, jitter included, in a
+//! [`Recording`]. Attach [`Recording::events`] to the bug report, and replay the same
+//! timeline in a test with [`from_log`].
+//!
+//! ```rust
+//! # #[cfg(feature = "jitter-std")] {
+//! use tokio_retry2::replay;
+//! use tokio_retry2::strategy::{jitter, ExponentialBackoff, MaxRetries};
+//!
+//! let strategy = ExponentialBackoff::from_millis(10).map(jitter).max_retries(3);
+//! let (strategy, recording) = replay::record(strategy);
+//! let delays: Vec<_> = strategy.collect();
+//!
+//! let replayed: Vec<_> = replay::from_log(recording.events()).collect();
+//! assert_eq!(replayed, delays);
+//! # }
+//! ```
+use std::iter::{Fuse, FusedIterator};
+use std::sync::{Arc, Mutex};
+
+use tokio::time::Duration;
+
+use crate::notify::StopReason;
+use crate::strategy::{Fused, FusedStrategy, Strategy};
+
+/// Wraps `strategy`, recording every delay it yields in the returned [`Recording`].
+pub fn record<I>(strategy: I) -> (Recorded<I::IntoIter>, Recording)
+where
+    I: IntoIterator<Item = Duration>,
+{
+    let recording = Recording::default();
+    let recorded = Recorded {
+        iter: strategy.into_iter().fused(),
+        recording: recording.clone(),
+    };
+    (recorded, recording)
+}
+
+/// Constructs a strategy yielding exactly the delays of a recorded session, and
+/// then stopping, as the recorded strategy did.
+pub fn from_log<I>(events: I) -> Replay<I::IntoIter>
+where
+    I: IntoIterator<Item = Duration>,
+{
+    Replay {
+        events: events.into_iter().fuse(),
+    }
+}
+
+/// The delays yielded by a [`Recorded`] strategy, shared between clones.
+#[derive(Debug, Clone, Default)]
+pub struct Recording {
+    events: Arc<Mutex<Vec<Duration>>>,
+}
+
+impl Recording {
+    /// The delays yielded so far, in order.
+    pub fn events(&self) -> Vec<Duration> {
+        self.events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+/// A strategy recording every delay it yields, created by [`record`].
+#[derive(Debug)]
+pub struct Recorded<I> {
+    iter: FusedStrategy<I>,
+    recording: Recording,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Recorded<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.iter.next()?;
+        self.recording
+            .events
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(delay);
+        Some(delay)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for Recorded<I> {}
+
+impl<I: Strategy> Strategy for Recorded<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+/// A strategy replaying recorded delays, created by [`from_log`].
+#[derive(Debug, Clone)]
+pub struct Replay<I> {
+    events: Fuse<I>,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Replay<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.events.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.events.size_hint()
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for Replay<I> {}
+
+impl<I: Iterator<Item = Duration>> Strategy for Replay<I> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+
+    use crate::strategy::FixedInterval;
+    use crate::{Retry, RetryError};
+
+    #[tokio::test]
+    async fn replays_a_recorded_session() {
+        let (strategy, recording) = record(FixedInterval::from_millis(1).take(3));
+        let res = Retry::spawn(strategy, || {
+            future::ready(RetryError::to_transient::<()>(42))
+        })
+        .await;
+        assert_eq!(res, Err(42));
+        assert_eq!(recording.events(), [Duration::from_millis(1); 3]);
+
+        let replay = from_log(recording.events());
+        assert_eq!(replay.size_hint(), (3, Some(3)));
+        assert_eq!(replay.collect::<Vec<_>>(), recording.events());
+    }
+}
@@ -0,0 +1,249 @@
+use std::fmt;
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::{Duration, Instant};
+
+use crate::action::Action;
+use crate::error::Error as RetryError;
+use crate::future::RetryIf;
+use crate::strategy::Strategy;
+
+/// The timings of one attempt of a retry loop, recorded in a [`RetryReport`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[non_exhaustive]
+pub struct AttemptRecord {
+    /// When the attempt started, relative to the start of the first one.
+    #[cfg_attr(feature = "serde", serde(rename = "start_ms", with = "millis"))]
+    pub start: Duration,
+    /// How long the attempt took to execute.
+    #[cfg_attr(feature = "serde", serde(rename = "duration_ms", with = "millis"))]
+    pub duration: Duration,
+    /// The error the attempt failed with, formatted with `Display`, or `None` if it
+    /// succeeded.
+    pub error: Option<String>,
+    /// The delay slept before the next attempt, or `None` if there was none.
+    #[cfg_attr(feature = "serde", serde(rename = "delay_ms", with = "millis_opt"))]
+    pub delay: Option<Duration>,
+}
+
+/// The per-attempt timings of a retry loop, returned by
+/// [`Retry::spawn_with_report`](crate::Retry::spawn_with_report), e.g. to render a
+/// waterfall of a slow request.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RetryReport {
+    attempts: Vec<AttemptRecord>,
+}
+
+impl RetryReport {
+    /// The attempts made, from the first to the last one.
+    pub fn attempts(&self) -> &[AttemptRecord] {
+        &self.attempts
+    }
+
+    /// The time elapsed from the start of the first attempt to the end of the last one.
+    pub fn elapsed(&self) -> Duration {
+        self.attempts
+            .last()
+            .map_or(Duration::ZERO, |last| last.start + last.duration)
+    }
+
+    /// Serializes the report into JSON, with durations in milliseconds, e.g.
+    /// `{"attempts":[{"start_ms":0.0,"duration_ms":12.5,"error":"unavailable","delay_ms":100.0}]}`.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("a report always serializes")
+    }
+}
+
+#[cfg(feature = "serde")]
+mod millis {
+    use std::time::Duration;
+
+    pub(super) fn serialize<S: serde::Serializer>(
+        duration: &Duration,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_f64(duration.as_secs_f64() * 1_000.0)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod millis_opt {
+    use std::time::Duration;
+
+    pub(super) fn serialize<S: serde::Serializer>(
+        duration: &Option<Duration>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match duration {
+            Some(duration) => super::millis::serialize(duration, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+}
+
+/// An action recording the timings of its attempts into a report shared with
+/// [`retry_with_report`].
+struct ReportAction<A> {
+    action: A,
+    report: Arc<Mutex<RetryReport>>,
+    started: Option<Instant>,
+}
+
+impl<A> Action for ReportAction<A>
+where
+    A: Action,
+    A::Error: fmt::Display,
+{
+    type Item = A::Item;
+    type Error = A::Error;
+    type Future = ReportFuture<A::Future>;
+
+    fn run(&mut self) -> Self::Future {
+        let attempt_start = Instant::now();
+        let started = *self.started.get_or_insert(attempt_start);
+        ReportFuture {
+            future: self.action.run(),
+            report: Arc::clone(&self.report),
+            start: attempt_start.duration_since(started),
+            attempt_start,
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        if let Some(record) = lock(&self.report).attempts.last_mut() {
+            record.delay = Some(delay);
+        }
+        self.action.before_retry(delay);
+    }
+}
+
+/// Future recording the outcome of an attempt of a [`ReportAction`] once it completes.
+#[pin_project]
+struct ReportFuture<Fut> {
+    #[pin]
+    future: Fut,
+    report: Arc<Mutex<RetryReport>>,
+    start: Duration,
+    attempt_start: Instant,
+}
+
+impl<T, E, Fut> Future for ReportFuture<Fut>
+where
+    Fut: Future<Output = Result<T, RetryError<E>>>,
+    E: fmt::Display,
+{
+    type Output = Fut::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = ready!(this.future.poll(cx));
+        let error = match &result {
+            Ok(_) => None,
+            Err(RetryError::Permanent(err, ..) | RetryError::Transient { err, .. }) => {
+                Some(err.to_string())
+            }
+        };
+        lock(this.report).attempts.push(AttemptRecord {
+            start: *this.start,
+            duration: this.attempt_start.elapsed(),
+            error,
+            // set if the loop retries, see `ReportAction::before_retry`
+            delay: None,
+        });
+        Poll::Ready(result)
+    }
+}
+
+fn lock(report: &Mutex<RetryReport>) -> MutexGuard<'_, RetryReport> {
+    report
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Drives `action` on a [`RetryIf`], recording the timings of every attempt into a
+/// report, see [`Retry::spawn_with_report`](crate::Retry::spawn_with_report).
+pub(crate) async fn retry_with_report<T, A>(
+    strategy: T,
+    action: A,
+) -> (Result<A::Item, A::Error>, RetryReport)
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+    A::Error: fmt::Display,
+{
+    let report = Arc::new(Mutex::new(RetryReport::default()));
+    let action = ReportAction {
+        action,
+        report: Arc::clone(&report),
+        started: None,
+    };
+    let result = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&A::Error) -> bool,
+        (|_, _| {}) as fn(&A::Error, Duration),
+    )
+    .await;
+
+    let report = std::mem::take(&mut *lock(&report));
+    (result, report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn records_every_attempt() {
+        let attempts = AtomicUsize::new(0);
+        let (res, report) = retry_with_report(FixedInterval::from_millis(10), || {
+            future::ready(match attempts.fetch_add(1, Ordering::SeqCst) {
+                0 => RetryError::to_transient("unavailable"),
+                _ => Ok(7),
+            })
+        })
+        .await;
+
+        assert_eq!(res, Ok(7));
+        let records = report.attempts();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].error.as_deref(), Some("unavailable"));
+        assert_eq!(records[0].delay, Some(Duration::from_millis(10)));
+        assert_eq!(records[1].error, None);
+        assert_eq!(records[1].delay, None);
+        assert!(records[1].start >= Duration::from_millis(10));
+        assert!(report.elapsed() >= records[1].start);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serializes_durations_in_milliseconds() {
+        let report = RetryReport {
+            attempts: vec![AttemptRecord {
+                start: Duration::ZERO,
+                duration: Duration::from_micros(1_500),
+                error: Some("unavailable".to_owned()),
+                delay: Some(Duration::from_millis(100)),
+            }],
+        };
+
+        assert_eq!(
+            report.to_json(),
+            r#"{"attempts":[{"start_ms":0.0,"duration_ms":1.5,"error":"unavailable","delay_ms":100.0}]}"#
+        );
+    }
+}
@@ -0,0 +1,46 @@
+//! Process-wide kill switch for retries.
+//!
+//! When retries are disabled, every retry loop makes exactly one attempt and
+//! returns its error straight away. This is useful during incident response,
+//! when retries are amplifying an outage, and for CI runs that should fail fast.
+//!
+//! Retries can also be disabled by setting the `TOKIO_RETRY2_DISABLED`
+//! environment variable, which is read once, the first time a retry loop
+//! checks the switch. Calling [`enable`] or [`disable`] overrides it.
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// Environment variable disabling retries when set.
+pub const DISABLED_ENV_VAR: &str = "TOKIO_RETRY2_DISABLED";
+
+const UNSET: u8 = 0;
+const ENABLED: u8 = 1;
+const DISABLED: u8 = 2;
+
+static STATE: AtomicU8 = AtomicU8::new(UNSET);
+
+/// Disables retries for the whole process.
+pub fn disable() {
+    STATE.store(DISABLED, Ordering::Relaxed);
+}
+
+/// Re-enables retries for the whole process.
+pub fn enable() {
+    STATE.store(ENABLED, Ordering::Relaxed);
+}
+
+/// Returns `true` unless retries have been disabled.
+pub fn is_enabled() -> bool {
+    match STATE.load(Ordering::Relaxed) {
+        ENABLED => true,
+        DISABLED => false,
+        _ => {
+            let state = if std::env::var_os(DISABLED_ENV_VAR).is_some() {
+                DISABLED
+            } else {
+                ENABLED
+            };
+            let _ = STATE.compare_exchange(UNSET, state, Ordering::Relaxed, Ordering::Relaxed);
+            STATE.load(Ordering::Relaxed) == ENABLED
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::IntoIterator;
+
+use tokio::time::Duration;
+
+use crate::action::Action;
+use crate::future::Retry;
+use crate::strategy::{RetryBudget, Strategy, WhileBudget, WhileBudgetIterator};
+
+/// Retry policies scoped by key, e.g. a tenant or customer, each made of a strategy
+/// and a [`RetryBudget`], with a default policy for keys without their own.
+///
+/// Call sites retry through [`ScopedPolicies::retry`] with the key of the request, so
+/// noisy tenants can be given stricter strategies or smaller budgets in one place.
+/// Every scope shares its budget between all the retries made for its key.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{RetryError, ScopedPolicies};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries, RetryBudget};
+///
+/// async fn query(tenant: &str) -> Result<u64, RetryError<std::io::Error>> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), std::io::Error> {
+/// let policies = ScopedPolicies::new(
+///     ExponentialBackoff::from_millis(10).max_retries(5),
+///     RetryBudget::new(1_000),
+/// )
+/// .scope(
+///     "noisy-tenant",
+///     ExponentialBackoff::from_millis(100).max_retries(2),
+///     RetryBudget::new(50),
+/// );
+///
+/// let rows = policies.retry("noisy-tenant", || query("noisy-tenant")).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ScopedPolicies<K, S> {
+    default: (S, RetryBudget),
+    scopes: HashMap<K, (S, RetryBudget)>,
+}
+
+impl<K, S> ScopedPolicies<K, S>
+where
+    K: Eq + Hash,
+    S: IntoIterator<Item = Duration> + Clone,
+    S::IntoIter: Strategy,
+{
+    /// Constructs scoped policies where every key falls back to `strategy`, spending
+    /// retries from the shared default `budget`.
+    pub fn new(strategy: S, budget: RetryBudget) -> Self {
+        ScopedPolicies {
+            default: (strategy, budget),
+            scopes: HashMap::new(),
+        }
+    }
+
+    /// Retries made for `key` use `strategy` and spend retries from `budget`,
+    /// replacing any previous policy of `key`.
+    pub fn scope(mut self, key: K, strategy: S, budget: RetryBudget) -> Self {
+        self.scopes.insert(key, (strategy, budget));
+        self
+    }
+
+    /// The budget retries made for `key` spend, e.g. to refill it periodically.
+    pub fn budget<Q>(&self, key: &Q) -> &RetryBudget
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        &self.policy(key).1
+    }
+
+    /// Retries `action` with the policy of `key`, or the default one.
+    pub fn retry<Q, A>(&self, key: &Q, action: A) -> Retry<WhileBudgetIterator<S::IntoIter>, A>
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+        A: Action,
+    {
+        let (strategy, budget) = self.policy(key);
+        Retry::spawn(
+            strategy.clone().into_iter().while_budget(budget.clone()),
+            action,
+        )
+    }
+
+    fn policy<Q>(&self, key: &Q) -> &(S, RetryBudget)
+    where
+        K: Borrow<Q>,
+        Q: Eq + Hash + ?Sized,
+    {
+        self.scopes.get(key).unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::error::Error as RetryError;
+    use crate::strategy::{FixedInterval, Limited, MaxRetries};
+
+    fn policies() -> ScopedPolicies<&'static str, Limited<FixedInterval>> {
+        ScopedPolicies::new(
+            FixedInterval::from_millis(1).max_retries(3),
+            RetryBudget::new(100),
+        )
+        .scope(
+            "noisy",
+            FixedInterval::from_millis(1).max_retries(3),
+            RetryBudget::new(1),
+        )
+    }
+
+    async fn attempts(
+        policies: &ScopedPolicies<&'static str, Limited<FixedInterval>>,
+        key: &str,
+    ) -> usize {
+        let counter = AtomicUsize::new(0);
+        let res = policies
+            .retry(key, || {
+                counter.fetch_add(1, Ordering::SeqCst);
+                future::ready(RetryError::to_transient::<()>(42))
+            })
+            .await;
+        assert_eq!(res, Err(42));
+        counter.load(Ordering::SeqCst)
+    }
+
+    #[tokio::test]
+    async fn scopes_budgets_by_key() {
+        let policies = policies();
+
+        // the noisy tenant spends its single retry, then only gets first attempts
+        assert_eq!(attempts(&policies, "noisy").await, 2);
+        assert_eq!(attempts(&policies, "noisy").await, 1);
+        assert_eq!(policies.budget("noisy").remaining(), 0);
+
+        // other tenants fall back to the default policy
+        assert_eq!(attempts(&policies, "quiet").await, 4);
+        assert_eq!(policies.budget("quiet").remaining(), 97);
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use sentry_core::protocol::{Breadcrumb, Level, Map, Value};
+use tokio::time::Duration;
+
+use crate::notify::{Notify, StopReason};
+
+/// A [`Notify`] recording the retry story as Sentry breadcrumbs.
+///
+/// A breadcrumb is buffered for every retried error, with the attempt number, the
+/// delay before the next attempt and the error. They are only added to the current
+/// Sentry scope once the loop stops without succeeding, along with a last breadcrumb
+/// holding the [`StopReason`], so the event reporting the final error shows every
+/// attempt. Nothing is recorded when the loop succeeds or is cancelled.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{RetryError, RetryIf, SentryNotify};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// async fn action() -> Result<u64, RetryError<std::io::Error>> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let strategy = ExponentialBackoff::from_millis(10).max_retries(3);
+/// let result = RetryIf::spawn(strategy, action, |_: &std::io::Error| true, SentryNotify::new()).await;
+/// # }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SentryNotify {
+    breadcrumbs: Vec<Breadcrumb>,
+    attempts: u32,
+}
+
+impl SentryNotify {
+    /// Constructs a notify without any recorded attempt.
+    pub fn new() -> SentryNotify {
+        SentryNotify::default()
+    }
+
+    fn breadcrumb(level: Level, message: String, data: Map<String, Value>) -> Breadcrumb {
+        Breadcrumb {
+            category: Some("retry".into()),
+            level,
+            message: Some(message),
+            data,
+            ..Breadcrumb::default()
+        }
+    }
+}
+
+impl<E: fmt::Display> Notify<E> for SentryNotify {
+    fn notify(&mut self, err: &E, duration: Duration) {
+        self.attempts = self.attempts.saturating_add(1);
+        let mut data = Map::new();
+        data.insert("attempt".into(), self.attempts.into());
+        data.insert(
+            "delay_ms".into(),
+            u64::try_from(duration.as_millis())
+                .unwrap_or(u64::MAX)
+                .into(),
+        );
+        data.insert("error".into(), err.to_string().into());
+        self.breadcrumbs.push(SentryNotify::breadcrumb(
+            Level::Warning,
+            format!("attempt {} failed, retrying", self.attempts),
+            data,
+        ));
+    }
+
+    fn notify_stop(&mut self, reason: StopReason) {
+        let breadcrumbs = std::mem::take(&mut self.breadcrumbs);
+        if reason == StopReason::Cancelled {
+            return;
+        }
+        let attempts = self.attempts.saturating_add(1);
+        let mut data = Map::new();
+        data.insert("attempts".into(), attempts.into());
+        data.insert("reason".into(), reason.as_str().into());
+
+        for breadcrumb in breadcrumbs {
+            sentry_core::add_breadcrumb(breadcrumb);
+        }
+        sentry_core::add_breadcrumb(SentryNotify::breadcrumb(
+            Level::Error,
+            format!("retry stopped after {attempts} attempts"),
+            data,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn attaches_breadcrumbs_once_stopped() {
+        let events = sentry_core::test::with_captured_events(|| {
+            let mut notify = SentryNotify::new();
+            Notify::<&str>::notify(&mut notify, &"unavailable", Duration::from_millis(10));
+            Notify::<&str>::notify(&mut notify, &"unavailable", Duration::from_millis(20));
+            sentry_core::capture_message("before stopping", Level::Error);
+
+            Notify::<&str>::notify_stop(&mut notify, StopReason::MaxRetriesExceeded);
+            sentry_core::capture_message("retries exhausted", Level::Error);
+        });
+
+        assert_eq!(events.len(), 2);
+        assert!(events[0].breadcrumbs.is_empty());
+
+        let breadcrumbs = &events[1].breadcrumbs.values;
+        assert_eq!(breadcrumbs.len(), 3);
+        assert_eq!(breadcrumbs[1].data["delay_ms"], Value::from(20));
+        assert_eq!(breadcrumbs[1].data["error"], Value::from("unavailable"));
+        assert_eq!(breadcrumbs[2].data["attempts"], Value::from(3));
+        assert_eq!(
+            breadcrumbs[2].data["reason"],
+            Value::from("max_retries_exceeded")
+        );
+    }
+
+    #[test]
+    fn records_nothing_when_cancelled() {
+        let events = sentry_core::test::with_captured_events(|| {
+            let mut notify = SentryNotify::new();
+            Notify::<&str>::notify(&mut notify, &"unavailable", Duration::from_millis(10));
+            Notify::<&str>::notify_stop(&mut notify, StopReason::Cancelled);
+            sentry_core::capture_message("cancelled", Level::Error);
+        });
+
+        assert!(events[0].breadcrumbs.is_empty());
+    }
+}
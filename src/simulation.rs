@@ -0,0 +1,225 @@
+//! Offline evaluation of retry strategies against simple failure models.
+//!
+//! [`simulate`] runs a strategy many times against a [`FailureModel`] without
+//! sleeping, and summarizes the outcome in a [`Report`]: how often and how fast calls
+//! succeed, their tail latency, and the load put on the dependency. Simulations are
+//! pure and deterministic for a given seed, so policy changes can be compared in
+//! tests or from a small CLI.
+//!
+//! ```rust
+//! use std::time::Duration;
+//! use tokio_retry2::simulation::{simulate, FailureModel};
+//! use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+//!
+//! let strategy = FixedInterval::from_millis(100).max_retries(5);
+//! let report = simulate(strategy, FailureModel::Probability(0.3));
+//!
+//! assert!(report.success_rate > 0.99);
+//! assert!(report.mean_attempts < 1.5);
+//! assert!(report.p99_latency < Duration::from_secs(1));
+//! ```
+use alloc::vec::Vec;
+use core::time::Duration;
+
+use crate::strategy::splitmix64;
+
+/// How the simulated dependency fails.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FailureModel {
+    /// Every attempt fails independently with the given probability, between `0.0`
+    /// and `1.0`.
+    Probability(f64),
+    /// The dependency is down for the given duration. Calls start at points evenly
+    /// spread over the outage, and every attempt starting before its end fails.
+    Outage(Duration),
+}
+
+/// The outcome of a simulation, see [`simulate`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct Report {
+    /// The number of simulated calls.
+    pub runs: u64,
+    /// The share of calls which succeeded before the strategy was exhausted.
+    pub success_rate: f64,
+    /// The mean time from the first attempt until success, over successful calls,
+    /// or `None` if no call succeeded.
+    pub mean_time_to_success: Option<Duration>,
+    /// The 99th percentile of the total time spent in a call, whether it succeeded
+    /// or gave up.
+    pub p99_latency: Duration,
+    /// The mean number of attempts per call, i.e. the load put on the dependency
+    /// relative to a policy without retries.
+    pub mean_attempts: f64,
+}
+
+/// A simulation, configured with builder methods and run with [`Simulation::run`].
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    model: FailureModel,
+    runs: u64,
+    seed: u64,
+    attempt_duration: Duration,
+    max_attempts: u64,
+}
+
+impl Simulation {
+    /// Constructs a simulation of 10 000 calls against `model`, with instantaneous
+    /// attempts and a seed of `0`.
+    pub const fn new(model: FailureModel) -> Simulation {
+        Simulation {
+            model,
+            runs: 10_000,
+            seed: 0,
+            attempt_duration: Duration::ZERO,
+            max_attempts: 1_000,
+        }
+    }
+
+    /// Sets the number of simulated calls. At least one call is simulated.
+    pub const fn runs(mut self, runs: u64) -> Simulation {
+        self.runs = if runs == 0 { 1 } else { runs };
+        self
+    }
+
+    /// Sets the seed of the failures drawn by [`FailureModel::Probability`].
+    pub const fn seed(mut self, seed: u64) -> Simulation {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets how long every attempt takes, added to the latency of calls.
+    pub const fn attempt_duration(mut self, attempt_duration: Duration) -> Simulation {
+        self.attempt_duration = attempt_duration;
+        self
+    }
+
+    /// Caps the attempts of a call, counted as giving up, so unbounded strategies
+    /// against a dependency that never recovers terminate. Default is `1000`.
+    pub const fn max_attempts(mut self, max_attempts: u64) -> Simulation {
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Runs a fresh clone of `strategy` for every simulated call.
+    pub fn run<S>(&self, strategy: S) -> Report
+    where
+        S: IntoIterator<Item = Duration> + Clone,
+    {
+        let mut state = self.seed;
+        let mut latencies = Vec::with_capacity(self.runs as usize);
+        let mut successes = 0u64;
+        let mut time_to_success = Duration::ZERO;
+        let mut attempts = 0u64;
+
+        for run in 0..self.runs {
+            let start = match self.model {
+                FailureModel::Outage(outage) => outage.mul_f64(run as f64 / self.runs as f64),
+                FailureModel::Probability(_) => Duration::ZERO,
+            };
+            let mut delays = strategy.clone().into_iter();
+            let mut elapsed = Duration::ZERO;
+            let mut succeeded = false;
+
+            for attempt in 1..=self.max_attempts {
+                attempts += 1;
+                let failed = match self.model {
+                    FailureModel::Probability(probability) => {
+                        // the 53 high bits fill the mantissa of a `f64` in `[0, 1)`
+                        let draw = (splitmix64(&mut state) >> 11) as f64 / (1u64 << 53) as f64;
+                        draw < probability
+                    }
+                    FailureModel::Outage(outage) => start.saturating_add(elapsed) < outage,
+                };
+                elapsed = elapsed.saturating_add(self.attempt_duration);
+                if !failed {
+                    succeeded = true;
+                    break;
+                }
+                match delays.next() {
+                    Some(delay) if attempt < self.max_attempts => {
+                        elapsed = elapsed.saturating_add(delay);
+                    }
+                    _ => break,
+                }
+            }
+
+            if succeeded {
+                successes += 1;
+                time_to_success = time_to_success.saturating_add(elapsed);
+            }
+            latencies.push(elapsed);
+        }
+
+        latencies.sort_unstable();
+        let p99 = (latencies.len() * 99).div_ceil(100).saturating_sub(1);
+        Report {
+            runs: self.runs,
+            success_rate: successes as f64 / self.runs as f64,
+            mean_time_to_success: (successes > 0)
+                .then(|| time_to_success.div_f64(successes as f64)),
+            p99_latency: latencies[p99],
+            mean_attempts: attempts as f64 / self.runs as f64,
+        }
+    }
+}
+
+/// Simulates 10 000 calls retried with `strategy` against `model`,
+/// see [`Simulation`] to change the defaults.
+pub fn simulate<S>(strategy: S, model: FailureModel) -> Report
+where
+    S: IntoIterator<Item = Duration> + Clone,
+{
+    Simulation::new(model).run(strategy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+
+    #[test]
+    fn is_deterministic_for_a_seed() {
+        let strategy = FixedInterval::from_millis(10).max_retries(3);
+        let simulation = Simulation::new(FailureModel::Probability(0.5)).seed(42);
+
+        assert_eq!(simulation.run(strategy.clone()), simulation.run(strategy));
+    }
+
+    #[test]
+    fn reports_never_recovering_dependency() {
+        let report = Simulation::new(FailureModel::Probability(1.0))
+            .runs(10)
+            .attempt_duration(Duration::from_millis(5))
+            .run(FixedInterval::from_millis(10).max_retries(3));
+
+        assert_eq!(report.success_rate, 0.0);
+        assert_eq!(report.mean_time_to_success, None);
+        assert_eq!(report.mean_attempts, 4.0);
+        assert_eq!(report.p99_latency, Duration::from_millis(50));
+    }
+
+    #[test]
+    fn rides_out_an_outage() {
+        let report = Simulation::new(FailureModel::Outage(Duration::from_secs(1)))
+            .runs(100)
+            .run(FixedInterval::from_millis(100));
+
+        // calls start every 10ms into the outage, and retry every 100ms until its end
+        assert_eq!(report.success_rate, 1.0);
+        assert_eq!(report.p99_latency, Duration::from_secs(1));
+        assert_eq!(report.mean_attempts, 6.5);
+    }
+
+    #[test]
+    fn caps_unbounded_strategies() {
+        let report = Simulation::new(FailureModel::Probability(1.0))
+            .runs(1)
+            .max_attempts(7)
+            .run(FixedInterval::from_millis(1));
+
+        assert_eq!(report.mean_attempts, 7.0);
+        assert_eq!(report.p99_latency, Duration::from_millis(6));
+    }
+}
@@ -0,0 +1,232 @@
+//! Sharing one retry loop between identical concurrent operations.
+//!
+//! When many callers fetch the same upstream resource at once, e.g. a cache miss on
+//! a hot key, each of them running its own retry loop multiplies the load on an
+//! upstream which is likely already struggling. A [`SingleFlight`] group runs one
+//! retry loop per key: the first caller drives it, and callers with the same key
+//! arriving meanwhile wait for it and receive a clone of its result.
+//!
+//! ```rust,no_run
+//! use tokio_retry2::{Retry, RetryError};
+//! use tokio_retry2::singleflight::SingleFlight;
+//! use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+//!
+//! async fn fetch_config() -> Result<String, RetryError<String>> {
+//!     Ok("{}".to_owned())
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> Result<(), String> {
+//! let group = SingleFlight::new();
+//! let strategy = || ExponentialBackoff::from_millis(10).max_retries(3);
+//!
+//! let (first, second) = tokio::join!(
+//!     Retry::spawn_keyed(&group, "config", strategy(), fetch_config),
+//!     Retry::spawn_keyed(&group, "config", strategy(), fetch_config),
+//! );
+//! assert_eq!(first?, second?);
+//! # Ok(())
+//! # }
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hash;
+use std::iter::IntoIterator;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use tokio::sync::watch;
+use tokio::time::Duration;
+
+use crate::action::Action;
+use crate::future::Retry;
+use crate::strategy::Strategy;
+
+type Calls<K, T, E> = HashMap<K, watch::Receiver<Option<Result<T, E>>>>;
+
+/// A group of retry loops deduplicated by key, see the [module documentation](self).
+///
+/// Clones share the same in-flight loops.
+pub struct SingleFlight<K, T, E> {
+    calls: Arc<Mutex<Calls<K, T, E>>>,
+}
+
+impl<K, T, E> SingleFlight<K, T, E>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+    E: Clone,
+{
+    /// Constructs a group without loops in flight.
+    pub fn new() -> SingleFlight<K, T, E> {
+        SingleFlight {
+            calls: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Retries `action` via `strategy`, unless a loop for `key` is already in flight,
+    /// in which case its result is awaited instead and `action` never runs.
+    ///
+    /// If the caller driving the loop is cancelled, one of the callers waiting for it
+    /// starts over with its own strategy and action.
+    pub async fn retry<S, A>(&self, key: K, strategy: S, action: A) -> Result<T, E>
+    where
+        S: IntoIterator<Item = Duration>,
+        S::IntoIter: Strategy,
+        A: Action<Item = T, Error = E>,
+    {
+        loop {
+            let leading = {
+                let mut calls = self.lock();
+                match calls.get(&key) {
+                    Some(call) => Err(call.clone()),
+                    None => {
+                        let (sender, receiver) = watch::channel(None);
+                        calls.insert(key.clone(), receiver);
+                        Ok(sender)
+                    }
+                }
+            };
+            let waiting = match leading {
+                Ok(sender) => return self.lead(key, sender, strategy, action).await,
+                Err(waiting) => waiting,
+            };
+            #[cfg(feature = "tracing")]
+            tracing::debug!("joining an in-flight retry");
+            if let Some(result) = Self::wait(waiting).await {
+                return result;
+            }
+            // the caller driving the loop was cancelled
+        }
+    }
+
+    /// The number of keys with a loop in flight.
+    pub fn in_flight(&self) -> usize {
+        self.lock().len()
+    }
+
+    async fn lead<S, A>(
+        &self,
+        key: K,
+        sender: watch::Sender<Option<Result<T, E>>>,
+        strategy: S,
+        action: A,
+    ) -> Result<T, E>
+    where
+        S: IntoIterator<Item = Duration>,
+        S::IntoIter: Strategy,
+        A: Action<Item = T, Error = E>,
+    {
+        // removes the key even when cancelled, waking up the callers waiting for it
+        let _call = Call { group: self, key };
+        let result = Retry::spawn(strategy, action).await;
+        sender.send_replace(Some(result.clone()));
+        result
+    }
+
+    async fn wait(mut waiting: watch::Receiver<Option<Result<T, E>>>) -> Option<Result<T, E>> {
+        let result = waiting.wait_for(Option::is_some).await.ok()?;
+        result.clone()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Calls<K, T, E>> {
+        self.calls
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// The loop in flight for `key`, removed from `group` on drop.
+struct Call<'a, K: Eq + Hash + Clone, T: Clone, E: Clone> {
+    group: &'a SingleFlight<K, T, E>,
+    key: K,
+}
+
+impl<K: Eq + Hash + Clone, T: Clone, E: Clone> Drop for Call<'_, K, T, E> {
+    fn drop(&mut self) {
+        self.group.lock().remove(&self.key);
+    }
+}
+
+impl<K, T, E> Clone for SingleFlight<K, T, E> {
+    fn clone(&self) -> Self {
+        SingleFlight {
+            calls: self.calls.clone(),
+        }
+    }
+}
+
+impl<K, T, E> Default for SingleFlight<K, T, E>
+where
+    K: Eq + Hash + Clone,
+    T: Clone,
+    E: Clone,
+{
+    fn default() -> Self {
+        SingleFlight::new()
+    }
+}
+
+impl<K, T, E> fmt::Debug for SingleFlight<K, T, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SingleFlight").finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+    use crate::RetryError;
+
+    #[tokio::test]
+    async fn concurrent_callers_share_one_loop() {
+        let group = SingleFlight::new();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let action = || {
+            let attempts = attempts.clone();
+            move || {
+                let attempts = attempts.clone();
+                async move {
+                    tokio::time::sleep(Duration::from_millis(20)).await;
+                    match attempts.fetch_add(1, Ordering::SeqCst) {
+                        0 => RetryError::to_transient("unavailable"),
+                        n => Ok(n),
+                    }
+                }
+            }
+        };
+        let strategy = || FixedInterval::from_millis(10).max_retries(3);
+
+        let (first, second, third) = tokio::join!(
+            group.retry("key", strategy(), action()),
+            group.retry("key", strategy(), action()),
+            group.retry("key", strategy(), action()),
+        );
+
+        assert_eq!((first, second, third), (Ok(1), Ok(1), Ok(1)));
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(group.in_flight(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_waiting_caller_takes_over_a_cancelled_loop() {
+        let group: SingleFlight<&str, u64, ()> = SingleFlight::new();
+        let strategy = || FixedInterval::from_millis(10).max_retries(3);
+
+        let leader = group.retry("key", strategy(), || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok(1)
+        });
+        let follower = group.retry("key", strategy(), || async { Ok(2) });
+        let (leader, follower) = tokio::join!(
+            tokio::time::timeout(Duration::from_millis(20), leader),
+            follower
+        );
+
+        assert!(leader.is_err());
+        assert_eq!(follower, Ok(2));
+    }
+}
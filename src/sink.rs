@@ -0,0 +1,227 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_sink::Sink;
+use pin_project::pin_project;
+use tokio::time::{sleep, Duration, Sleep};
+
+/// Wraps `sink` so that failed `send`/`flush` operations are retried with backoff.
+///
+/// Every item is buffered until the underlying sink has accepted and flushed it,
+/// and is sent again on failure. `strategy_factory` builds a fresh strategy for every
+/// item, so the strategy caps the retries per item. Once it is exhausted, the
+/// buffered item is dropped and the last error is returned.
+///
+/// Because an item only leaves the buffer once it has been flushed, the wrapped
+/// sink is flushed after every item.
+pub fn retrying<S, F, P, Item>(sink: S, strategy_factory: F) -> RetrySink<S, F, P, Item>
+where
+    S: Sink<Item>,
+    Item: Clone,
+    F: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    RetrySink {
+        sink,
+        strategy_factory,
+        strategy: None,
+        sleep: None,
+        buffered: None,
+        sent: false,
+    }
+}
+
+/// Sink that retries failed writes of the underlying sink,
+/// created by the [`retrying`] function.
+#[pin_project]
+pub struct RetrySink<S, F, P, Item>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    #[pin]
+    sink: S,
+    strategy_factory: F,
+    strategy: Option<P::IntoIter>,
+    #[pin]
+    sleep: Option<Sleep>,
+    buffered: Option<Item>,
+    sent: bool,
+}
+
+impl<S, F, P, Item> RetrySink<S, F, P, Item>
+where
+    S: Sink<Item>,
+    Item: Clone,
+    F: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    /// Drives the buffered item (if any) through `poll_ready`, `start_send` and
+    /// `poll_flush` of the underlying sink, backing off after every failure.
+    fn poll_deliver(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), S::Error>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut().as_pin_mut() {
+                ready!(sleep.poll(cx));
+                this.sleep.set(None);
+            }
+
+            let result = match this.buffered {
+                Some(item) if !*this.sent => match ready!(this.sink.as_mut().poll_ready(cx)) {
+                    Ok(()) => this.sink.as_mut().start_send(item.clone()).map(|_| {
+                        *this.sent = true;
+                    }),
+                    Err(err) => Err(err),
+                },
+                _ => match ready!(this.sink.as_mut().poll_flush(cx)) {
+                    Ok(()) => {
+                        *this.buffered = None;
+                        *this.sent = false;
+                        *this.strategy = None;
+                        return Poll::Ready(Ok(()));
+                    }
+                    Err(err) => Err(err),
+                },
+            };
+
+            if let Err(err) = result {
+                *this.sent = false;
+                let strategy_factory = &mut *this.strategy_factory;
+                let strategy = this
+                    .strategy
+                    .get_or_insert_with(|| strategy_factory().into_iter());
+                match crate::delay::plain_delay(strategy, &err, None) {
+                    Some(duration) => this.sleep.set(Some(sleep(duration))),
+                    None => {
+                        #[cfg(feature = "tracing")]
+                        tracing::warn!("ending sink retry: strategy reached its limit");
+                        *this.strategy = None;
+                        *this.buffered = None;
+                        return Poll::Ready(Err(err));
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<S, F, P, Item> Sink<Item> for RetrySink<S, F, P, Item>
+where
+    S: Sink<Item>,
+    Item: Clone,
+    F: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    type Error = S::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        if self.buffered.is_some() {
+            self.poll_deliver(cx)
+        } else {
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Item) -> Result<(), Self::Error> {
+        let this = self.project();
+        *this.buffered = Some(item);
+        *this.sent = false;
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        self.poll_deliver(cx)
+    }
+
+    fn poll_close(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+        ready!(self.as_mut().poll_deliver(cx))?;
+        self.project().sink.poll_close(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::pin::pin;
+
+    use futures::SinkExt;
+
+    use crate::strategy::FixedInterval;
+
+    /// Sink failing the first `send_failures` sends and `flush_failures` flushes.
+    #[derive(Default)]
+    struct FlakySink {
+        send_failures: usize,
+        flush_failures: usize,
+        pending: Vec<u64>,
+        delivered: Vec<u64>,
+    }
+
+    impl Sink<u64> for FlakySink {
+        type Error = &'static str;
+
+        fn poll_ready(self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn start_send(mut self: Pin<&mut Self>, item: u64) -> Result<(), Self::Error> {
+            if self.send_failures > 0 {
+                self.send_failures -= 1;
+                return Err("send failed");
+            }
+            self.pending.push(item);
+            Ok(())
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, _: &mut Context) -> Poll<Result<(), Self::Error>> {
+            if self.flush_failures > 0 {
+                self.flush_failures -= 1;
+                self.pending.clear();
+                return Poll::Ready(Err("flush failed"));
+            }
+            let pending = std::mem::take(&mut self.pending);
+            self.delivered.extend(pending);
+            Poll::Ready(Ok(()))
+        }
+
+        fn poll_close(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), Self::Error>> {
+            self.poll_flush(cx)
+        }
+    }
+
+    #[tokio::test]
+    async fn retries_failed_sends_and_flushes() {
+        let mut inner = FlakySink {
+            send_failures: 2,
+            flush_failures: 1,
+            ..Default::default()
+        };
+        {
+            let mut sink = pin!(retrying(&mut inner, || {
+                FixedInterval::from_millis(1).take(3)
+            }));
+
+            assert_eq!(sink.send(1).await, Ok(()));
+            assert_eq!(sink.send(2).await, Ok(()));
+        }
+        assert_eq!(inner.delivered, vec![1, 2]);
+    }
+
+    #[tokio::test]
+    async fn returns_error_once_strategy_is_exhausted() {
+        let mut inner = FlakySink {
+            send_failures: 3,
+            ..Default::default()
+        };
+        {
+            let mut sink = pin!(retrying(&mut inner, || {
+                FixedInterval::from_millis(1).take(2)
+            }));
+
+            assert_eq!(sink.send(1).await, Err("send failed"));
+            assert_eq!(sink.send(2).await, Ok(()));
+        }
+        assert_eq!(inner.delivered, vec![2]);
+    }
+}
@@ -0,0 +1,273 @@
+use std::fmt;
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::Duration;
+
+use crate::error::Error as RetryError;
+use crate::future::{RetryIf, WithHooks};
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// Error returned by [`poll_until_stable`].
+#[derive(Debug, PartialEq)]
+pub enum StableError<T, E> {
+    /// The action failed permanently, or transiently once the strategy was exhausted.
+    Action(E),
+    /// The strategy was exhausted before the action returned the same value
+    /// `eq_window` times in a row. Holds the last value returned.
+    Unstable(T),
+}
+
+impl<T, E> fmt::Display for StableError<T, E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            StableError::Action(err) => err.fmt(f),
+            StableError::Unstable(_) => f.write_str("value did not stabilize"),
+        }
+    }
+}
+
+/// Keeps invoking `action` until it returns the same `Ok` value `eq_window` times
+/// in a row, and returns that stabilized value.
+///
+/// Every invocation but the first is preceded by a delay taken from `strategy`, so
+/// the strategy bounds both the retries after errors and the polls while the value
+/// is still changing. Transient errors reset the streak and honor `retry_after`.
+/// An `eq_window` of `0` or `1` returns the first `Ok` value.
+///
+/// Useful for eventually consistent APIs, e.g. waiting for a provisioned cloud
+/// resource to report the same state on consecutive reads.
+pub fn poll_until_stable<T, A>(
+    strategy: T,
+    action: A,
+    eq_window: usize,
+) -> WithHooks<impl Future<Output = Result<A::Item, StableError<A::Item, A::Error>>>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+    A::Item: PartialEq,
+{
+    WithHooks::run(action, |action| until_stable(strategy, action, eq_window))
+}
+
+async fn until_stable<T, A>(
+    strategy: T,
+    action: A,
+    eq_window: usize,
+) -> Result<A::Item, StableError<A::Item, A::Error>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+    A::Item: PartialEq,
+{
+    let streak = Arc::new(Mutex::new(Streak {
+        last: None,
+        count: 0,
+    }));
+    let action = StableAction {
+        action,
+        streak: Arc::clone(&streak),
+        eq_window,
+    };
+    let result = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&Unsettled<A::Error>) -> bool,
+        (|_, _| {}) as fn(&Unsettled<A::Error>, Duration),
+    )
+    .await;
+
+    result.map_err(|err| match err {
+        Unsettled::Action(err) => StableError::Action(err),
+        Unsettled::Changing => StableError::Unstable(
+            lock(&streak)
+                .last
+                .take()
+                .expect("an unstable value was just stored"),
+        ),
+    })
+}
+
+/// Why a poll of [`poll_until_stable`] is retried.
+enum Unsettled<E> {
+    /// The action failed.
+    Action(E),
+    /// The action returned a value, but not `eq_window` times in a row yet.
+    Changing,
+}
+
+/// The values returned by the attempts of a [`StableAction`] so far.
+struct Streak<T> {
+    last: Option<T>,
+    count: usize,
+}
+
+fn lock<T>(streak: &Mutex<Streak<T>>) -> MutexGuard<'_, Streak<T>> {
+    streak
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An action failing with [`Unsettled::Changing`] until the wrapped action returned
+/// the same value `eq_window` times in a row.
+struct StableAction<A: Action> {
+    action: A,
+    streak: Arc<Mutex<Streak<A::Item>>>,
+    eq_window: usize,
+}
+
+impl<A> Action for StableAction<A>
+where
+    A: Action,
+    A::Item: PartialEq,
+{
+    type Item = A::Item;
+    type Error = Unsettled<A::Error>;
+    type Future = StableFuture<A::Future, A::Item>;
+
+    fn run(&mut self) -> Self::Future {
+        StableFuture {
+            future: self.action.run(),
+            streak: Arc::clone(&self.streak),
+            eq_window: self.eq_window,
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.action.before_retry(delay);
+    }
+}
+
+/// Future comparing the value of an attempt of a [`StableAction`] to the previous one.
+#[pin_project]
+struct StableFuture<Fut, T> {
+    #[pin]
+    future: Fut,
+    streak: Arc<Mutex<Streak<T>>>,
+    eq_window: usize,
+}
+
+impl<T, E, Fut> Future for StableFuture<Fut, T>
+where
+    Fut: Future<Output = Result<T, RetryError<E>>>,
+    T: PartialEq,
+{
+    type Output = Result<T, RetryError<Unsettled<E>>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let item = match ready!(this.future.poll(cx)) {
+            Ok(item) => item,
+            Err(err) => {
+                if matches!(err, RetryError::Transient { .. }) {
+                    // transient errors reset the streak
+                    lock(this.streak).count = 0;
+                }
+                return Poll::Ready(Err(err.map(Unsettled::Action)));
+            }
+        };
+        let mut streak = lock(this.streak);
+        if streak.last.as_ref() == Some(&item) {
+            streak.count += 1;
+        } else {
+            streak.count = 1;
+        }
+        if streak.count >= *this.eq_window {
+            return Poll::Ready(Ok(item));
+        }
+        streak.last = Some(item);
+        Poll::Ready(Err(RetryError::transient(Unsettled::Changing)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn returns_value_once_stable() {
+        let values = [1, 2, 2, 3, 3, 3, 4];
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cloned_counter = counter.clone();
+        let result = poll_until_stable(
+            FixedInterval::from_millis(1).take(10),
+            move || {
+                let i = cloned_counter.fetch_add(1, Ordering::SeqCst);
+                future::ready(Ok::<_, RetryError<()>>(values[i]))
+            },
+            3,
+        )
+        .await;
+
+        assert_eq!(result, Ok(3));
+        assert_eq!(counter.load(Ordering::SeqCst), 6);
+    }
+
+    #[tokio::test]
+    async fn transient_errors_reset_the_streak() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cloned_counter = counter.clone();
+        let result = poll_until_stable(
+            FixedInterval::from_millis(1).take(10),
+            move || {
+                let i = cloned_counter.fetch_add(1, Ordering::SeqCst);
+                if i == 1 {
+                    future::ready(RetryError::to_transient(42))
+                } else {
+                    future::ready(Ok(7))
+                }
+            },
+            2,
+        )
+        .await;
+
+        assert_eq!(result, Ok(7));
+        assert_eq!(counter.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn returns_last_value_when_exhausted() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cloned_counter = counter.clone();
+        let result = poll_until_stable(
+            FixedInterval::from_millis(1).take(2),
+            move || {
+                let i = cloned_counter.fetch_add(1, Ordering::SeqCst);
+                future::ready(Ok::<_, RetryError<()>>(i))
+            },
+            2,
+        )
+        .await;
+
+        assert_eq!(result, Err(StableError::Unstable(2)));
+    }
+
+    #[tokio::test]
+    async fn stops_on_permanent_error() {
+        let result = poll_until_stable(
+            FixedInterval::from_millis(1).take(2),
+            || future::ready(RetryError::to_permanent::<u64>(42)),
+            2,
+        )
+        .await;
+
+        assert_eq!(result, Err(StableError::Action(42)));
+    }
+}
@@ -0,0 +1,424 @@
+use std::error;
+use std::fmt;
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::{pin, Pin};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::{sleep_until, Duration, Instant, Sleep};
+
+use crate::error::Error as RetryError;
+use crate::future::{RetryIf, WithHooks};
+use crate::history::ErrorHistory;
+use crate::notify::{AttemptInfo, Notify, StopReason};
+use crate::strategy::Strategy;
+
+use super::action::Action;
+
+/// Error returned by [`init_with_retry`] and [`init_with_retry_until`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StartupError<E> {
+    /// The resource failed permanently, e.g. because of an invalid configuration.
+    Misconfigured(E),
+    /// The strategy was exhausted. Holds the number of attempts made and the last error.
+    Exhausted {
+        /// The number of attempts made.
+        attempts: u32,
+        /// The error of the last attempt.
+        last_error: E,
+    },
+    /// The deadline was reached. Holds the number of attempts started and the last error,
+    /// if an attempt completed.
+    DeadlineExceeded {
+        /// The number of attempts started.
+        attempts: u32,
+        /// The error of the last completed attempt.
+        last_error: Option<E>,
+    },
+}
+
+impl<E> fmt::Display for StartupError<E>
+where
+    E: fmt::Display,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            StartupError::Misconfigured(err) => write!(f, "misconfigured resource: {err}"),
+            StartupError::Exhausted {
+                attempts,
+                last_error,
+            } => write!(
+                f,
+                "resource unavailable after {attempts} attempts: {last_error}"
+            ),
+            StartupError::DeadlineExceeded { attempts, .. } => write!(
+                f,
+                "resource unavailable before the deadline, after {attempts} attempts"
+            ),
+        }
+    }
+}
+
+impl<E> error::Error for StartupError<E>
+where
+    E: error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            StartupError::Misconfigured(err) => Some(err),
+            StartupError::Exhausted { last_error, .. } => Some(last_error),
+            StartupError::DeadlineExceeded { last_error, .. } => {
+                last_error.as_ref().map(|err| err as _)
+            }
+        }
+    }
+}
+
+/// Constructs a resource at startup, such as a database or queue client, retrying
+/// transient failures via a retry strategy.
+///
+/// Permanent errors are reported as [`StartupError::Misconfigured`], so a service can
+/// tell a bad configuration apart from a dependency that is still unavailable once
+/// the strategy is exhausted. With the `tracing` feature every failed attempt is logged.
+///
+/// ```rust,no_run
+/// use tokio_retry2::{init_with_retry, RetryError, StartupError};
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// struct Client;
+///
+/// async fn build_client() -> Result<Client, RetryError<std::io::Error>> {
+///     Ok(Client)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), StartupError<std::io::Error>> {
+/// let strategy = ExponentialBackoff::from_millis(100).max_retries(10);
+/// let client = init_with_retry(strategy, build_client).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn init_with_retry<S, A>(
+    strategy: S,
+    action: A,
+) -> WithHooks<impl Future<Output = Result<A::Item, StartupError<A::Error>>>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+{
+    WithHooks::run(action, |action| init(strategy, action, None))
+}
+
+/// Same as [`init_with_retry`], but gives up with [`StartupError::DeadlineExceeded`]
+/// once `deadline` has elapsed, cancelling a running attempt.
+pub fn init_with_retry_until<S, A>(
+    strategy: S,
+    deadline: Duration,
+    action: A,
+) -> WithHooks<impl Future<Output = Result<A::Item, StartupError<A::Error>>>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+{
+    WithHooks::run(action, |action| init(strategy, action, Some(deadline)))
+}
+
+async fn init<S, A>(
+    strategy: S,
+    action: A,
+    deadline: Option<Duration>,
+) -> Result<A::Item, StartupError<A::Error>>
+where
+    S: IntoIterator<Item = Duration>,
+    S::IntoIter: Strategy,
+    A: Action,
+{
+    let deadline = deadline.map(|deadline| Instant::now() + deadline);
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let strategy = BeforeDeadline {
+        iter: strategy.into_iter(),
+        deadline,
+        shared: Arc::clone(&shared),
+    };
+    let action = InitAction {
+        action,
+        deadline,
+        shared: Arc::clone(&shared),
+    };
+    let retry = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&Failed<A::Error>) -> bool,
+        InitLog { failures: 0 },
+    )
+    // keeps the error of the last retried attempt, for when the next one reaches the
+    // deadline
+    .record_history(ErrorHistory::sampled(1));
+    let mut retry = pin!(retry);
+
+    let result = retry.as_mut().await;
+    let attempts = retry.attempts();
+    let expired = lock(&shared).expired;
+    let err = match result {
+        Ok(item) => {
+            #[cfg(feature = "tracing")]
+            tracing::info!(attempts, "resource initialized");
+            return Ok(item);
+        }
+        Err(err) => err,
+    };
+    Err(match err {
+        Failed::Misconfigured(err) => {
+            #[cfg(feature = "tracing")]
+            tracing::error!(attempts, "ending initialization: resource is misconfigured");
+            StartupError::Misconfigured(err)
+        }
+        Failed::Unavailable(last_error) if !expired => StartupError::Exhausted {
+            attempts,
+            last_error,
+        },
+        Failed::Unavailable(last_error) => StartupError::DeadlineExceeded {
+            attempts,
+            last_error: Some(last_error),
+        },
+        Failed::DeadlineExceeded => StartupError::DeadlineExceeded {
+            attempts,
+            last_error: retry
+                .take_history()
+                .and_then(|mut history| history.pop_last())
+                .and_then(|err| match err {
+                    Failed::Unavailable(err) => Some(err),
+                    _ => None,
+                }),
+        },
+    })
+}
+
+/// Why an attempt of an [`InitAction`] failed.
+enum Failed<E> {
+    /// The resource failed permanently.
+    Misconfigured(E),
+    /// The resource failed transiently.
+    Unavailable(E),
+    /// The deadline was reached while the attempt was running.
+    DeadlineExceeded,
+}
+
+/// What an [`InitAction`] and its [`BeforeDeadline`] strategy tell each other.
+#[derive(Default)]
+struct Shared {
+    /// The `retry_after` hint of the last attempt.
+    retry_after: Option<Duration>,
+    /// Whether the deadline stopped the loop.
+    expired: bool,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs every attempt to construct the resource until the deadline, if any.
+struct InitAction<A> {
+    action: A,
+    deadline: Option<Instant>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<A: Action> Action for InitAction<A> {
+    type Item = A::Item;
+    type Error = Failed<A::Error>;
+    type Future = InitAttempt<A::Future>;
+
+    fn run(&mut self) -> Self::Future {
+        InitAttempt {
+            future: self.action.run(),
+            deadline: self.deadline.map(sleep_until),
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        self.action.before_retry(delay);
+    }
+}
+
+/// Future running an attempt of an [`InitAction`], cancelled at the deadline.
+#[pin_project]
+struct InitAttempt<F> {
+    #[pin]
+    future: F,
+    #[pin]
+    deadline: Option<Sleep>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<T, E, F> Future for InitAttempt<F>
+where
+    F: Future<Output = Result<T, RetryError<E>>>,
+{
+    type Output = Result<T, RetryError<Failed<E>>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        let result = match this.future.poll(cx) {
+            Poll::Ready(result) => result,
+            Poll::Pending => {
+                ready!(this
+                    .deadline
+                    .as_pin_mut()
+                    .map_or(Poll::Pending, |deadline| deadline.poll(cx)));
+                return Poll::Ready(Err(RetryError::permanent(Failed::DeadlineExceeded)));
+            }
+        };
+        let mut shared = lock(this.shared);
+        shared.retry_after = None;
+        Poll::Ready(result.map_err(|err| match err {
+            RetryError::Permanent(..) => err.map(Failed::Misconfigured),
+            RetryError::Transient { retry_after, .. } => {
+                shared.retry_after = retry_after;
+                err.map(Failed::Unavailable)
+            }
+        }))
+    }
+}
+
+/// Strategy ending once the next attempt would start past the deadline, if any.
+struct BeforeDeadline<I> {
+    iter: I,
+    deadline: Option<Instant>,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for BeforeDeadline<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let delay = self.iter.next()?;
+        let Some(deadline) = self.deadline else {
+            return Some(delay);
+        };
+        let mut shared = lock(&self.shared);
+        // a hint replaces the delay
+        let next = Instant::now() + shared.retry_after.unwrap_or(delay);
+        shared.expired = next >= deadline;
+        (!shared.expired).then_some(delay)
+    }
+}
+
+impl<I: Strategy> Strategy for BeforeDeadline<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        if lock(&self.shared).expired {
+            Some(StopReason::MaxDurationExceeded)
+        } else {
+            self.iter.stop_reason()
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+/// Logs every failed attempt which is retried, with the `tracing` feature.
+struct InitLog {
+    failures: u32,
+}
+
+impl<E> Notify<E> for InitLog {
+    fn notify(&mut self, _err: &E, _duration: Duration) {}
+
+    fn notify_attempt(&mut self, _err: &E, _duration: Duration, attempt: &AttemptInfo) {
+        self.failures = self.failures.saturating_add(1);
+        #[cfg(feature = "tracing")]
+        if let Some(delay) = attempt.next_delay {
+            let attempts = self.failures;
+            tracing::warn!(attempts, ?delay, "resource initialization failed, retrying");
+        }
+        #[cfg(not(feature = "tracing"))]
+        let _ = attempt;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn returns_resource_once_available() {
+        let counter = AtomicUsize::new(0);
+        let result = init_with_retry(FixedInterval::from_millis(1).take(5), || {
+            if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                future::ready(RetryError::to_transient("unavailable"))
+            } else {
+                future::ready(Ok("client"))
+            }
+        })
+        .await;
+
+        assert_eq!(result, Ok("client"));
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn distinguishes_misconfiguration_from_exhaustion() {
+        let result = init_with_retry(FixedInterval::from_millis(1).take(5), || {
+            future::ready(RetryError::to_permanent::<()>("bad url"))
+        })
+        .await;
+        assert_eq!(result, Err(StartupError::Misconfigured("bad url")));
+
+        let result = init_with_retry(FixedInterval::from_millis(1).take(2), || {
+            future::ready(RetryError::to_transient::<()>("unavailable"))
+        })
+        .await;
+        assert_eq!(
+            result,
+            Err(StartupError::Exhausted {
+                attempts: 3,
+                last_error: "unavailable"
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn gives_up_at_deadline() {
+        let result = init_with_retry_until(
+            FixedInterval::from_millis(40),
+            Duration::from_millis(100),
+            || future::ready(RetryError::to_transient::<()>("unavailable")),
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(StartupError::DeadlineExceeded {
+                attempts: 3,
+                last_error: Some("unavailable")
+            })
+        );
+
+        let result = init_with_retry_until(
+            FixedInterval::from_millis(1),
+            Duration::from_millis(10),
+            future::pending::<Result<(), RetryError<()>>>,
+        )
+        .await;
+        assert_eq!(
+            result,
+            Err(StartupError::DeadlineExceeded {
+                attempts: 1,
+                last_error: None
+            })
+        );
+    }
+}
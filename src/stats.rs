@@ -0,0 +1,198 @@
+//! Process-wide retry statistics.
+//!
+//! With the `stats` feature, [`Retry`](crate::Retry) and [`RetryIf`](crate::RetryIf)
+//! futures maintain a few aggregate counters with relaxed atomics, so applications
+//! without a metrics stack can still expose them, e.g. on a health endpoint.
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, OnceLock};
+use std::time::Instant;
+
+use crate::notify::StopReason;
+
+/// Number of one-second buckets counting recent attempts.
+const WINDOW_SECS: u64 = 60;
+
+static ACTIVE_RETRIES: AtomicU64 = AtomicU64::new(0);
+static EXHAUSTIONS: AtomicU64 = AtomicU64::new(0);
+static BUDGET_WITHDRAWALS: AtomicU64 = AtomicU64::new(0);
+static BUDGET_REJECTIONS: AtomicU64 = AtomicU64::new(0);
+static ATTEMPTS: [Bucket; WINDOW_SECS as usize] = [const { Bucket::new() }; WINDOW_SECS as usize];
+static START: OnceLock<Instant> = OnceLock::new();
+static RETRIES_BY_LABEL: Mutex<BTreeMap<&'static str, u64>> = Mutex::new(BTreeMap::new());
+
+/// Counts the attempts made during one second, identified by `second`.
+struct Bucket {
+    second: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Bucket {
+    const fn new() -> Self {
+        Bucket {
+            second: AtomicU64::new(u64::MAX),
+            count: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Aggregate retry counters, returned by [`snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Snapshot {
+    /// Retry futures that are still running.
+    pub active_retries: u64,
+    /// Attempts started during the last minute, including first attempts.
+    pub attempts_last_minute: u64,
+    /// Retry loops that stopped because their strategy was exhausted.
+    pub exhaustions: u64,
+    /// Retries withdrawn from any [`RetryBudget`](crate::strategy::RetryBudget).
+    pub budget_withdrawals: u64,
+    /// Retries rejected because a [`RetryBudget`](crate::strategy::RetryBudget) was spent.
+    pub budget_rejections: u64,
+}
+
+/// Returns the current values of the process-wide retry counters.
+///
+/// Counters are updated without synchronization between each other, so a snapshot
+/// taken while retries are running is approximate.
+pub fn snapshot() -> Snapshot {
+    let now = now_secs();
+    let attempts_last_minute = ATTEMPTS
+        .iter()
+        .filter(|bucket| {
+            let second = bucket.second.load(Ordering::Relaxed);
+            second <= now && now - second < WINDOW_SECS
+        })
+        .map(|bucket| bucket.count.load(Ordering::Relaxed))
+        .sum();
+
+    Snapshot {
+        active_retries: ACTIVE_RETRIES.load(Ordering::Relaxed),
+        attempts_last_minute,
+        exhaustions: EXHAUSTIONS.load(Ordering::Relaxed),
+        budget_withdrawals: BUDGET_WITHDRAWALS.load(Ordering::Relaxed),
+        budget_rejections: BUDGET_REJECTIONS.load(Ordering::Relaxed),
+    }
+}
+
+/// Returns how many retried errors were counted under every label, for the futures
+/// labelling their errors with [`Retry::stats_label`](crate::Retry::stats_label) or
+/// [`RetryIf::stats_label`](crate::RetryIf::stats_label).
+pub fn retries_by_label() -> BTreeMap<&'static str, u64> {
+    RETRIES_BY_LABEL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+fn now_secs() -> u64 {
+    START.get_or_init(Instant::now).elapsed().as_secs()
+}
+
+pub(crate) fn retry_started() {
+    ACTIVE_RETRIES.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn attempt_started() {
+    let now = now_secs();
+    let bucket = &ATTEMPTS[(now % WINDOW_SECS) as usize];
+    let second = bucket.second.load(Ordering::Relaxed);
+    if second != now
+        && bucket
+            .second
+            .compare_exchange(second, now, Ordering::Relaxed, Ordering::Relaxed)
+            .is_ok()
+    {
+        bucket.count.store(0, Ordering::Relaxed);
+    }
+    bucket.count.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn retried(label: &'static str) {
+    *RETRIES_BY_LABEL
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .entry(label)
+        .or_default() += 1;
+}
+
+pub(crate) fn budget_withdrawn() {
+    BUDGET_WITHDRAWALS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn budget_rejected() {
+    BUDGET_REJECTIONS.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Records the end of a retry loop, with the reason if it didn't succeed.
+pub(crate) fn retry_finished(reason: Option<StopReason>) {
+    ACTIVE_RETRIES.fetch_sub(1, Ordering::Relaxed);
+    if let Some(
+        StopReason::MaxRetriesExceeded
+        | StopReason::MaxDurationExceeded
+        | StopReason::BudgetExhausted
+        | StopReason::StrategyExhausted,
+    ) = reason
+    {
+        EXHAUSTIONS.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future;
+
+    use crate::strategy::FixedInterval;
+    use crate::{Retry, RetryError};
+
+    #[tokio::test]
+    async fn counts_attempts_and_exhaustions() {
+        let before = snapshot();
+        let _ = Retry::spawn(FixedInterval::from_millis(1).take(2), || {
+            future::ready(RetryError::to_transient::<()>(42))
+        })
+        .await;
+        let after = snapshot();
+
+        // other tests may run retries concurrently
+        assert!(after.attempts_last_minute >= before.attempts_last_minute + 3);
+        assert!(after.exhaustions > before.exhaustions);
+    }
+
+    #[tokio::test]
+    async fn counts_retries_by_label() {
+        let _ = Retry::spawn(FixedInterval::from_millis(1).take(3), || {
+            future::ready(RetryError::to_transient::<()>(504))
+        })
+        .stats_label(|status: &u16| if *status >= 500 { "5xx" } else { "other" })
+        .await;
+
+        assert_eq!(retries_by_label()["5xx"], 3);
+        assert!(!retries_by_label().contains_key("other"));
+    }
+
+    #[test]
+    fn counts_budget_withdrawals_and_rejections() {
+        let before = snapshot();
+        let budget = crate::strategy::RetryBudget::new(1);
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+        let after = snapshot();
+
+        assert!(after.budget_withdrawals > before.budget_withdrawals);
+        assert!(after.budget_rejections > before.budget_rejections);
+    }
+
+    #[tokio::test]
+    async fn counts_active_retries() {
+        let retry = Retry::spawn(FixedInterval::from_millis(1), || {
+            future::pending::<Result<(), RetryError<()>>>()
+        });
+
+        assert!(snapshot().active_retries >= 1);
+        drop(retry);
+    }
+}
@@ -0,0 +1,422 @@
+use alloc::sync::Arc;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+#[cfg(any(feature = "std", feature = "wasm"))]
+use super::max_interval::{Clock, MonotonicClock};
+use super::{Fused, FusedStrategy, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// A quota of retries shared by every strategy it is attached to with
+/// [`WhileBudget::while_budget`], or by every retry future it is attached to with
+/// `RetryIf::retry_budget`.
+///
+/// Like the retry budgets of linkerd or tower, it can be refilled by successful calls
+/// with [`refill_per_successes`](RetryBudget::refill_per_successes), so retries stay
+/// a fraction of the traffic instead of piling up while a dependency is down.
+///
+/// Clones share the same quota, so a budget can be cloned into every retry loop
+/// hitting the same dependency to cap the retries they make altogether.
+///
+/// A spent budget stops retries silently, so expose its [`metrics`](RetryBudget::metrics)
+/// as gauges, or get called when it runs out with
+/// [`on_budget_exhausted`](RetryBudget::on_budget_exhausted), so operators can tell
+/// a rejected retry from a permanent error. With the `stats` feature, withdrawals and
+/// rejections of all budgets are also counted in [`stats`](crate::stats::snapshot).
+#[derive(Clone)]
+pub struct RetryBudget {
+    state: Arc<BudgetState>,
+    on_exhausted: Option<Arc<ExhaustedHook>>,
+    refill_per_successes: u64,
+    #[cfg(any(feature = "std", feature = "wasm"))]
+    refill_every: Option<Duration>,
+}
+
+type ExhaustedHook = dyn Fn(BudgetMetrics) + Send + Sync;
+
+#[derive(Debug)]
+struct BudgetState {
+    remaining: AtomicU64,
+    withdrawals: AtomicU64,
+    rejections: AtomicU64,
+    successes: AtomicU64,
+    /// Set by the first rejection, cleared by deposits.
+    exhausted: AtomicBool,
+    /// The quota the budget was created with, capping periodic refills.
+    quota: u64,
+    /// The origin of periodic refills.
+    #[cfg(any(feature = "std", feature = "wasm"))]
+    clock: MonotonicClock,
+    /// The number of periodic refills deposited so far.
+    #[cfg(any(feature = "std", feature = "wasm"))]
+    refills: AtomicU64,
+}
+
+/// The counters of a [`RetryBudget`], returned by [`RetryBudget::metrics`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct BudgetMetrics {
+    /// Retries left in the budget.
+    pub remaining: u64,
+    /// Retries withdrawn since the budget was created. Its rate is the rate at which
+    /// the budget is spent.
+    pub withdrawals: u64,
+    /// Retries rejected because the budget was spent, since it was created.
+    pub rejections: u64,
+    /// Successful calls recorded since the budget was created.
+    pub successes: u64,
+}
+
+impl RetryBudget {
+    /// Constructs a budget allowing `quota` retries.
+    pub fn new(quota: u64) -> RetryBudget {
+        RetryBudget {
+            state: Arc::new(BudgetState {
+                remaining: AtomicU64::new(quota),
+                withdrawals: AtomicU64::new(0),
+                rejections: AtomicU64::new(0),
+                successes: AtomicU64::new(0),
+                exhausted: AtomicBool::new(false),
+                quota,
+                #[cfg(any(feature = "std", feature = "wasm"))]
+                clock: MonotonicClock::new(),
+                #[cfg(any(feature = "std", feature = "wasm"))]
+                refills: AtomicU64::new(0),
+            }),
+            on_exhausted: None,
+            refill_per_successes: 0,
+            #[cfg(any(feature = "std", feature = "wasm"))]
+            refill_every: None,
+        }
+    }
+
+    /// Deposits one retry every `successes` successful calls recorded with
+    /// [`record_success`](RetryBudget::record_success), e.g. `5` to allow one retry
+    /// for every five successes. `0`, the default, never refills the budget.
+    ///
+    /// Like the exhaustion hook, it only applies through this budget and the clones
+    /// made from it afterwards, so set it before handing clones out.
+    pub fn refill_per_successes(mut self, successes: u64) -> RetryBudget {
+        self.refill_per_successes = successes;
+        self
+    }
+
+    /// Deposits one retry every `interval` since the budget was created, up to the
+    /// quota it was created with, so a spent budget recovers even without successes.
+    /// [`refill_in`](RetryBudget::refill_in) tells when the next one is due.
+    ///
+    /// Like the refill per successes, it only applies through this budget and the
+    /// clones made from it afterwards, so set it before handing clones out.
+    #[cfg(any(feature = "std", feature = "wasm"))]
+    pub fn refill_every(mut self, interval: Duration) -> RetryBudget {
+        self.refill_every = Some(interval).filter(|interval| !interval.is_zero());
+        self
+    }
+
+    /// The time left until [`refill_every`](RetryBudget::refill_every) deposits the
+    /// next retry, or `None` without a periodic refill.
+    #[cfg(any(feature = "std", feature = "wasm"))]
+    pub fn refill_in(&self) -> Option<Duration> {
+        let interval = self.refill_every?.as_nanos();
+        let into_period = self.state.clock.now().as_nanos() % interval;
+        Some(Duration::from_nanos(
+            u64::try_from(interval - into_period).unwrap_or(u64::MAX),
+        ))
+    }
+
+    /// Deposits the periodic refills due since the last call, if any.
+    fn refill(&self) {
+        #[cfg(any(feature = "std", feature = "wasm"))]
+        if let Some(interval) = self.refill_every {
+            let due = self.state.clock.now().as_nanos() / interval.as_nanos();
+            let due = u64::try_from(due).unwrap_or(u64::MAX);
+            let refilled = self.state.refills.fetch_max(due, Ordering::Relaxed);
+            if due > refilled {
+                let quota = self.state.quota;
+                let _ = self.state.remaining.fetch_update(
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                    // never takes back retries deposited past the quota by hand
+                    |remaining| {
+                        Some(remaining.max(remaining.saturating_add(due - refilled).min(quota)))
+                    },
+                );
+                self.state.exhausted.store(false, Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Calls `hook` with the metrics of the budget when a retry is first rejected
+    /// because it is spent, and again every time it is spent after a deposit.
+    ///
+    /// The hook is only called through this budget and the clones made from it
+    /// afterwards, so set it before handing clones out.
+    pub fn on_budget_exhausted<F>(mut self, hook: F) -> RetryBudget
+    where
+        F: Fn(BudgetMetrics) + Send + Sync + 'static,
+    {
+        self.on_exhausted = Some(Arc::new(hook));
+        self
+    }
+
+    /// Spends one retry from the budget, returning `false` if it is already spent.
+    pub fn try_withdraw(&self) -> bool {
+        self.refill();
+        let withdrawn = self
+            .state
+            .remaining
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                remaining.checked_sub(1)
+            })
+            .is_ok();
+        if withdrawn {
+            self.state.withdrawals.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "stats")]
+            crate::stats::budget_withdrawn();
+        } else {
+            self.state.rejections.fetch_add(1, Ordering::Relaxed);
+            #[cfg(feature = "stats")]
+            crate::stats::budget_rejected();
+            if !self.state.exhausted.swap(true, Ordering::Relaxed) {
+                if let Some(hook) = &self.on_exhausted {
+                    hook(self.metrics());
+                }
+            }
+        }
+        withdrawn
+    }
+
+    /// Records a successful call, refilling the budget as configured by
+    /// [`refill_per_successes`](RetryBudget::refill_per_successes). Retry futures the
+    /// budget is attached to record their successes themselves.
+    pub fn record_success(&self) {
+        let successes = self.state.successes.fetch_add(1, Ordering::Relaxed) + 1;
+        if self.refill_per_successes > 0 && successes % self.refill_per_successes == 0 {
+            self.deposit(1);
+        }
+    }
+
+    /// Gives `retries` back to the budget, e.g. periodically or after successes.
+    pub fn deposit(&self, retries: u64) {
+        let _ =
+            self.state
+                .remaining
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |remaining| {
+                    Some(remaining.saturating_add(retries))
+                });
+        if retries > 0 {
+            self.state.exhausted.store(false, Ordering::Relaxed);
+        }
+    }
+
+    /// The number of retries left in the budget.
+    pub fn remaining(&self) -> u64 {
+        self.refill();
+        self.state.remaining.load(Ordering::Relaxed)
+    }
+
+    /// The current counters of the budget, e.g. to export as gauges.
+    pub fn metrics(&self) -> BudgetMetrics {
+        BudgetMetrics {
+            remaining: self.remaining(),
+            withdrawals: self.state.withdrawals.load(Ordering::Relaxed),
+            rejections: self.state.rejections.load(Ordering::Relaxed),
+            successes: self.state.successes.load(Ordering::Relaxed),
+        }
+    }
+}
+
+impl fmt::Debug for RetryBudget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryBudget")
+            .field("metrics", &self.metrics())
+            .finish_non_exhaustive()
+    }
+}
+
+/// Wraps a strategy, stopping it once a shared [`RetryBudget`] is spent.
+pub trait WhileBudget: Iterator<Item = Duration> {
+    /// Spends one retry from `budget` for every delay yielded by the strategy, and
+    /// stops retrying once it is spent. This enforces a budget at the strategy layer,
+    /// without changing how retries are spawned.
+    fn while_budget(self, budget: RetryBudget) -> WhileBudgetIterator<Self>
+    where
+        Self: Sized,
+    {
+        WhileBudgetIterator {
+            iter: self.fused(),
+            budget,
+            spent: false,
+        }
+    }
+}
+
+impl<I> WhileBudget for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper with an applied retry budget,
+/// created by [`WhileBudget::while_budget`] function.
+///
+/// As it stops once the budget is spent, its `size_hint` has a lower bound of `0`,
+/// so it isn't reported as infinite.
+#[derive(Debug, Clone)]
+pub struct WhileBudgetIterator<I> {
+    iter: FusedStrategy<I>,
+    budget: RetryBudget,
+    spent: bool,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for WhileBudgetIterator<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        // stays stopped even if the budget is refilled meanwhile
+        if self.spent {
+            return None;
+        }
+        let duration = self.iter.next()?;
+        let withdrawn = if super::is_peeking() {
+            self.budget.remaining() > 0
+        } else {
+            self.budget.try_withdraw()
+        };
+        if withdrawn {
+            Some(duration)
+        } else {
+            self.spent = true;
+            #[cfg(feature = "tracing")]
+            tracing::warn!("retry budget spent, cancelling retry");
+
+            None
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for WhileBudgetIterator<I> {}
+
+impl<I: Strategy> Strategy for WhileBudgetIterator<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        if self.spent {
+            Some(StopReason::BudgetExhausted)
+        } else {
+            self.iter.stop_reason()
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone> Snapshot for WhileBudgetIterator<I> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot {
+            remaining_retries: usize::try_from(self.budget.remaining()).ok(),
+            ..StrategySnapshot::new(super::snapshot::peek(self))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    fn stops_once_budget_is_spent() {
+        let budget = RetryBudget::new(3);
+        let mut first = FixedInterval::from_millis(10).while_budget(budget.clone());
+        let mut second = FixedInterval::from_millis(10).while_budget(budget.clone());
+
+        assert_eq!(first.next(), Some(Duration::from_millis(10)));
+        assert_eq!(second.next(), Some(Duration::from_millis(10)));
+        assert_eq!(first.next(), Some(Duration::from_millis(10)));
+        assert_eq!(second.next(), None);
+        assert_eq!(budget.remaining(), 0);
+
+        // a stopped strategy stays stopped, new ones use the refilled budget
+        budget.deposit(1);
+        assert_eq!(second.next(), None);
+        let mut third = FixedInterval::from_millis(10).while_budget(budget.clone());
+        assert_eq!(third.next(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn does_not_spend_budget_when_strategy_is_exhausted() {
+        let budget = RetryBudget::new(3);
+        let mut s = FixedInterval::from_millis(10)
+            .take(1)
+            .while_budget(budget.clone());
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), None);
+        assert_eq!(budget.remaining(), 2);
+    }
+
+    #[test]
+    fn reports_metrics_and_exhaustion() {
+        let exhaustions = Arc::new(AtomicU64::new(0));
+        let counter = exhaustions.clone();
+        let budget = RetryBudget::new(1).on_budget_exhausted(move |metrics| {
+            assert_eq!(metrics.remaining, 0);
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+        assert!(!budget.clone().try_withdraw());
+        assert_eq!(exhaustions.load(Ordering::Relaxed), 1);
+        assert_eq!(
+            budget.metrics(),
+            BudgetMetrics {
+                remaining: 0,
+                withdrawals: 1,
+                rejections: 2,
+                successes: 0,
+            }
+        );
+
+        // spent again after a deposit
+        budget.deposit(1);
+        let mut s = FixedInterval::from_millis(10).while_budget(budget.clone());
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), None);
+        assert_eq!(exhaustions.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn successes_refill_the_budget() {
+        let budget = RetryBudget::new(0).refill_per_successes(3);
+
+        budget.record_success();
+        budget.record_success();
+        assert!(!budget.try_withdraw());
+        budget.clone().record_success();
+        assert!(budget.try_withdraw());
+        assert_eq!(budget.metrics().successes, 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn refills_periodically_up_to_the_quota() {
+        let interval = Duration::from_millis(50);
+        let budget = RetryBudget::new(2).refill_every(interval);
+        assert!(budget.try_withdraw());
+        assert!(budget.try_withdraw());
+        assert!(!budget.try_withdraw());
+        assert!(budget
+            .refill_in()
+            .is_some_and(|refill_in| refill_in <= interval));
+
+        std::thread::sleep(interval * 4);
+        // four refills were due, but only two fit in the quota
+        assert_eq!(budget.remaining(), 2);
+        assert_eq!(RetryBudget::new(1).refill_in(), None);
+    }
+}
@@ -0,0 +1,147 @@
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+use std::collections::hash_map::{DefaultHasher, RandomState};
+use std::hash::{BuildHasher, Hash, Hasher};
+
+use super::Strategy;
+
+/// A slot-based collision back-off, as used by Ethernet, tuned for contenders spinning
+/// on a distributed lock or lease.
+///
+/// After the `n`th failed acquisition, the delay is a random number of `slot`s between
+/// `0` and `2^n - 1`, with `n` capped by [`max_exponent`](CollisionBackoff::max_exponent).
+/// Contenders thus spread over more and more slots while the contention lasts. A fresh
+/// strategy starts over from a single slot, so clone a template for every acquisition.
+/// Delays saturate at `Duration::MAX` instead of overflowing.
+#[derive(Debug, Clone)]
+pub struct CollisionBackoff {
+    slot: Duration,
+    collisions: u32,
+    max_exponent: u32,
+    max_delay: Option<Duration>,
+    clamped: bool,
+    state: u64,
+}
+
+impl CollisionBackoff {
+    /// Constructs a new collision back-off strategy,
+    /// given a slot duration in milliseconds.
+    pub fn from_millis(millis: u64) -> CollisionBackoff {
+        CollisionBackoff::new(Duration::from_millis(millis))
+    }
+
+    /// Constructs a new collision back-off strategy with randomly seeded slots.
+    pub fn new(slot: Duration) -> CollisionBackoff {
+        CollisionBackoff {
+            slot,
+            collisions: 0,
+            max_exponent: 10,
+            max_delay: None,
+            clamped: false,
+            state: RandomState::new().build_hasher().finish(),
+        }
+    }
+
+    /// Caps the number of doublings of the slot range. Default is `10`, i.e. at most
+    /// `1023` slots.
+    pub const fn max_exponent(mut self, max_exponent: u32) -> CollisionBackoff {
+        self.max_exponent = max_exponent;
+        self
+    }
+
+    /// Seeds the slot choice with the identity of the contender, e.g. its host name or
+    /// lease holder id, instead of randomly. Distinct contenders then pick distinct
+    /// slot sequences even when they start in lockstep, while the delays of a given
+    /// contender are reproducible.
+    pub fn contender_id<T: Hash + ?Sized>(mut self, id: &T) -> CollisionBackoff {
+        let mut hasher = DefaultHasher::new();
+        id.hash(&mut hasher);
+        self.state = hasher.finish();
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    pub const fn max_delay(mut self, duration: Duration) -> CollisionBackoff {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    pub const fn max_delay_millis(mut self, duration: u64) -> CollisionBackoff {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+}
+
+impl Iterator for CollisionBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        self.collisions = self.collisions.saturating_add(1);
+        let exponent = self.collisions.min(self.max_exponent).min(63);
+        let slots = super::splitmix64(&mut self.state) % (1u64 << exponent);
+        let duration = super::saturating_mul(self.slot, slots);
+
+        // check if we reached max delay
+        self.clamped = false;
+        if let Some(max_delay) = self.max_delay {
+            if duration > max_delay {
+                self.clamped = true;
+                return Some(max_delay);
+            }
+        }
+
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for CollisionBackoff {}
+
+impl Strategy for CollisionBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spreads_over_doubling_slot_ranges() {
+        let s = CollisionBackoff::from_millis(10).max_exponent(4);
+
+        for (n, delay) in s.take(20).enumerate() {
+            let slots = 1u128 << (n as u32 + 1).min(4);
+            assert!(delay.as_millis() < 10 * slots);
+            assert_eq!(delay.as_millis() % 10, 0);
+        }
+    }
+
+    #[test]
+    fn contender_id_makes_slots_reproducible_and_distinct() {
+        let delays = |id: &str| {
+            CollisionBackoff::from_millis(1)
+                .contender_id(id)
+                .take(16)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(delays("node-a"), delays("node-a"));
+        assert_ne!(delays("node-a"), delays("node-b"));
+    }
+
+    #[test]
+    fn caps_at_max_delay() {
+        let s = CollisionBackoff::from_millis(1_000)
+            .max_exponent(20)
+            .max_delay_millis(5);
+
+        assert!(s.take(32).all(|delay| delay <= Duration::from_millis(5)));
+    }
+}
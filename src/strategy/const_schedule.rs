@@ -0,0 +1,89 @@
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+
+use super::{BoundedPolicy, Strategy};
+
+/// A fixed schedule of delays known at compile time, yielding the `N` delays it is
+/// built from and then stopping.
+///
+/// It is `Copy` and const-constructible, so it can live in a `static` or be created
+/// on a hot path without allocating, with the number of retries encoded in its type.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_retry2::strategy::ConstSchedule;
+///
+/// static SCHEDULE: ConstSchedule<3> = ConstSchedule::from_millis([10, 50, 250]);
+///
+/// let mut strategy = SCHEDULE;
+/// assert_eq!(strategy.next(), Some(Duration::from_millis(10)));
+/// assert_eq!(strategy.len(), 2);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConstSchedule<const N: usize> {
+    delays: [u64; N],
+    next: usize,
+}
+
+impl<const N: usize> ConstSchedule<N> {
+    /// Constructs a schedule yielding `delays`, in milliseconds, in order.
+    pub const fn from_millis(delays: [u64; N]) -> ConstSchedule<N> {
+        ConstSchedule { delays, next: 0 }
+    }
+}
+
+impl<const N: usize> Iterator for ConstSchedule<N> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let millis = *self.delays.get(self.next)?;
+        self.next += 1;
+        Some(Duration::from_millis(millis))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = N - self.next;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<const N: usize> ExactSizeIterator for ConstSchedule<N> {}
+
+impl<const N: usize> FusedIterator for ConstSchedule<N> {}
+
+impl<const N: usize> Strategy for ConstSchedule<N> {}
+
+impl<const N: usize> BoundedPolicy for ConstSchedule<N> {
+    fn remaining_retries(&self) -> Option<usize> {
+        Some(N - self.next)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn yields_the_schedule_then_stops() {
+        const SCHEDULE: ConstSchedule<2> = ConstSchedule::from_millis([10, 20]);
+        let mut s = SCHEDULE;
+
+        assert_eq!(s.remaining_retries(), Some(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(20)));
+        assert_eq!(s.len(), 0);
+        assert_eq!(s.next(), None);
+        assert_eq!(s.next(), None);
+
+        // the constant itself is untouched
+        assert_eq!(SCHEDULE.len(), 2);
+    }
+
+    #[test]
+    fn empty_schedule_never_retries() {
+        let mut s = ConstSchedule::from_millis([]);
+
+        assert_eq!(s.size_hint(), (0, Some(0)));
+        assert_eq!(s.next(), None);
+    }
+}
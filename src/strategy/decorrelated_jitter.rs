@@ -0,0 +1,160 @@
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+use super::Strategy;
+
+/// The "decorrelated jitter" back-off described by AWS, spreading high-concurrency
+/// clients over time better than jittering a deterministic sequence.
+///
+/// Every delay is picked at random between `base` and three times the previous
+/// delay, starting from `base`, so delays grow on average while two clients failing
+/// at the same instant quickly drift apart. Delays saturate at `Duration::MAX`
+/// instead of overflowing.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_retry2::strategy::DecorrelatedJitterBackoff;
+///
+/// let strategy = DecorrelatedJitterBackoff::from_millis(100).max_delay_millis(10_000);
+///
+/// for delay in strategy.take(10) {
+///     assert!(Duration::from_millis(100) <= delay && delay <= Duration::from_secs(10));
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct DecorrelatedJitterBackoff {
+    base: Duration,
+    previous: Duration,
+    max_delay: Option<Duration>,
+    clamped: bool,
+    state: u64,
+}
+
+impl DecorrelatedJitterBackoff {
+    /// Constructs a new decorrelated jitter back-off strategy,
+    /// given a base duration in milliseconds.
+    pub fn from_millis(millis: u64) -> DecorrelatedJitterBackoff {
+        DecorrelatedJitterBackoff::new(Duration::from_millis(millis))
+    }
+
+    /// Constructs a new decorrelated jitter back-off strategy with randomly seeded
+    /// delays, never shorter than `base`.
+    pub fn new(base: Duration) -> DecorrelatedJitterBackoff {
+        DecorrelatedJitterBackoff {
+            base,
+            previous: base,
+            max_delay: None,
+            clamped: false,
+            state: RandomState::new().build_hasher().finish(),
+        }
+    }
+
+    /// Seeds the random delays, making them reproducible, e.g. in tests.
+    pub const fn seed(mut self, seed: u64) -> DecorrelatedJitterBackoff {
+        self.state = seed;
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    ///
+    /// Takes precedence over the base delay if it is shorter.
+    pub const fn max_delay(mut self, duration: Duration) -> DecorrelatedJitterBackoff {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    ///
+    /// Takes precedence over the base delay if it is shorter.
+    pub const fn max_delay_millis(mut self, duration: u64) -> DecorrelatedJitterBackoff {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+}
+
+impl Iterator for DecorrelatedJitterBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let low = self.base.as_nanos();
+        let high = super::saturating_mul(self.previous, 3).as_nanos().max(low);
+        let span = (high - low).saturating_add(1);
+        let nanos = low + u128::from(super::splitmix64(&mut self.state)) % span;
+        let duration = u64::try_from(nanos / 1_000_000_000).map_or(Duration::MAX, |secs| {
+            Duration::new(secs, (nanos % 1_000_000_000) as u32)
+        });
+
+        // check if we reached max delay
+        self.clamped = false;
+        if let Some(max_delay) = self.max_delay {
+            if duration > max_delay {
+                self.clamped = true;
+                self.previous = max_delay;
+                return Some(max_delay);
+            }
+        }
+
+        self.previous = duration;
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for DecorrelatedJitterBackoff {}
+
+impl Strategy for DecorrelatedJitterBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn picks_delays_between_base_and_three_times_the_previous_one() {
+        let mut s = DecorrelatedJitterBackoff::from_millis(10).seed(7);
+        let mut previous = Duration::from_millis(10);
+
+        for _ in 0..50 {
+            let delay = s.next().unwrap();
+            assert!(delay >= Duration::from_millis(10));
+            assert!(delay <= previous * 3);
+            previous = delay;
+        }
+    }
+
+    #[test]
+    fn seed_makes_delays_reproducible() {
+        let delays = |seed| {
+            DecorrelatedJitterBackoff::from_millis(10)
+                .seed(seed)
+                .take(16)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(delays(1), delays(1));
+        assert_ne!(delays(1), delays(2));
+    }
+
+    #[test]
+    fn caps_at_max_delay() {
+        let s = DecorrelatedJitterBackoff::from_millis(100).max_delay_millis(150);
+
+        assert!(s.take(32).all(|delay| delay <= Duration::from_millis(150)));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = DecorrelatedJitterBackoff::new(Duration::MAX);
+
+        assert_eq!(s.next(), Some(Duration::MAX));
+    }
+}
@@ -1,15 +1,20 @@
-use std::iter::Iterator;
-use tokio::time::Duration;
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+
+use super::Strategy;
 
 /// A retry strategy driven by exponential back-off.
 ///
 /// The power corresponds to the number of past attempts.
+/// Delays saturate at `u64::MAX` milliseconds instead of overflowing.
 #[derive(Debug, Clone)]
 pub struct ExponentialBackoff {
     current: u64,
     base: u64,
     factor: u64,
     max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    clamped: bool,
 }
 
 impl ExponentialBackoff {
@@ -24,6 +29,8 @@ impl ExponentialBackoff {
             base,
             factor: 1u64,
             max_delay: None,
+            min_delay: None,
+            clamped: false,
         }
     }
 
@@ -48,6 +55,22 @@ impl ExponentialBackoff {
         self.max_delay = Some(Duration::from_millis(duration));
         self
     }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> ExponentialBackoff {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> ExponentialBackoff {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
 }
 
 impl Iterator for ExponentialBackoff {
@@ -55,29 +78,42 @@ impl Iterator for ExponentialBackoff {
 
     fn next(&mut self) -> Option<Duration> {
         // set delay duration by applying factor
-        let duration = if let Some(duration) = self.current.checked_mul(self.factor) {
-            Duration::from_millis(duration)
-        } else {
-            Duration::from_millis(u64::MAX)
+        let duration = Duration::from_millis(self.current.saturating_mul(self.factor));
+
+        // check if we are under min delay
+        let duration = match self.min_delay {
+            Some(min_delay) if duration < min_delay => min_delay,
+            _ => duration,
         };
 
         // check if we reached max delay
+        self.clamped = false;
         if let Some(ref max_delay) = self.max_delay {
             if duration > *max_delay {
                 #[cfg(feature = "tracing")]
                 tracing::warn!("`max_delay` for strategy reached");
+                self.clamped = true;
                 return Some(*max_delay);
             }
         }
 
-        if let Some(next) = self.current.checked_mul(self.base) {
-            self.current = next;
-        } else {
-            self.current = u64::MAX;
-        }
+        self.current = self.current.saturating_mul(self.base);
 
         Some(duration)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for ExponentialBackoff {}
+
+impl Strategy for ExponentialBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
+    }
 }
 
 #[cfg(test)]
@@ -137,4 +173,36 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_millis(10)));
         assert_eq!(s.next(), Some(Duration::from_millis(10)));
     }
+
+    #[test]
+    fn applies_min_delay() {
+        let mut s = ExponentialBackoff::from_millis(2).min_delay_millis(3);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(3)));
+        assert_eq!(s.next(), Some(Duration::from_millis(4)));
+    }
+
+    fn assert_monotonic(mut s: impl Iterator<Item = Duration>) {
+        let mut previous = Duration::ZERO;
+        for _ in 0..200 {
+            let duration = s.next().unwrap();
+            assert!(duration >= previous);
+            previous = duration;
+        }
+    }
+
+    #[test]
+    fn never_decreases_nor_overflows() {
+        for base in [0, 1, 2, 10, u64::MAX / 2, u64::MAX] {
+            for factor in [0, 1, 1000, u64::MAX] {
+                assert_monotonic(ExponentialBackoff::from_millis(base).factor(factor));
+                assert_monotonic(
+                    ExponentialBackoff::from_millis(base)
+                        .factor(factor)
+                        .min_delay_millis(5)
+                        .max_delay(Duration::MAX),
+                );
+            }
+        }
+    }
 }
@@ -1,16 +1,27 @@
-use std::iter::Iterator;
-use tokio::time::Duration;
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+
+use super::Strategy;
+
+const MAX_DELAY_MILLIS: u64 = u32::MAX as u64;
 
 /// A retry strategy driven by exponential factor back-off.
 /// Duration is capped at a maximum value of `u32::MAX millis = 4294967295 ms` ~49 days.
+/// A negative or NaN base factor yields zero delays after the first one.
 ///
 /// The power corresponds to the number of past attempts.
 #[derive(Debug, Clone)]
 pub struct ExponentialFactorBackoff {
     base: u64,
-    factor: f64,
+    current: f64,
     base_factor: f64,
+    factor: u64,
     max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    clamped: bool,
+    max_doublings: Option<u32>,
+    doublings: u32,
+    full_jitter: bool,
 }
 
 impl ExponentialFactorBackoff {
@@ -23,12 +34,41 @@ impl ExponentialFactorBackoff {
     pub const fn from_millis(initial_delay: u64, base_factor: f64) -> Self {
         ExponentialFactorBackoff {
             base: initial_delay,
-            factor: 1f64,
-            max_delay: None,
+            current: 1f64,
             base_factor,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+            clamped: false,
+            max_doublings: None,
+            doublings: 0,
+            full_jitter: false,
         }
     }
 
+    /// Same as [`from_millis`](ExponentialFactorBackoff::from_millis), but rejects
+    /// settings prone to causing retry storms in debug builds: an initial delay under
+    /// 1ms, or a base factor under `1.0` (or NaN), making delays shrink instead of
+    /// backing off.
+    ///
+    /// `from_millis` is the unchecked path, e.g. for a shrinking delay intentionally
+    /// bounded by `max_retries`.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, if a setting is rejected.
+    pub fn from_millis_checked(initial_delay: u64, base_factor: f64) -> Self {
+        debug_assert!(
+            initial_delay >= 1,
+            "`ExponentialFactorBackoff` initial delay under 1ms risks a retry storm, use `from_millis` to allow it"
+        );
+        debug_assert!(
+            base_factor >= 1.0,
+            "`ExponentialFactorBackoff` base factor under 1.0 makes delays shrink and risks a retry storm, use `from_millis` to allow it"
+        );
+        ExponentialFactorBackoff::from_millis(initial_delay, base_factor)
+    }
+
     /// Constructs a new exponential factor back-off strategy,
     /// given a base factor. The initial delay is set to `500`.
     /// Starting factor is `1.0` to use `initial_delay` as the base.
@@ -38,12 +78,41 @@ impl ExponentialFactorBackoff {
     pub const fn from_factor(base_factor: f64) -> Self {
         ExponentialFactorBackoff {
             base: 500,
-            factor: 1f64,
-            max_delay: None,
+            current: 1f64,
             base_factor,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+            clamped: false,
+            max_doublings: None,
+            doublings: 0,
+            full_jitter: false,
         }
     }
 
+    /// Constructs the strategy used by the AWS SDKs: a base delay of 100ms doubled
+    /// on every attempt, capped at 20 seconds, with full jitter, limited to 3 retries.
+    ///
+    /// The retries are limited with [`MaxRetries::max_retries`](super::MaxRetries::max_retries),
+    /// so the returned [`Limited`](super::Limited) strategy reports how many are left.
+    #[cfg(feature = "jitter-std")]
+    pub fn aws_default() -> super::Limited<ExponentialFactorBackoff> {
+        use super::MaxRetries;
+
+        ExponentialFactorBackoff::from_millis(100, 2.)
+            .max_delay(Duration::from_secs(20))
+            .with_full_jitter()
+            .max_retries(3)
+    }
+
+    /// Applies "full jitter" to every delay, so each retry happens after a random
+    /// duration between zero and the computed delay (capped by `max_delay`).
+    #[cfg(feature = "jitter-std")]
+    pub const fn with_full_jitter(mut self) -> ExponentialFactorBackoff {
+        self.full_jitter = true;
+        self
+    }
+
     /// A initial delay in milliseconds for the strategy.
     ///
     /// Default initial_delay is `500`.
@@ -52,6 +121,16 @@ impl ExponentialFactorBackoff {
         self
     }
 
+    /// A multiplicative factor that will be applied to the retry delay.
+    ///
+    /// For example, using a factor of `1000` will make each delay in units of seconds.
+    ///
+    /// Default factor is `1`.
+    pub const fn factor(mut self, factor: u64) -> ExponentialFactorBackoff {
+        self.factor = factor;
+        self
+    }
+
     /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
     pub const fn max_delay(mut self, duration: Duration) -> ExponentialFactorBackoff {
         self.max_delay = Some(duration);
@@ -63,6 +142,34 @@ impl ExponentialFactorBackoff {
         self.max_delay = Some(Duration::from_millis(duration));
         self
     }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`,
+    /// including after jitter.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> ExponentialFactorBackoff {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Stops growing the delay after it was multiplied by the base factor `max_doublings`
+    /// times, but keeps retrying at that delay, like the `max_doublings` setting of
+    /// Google Cloud Tasks with a base factor of `2`.
+    ///
+    /// `max_delay` still applies.
+    pub const fn max_doublings(mut self, max_doublings: u32) -> ExponentialFactorBackoff {
+        self.max_doublings = Some(max_doublings);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`,
+    /// including after jitter.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> ExponentialFactorBackoff {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
 }
 
 impl Iterator for ExponentialFactorBackoff {
@@ -70,27 +177,67 @@ impl Iterator for ExponentialFactorBackoff {
 
     fn next(&mut self) -> Option<Duration> {
         // set delay duration by applying factor
-        let duration = (self.base as f64) * self.factor;
+        let millis = (self.base as f64) * self.current * (self.factor as f64);
 
-        let duration = if duration > u32::MAX as f64 {
-            Duration::from_millis(u32::MAX as u64)
+        // NaN fails both comparisons and yields a zero delay
+        let duration = if millis >= MAX_DELAY_MILLIS as f64 {
+            Duration::from_millis(MAX_DELAY_MILLIS)
+        } else if millis > 0.0 {
+            Duration::from_millis(millis as u64)
         } else {
-            Duration::from_millis(duration as u64)
+            Duration::ZERO
         };
 
         // check if we reached max delay
+        self.clamped = false;
         if let Some(ref max_delay) = self.max_delay {
             if duration > *max_delay {
                 #[cfg(feature = "tracing")]
                 tracing::warn!("`max_delay` for strategy reached");
-                return Some(*max_delay);
+                self.clamped = true;
+                return Some(self.adjusted(*max_delay));
             }
         }
 
-        let next = self.factor * self.base_factor;
-        self.factor = next;
+        // check if we reached max doublings
+        if self.max_doublings.is_some_and(|max| self.doublings >= max) {
+            return Some(self.adjusted(duration));
+        }
+        self.doublings = self.doublings.saturating_add(1);
+
+        // `f64::max` discards NaN, so a NaN or negative product resets to zero
+        self.current = (self.current * self.base_factor).max(0.0);
 
-        Some(duration)
+        Some(self.adjusted(duration))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl ExponentialFactorBackoff {
+    fn adjusted(&self, duration: Duration) -> Duration {
+        #[cfg(feature = "jitter-std")]
+        let duration = if self.full_jitter {
+            super::jitter::full_jitter(duration)
+        } else {
+            duration
+        };
+        match (self.min_delay, self.max_delay) {
+            (Some(min_delay), Some(max_delay)) => duration.max(min_delay.min(max_delay)),
+            (Some(min_delay), None) => duration.max(min_delay),
+            _ => duration,
+        }
+    }
+}
+
+// never yields `None`
+impl FusedIterator for ExponentialFactorBackoff {}
+
+impl Strategy for ExponentialFactorBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
     }
 }
 
@@ -98,6 +245,21 @@ impl Iterator for ExponentialFactorBackoff {
 mod tests {
     use super::*;
 
+    #[test]
+    fn checked_constructor_accepts_safe_settings() {
+        let mut s = ExponentialFactorBackoff::from_millis_checked(10, 2.);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "base factor under 1.0")]
+    fn checked_constructor_rejects_shrinking_delays() {
+        let _ = ExponentialFactorBackoff::from_millis_checked(10, 0.5);
+    }
+
     #[test]
     fn returns_some_exponential_base_10() {
         let mut s = ExponentialFactorBackoff::from_millis(10, 10.);
@@ -135,6 +297,25 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_millis(u32::MAX as u64)));
     }
 
+    #[test]
+    fn stops_growing_after_max_doublings() {
+        let mut s = ExponentialFactorBackoff::from_millis(10, 2.).max_doublings(2);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(20)));
+        assert_eq!(s.next(), Some(Duration::from_millis(40)));
+        assert_eq!(s.next(), Some(Duration::from_millis(40)));
+        assert_eq!(s.next(), Some(Duration::from_millis(40)));
+    }
+
+    #[test]
+    fn zero_max_doublings_keeps_initial_delay() {
+        let mut s = ExponentialFactorBackoff::from_millis(10, 2.).max_doublings(0);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+    }
+
     #[test]
     fn can_use_factor_to_get_seconds() {
         let one_second = 1000;
@@ -166,6 +347,34 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_millis(10)));
     }
 
+    #[test]
+    #[cfg(feature = "jitter-std")]
+    fn full_jitter_stays_below_delay() {
+        let mut s = ExponentialFactorBackoff::from_millis(100, 2.)
+            .max_delay(Duration::from_millis(300))
+            .with_full_jitter();
+
+        assert!(s.next().unwrap() <= Duration::from_millis(100));
+        assert!(s.next().unwrap() <= Duration::from_millis(200));
+        assert!(s.next().unwrap() <= Duration::from_millis(300));
+        assert!(s.next().unwrap() <= Duration::from_millis(300));
+    }
+
+    #[test]
+    #[cfg(feature = "jitter-std")]
+    fn aws_default_retries_three_times() {
+        use crate::strategy::BoundedPolicy;
+
+        let strategy = ExponentialFactorBackoff::aws_default();
+        assert_eq!(strategy.remaining_retries(), Some(3));
+        let delays: Vec<Duration> = strategy.collect();
+
+        assert_eq!(delays.len(), 3);
+        assert!(delays[0] <= Duration::from_millis(100));
+        assert!(delays[1] <= Duration::from_millis(200));
+        assert!(delays[2] <= Duration::from_millis(400));
+    }
+
     #[test]
     fn demo() {
         let mut s = ExponentialFactorBackoff::from_millis(500, 2.);
@@ -175,4 +384,54 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_millis(2000)));
         assert_eq!(s.next(), Some(Duration::from_millis(4000)));
     }
+
+    #[test]
+    fn can_use_unit_factor_to_get_seconds() {
+        let mut s = ExponentialFactorBackoff::from_millis(1, 2.).factor(1000);
+
+        assert_eq!(s.next(), Some(Duration::from_secs(1)));
+        assert_eq!(s.next(), Some(Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn applies_min_delay() {
+        let mut s = ExponentialFactorBackoff::from_millis(2, 2.).min_delay_millis(3);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(3)));
+        assert_eq!(s.next(), Some(Duration::from_millis(4)));
+    }
+
+    fn assert_monotonic(mut s: impl Iterator<Item = Duration>) {
+        let mut previous = Duration::ZERO;
+        for _ in 0..200 {
+            let duration = s.next().unwrap();
+            assert!(duration >= previous);
+            previous = duration;
+        }
+    }
+
+    #[test]
+    fn never_decreases_nor_overflows() {
+        for initial_delay in [0, 1, 500, u64::MAX] {
+            for base_factor in [1., 1.5, 2., 1e300, f64::INFINITY] {
+                for factor in [0, 1, u64::MAX] {
+                    assert_monotonic(
+                        ExponentialFactorBackoff::from_millis(initial_delay, base_factor)
+                            .factor(factor),
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_base_factor_yields_zero_delays() {
+        for base_factor in [-2., f64::NAN] {
+            let mut s = ExponentialFactorBackoff::from_millis(10, base_factor);
+
+            assert_eq!(s.next(), Some(Duration::from_millis(10)));
+            assert_eq!(s.next(), Some(Duration::ZERO));
+            assert_eq!(s.next(), Some(Duration::ZERO));
+        }
+    }
 }
@@ -1,9 +1,12 @@
-use std::iter::Iterator;
-use tokio::time::Duration;
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+
+use super::Strategy;
 
 /// A retry strategy driven by the fibonacci series.
 ///
 /// Each retry uses a delay which is the sum of the two previous delays.
+/// Delays saturate at `u64::MAX` milliseconds instead of overflowing.
 ///
 /// Depending on the problem at hand, a fibonacci retry strategy might
 /// perform better and lead to better throughput than the `ExponentialBackoff`
@@ -17,6 +20,8 @@ pub struct FibonacciBackoff {
     next: u64,
     factor: u64,
     max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    clamped: bool,
 }
 
 impl FibonacciBackoff {
@@ -28,6 +33,8 @@ impl FibonacciBackoff {
             next: millis,
             factor: 1u64,
             max_delay: None,
+            min_delay: None,
+            clamped: false,
         }
     }
 
@@ -52,6 +59,22 @@ impl FibonacciBackoff {
         self.max_delay = Some(Duration::from_millis(duration));
         self
     }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> FibonacciBackoff {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> FibonacciBackoff {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
 }
 
 impl Iterator for FibonacciBackoff {
@@ -59,31 +82,44 @@ impl Iterator for FibonacciBackoff {
 
     fn next(&mut self) -> Option<Duration> {
         // set delay duration by applying factor
-        let duration = if let Some(duration) = self.current.checked_mul(self.factor) {
-            Duration::from_millis(duration)
-        } else {
-            Duration::from_millis(u64::MAX)
+        let duration = Duration::from_millis(self.current.saturating_mul(self.factor));
+
+        // check if we are under min delay
+        let duration = match self.min_delay {
+            Some(min_delay) if duration < min_delay => min_delay,
+            _ => duration,
         };
 
         // check if we reached max delay
+        self.clamped = false;
         if let Some(ref max_delay) = self.max_delay {
             if duration > *max_delay {
                 #[cfg(feature = "tracing")]
                 tracing::warn!("`max_delay` for strategy reached");
+                self.clamped = true;
                 return Some(*max_delay);
             }
         }
 
-        if let Some(next_next) = self.current.checked_add(self.next) {
-            self.current = self.next;
-            self.next = next_next;
-        } else {
-            self.current = self.next;
-            self.next = u64::MAX;
-        }
+        let next_next = self.current.saturating_add(self.next);
+        self.current = self.next;
+        self.next = next_next;
 
         Some(duration)
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for FibonacciBackoff {}
+
+impl Strategy for FibonacciBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
+    }
 }
 
 #[cfg(test)]
@@ -135,4 +171,37 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_secs(1)));
         assert_eq!(s.next(), Some(Duration::from_secs(2)));
     }
+
+    #[test]
+    fn applies_min_delay() {
+        let mut s = FibonacciBackoff::from_millis(2).min_delay_millis(3);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(3)));
+        assert_eq!(s.next(), Some(Duration::from_millis(3)));
+        assert_eq!(s.next(), Some(Duration::from_millis(4)));
+    }
+
+    fn assert_monotonic(mut s: impl Iterator<Item = Duration>) {
+        let mut previous = Duration::ZERO;
+        for _ in 0..200 {
+            let duration = s.next().unwrap();
+            assert!(duration >= previous);
+            previous = duration;
+        }
+    }
+
+    #[test]
+    fn never_decreases_nor_overflows() {
+        for millis in [0, 1, 10, u64::MAX / 2, u64::MAX] {
+            for factor in [0, 1, 1000, u64::MAX] {
+                assert_monotonic(FibonacciBackoff::from_millis(millis).factor(factor));
+                assert_monotonic(
+                    FibonacciBackoff::from_millis(millis)
+                        .factor(factor)
+                        .min_delay_millis(5)
+                        .max_delay_millis(1000),
+                );
+            }
+        }
+    }
 }
@@ -1,24 +1,88 @@
-use std::iter::Iterator;
-use tokio::time::Duration;
+use core::iter::{FusedIterator, Iterator};
+use core::time::Duration;
+
+use super::Strategy;
 
 /// A retry strategy driven by a fixed interval.
+/// Delays saturate at `Duration::MAX` instead of overflowing.
 #[derive(Debug, Clone)]
 pub struct FixedInterval {
     duration: Duration,
+    factor: u64,
+    max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    clamped: bool,
 }
 
 impl FixedInterval {
     /// Constructs a new fixed interval strategy,
     /// given a duration in milliseconds.
     pub const fn from_millis(millis: u64) -> FixedInterval {
-        FixedInterval {
-            duration: Duration::from_millis(millis),
-        }
+        FixedInterval::new(Duration::from_millis(millis))
+    }
+
+    /// Same as [`from_millis`](FixedInterval::from_millis), but rejects an interval
+    /// under 1ms in debug builds, which risks a retry storm. `from_millis` is the
+    /// unchecked path.
+    ///
+    /// # Panics
+    ///
+    /// In debug builds, if `millis` is `0`.
+    pub const fn from_millis_checked(millis: u64) -> FixedInterval {
+        debug_assert!(
+            millis >= 1,
+            "`FixedInterval` under 1ms risks a retry storm, use `from_millis` to allow it"
+        );
+        FixedInterval::from_millis(millis)
     }
 
     /// Constructs a new fixed interval strategy.
     pub const fn new(duration: Duration) -> FixedInterval {
-        FixedInterval { duration }
+        FixedInterval {
+            duration,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+            clamped: false,
+        }
+    }
+
+    /// A multiplicative factor that will be applied to the retry delay.
+    ///
+    /// For example, using a factor of `1000` will make each delay in units of seconds.
+    ///
+    /// Default factor is `1`.
+    pub const fn factor(mut self, factor: u64) -> FixedInterval {
+        self.factor = factor;
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    pub const fn max_delay(mut self, duration: Duration) -> FixedInterval {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    pub const fn max_delay_millis(mut self, duration: u64) -> FixedInterval {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> FixedInterval {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> FixedInterval {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
     }
 }
 
@@ -26,7 +90,38 @@ impl Iterator for FixedInterval {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Duration> {
-        Some(self.duration)
+        // set delay duration by applying factor
+        let duration = super::saturating_mul(self.duration, self.factor);
+
+        // check if we are under min delay
+        let duration = match self.min_delay {
+            Some(min_delay) if duration < min_delay => min_delay,
+            _ => duration,
+        };
+
+        // check if we reached max delay
+        self.clamped = false;
+        if let Some(max_delay) = self.max_delay {
+            if duration > max_delay {
+                self.clamped = true;
+                return Some(max_delay);
+            }
+        }
+
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for FixedInterval {}
+
+impl Strategy for FixedInterval {
+    fn clamped(&self) -> bool {
+        self.clamped
     }
 }
 
@@ -42,4 +137,42 @@ mod tests {
         assert_eq!(s.next(), Some(Duration::from_millis(123)));
         assert_eq!(s.next(), Some(Duration::from_millis(123)));
     }
+
+    #[test]
+    fn applies_factor_and_delay_bounds() {
+        let mut s = FixedInterval::from_millis(2).factor(1000);
+        assert_eq!(s.next(), Some(Duration::from_secs(2)));
+
+        let mut s = FixedInterval::from_millis(2)
+            .factor(1000)
+            .max_delay_millis(1500);
+        assert_eq!(s.next(), Some(Duration::from_millis(1500)));
+
+        let mut s = FixedInterval::from_millis(2).min_delay_millis(10);
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+
+        let mut s = FixedInterval::from_millis(2)
+            .min_delay_millis(10)
+            .max_delay_millis(5);
+        assert_eq!(s.next(), Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn is_infinite() {
+        let s = FixedInterval::new(Duration::from_millis(123));
+
+        assert_eq!(s.size_hint(), (usize::MAX, None));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = FixedInterval::new(Duration::MAX).factor(2);
+        assert_eq!(s.next(), Some(Duration::MAX));
+
+        let mut s = FixedInterval::new(Duration::from_secs(u64::MAX / 2)).factor(3);
+        assert_eq!(s.next(), Some(Duration::MAX));
+
+        let mut s = FixedInterval::new(Duration::from_nanos(3)).factor(u64::MAX);
+        assert_eq!(s.next(), Some(Duration::new(55_340_232_221, 128_654_845)));
+    }
 }
@@ -0,0 +1,121 @@
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::Strategy;
+use crate::notify::StopReason;
+
+/// Wraps a strategy, so it keeps yielding `None` once it yielded `None`.
+///
+/// The strategies and wrappers of this crate are all fused: once one of them stops,
+/// e.g. because its `max_interval` passed or its budget was spent, it keeps stopping,
+/// even if the budget is refilled meanwhile. User-supplied iterators may instead yield
+/// delays again after `None`; fuse them before wrapping them, so every wrapper sees a
+/// single, well-defined end.
+pub trait Fused: Iterator<Item = Duration> {
+    /// Fuses the strategy. Same as `fuse`, but returns the crate-owned
+    /// [`FusedStrategy`], which still tells why the strategy stopped.
+    fn fused(self) -> FusedStrategy<Self>
+    where
+        Self: Sized,
+    {
+        FusedStrategy {
+            iter: self,
+            stopped: false,
+        }
+    }
+}
+
+impl<I> Fused for I where I: Iterator<Item = Duration> {}
+
+/// A fused strategy wrapper, created by [`Fused::fused`] function.
+///
+/// Unlike `core::iter::Fuse`, it keeps the strategy it wraps once it stopped, so its
+/// [`Strategy::stop_reason`] can be forwarded.
+#[derive(Debug, Clone)]
+pub struct FusedStrategy<I> {
+    iter: I,
+    stopped: bool,
+}
+
+impl<I> FusedStrategy<I> {
+    /// Whether the strategy stopped yielding delays.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stopped
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for FusedStrategy<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.stopped {
+            return None;
+        }
+        let next = self.iter.next();
+        self.stopped = next.is_none();
+        next
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        if self.stopped {
+            (0, Some(0))
+        } else {
+            self.iter.size_hint()
+        }
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for FusedStrategy<I> {}
+
+impl<I: ExactSizeIterator<Item = Duration>> ExactSizeIterator for FusedStrategy<I> {}
+
+impl<I: Strategy> Strategy for FusedStrategy<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{FixedInterval, MaxRetries, Offset, RetryBudget, WhileBudget};
+
+    /// A strategy "coming back" after yielding `None`.
+    fn flaky() -> impl Iterator<Item = Duration> {
+        let mut delays = [Some(1), None, Some(2)].into_iter();
+        core::iter::from_fn(move || delays.next().flatten().map(Duration::from_millis))
+    }
+
+    #[test]
+    fn user_strategies_come_back_unless_fused() {
+        let mut s = flaky();
+        assert_eq!(s.next(), Some(Duration::from_millis(1)));
+        assert_eq!(s.next(), None);
+        assert_eq!(s.next(), Some(Duration::from_millis(2)));
+
+        let mut s = flaky().fused();
+        assert_eq!(s.next(), Some(Duration::from_millis(1)));
+        assert_eq!(s.next(), None);
+        assert_eq!(s.next(), None);
+    }
+
+    #[test]
+    fn wrappers_stay_stopped() {
+        let mut s = flaky().offset(Duration::from_millis(1)).max_retries(5);
+        assert_eq!(s.next(), Some(Duration::from_millis(2)));
+        assert_eq!(s.next(), None);
+        assert_eq!(s.next(), None);
+
+        let budget = RetryBudget::new(1);
+        let mut s = FixedInterval::from_millis(1).while_budget(budget.clone());
+        assert_eq!(s.next(), Some(Duration::from_millis(1)));
+        assert_eq!(s.next(), None);
+        budget.deposit(1);
+        assert_eq!(s.next(), None);
+    }
+}
@@ -1,11 +1,116 @@
-use tokio::time::Duration;
+use core::time::Duration;
 
+use super::saturating_mul_f64;
+
+/// Jitters a duration by a random factor between `0.5` and `1.5`.
+///
+/// Delays saturate at `Duration::MAX` instead of overflowing.
 pub fn jitter(duration: Duration) -> Duration {
-    duration.mul_f64(rand::random::<f64>() + 0.5)
+    saturating_mul_f64(duration, random() + 0.5)
+}
+
+/// "Full jitter": picks a random duration between zero and `duration`.
+///
+/// To only jitter some segments of a composed strategy, see
+/// [`Piecewise::jittered_segment`](super::Piecewise::jittered_segment).
+pub fn full_jitter(duration: Duration) -> Duration {
+    saturating_mul_f64(duration, random())
 }
 
+/// Jitters a duration by a random factor between `min` and `max`.
+///
+/// Invalid bounds are clamped instead of panicking when a delay is computed:
+/// negative or NaN factors become `0.0`, and `min` and `max` are swapped if
+/// `min > max`. With the `tracing` feature, a warning is emitted when clamping.
 pub fn jitter_range(min: f64, max: f64) -> impl Fn(Duration) -> Duration {
-    move |x| x.mul_f64(rand::random::<f64>() * (max - min) + min)
+    let (min, max) = checked_range(min, max);
+    move |x| saturating_mul_f64(x, random() * (max - min) + min)
+}
+
+/// Same as [`jitter_range`], but rejects a range reaching zero or below in debug
+/// builds, which jitters some delays down to nothing and risks a retry storm, as
+/// well as `min > max` and NaN bounds. `jitter_range` is the unchecked path.
+///
+/// # Panics
+///
+/// In debug builds, if the range is rejected.
+pub fn jitter_range_checked(min: f64, max: f64) -> impl Fn(Duration) -> Duration {
+    debug_assert!(
+        min > 0.0,
+        "`jitter_range` reaching zero risks a retry storm, use `jitter_range` to allow it"
+    );
+    debug_assert!(
+        min <= max,
+        "`jitter_range` called with `min > max` or a NaN bound"
+    );
+    jitter_range(min, max)
+}
+
+/// Jitters a duration by adding a random duration between `min` and `max`,
+/// i.e. within absolute bounds rather than multiplicative factors.
+///
+/// `min` and `max` are swapped if `min > max`. With the `tracing` feature, a
+/// warning is emitted when doing so.
+pub fn jitter_range_duration(min: Duration, max: Duration) -> impl Fn(Duration) -> Duration {
+    let (min, max) = if min > max {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("`jitter_range_duration` called with `min > max`, swapping bounds");
+        (max, min)
+    } else {
+        (min, max)
+    };
+    move |x| x.saturating_add(min + saturating_mul_f64(max - min, random()))
+}
+
+/// A random number in `[0, 1)`, from `rand`.
+#[cfg(feature = "rand")]
+fn random() -> f64 {
+    rand::random::<f64>()
+}
+
+/// A random number in `[0, 1)`, from a thread-local `SplitMix64` generator seeded
+/// by `std`'s `RandomState`, so the `jitter-std` feature doesn't depend on `rand`.
+#[cfg(not(feature = "rand"))]
+fn random() -> f64 {
+    use std::cell::Cell;
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hasher};
+
+    thread_local! {
+        static STATE: Cell<u64> = Cell::new(RandomState::new().build_hasher().finish());
+    }
+
+    let bits = STATE.with(|state| {
+        let mut current = state.get();
+        let bits = super::splitmix64(&mut current);
+        state.set(current);
+        bits
+    });
+    // the 53 high bits fill the mantissa of a `f64` in `[0, 1)`
+    (bits >> 11) as f64 / (1u64 << 53) as f64
+}
+
+fn checked_range(min: f64, max: f64) -> (f64, f64) {
+    let clamp = |factor: f64| {
+        if factor >= 0.0 {
+            factor
+        } else {
+            #[cfg(feature = "tracing")]
+            tracing::warn!(
+                factor,
+                "`jitter_range` factor is negative or NaN, clamping to 0"
+            );
+            0.0
+        }
+    };
+    let (min, max) = (clamp(min), clamp(max));
+    if min > max {
+        #[cfg(feature = "tracing")]
+        tracing::warn!("`jitter_range` called with `min > max`, swapping bounds");
+        (max, min)
+    } else {
+        (min, max)
+    }
 }
 
 #[cfg(test)]
@@ -20,6 +125,12 @@ mod tests {
         assert!(jitter.as_millis() != 100);
     }
 
+    #[test]
+    fn test_full_jitter() {
+        let jitter = full_jitter(Duration::from_millis(100));
+        assert!(jitter.as_millis() <= 100);
+    }
+
     #[test]
     fn test_jitter_range() {
         let jitter = jitter_range(0.01, 0.1)(Duration::from_millis(100));
@@ -37,4 +148,40 @@ mod tests {
         assert!(jitter.as_millis() <= 60);
         assert!(jitter.as_millis() != 100);
     }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "reaching zero")]
+    fn test_jitter_range_checked_rejects_ranges_reaching_zero() {
+        let _ = jitter_range_checked(-0.5, 1.5);
+    }
+
+    #[test]
+    fn test_jitter_range_clamps_invalid_bounds() {
+        let jitter = jitter_range(0.6, 0.5)(Duration::from_millis(100));
+        assert!(jitter.as_millis() >= 50);
+        assert!(jitter.as_millis() <= 60);
+
+        let jitter = jitter_range(-1.0, f64::NAN)(Duration::from_millis(100));
+        assert_eq!(jitter, Duration::ZERO);
+    }
+
+    #[test]
+    fn test_jitter_range_duration() {
+        let jitter = jitter_range_duration(Duration::from_millis(10), Duration::from_millis(20));
+        let jittered = jitter(Duration::from_millis(100));
+        assert!(jittered >= Duration::from_millis(110));
+        assert!(jittered <= Duration::from_millis(120));
+
+        let jitter = jitter_range_duration(Duration::from_millis(20), Duration::from_millis(10));
+        let jittered = jitter(Duration::from_millis(100));
+        assert!(jittered >= Duration::from_millis(110));
+        assert!(jittered <= Duration::from_millis(120));
+    }
+
+    #[test]
+    fn test_jitter_saturates() {
+        assert!(jitter(Duration::MAX) >= Duration::MAX / 2);
+        assert!(jitter_range(1.0, 2.0)(Duration::MAX) == Duration::MAX);
+    }
 }
@@ -0,0 +1,137 @@
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::{
+    ExponentialBackoff, ExponentialFactorBackoff, FibonacciBackoff, FixedInterval, Fused,
+    FusedStrategy, Snapshot, Strategy, StrategySnapshot, StreakBackoff,
+};
+use crate::notify::StopReason;
+
+/// Introspection of how many retries a strategy still allows.
+pub trait BoundedPolicy {
+    /// Returns the number of retries left, or `None` if the strategy has no known bound.
+    fn remaining_retries(&self) -> Option<usize>;
+}
+
+/// Wraps a strategy, limiting the number of retries.
+pub trait MaxRetries: Iterator<Item = Duration> {
+    /// Limits the strategy to `max_retries` retries.
+    ///
+    /// Same as `take`, but returns the crate-owned, nameable [`Limited`] type.
+    fn max_retries(self, max_retries: usize) -> Limited<Self>
+    where
+        Self: Sized,
+    {
+        Limited {
+            iter: self.fused(),
+            remaining: max_retries,
+        }
+    }
+}
+
+impl<I> MaxRetries for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper with a limited number of retries,
+/// created by [`MaxRetries::max_retries`] function.
+#[derive(Debug, Clone)]
+pub struct Limited<I> {
+    iter: FusedStrategy<I>,
+    remaining: usize,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for Limited<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("`max_retries` reached, cancelling retry");
+
+            None
+        } else {
+            self.remaining -= 1;
+            self.iter.next()
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.iter.size_hint();
+        let upper = upper.map_or(self.remaining, |upper| upper.min(self.remaining));
+        (lower.min(self.remaining), Some(upper))
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for Limited<I> {}
+
+impl<I: Strategy> Strategy for Limited<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        if self.remaining == 0 && !self.iter.is_stopped() {
+            Some(StopReason::MaxRetriesExceeded)
+        } else {
+            self.iter.stop_reason()
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone> Snapshot for Limited<I> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot {
+            remaining_retries: Some(self.remaining),
+            ..StrategySnapshot::new(super::snapshot::peek(self))
+        }
+    }
+}
+
+impl<I: ExactSizeIterator<Item = Duration>> ExactSizeIterator for Limited<I> {}
+
+// The built-in strategies are infinite, so limiting them yields exactly `max_retries` delays.
+impl ExactSizeIterator for Limited<FixedInterval> {}
+impl ExactSizeIterator for Limited<ExponentialBackoff> {}
+impl ExactSizeIterator for Limited<ExponentialFactorBackoff> {}
+impl ExactSizeIterator for Limited<FibonacciBackoff> {}
+impl ExactSizeIterator for Limited<StreakBackoff> {}
+
+impl<I> BoundedPolicy for Limited<I> {
+    fn remaining_retries(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    fn returns_none_after_max_retries() {
+        let mut s = FixedInterval::from_millis(10).max_retries(2);
+
+        assert_eq!(s.remaining_retries(), Some(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.remaining_retries(), Some(0));
+        assert_eq!(s.next(), None);
+    }
+
+    #[test]
+    fn is_bounded_by_max_retries() {
+        let mut s = FixedInterval::from_millis(10).max_retries(2);
+
+        assert_eq!(s.size_hint(), (2, Some(2)));
+        assert_eq!(s.len(), 2);
+        s.next();
+        assert_eq!(s.len(), 1);
+    }
+
+    #[test]
+    fn is_bounded_by_inner_strategy() {
+        let s = FixedInterval::from_millis(10).take(1).max_retries(2);
+
+        assert_eq!(s.size_hint(), (1, Some(1)));
+    }
+}
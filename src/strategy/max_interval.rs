@@ -1,5 +1,48 @@
+use core::fmt;
+use core::iter::FusedIterator;
+use core::time::Duration;
+#[cfg(not(feature = "wasm"))]
 use std::time::Instant;
-use tokio::time::Duration;
+#[cfg(feature = "wasm")]
+use web_time::Instant;
+
+use super::{Fused, FusedStrategy, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// A monotonic clock measuring the time budget of [`MaxInterval::max_duration_with_clock`].
+pub trait Clock {
+    /// The time elapsed since an arbitrary, fixed origin. Must never go backwards.
+    fn now(&self) -> Duration;
+}
+
+/// The default [`Clock`], measuring time since its construction with
+/// `std::time::Instant`, or with the `web-time` crate under the `wasm` feature, as
+/// `std::time::Instant::now` panics on `wasm32-unknown-unknown`.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    origin: Instant,
+}
+
+impl MonotonicClock {
+    /// Constructs a clock starting at zero now.
+    pub fn new() -> MonotonicClock {
+        MonotonicClock {
+            origin: Instant::now(),
+        }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        MonotonicClock::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
 
 /// Wraps a strategy, applying `max_interval``, after which strategy will
 /// stop retrying.
@@ -9,23 +52,33 @@ pub trait MaxInterval: Iterator<Item = Duration> {
     where
         Self: Sized,
     {
-        MaxIntervalIterator {
-            iter: self,
-            start: Instant::now(),
-            max_duration: Duration::from_millis(max_interval),
-        }
+        self.max_duration(Duration::from_millis(max_interval))
     }
 
     /// Applies a `max_duration` for a strategy. In `max_duration` from now,
     /// the strategy will stop retrying. If `max_duration` is passed, the strategy
     /// will stop retrying after `max_duration` is reached.
     fn max_duration(self, max_duration: Duration) -> MaxIntervalIterator<Self>
+    where
+        Self: Sized,
+    {
+        self.max_duration_with_clock(max_duration, MonotonicClock::new())
+    }
+
+    /// Same as `max_duration`, but measures the time with `clock`, e.g. a timer of
+    /// the platform where `std::time::Instant` is unavailable, or a mocked clock.
+    fn max_duration_with_clock<C: Clock>(
+        self,
+        max_duration: Duration,
+        clock: C,
+    ) -> MaxIntervalIterator<Self, C>
     where
         Self: Sized,
     {
         MaxIntervalIterator {
-            iter: self,
-            start: Instant::now(),
+            iter: self.fused(),
+            start: clock.now(),
+            clock,
             max_duration,
         }
     }
@@ -35,18 +88,22 @@ impl<I> MaxInterval for I where I: Iterator<Item = Duration> {}
 
 /// A strategy wrapper with applied max_interval,
 /// created by [`MaxInterval::max_interval`] function.
-#[derive(Debug)]
-pub struct MaxIntervalIterator<I> {
-    iter: I,
-    start: Instant,
+///
+/// As it may stop at any time, its `size_hint` has a lower bound of `0`, so it isn't
+/// reported as infinite.
+#[derive(Clone)]
+pub struct MaxIntervalIterator<I, C = MonotonicClock> {
+    iter: FusedStrategy<I>,
+    clock: C,
+    start: Duration,
     max_duration: Duration,
 }
 
-impl<I: Iterator<Item = Duration>> Iterator for MaxIntervalIterator<I> {
+impl<I: Iterator<Item = Duration>, C: Clock> Iterator for MaxIntervalIterator<I, C> {
     type Item = Duration;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.start.elapsed() > self.max_duration {
+        if self.clock.now().saturating_sub(self.start) > self.max_duration {
             #[cfg(feature = "tracing")]
             tracing::warn!("`max_duration` reached, cancelling retry");
 
@@ -55,6 +112,55 @@ impl<I: Iterator<Item = Duration>> Iterator for MaxIntervalIterator<I> {
             self.iter.next()
         }
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<I: Iterator<Item = Duration>, C: Clock> FusedIterator for MaxIntervalIterator<I, C> {}
+
+impl<I: Strategy, C: Clock> Strategy for MaxIntervalIterator<I, C> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        if !self.iter.is_stopped()
+            && self.clock.now().saturating_sub(self.start) > self.max_duration
+        {
+            Some(StopReason::MaxDurationExceeded)
+        } else {
+            self.iter.stop_reason()
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone, C: Clock> Snapshot for MaxIntervalIterator<I, C> {
+    fn snapshot(&self) -> StrategySnapshot {
+        let elapsed = self.clock.now().saturating_sub(self.start);
+        let next_delay = if elapsed > self.max_duration {
+            None
+        } else {
+            super::snapshot::peek(&self.iter)
+        };
+        StrategySnapshot {
+            elapsed: Some(elapsed),
+            max_duration: Some(self.max_duration),
+            ..StrategySnapshot::new(next_delay)
+        }
+    }
+}
+
+// prints the elapsed time rather than the clock reading it started at
+impl<I: fmt::Debug, C: Clock> fmt::Debug for MaxIntervalIterator<I, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MaxIntervalIterator")
+            .field("iter", &self.iter)
+            .field("elapsed", &self.clock.now().saturating_sub(self.start))
+            .field("max_duration", &self.max_duration)
+            .finish()
+    }
 }
 
 #[cfg(test)]
@@ -73,6 +179,13 @@ mod tests {
         assert_eq!(s.next(), None);
     }
 
+    #[test]
+    fn is_bounded_by_max_duration() {
+        let s = FixedInterval::from_millis(10).max_duration(Duration::from_millis(50));
+
+        assert_eq!(s.size_hint(), (0, None));
+    }
+
     #[tokio::test]
     async fn returns_none_after_max_duration_passes() {
         let mut s = FixedInterval::from_millis(10).max_duration(Duration::from_millis(50));
@@ -82,4 +195,28 @@ mod tests {
         tokio::time::sleep(Duration::from_millis(100)).await;
         assert_eq!(s.next(), None);
     }
+
+    #[test]
+    fn measures_time_with_the_given_clock() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        struct MockClock(Rc<Cell<Duration>>);
+        impl Clock for MockClock {
+            fn now(&self) -> Duration {
+                self.0.get()
+            }
+        }
+
+        let now = Rc::new(Cell::new(Duration::from_secs(100)));
+        let mut s = FixedInterval::from_millis(10)
+            .max_duration_with_clock(Duration::from_secs(5), MockClock(now.clone()));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        now.set(Duration::from_secs(105));
+        assert_eq!(s.to_string(), "next delay: 10ms, elapsed: 5s of 5s");
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        now.set(Duration::from_secs(106));
+        assert_eq!(s.snapshot().next_delay, None);
+        assert_eq!(s.next(), None);
+    }
 }
@@ -1,16 +1,217 @@
+mod budget;
+#[cfg(feature = "std")]
+mod collision_backoff;
+mod const_schedule;
+#[cfg(feature = "std")]
+mod decorrelated_jitter;
 mod exponential_backoff;
 mod exponential_factor_backoff;
 mod fibonacci_backoff;
 mod fixed_interval;
-#[cfg(feature = "jitter")]
+mod fused;
+#[cfg(feature = "jitter-std")]
 mod jitter;
+mod limited;
+#[cfg(any(feature = "std", feature = "wasm"))]
 mod max_interval;
+mod offset;
+mod piecewise;
+mod quantize;
+mod ramp;
+#[cfg(feature = "std")]
+mod registry;
+mod rollout;
+mod snapshot;
+mod streak_backoff;
+mod sync;
+#[cfg(any(feature = "std", feature = "wasm"))]
+mod unlimited;
 
+pub use self::budget::{BudgetMetrics, RetryBudget, WhileBudget, WhileBudgetIterator};
+#[cfg(feature = "std")]
+pub use self::collision_backoff::CollisionBackoff;
+pub use self::const_schedule::ConstSchedule;
+#[cfg(feature = "std")]
+pub use self::decorrelated_jitter::DecorrelatedJitterBackoff;
 pub use self::exponential_backoff::ExponentialBackoff;
 pub use self::exponential_factor_backoff::ExponentialFactorBackoff;
 pub use self::fibonacci_backoff::FibonacciBackoff;
 pub use self::fixed_interval::FixedInterval;
-pub use self::max_interval::{MaxInterval, MaxIntervalIterator};
+pub use self::fused::{Fused, FusedStrategy};
+pub use self::limited::{BoundedPolicy, Limited, MaxRetries};
+#[cfg(any(feature = "std", feature = "wasm"))]
+pub use self::max_interval::{Clock, MaxInterval, MaxIntervalIterator, MonotonicClock};
+pub use self::offset::{Offset, OffsetIterator};
+pub use self::piecewise::Piecewise;
+pub use self::quantize::{Quantize, QuantizeIterator};
+pub use self::ramp::{Ramp, RampIterator};
+#[cfg(feature = "std")]
+pub use self::registry::{BackoffRegistry, RegistryStrategy};
+pub use self::rollout::{rollout, Rollout, RolloutArm, RolloutStrategy};
+pub use self::snapshot::{Snapshot, StrategySnapshot};
+pub use self::streak_backoff::{StreakBackoff, StreakFeedback};
+pub use self::sync::{SyncExponentialBackoff, SyncFixedInterval};
+#[cfg(any(feature = "std", feature = "wasm"))]
+pub use self::unlimited::{Heartbeat, UnboundedWithHeartbeat, UnlimitedRetries};
 
-#[cfg(feature = "jitter")]
-pub use self::jitter::{jitter, jitter_range};
+#[cfg(feature = "jitter-std")]
+pub use self::jitter::{
+    full_jitter, jitter, jitter_range, jitter_range_checked, jitter_range_duration,
+};
+
+#[cfg(feature = "std")]
+use std::cell::Cell;
+
+use core::time::Duration;
+
+use crate::notify::StopReason;
+
+/// A retry strategy: an iterator of delays, which also tells the retry loop driving
+/// it why it stopped yielding delays, and whether it capped the last one.
+///
+/// Both methods report nothing by default, which suits a plain schedule, so a custom
+/// strategy only needs an empty `impl Strategy for MyStrategy {}`. The wrappers of this
+/// crate forward them to the strategy they wrap. The adapters of [`Iterator`], such as
+/// `map`, `take` or `chain`, can't see the strategy they wrap and report nothing: apply
+/// them innermost, or use the wrappers of this crate instead, e.g.
+/// [`max_retries`](MaxRetries::max_retries) instead of `take`, [`Piecewise`] instead of
+/// `chain`, or [`fused`](Fused::fused) instead of `fuse`.
+///
+/// ```rust
+/// use std::time::Duration;
+/// use tokio_retry2::StopReason;
+/// use tokio_retry2::strategy::{FixedInterval, MaxRetries, Strategy};
+///
+/// let mut strategy = FixedInterval::from_millis(10)
+///     .max_delay_millis(5)
+///     .max_retries(1);
+/// assert_eq!(strategy.next(), Some(Duration::from_millis(5)));
+/// assert!(strategy.clamped());
+/// assert_eq!(strategy.next(), None);
+/// assert_eq!(strategy.stop_reason(), Some(StopReason::MaxRetriesExceeded));
+/// ```
+pub trait Strategy: Iterator<Item = Duration> {
+    /// Why the strategy stopped yielding delays, once `next` returned `None`, or `None`
+    /// if it simply ran out, i.e. [`StopReason::StrategyExhausted`].
+    fn stop_reason(&self) -> Option<StopReason> {
+        None
+    }
+
+    /// Whether the last delay yielded was capped at a `max_delay`.
+    fn clamped(&self) -> bool {
+        false
+    }
+}
+
+impl<S: Strategy + ?Sized> Strategy for &mut S {
+    fn stop_reason(&self) -> Option<StopReason> {
+        (**self).stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        (**self).clamped()
+    }
+}
+
+impl<S: Strategy + ?Sized> Strategy for alloc::boxed::Box<S> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        (**self).stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        (**self).clamped()
+    }
+}
+
+// Plain schedules, and adapters which can't reach the strategy they wrap.
+impl Strategy for core::iter::Empty<Duration> {}
+impl Strategy for core::iter::Once<Duration> {}
+impl Strategy for core::iter::Repeat<Duration> {}
+impl<const N: usize> Strategy for core::array::IntoIter<Duration, N> {}
+impl Strategy for alloc::vec::IntoIter<Duration> {}
+impl<'a> Strategy for core::iter::Copied<core::slice::Iter<'a, Duration>> {}
+impl<F: FnMut() -> Option<Duration>> Strategy for core::iter::FromFn<F> {}
+impl<F: FnMut() -> Duration> Strategy for core::iter::RepeatWith<F> {}
+impl<I: Iterator<Item = Duration>> Strategy for core::iter::Take<I> {}
+impl<I: Iterator<Item = Duration>> Strategy for core::iter::Skip<I> {}
+impl<I: Iterator<Item = Duration>> Strategy for core::iter::StepBy<I> {}
+impl<I: Iterator<Item = Duration>> Strategy for core::iter::Fuse<I> {}
+impl<I: Iterator<Item = Duration>> Strategy for core::iter::Peekable<I> {}
+impl<I: Iterator<Item = Duration> + Clone> Strategy for core::iter::Cycle<I> {}
+impl<A, B> Strategy for core::iter::Chain<A, B>
+where
+    A: Iterator<Item = Duration>,
+    B: Iterator<Item = Duration>,
+{
+}
+impl<I: Iterator, F: FnMut(I::Item) -> Duration> Strategy for core::iter::Map<I, F> {}
+impl<I, F> Strategy for core::iter::Filter<I, F>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Duration) -> bool,
+{
+}
+impl<I, F> Strategy for core::iter::Inspect<I, F>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Duration),
+{
+}
+impl<I, F> Strategy for core::iter::TakeWhile<I, F>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Duration) -> bool,
+{
+}
+impl<I, F> Strategy for core::iter::SkipWhile<I, F>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Duration) -> bool,
+{
+}
+impl<I: Iterator, F: FnMut(I::Item) -> Option<Duration>> Strategy for core::iter::FilterMap<I, F> {}
+impl<I: Iterator, F: FnMut(I::Item) -> Option<Duration>> Strategy for core::iter::MapWhile<I, F> {}
+
+#[cfg(feature = "std")]
+thread_local! {
+    static PEEKING: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Whether a strategy is advanced by [`snapshot::peek`] on this thread, in which case
+/// it must not touch state shared with other strategies, such as a [`RetryBudget`].
+/// Without `std`, snapshots can't be told apart from retries.
+pub(crate) fn is_peeking() -> bool {
+    #[cfg(feature = "std")]
+    return PEEKING.with(Cell::get);
+    #[cfg(not(feature = "std"))]
+    false
+}
+
+/// Multiplies `duration` by `factor`, saturating at `Duration::MAX`.
+pub(crate) fn saturating_mul(duration: Duration, factor: u64) -> Duration {
+    let nanos = duration.as_nanos().saturating_mul(u128::from(factor));
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+/// Advances a `SplitMix64` generator, a small PRNG good enough to spread delays.
+pub(crate) fn splitmix64(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Multiplies `duration` by `factor`, saturating at `Duration::MAX`.
+/// Negative and NaN factors yield `Duration::ZERO`.
+pub(crate) fn saturating_mul_f64(duration: Duration, factor: f64) -> Duration {
+    let secs = duration.as_secs_f64() * factor;
+    if secs > 0.0 {
+        Duration::try_from_secs_f64(secs).unwrap_or(Duration::MAX)
+    } else {
+        Duration::ZERO
+    }
+}
@@ -0,0 +1,93 @@
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::{Fused, FusedStrategy, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// Wraps a strategy, adding a constant offset to every delay.
+pub trait Offset: Iterator<Item = Duration> {
+    /// Adds `offset` to every delay yielded by the strategy,
+    /// e.g. to "add 200ms to everything" when tuning a policy.
+    fn offset(self, offset: Duration) -> OffsetIterator<Self>
+    where
+        Self: Sized,
+    {
+        OffsetIterator {
+            iter: self.fused(),
+            offset,
+        }
+    }
+}
+
+impl<I> Offset for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper with an applied offset,
+/// created by [`Offset::offset`] function.
+#[derive(Debug, Clone)]
+pub struct OffsetIterator<I> {
+    iter: FusedStrategy<I>,
+    offset: Duration,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for OffsetIterator<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter
+            .next()
+            .map(|duration| duration.saturating_add(self.offset))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for OffsetIterator<I> {}
+
+impl<I: Strategy> Strategy for OffsetIterator<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone> Snapshot for OffsetIterator<I> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(super::snapshot::peek(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+
+    #[test]
+    fn adds_offset_to_every_delay() {
+        let mut s = FixedInterval::from_millis(100).offset(Duration::from_millis(200));
+
+        assert_eq!(s.next(), Some(Duration::from_millis(300)));
+        assert_eq!(s.next(), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = FixedInterval::new(Duration::MAX).offset(Duration::from_millis(1));
+
+        assert_eq!(s.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn forwards_size_hint() {
+        let s = FixedInterval::from_millis(100)
+            .max_retries(3)
+            .offset(Duration::from_millis(200));
+
+        assert_eq!(s.size_hint(), (3, Some(3)));
+    }
+}
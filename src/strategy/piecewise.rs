@@ -0,0 +1,193 @@
+use alloc::boxed::Box;
+use alloc::collections::VecDeque;
+use core::fmt;
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::Strategy;
+use crate::notify::StopReason;
+
+type Delays = Box<dyn Strategy + Send + Sync>;
+type JitterFn = Box<dyn Fn(Duration) -> Duration + Send + Sync>;
+
+/// A strategy made of consecutive segments, each taking a number of delays from a
+/// strategy of its own and jittering them on its own terms, e.g. quick retries
+/// without jitter first, then an exponential back-off with full jitter.
+///
+/// Unlike mapping a chained strategy with `.map(jitter)`, which jitters every
+/// segment alike, the jitter of a segment only applies to its delays.
+///
+/// ```rust
+/// # #[cfg(feature = "jitter-std")] {
+/// use tokio_retry2::strategy::{full_jitter, ExponentialBackoff, FixedInterval, Piecewise};
+///
+/// let strategy = Piecewise::new()
+///     .segment(3, FixedInterval::from_millis(10))
+///     .jittered_segment(7, ExponentialBackoff::from_millis(100), full_jitter);
+/// # }
+/// ```
+///
+/// A segment ends after its number of delays, or earlier once its strategy is
+/// exhausted, and the whole strategy once its last segment ended.
+#[derive(Default)]
+pub struct Piecewise {
+    segments: VecDeque<Segment>,
+    /// Why the strategy of the last segment ended, if it ended early.
+    stop: Option<StopReason>,
+}
+
+struct Segment {
+    delays: Delays,
+    remaining: usize,
+    jitter: Option<JitterFn>,
+}
+
+impl Segment {
+    /// The number of delays left in the segment, as bounds.
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.delays.size_hint();
+        (
+            lower.min(self.remaining),
+            Some(upper.map_or(self.remaining, |upper| upper.min(self.remaining))),
+        )
+    }
+}
+
+impl Piecewise {
+    /// An empty strategy, to add segments to.
+    pub fn new() -> Piecewise {
+        Piecewise::default()
+    }
+
+    /// Appends a segment yielding up to `retries` delays of `strategy` as they are.
+    pub fn segment<S>(self, retries: usize, strategy: S) -> Piecewise
+    where
+        S: IntoIterator<Item = Duration>,
+        S::IntoIter: Strategy + Send + Sync + 'static,
+    {
+        self.push(retries, strategy, None)
+    }
+
+    /// Appends a segment yielding up to `retries` delays of `strategy`, each of them
+    /// jittered with `jitter`, e.g. [`full_jitter`](super::full_jitter).
+    pub fn jittered_segment<S, J>(self, retries: usize, strategy: S, jitter: J) -> Piecewise
+    where
+        S: IntoIterator<Item = Duration>,
+        S::IntoIter: Strategy + Send + Sync + 'static,
+        J: Fn(Duration) -> Duration + Send + Sync + 'static,
+    {
+        self.push(retries, strategy, Some(Box::new(jitter)))
+    }
+
+    fn push<S>(mut self, retries: usize, strategy: S, jitter: Option<JitterFn>) -> Piecewise
+    where
+        S: IntoIterator<Item = Duration>,
+        S::IntoIter: Strategy + Send + Sync + 'static,
+    {
+        self.segments.push_back(Segment {
+            delays: Box::new(strategy.into_iter()),
+            remaining: retries,
+            jitter,
+        });
+        self
+    }
+}
+
+impl Iterator for Piecewise {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        loop {
+            let segment = self.segments.front_mut()?;
+            self.stop = None;
+            if segment.remaining > 0 {
+                if let Some(delay) = segment.delays.next() {
+                    segment.remaining -= 1;
+                    return Some(match &segment.jitter {
+                        Some(jitter) => jitter(delay),
+                        None => delay,
+                    });
+                }
+                self.stop = segment.delays.stop_reason();
+            }
+            self.segments.pop_front();
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.segments.iter().map(Segment::size_hint).fold(
+            (0, Some(0)),
+            |(lower, upper), (seg_lower, seg_upper)| {
+                (
+                    lower.saturating_add(seg_lower),
+                    upper.zip(seg_upper).and_then(|(u, s)| u.checked_add(s)),
+                )
+            },
+        )
+    }
+}
+
+impl FusedIterator for Piecewise {}
+
+impl Strategy for Piecewise {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.stop
+    }
+
+    fn clamped(&self) -> bool {
+        self.segments
+            .front()
+            .is_some_and(|segment| segment.delays.clamped())
+    }
+}
+
+impl fmt::Debug for Piecewise {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Piecewise")
+            .field("segments", &self.segments.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use alloc::vec::Vec;
+
+    use crate::strategy::{ExponentialBackoff, FixedInterval};
+
+    #[test]
+    fn jitters_each_segment_on_its_own() {
+        let strategy = Piecewise::new()
+            .segment(2, FixedInterval::from_millis(10))
+            .jittered_segment(3, FixedInterval::from_millis(100), |d| d * 2)
+            .segment(1, FixedInterval::from_millis(1));
+
+        let delays: Vec<_> = strategy.map(|d| d.as_millis()).collect();
+        assert_eq!(delays, [10, 10, 200, 200, 200, 1]);
+    }
+
+    #[test]
+    fn moves_on_once_a_segment_strategy_is_exhausted() {
+        let strategy = Piecewise::new()
+            .segment(5, [1, 2].map(Duration::from_millis))
+            .jittered_segment(2, ExponentialBackoff::from_millis(10), |d| d + d / 2);
+
+        let delays: Vec<_> = strategy.map(|d| d.as_millis()).collect();
+        assert_eq!(delays, [1, 2, 15, 150]);
+    }
+
+    #[test]
+    fn size_hint_sums_the_segments() {
+        let mut strategy = Piecewise::new()
+            .segment(2, FixedInterval::from_millis(10))
+            .segment(5, [1, 2, 3].map(Duration::from_millis));
+        assert_eq!(strategy.size_hint(), (5, Some(5)));
+        strategy.next();
+        assert_eq!(strategy.size_hint(), (4, Some(4)));
+
+        let unbounded = Piecewise::new().segment(usize::MAX, FixedInterval::from_millis(10));
+        assert_eq!(unbounded.size_hint().0, usize::MAX);
+    }
+}
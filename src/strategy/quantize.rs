@@ -0,0 +1,181 @@
+use core::iter::FusedIterator;
+use core::time::Duration;
+#[cfg(feature = "std")]
+use std::sync::OnceLock;
+#[cfg(feature = "std")]
+use std::time::Instant;
+
+use super::{Fused, FusedStrategy, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// The process-wide epoch ticks are counted from, so every aligned strategy shares them.
+#[cfg(feature = "std")]
+static EPOCH: OnceLock<Instant> = OnceLock::new();
+
+/// Wraps a strategy, coarsening its delays so that many concurrent retries wake up
+/// together, reducing timer churn.
+pub trait Quantize: Iterator<Item = Duration> {
+    /// Rounds every delay up to a multiple of `bucket`, e.g. 250ms.
+    /// A zero `bucket` leaves delays untouched.
+    fn quantize(self, bucket: Duration) -> QuantizeIterator<Self>
+    where
+        Self: Sized,
+    {
+        QuantizeIterator {
+            iter: self.fused(),
+            bucket,
+            align: false,
+        }
+    }
+
+    /// Rounds every delay up to whole seconds, for delays displayed to humans or aligned
+    /// with per-second rate limits. Apply it after jitter, e.g.
+    /// `ExponentialBackoff::from_millis(2).factor(500).map(jitter).whole_seconds()`.
+    /// A `max_delay` that isn't a whole number of seconds is rounded up as well.
+    fn whole_seconds(self) -> QuantizeIterator<Self>
+    where
+        Self: Sized,
+    {
+        self.quantize(Duration::from_secs(1))
+    }
+
+    /// Extends every delay so the wake-up lands on the next boundary of a global
+    /// `tick`, shared by every aligned strategy in the process, so retries started
+    /// at different times are woken up in batches.
+    /// A zero `tick` leaves delays untouched.
+    #[cfg(feature = "std")]
+    fn align_to_tick(self, tick: Duration) -> QuantizeIterator<Self>
+    where
+        Self: Sized,
+    {
+        EPOCH.get_or_init(Instant::now);
+        QuantizeIterator {
+            iter: self.fused(),
+            bucket: tick,
+            align: true,
+        }
+    }
+}
+
+impl<I> Quantize for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper with quantized delays,
+/// created by [`Quantize::quantize`] or [`Quantize::align_to_tick`] functions.
+#[derive(Debug, Clone)]
+pub struct QuantizeIterator<I> {
+    iter: FusedStrategy<I>,
+    bucket: Duration,
+    align: bool,
+}
+
+/// Rounds `duration` up to a multiple of `bucket`, saturating at `Duration::MAX`.
+fn round_up(duration: Duration, bucket: Duration) -> Duration {
+    let bucket = bucket.as_nanos();
+    let nanos = duration.as_nanos().div_ceil(bucket).saturating_mul(bucket);
+    match u64::try_from(nanos / 1_000_000_000) {
+        Ok(secs) => Duration::new(secs, (nanos % 1_000_000_000) as u32),
+        Err(_) => Duration::MAX,
+    }
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for QuantizeIterator<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let duration = self.iter.next()?;
+        if self.bucket.is_zero() {
+            return Some(duration);
+        }
+        #[cfg(feature = "std")]
+        if self.align {
+            let elapsed = EPOCH.get_or_init(Instant::now).elapsed();
+            let wake_up = round_up(elapsed.saturating_add(duration), self.bucket);
+            return Some(wake_up.saturating_sub(elapsed));
+        }
+        Some(round_up(duration, self.bucket))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for QuantizeIterator<I> {}
+
+impl<I: Strategy> Strategy for QuantizeIterator<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone> Snapshot for QuantizeIterator<I> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(super::snapshot::peek(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{ExponentialBackoff, FixedInterval};
+
+    #[test]
+    fn rounds_delays_up_to_bucket() {
+        let mut s = ExponentialBackoff::from_millis(10).quantize(Duration::from_millis(250));
+
+        assert_eq!(s.next(), Some(Duration::from_millis(250)));
+        assert_eq!(s.next(), Some(Duration::from_millis(250)));
+        assert_eq!(s.next(), Some(Duration::from_millis(1000)));
+        assert_eq!(s.next(), Some(Duration::from_millis(10000)));
+    }
+
+    #[test]
+    fn rounds_delays_up_to_whole_seconds() {
+        let mut s = ExponentialBackoff::from_millis(2)
+            .factor(300)
+            .max_delay(Duration::from_millis(2500))
+            .whole_seconds();
+
+        assert_eq!(s.next(), Some(Duration::from_secs(1)));
+        assert_eq!(s.next(), Some(Duration::from_secs(2)));
+        assert_eq!(s.next(), Some(Duration::from_secs(3)));
+        assert_eq!(s.next(), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn zero_bucket_is_a_no_op() {
+        let mut s = FixedInterval::from_millis(7).quantize(Duration::ZERO);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(7)));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = FixedInterval::new(Duration::MAX).quantize(Duration::from_millis(3));
+
+        assert_eq!(s.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn aligns_wake_ups_to_global_tick() {
+        let tick = Duration::from_millis(100);
+        let mut s = FixedInterval::from_millis(30).align_to_tick(tick);
+
+        let before = EPOCH.get().unwrap().elapsed();
+        let delay = s.next().unwrap();
+        let after = EPOCH.get().unwrap().elapsed();
+
+        assert!(delay >= Duration::from_millis(30));
+        assert!(delay < Duration::from_millis(130));
+        let wake_up = (before + delay).as_nanos();
+        let slack = (after - before).as_nanos();
+        let offset = wake_up % tick.as_nanos();
+        assert!(offset == 0 || offset + slack >= tick.as_nanos());
+    }
+}
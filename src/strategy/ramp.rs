@@ -0,0 +1,112 @@
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::{Fused, FusedStrategy, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// Wraps a strategy, multiplying its delays after a given number of attempts.
+pub trait Ramp: Iterator<Item = Duration> {
+    /// Multiplies every delay after the `attempts`-th one by `multiplier`,
+    /// e.g. to "triple delays after the 5th attempt" when tuning a policy.
+    ///
+    /// Delays saturate at `Duration::MAX`, and a negative or NaN `multiplier` yields zero delays.
+    fn ramp_after(self, attempts: usize, multiplier: f64) -> RampIterator<Self>
+    where
+        Self: Sized,
+    {
+        RampIterator {
+            iter: self.fused(),
+            attempts,
+            multiplier,
+            yielded: 0,
+        }
+    }
+}
+
+impl<I> Ramp for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper with an applied ramp,
+/// created by [`Ramp::ramp_after`] function.
+#[derive(Debug, Clone)]
+pub struct RampIterator<I> {
+    iter: FusedStrategy<I>,
+    attempts: usize,
+    multiplier: f64,
+    yielded: usize,
+}
+
+impl<I: Iterator<Item = Duration>> Iterator for RampIterator<I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let duration = self.iter.next()?;
+        if self.yielded < self.attempts {
+            self.yielded += 1;
+            return Some(duration);
+        }
+
+        Some(super::saturating_mul_f64(duration, self.multiplier))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+impl<I: Iterator<Item = Duration>> FusedIterator for RampIterator<I> {}
+
+impl<I: Strategy> Strategy for RampIterator<I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I: Iterator<Item = Duration> + Clone> Snapshot for RampIterator<I> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(super::snapshot::peek(self))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+
+    #[test]
+    fn multiplies_delays_after_attempts() {
+        let mut s = FixedInterval::from_millis(100).ramp_after(2, 3.);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(100)));
+        assert_eq!(s.next(), Some(Duration::from_millis(100)));
+        assert_eq!(s.next(), Some(Duration::from_millis(300)));
+        assert_eq!(s.next(), Some(Duration::from_millis(300)));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = FixedInterval::new(Duration::MAX).ramp_after(0, 2.);
+
+        assert_eq!(s.next(), Some(Duration::MAX));
+    }
+
+    #[test]
+    fn nan_multiplier_yields_zero() {
+        let mut s = FixedInterval::from_millis(100).ramp_after(0, f64::NAN);
+
+        assert_eq!(s.next(), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn forwards_size_hint() {
+        let s = FixedInterval::from_millis(100)
+            .max_retries(3)
+            .ramp_after(1, 2.);
+
+        assert_eq!(s.size_hint(), (3, Some(3)));
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::iter::FusedIterator;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::time::Duration;
+
+use super::{Fused, FusedStrategy, Strategy};
+use crate::notify::StopReason;
+
+/// Remembers how far the back-off escalated for every key, e.g. a peer or endpoint,
+/// across separate retry loops.
+///
+/// Strategies created by [`BackoffRegistry::strategy`] resume the schedule at the
+/// escalation level left by the previous retry loop for the same key, so rapid
+/// connect, fail, reconnect cycles don't restart at the shortest delay. The level
+/// decays by one for every `decay` elapsed since the last failure, and is reset by
+/// [`BackoffRegistry::success`]. Clones share the same levels.
+///
+/// ```rust
+/// use tokio_retry2::strategy::{BackoffRegistry, ExponentialBackoff};
+/// use std::time::Duration;
+///
+/// let registry = BackoffRegistry::new(Duration::from_secs(30));
+///
+/// let mut first = registry.strategy("10.0.0.1:443", ExponentialBackoff::from_millis(10));
+/// assert_eq!(first.next(), Some(Duration::from_millis(10)));
+///
+/// // a new retry loop for the same peer picks up where the last one stopped
+/// let mut second = registry.strategy("10.0.0.1:443", ExponentialBackoff::from_millis(10));
+/// assert_eq!(second.next(), Some(Duration::from_millis(100)));
+/// ```
+#[derive(Debug, Clone)]
+pub struct BackoffRegistry<K> {
+    levels: Arc<Mutex<HashMap<K, Level>>>,
+    decay: Duration,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Level {
+    level: u32,
+    last_failure: Instant,
+}
+
+impl<K: Eq + Hash + Clone> BackoffRegistry<K> {
+    /// Constructs an empty registry, where levels decay by one every `decay`.
+    pub fn new(decay: Duration) -> BackoffRegistry<K> {
+        BackoffRegistry {
+            levels: Arc::new(Mutex::new(HashMap::new())),
+            decay,
+        }
+    }
+
+    /// Wraps `strategy` so it resumes at the escalation level of `key`, and escalates
+    /// it with every delay it yields.
+    pub fn strategy<I>(&self, key: K, strategy: I) -> RegistryStrategy<K, I>
+    where
+        I: Iterator<Item = Duration>,
+    {
+        RegistryStrategy {
+            registry: self.clone(),
+            key,
+            strategy: strategy.fused(),
+            resumed: false,
+        }
+    }
+
+    /// Resets the escalation level of `key`, e.g. after connecting successfully.
+    pub fn success(&self, key: &K) {
+        self.lock().remove(key);
+    }
+
+    /// The current escalation level of `key`, after decay.
+    pub fn level(&self, key: &K) -> u32 {
+        self.lock().get(key).map_or(0, |level| self.decayed(level))
+    }
+
+    fn escalate(&self, key: &K) -> u32 {
+        let mut levels = self.lock();
+        let level = levels.get(key).map_or(0, |level| self.decayed(level));
+        levels.insert(
+            key.clone(),
+            Level {
+                level: level.saturating_add(1),
+                last_failure: Instant::now(),
+            },
+        );
+        level
+    }
+
+    fn decayed(&self, level: &Level) -> u32 {
+        if self.decay.is_zero() {
+            return 0;
+        }
+        let decayed = level.last_failure.elapsed().as_nanos() / self.decay.as_nanos();
+        level
+            .level
+            .saturating_sub(u32::try_from(decayed).unwrap_or(u32::MAX))
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<K, Level>> {
+        // the map stays consistent even if a thread panicked while holding the lock
+        self.levels
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// A strategy wrapper sharing its escalation level through a [`BackoffRegistry`],
+/// created by [`BackoffRegistry::strategy`].
+#[derive(Debug)]
+pub struct RegistryStrategy<K, I> {
+    registry: BackoffRegistry<K>,
+    key: K,
+    strategy: FusedStrategy<I>,
+    resumed: bool,
+}
+
+impl<K: Eq + Hash + Clone, I: Iterator<Item = Duration>> Iterator for RegistryStrategy<K, I> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let level = self.registry.escalate(&self.key);
+        if !self.resumed {
+            self.resumed = true;
+            // skip the delays already taken by previous retry loops for this key
+            for _ in 0..level {
+                self.strategy.next()?;
+            }
+        }
+        self.strategy.next()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (_, upper) = self.strategy.size_hint();
+        (0, upper)
+    }
+}
+
+impl<K: Eq + Hash + Clone, I: Iterator<Item = Duration>> FusedIterator for RegistryStrategy<K, I> {}
+
+impl<K: Eq + Hash + Clone, I: Strategy> Strategy for RegistryStrategy<K, I> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.strategy.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.strategy.clamped()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::ExponentialBackoff;
+
+    #[test]
+    fn resumes_escalation_per_key() {
+        let registry = BackoffRegistry::new(Duration::from_secs(60));
+
+        let mut s = registry.strategy("a", ExponentialBackoff::from_millis(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(2)));
+        assert_eq!(s.next(), Some(Duration::from_millis(4)));
+
+        let mut s = registry.strategy("a", ExponentialBackoff::from_millis(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(8)));
+        assert_eq!(registry.level(&"a"), 3);
+
+        let mut s = registry.strategy("b", ExponentialBackoff::from_millis(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(2)));
+
+        registry.success(&"a");
+        let mut s = registry.strategy("a", ExponentialBackoff::from_millis(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(2)));
+    }
+
+    #[tokio::test]
+    async fn levels_decay_over_time() {
+        let registry = BackoffRegistry::new(Duration::from_millis(20));
+
+        let mut s = registry.strategy("a", ExponentialBackoff::from_millis(2));
+        s.next();
+        s.next();
+        assert_eq!(registry.level(&"a"), 2);
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        assert_eq!(registry.level(&"a"), 1);
+
+        let mut s = registry.strategy("a", ExponentialBackoff::from_millis(2));
+        assert_eq!(s.next(), Some(Duration::from_millis(4)));
+    }
+}
@@ -0,0 +1,256 @@
+use core::hash::{Hash, Hasher};
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::{Fused, FusedStrategy, Strategy};
+use crate::notify::StopReason;
+
+/// Canaries a new retry policy: `percent` of the keys, e.g. tenants or request ids,
+/// are served by `new`, the others by `old`.
+///
+/// Keys are assigned with a stable hash, so the same key always gets the same policy,
+/// across processes and restarts, and raising `percent` only moves keys from `old`
+/// to `new`. `percent` is clamped between `0.0` and `100.0`, with a resolution of
+/// `0.01`.
+///
+/// The selected strategy tells which policy serves the retry loop through
+/// [`RolloutStrategy::arm`], e.g. to label metrics:
+///
+/// ```rust,no_run
+/// use tokio_retry2::{Retry, RetryError};
+/// use tokio_retry2::strategy::{rollout, ExponentialBackoff, FixedInterval, MaxRetries};
+///
+/// async fn action() -> Result<u64, RetryError<()>> {
+///     Ok(42)
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> Result<(), ()> {
+/// let policies = rollout(
+///     FixedInterval::from_millis(100).max_retries(3),
+///     ExponentialBackoff::from_millis(10).max_retries(5),
+///     5.0,
+/// );
+///
+/// let strategy = policies.select("tenant-42");
+/// println!("retrying with the {} policy", strategy.arm().as_str());
+/// let value = Retry::spawn(strategy, action).await?;
+/// # Ok(())
+/// # }
+/// ```
+pub fn rollout<O, N>(old: O, new: N, percent: f64) -> Rollout<O, N>
+where
+    O: IntoIterator<Item = Duration> + Clone,
+    N: IntoIterator<Item = Duration> + Clone,
+{
+    // negative and NaN percentages roll out to nobody
+    let basis_points = if percent > 0.0 {
+        (percent.min(100.0) * 100.0) as u64
+    } else {
+        0
+    };
+    Rollout {
+        old,
+        new,
+        basis_points,
+    }
+}
+
+/// A gradual rollout of a retry policy, created by [`rollout`].
+#[derive(Debug, Clone)]
+pub struct Rollout<O, N> {
+    old: O,
+    new: N,
+    basis_points: u64,
+}
+
+impl<O, N> Rollout<O, N>
+where
+    O: IntoIterator<Item = Duration> + Clone,
+    N: IntoIterator<Item = Duration> + Clone,
+{
+    /// Which policy serves `key`.
+    pub fn arm<K: Hash + ?Sized>(&self, key: &K) -> RolloutArm {
+        let mut hasher = Fnv1a::default();
+        key.hash(&mut hasher);
+        if hasher.finish() % 10_000 < self.basis_points {
+            RolloutArm::New
+        } else {
+            RolloutArm::Old
+        }
+    }
+
+    /// A fresh strategy of the policy serving `key`.
+    pub fn select<K: Hash + ?Sized>(&self, key: &K) -> RolloutStrategy<O::IntoIter, N::IntoIter> {
+        let arm = self.arm(key);
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            policy = arm.as_str(),
+            "selected retry policy of the rollout"
+        );
+        match arm {
+            RolloutArm::Old => RolloutStrategy::Old(self.old.clone().into_iter().fused()),
+            RolloutArm::New => RolloutStrategy::New(self.new.clone().into_iter().fused()),
+        }
+    }
+}
+
+/// Which policy of a [`Rollout`] serves a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RolloutArm {
+    /// The policy being replaced.
+    Old,
+    /// The policy being rolled out.
+    New,
+}
+
+impl RolloutArm {
+    /// A stable name for the arm, `"old"` or `"new"`, suitable for logs and metrics.
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            RolloutArm::Old => "old",
+            RolloutArm::New => "new",
+        }
+    }
+}
+
+/// The strategy of the policy serving a key, created by [`Rollout::select`].
+#[derive(Debug, Clone)]
+pub enum RolloutStrategy<O, N> {
+    /// A strategy of the policy being replaced.
+    Old(FusedStrategy<O>),
+    /// A strategy of the policy being rolled out.
+    New(FusedStrategy<N>),
+}
+
+impl<O, N> RolloutStrategy<O, N> {
+    /// Which policy this strategy belongs to.
+    pub const fn arm(&self) -> RolloutArm {
+        match self {
+            RolloutStrategy::Old(_) => RolloutArm::Old,
+            RolloutStrategy::New(_) => RolloutArm::New,
+        }
+    }
+}
+
+impl<O, N> Iterator for RolloutStrategy<O, N>
+where
+    O: Iterator<Item = Duration>,
+    N: Iterator<Item = Duration>,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        match self {
+            RolloutStrategy::Old(iter) => iter.next(),
+            RolloutStrategy::New(iter) => iter.next(),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            RolloutStrategy::Old(iter) => iter.size_hint(),
+            RolloutStrategy::New(iter) => iter.size_hint(),
+        }
+    }
+}
+
+impl<O, N> FusedIterator for RolloutStrategy<O, N>
+where
+    O: Iterator<Item = Duration>,
+    N: Iterator<Item = Duration>,
+{
+}
+
+impl<O: Strategy, N: Strategy> Strategy for RolloutStrategy<O, N> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        match self {
+            RolloutStrategy::Old(iter) => iter.stop_reason(),
+            RolloutStrategy::New(iter) => iter.stop_reason(),
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        match self {
+            RolloutStrategy::Old(iter) => iter.clamped(),
+            RolloutStrategy::New(iter) => iter.clamped(),
+        }
+    }
+}
+
+/// The 64-bit FNV-1a hash, which unlike `std`'s `DefaultHasher` is stable across
+/// processes and Rust releases.
+struct Fnv1a(u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Fnv1a(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Hasher for Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::FixedInterval;
+
+    #[test]
+    fn serves_a_stable_share_of_keys() {
+        let policies = rollout(
+            FixedInterval::from_millis(1),
+            FixedInterval::from_millis(2),
+            5.0,
+        );
+
+        let new = (0..10_000u32)
+            .filter(|key| policies.arm(key) == RolloutArm::New)
+            .count();
+        assert!((400..600).contains(&new), "{new}");
+        assert_eq!(policies.arm("tenant-42"), policies.arm("tenant-42"));
+
+        // raising the percentage only moves keys to the new policy
+        let wider = rollout(
+            FixedInterval::from_millis(1),
+            FixedInterval::from_millis(2),
+            50.0,
+        );
+        assert!((0..10_000u32)
+            .filter(|key| policies.arm(key) == RolloutArm::New)
+            .all(|key| wider.arm(&key) == RolloutArm::New));
+    }
+
+    #[test]
+    fn selects_the_strategy_of_the_arm() {
+        let none = rollout(
+            FixedInterval::from_millis(1),
+            FixedInterval::from_millis(2),
+            0.0,
+        );
+        let all = rollout(
+            FixedInterval::from_millis(1),
+            FixedInterval::from_millis(2),
+            100.0,
+        );
+
+        let mut old = none.select("tenant-42");
+        assert_eq!(old.arm(), RolloutArm::Old);
+        assert_eq!(old.next(), Some(Duration::from_millis(1)));
+
+        let mut new = all.select("tenant-42");
+        assert_eq!(new.arm().as_str(), "new");
+        assert_eq!(new.next(), Some(Duration::from_millis(2)));
+    }
+}
@@ -0,0 +1,240 @@
+use core::fmt;
+use core::time::Duration;
+
+#[cfg(any(feature = "std", feature = "wasm"))]
+use super::{Clock, MaxIntervalIterator};
+#[cfg(feature = "std")]
+use super::{CollisionBackoff, DecorrelatedJitterBackoff};
+use super::{
+    ConstSchedule, ExponentialBackoff, ExponentialFactorBackoff, FibonacciBackoff, FixedInterval,
+    Limited, OffsetIterator, QuantizeIterator, RampIterator, RolloutStrategy, StreakBackoff,
+    WhileBudgetIterator,
+};
+
+/// Introspection of the current state of a strategy, e.g. to dump a stuck retry loop
+/// from a diagnostics endpoint.
+///
+/// Taking a snapshot doesn't advance the strategy.
+pub trait Snapshot {
+    /// Returns the current state of the strategy.
+    fn snapshot(&self) -> StrategySnapshot;
+}
+
+/// The state of a strategy at some point, returned by [`Snapshot::snapshot`].
+///
+/// Wrappers fill in what they know about, e.g. [`Limited`] the retries left or
+/// [`MaxIntervalIterator`] the time elapsed, and leave the rest as `None`. It is
+/// printed by the `Display` implementation of every wrapper:
+///
+/// ```rust
+/// use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+///
+/// let mut strategy = FixedInterval::from_millis(100).max_retries(3);
+/// strategy.next();
+/// assert_eq!(strategy.to_string(), "next delay: 100ms, retries left: 2");
+/// ```
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StrategySnapshot {
+    /// The delay the strategy yields next, or `None` if it is exhausted.
+    pub next_delay: Option<Duration>,
+    /// The number of retries left, if the strategy is bounded by a count.
+    pub remaining_retries: Option<usize>,
+    /// The time elapsed since the strategy started, if it is bounded by time.
+    pub elapsed: Option<Duration>,
+    /// The time after which the strategy stops, if it is bounded by time.
+    pub max_duration: Option<Duration>,
+}
+
+impl StrategySnapshot {
+    /// Constructs a snapshot of a strategy yielding `next_delay` next.
+    pub const fn new(next_delay: Option<Duration>) -> StrategySnapshot {
+        StrategySnapshot {
+            next_delay,
+            remaining_retries: None,
+            elapsed: None,
+            max_duration: None,
+        }
+    }
+}
+
+impl fmt::Display for StrategySnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.next_delay {
+            Some(delay) => write!(f, "next delay: {delay:?}")?,
+            None => f.write_str("exhausted")?,
+        }
+        if let Some(remaining) = self.remaining_retries {
+            write!(f, ", retries left: {remaining}")?;
+        }
+        match (self.elapsed, self.max_duration) {
+            (Some(elapsed), Some(max)) => write!(f, ", elapsed: {elapsed:?} of {max:?}"),
+            (Some(elapsed), None) => write!(f, ", elapsed: {elapsed:?}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Yields the next delay of a clone of `iter`, without spending shared state.
+pub(crate) fn peek<I>(iter: &I) -> Option<Duration>
+where
+    I: Iterator<Item = Duration> + Clone,
+{
+    #[cfg(feature = "std")]
+    let peeking = super::PEEKING.with(|cell| cell.replace(true));
+    let next = iter.clone().next();
+    #[cfg(feature = "std")]
+    super::PEEKING.with(|cell| cell.set(peeking));
+    next
+}
+
+impl Snapshot for FixedInterval {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl<const N: usize> Snapshot for ConstSchedule<N> {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot {
+            remaining_retries: Some(self.len()),
+            ..StrategySnapshot::new(peek(self))
+        }
+    }
+}
+
+impl Snapshot for ExponentialBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl Snapshot for ExponentialFactorBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl Snapshot for FibonacciBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Snapshot for CollisionBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+#[cfg(feature = "std")]
+impl Snapshot for DecorrelatedJitterBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl Snapshot for StreakBackoff {
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl<O, N> Snapshot for RolloutStrategy<O, N>
+where
+    O: Iterator<Item = Duration> + Clone,
+    N: Iterator<Item = Duration> + Clone,
+{
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(peek(self))
+    }
+}
+
+impl<I> fmt::Display for Limited<I>
+where
+    Limited<I>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+#[cfg(any(feature = "std", feature = "wasm"))]
+impl<I, C: Clock> fmt::Display for MaxIntervalIterator<I, C>
+where
+    MaxIntervalIterator<I, C>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<I> fmt::Display for OffsetIterator<I>
+where
+    OffsetIterator<I>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<I> fmt::Display for QuantizeIterator<I>
+where
+    QuantizeIterator<I>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<I> fmt::Display for RampIterator<I>
+where
+    RampIterator<I>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<I> fmt::Display for WhileBudgetIterator<I>
+where
+    WhileBudgetIterator<I>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+impl<O, N> fmt::Display for RolloutStrategy<O, N>
+where
+    RolloutStrategy<O, N>: Snapshot,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.snapshot().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{MaxRetries, Offset, RetryBudget, WhileBudget};
+
+    #[test]
+    fn doesnt_advance_the_strategy() {
+        let budget = RetryBudget::new(1);
+        let mut s = ExponentialBackoff::from_millis(10)
+            .offset(Duration::from_millis(1))
+            .while_budget(budget.clone())
+            .max_retries(3);
+
+        let snapshot = s.snapshot();
+        assert_eq!(snapshot.next_delay, Some(Duration::from_millis(11)));
+        assert_eq!(snapshot.remaining_retries, Some(3));
+        assert_eq!(budget.remaining(), 1);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(11)));
+        assert_eq!(s.snapshot().to_string(), "exhausted, retries left: 2");
+    }
+}
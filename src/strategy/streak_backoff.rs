@@ -0,0 +1,239 @@
+use alloc::sync::Arc;
+use core::iter::{FusedIterator, Iterator};
+use core::sync::atomic::{AtomicU32, Ordering};
+use core::time::Duration;
+
+use super::Strategy;
+
+/// A retry strategy driven by the length of the current failure streak.
+///
+/// Every delay taken from the strategy counts as one more consecutive failure, and
+/// [`StreakFeedback::success`] ends the streak. Short streaks, as caused by an
+/// intermittent failure, grow linearly from the base delay. Once a streak gets longer
+/// than `short_streak`, delays escalate faster than an exponential back-off, protecting
+/// flapping dependencies.
+///
+/// Clones share the same streak, so a strategy can be cloned into every retry loop
+/// hitting the same dependency. Delays saturate at `u64::MAX` milliseconds.
+#[derive(Debug, Clone)]
+pub struct StreakBackoff {
+    base: u64,
+    short_streak: u32,
+    factor: u64,
+    max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    clamped: bool,
+    streak: Arc<AtomicU32>,
+}
+
+/// Handle reporting the outcome of retried actions to a [`StreakBackoff`],
+/// created by [`StreakBackoff::feedback`].
+#[derive(Debug, Clone)]
+pub struct StreakFeedback {
+    streak: Arc<AtomicU32>,
+}
+
+impl StreakBackoff {
+    /// Constructs a new streak back-off strategy,
+    /// given a base duration in milliseconds.
+    ///
+    /// Default `short_streak` is `3`.
+    pub fn from_millis(millis: u64) -> StreakBackoff {
+        StreakBackoff {
+            base: millis,
+            short_streak: 3,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+            clamped: false,
+            streak: Arc::new(AtomicU32::new(0)),
+        }
+    }
+
+    /// The number of consecutive failures considered intermittent, which get
+    /// linearly growing delays.
+    ///
+    /// Default short_streak is `3`.
+    pub const fn short_streak(mut self, short_streak: u32) -> StreakBackoff {
+        self.short_streak = short_streak;
+        self
+    }
+
+    /// A multiplicative factor that will be applied to the retry delay.
+    ///
+    /// For example, using a factor of `1000` will make each delay in units of seconds.
+    ///
+    /// Default factor is `1`.
+    pub const fn factor(mut self, factor: u64) -> StreakBackoff {
+        self.factor = factor;
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    pub const fn max_delay(mut self, duration: Duration) -> StreakBackoff {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    pub const fn max_delay_millis(mut self, duration: u64) -> StreakBackoff {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> StreakBackoff {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> StreakBackoff {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Returns a handle to report successes, ending the current failure streak.
+    pub fn feedback(&self) -> StreakFeedback {
+        StreakFeedback {
+            streak: self.streak.clone(),
+        }
+    }
+
+    /// The delay in milliseconds for the `streak`-th consecutive failure.
+    fn streak_millis(&self, streak: u32) -> u64 {
+        if streak <= self.short_streak {
+            return self.base.saturating_mul(u64::from(streak));
+        }
+
+        // the exponent grows with the streak, so delays escalate faster than exponentially
+        let escalation = u64::from(streak - self.short_streak);
+        let exponent = escalation.saturating_mul(escalation + 1) / 2;
+        let multiplier = u32::try_from(exponent)
+            .ok()
+            .and_then(|exponent| 2u64.checked_pow(exponent))
+            .unwrap_or(u64::MAX);
+        self.base
+            .saturating_mul(u64::from(self.short_streak.max(1)))
+            .saturating_mul(multiplier)
+    }
+}
+
+impl StreakFeedback {
+    /// Reports a success, ending the current failure streak.
+    pub fn success(&self) {
+        self.streak.store(0, Ordering::Relaxed);
+    }
+
+    /// The number of consecutive failures in the current streak.
+    pub fn streak(&self) -> u32 {
+        self.streak.load(Ordering::Relaxed)
+    }
+}
+
+impl Iterator for StreakBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let streak = if super::is_peeking() {
+            self.streak.load(Ordering::Relaxed)
+        } else {
+            self.streak
+                .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |streak| {
+                    Some(streak.saturating_add(1))
+                })
+                .unwrap_or_default()
+        }
+        .saturating_add(1);
+
+        // set delay duration by applying factor
+        let duration =
+            Duration::from_millis(self.streak_millis(streak).saturating_mul(self.factor));
+
+        // check if we are under min delay
+        let duration = match self.min_delay {
+            Some(min_delay) if duration < min_delay => min_delay,
+            _ => duration,
+        };
+
+        // check if we reached max delay
+        self.clamped = false;
+        if let Some(ref max_delay) = self.max_delay {
+            if duration > *max_delay {
+                #[cfg(feature = "tracing")]
+                tracing::warn!("`max_delay` for strategy reached");
+                self.clamped = true;
+                return Some(*max_delay);
+            }
+        }
+
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+// never yields `None`
+impl FusedIterator for StreakBackoff {}
+
+impl Strategy for StreakBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn grows_linearly_then_escalates() {
+        let mut s = StreakBackoff::from_millis(10);
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(20)));
+        assert_eq!(s.next(), Some(Duration::from_millis(30)));
+        assert_eq!(s.next(), Some(Duration::from_millis(60)));
+        assert_eq!(s.next(), Some(Duration::from_millis(240)));
+        assert_eq!(s.next(), Some(Duration::from_millis(1920)));
+    }
+
+    #[test]
+    fn success_ends_the_streak() {
+        let mut s = StreakBackoff::from_millis(10).short_streak(1);
+        let feedback = s.feedback();
+
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        assert_eq!(s.next(), Some(Duration::from_millis(20)));
+        assert_eq!(feedback.streak(), 2);
+
+        feedback.success();
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+    }
+
+    #[test]
+    fn clones_share_the_streak() {
+        let mut first = StreakBackoff::from_millis(10);
+        let mut second = first.clone();
+
+        assert_eq!(first.next(), Some(Duration::from_millis(10)));
+        assert_eq!(second.next(), Some(Duration::from_millis(20)));
+    }
+
+    #[test]
+    fn saturates_at_maximum_value() {
+        let mut s = StreakBackoff::from_millis(10).short_streak(0);
+
+        let last = s.by_ref().take(100).last();
+        assert_eq!(last, Some(Duration::from_millis(u64::MAX)));
+
+        let mut s = StreakBackoff::from_millis(10).max_delay_millis(100);
+        assert_eq!(s.by_ref().take(10).last(), Some(Duration::from_millis(100)));
+    }
+}
@@ -0,0 +1,293 @@
+use core::iter::Iterator;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use core::time::Duration;
+
+use super::Strategy;
+
+/// A fixed interval strategy meant to be shared between many concurrent retries,
+/// e.g. through a `static`.
+///
+/// `Iterator` is implemented for `&SyncFixedInterval`, so every retry loop can borrow
+/// the same policy without a lock.
+///
+/// ```rust
+/// use tokio_retry2::strategy::SyncFixedInterval;
+///
+/// static POLICY: SyncFixedInterval = SyncFixedInterval::from_millis(10).max_delay_millis(100);
+///
+/// let delays: Vec<_> = (&POLICY).take(3).collect();
+/// ```
+#[derive(Debug)]
+pub struct SyncFixedInterval {
+    duration: Duration,
+    factor: u64,
+    max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+}
+
+impl SyncFixedInterval {
+    /// Constructs a new shared fixed interval strategy,
+    /// given a duration in milliseconds.
+    pub const fn from_millis(millis: u64) -> SyncFixedInterval {
+        SyncFixedInterval::new(Duration::from_millis(millis))
+    }
+
+    /// Constructs a new shared fixed interval strategy.
+    pub const fn new(duration: Duration) -> SyncFixedInterval {
+        SyncFixedInterval {
+            duration,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+        }
+    }
+
+    /// A multiplicative factor that will be applied to the retry delay.
+    ///
+    /// For example, using a factor of `1000` will make each delay in units of seconds.
+    ///
+    /// Default factor is `1`.
+    pub const fn factor(mut self, factor: u64) -> SyncFixedInterval {
+        self.factor = factor;
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    pub const fn max_delay(mut self, duration: Duration) -> SyncFixedInterval {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    pub const fn max_delay_millis(mut self, duration: u64) -> SyncFixedInterval {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> SyncFixedInterval {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> SyncFixedInterval {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
+}
+
+impl Iterator for &SyncFixedInterval {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let duration = super::saturating_mul(self.duration, self.factor);
+        Some(bounded(duration, self.min_delay, self.max_delay).0)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl Strategy for &SyncFixedInterval {
+    // every delay is the same, so is whether it was capped
+    fn clamped(&self) -> bool {
+        let duration = super::saturating_mul(self.duration, self.factor);
+        let duration = self.min_delay.map_or(duration, |min| duration.max(min));
+        self.max_delay.is_some_and(|max_delay| duration > max_delay)
+    }
+}
+
+/// An exponential back-off strategy meant to be shared between many concurrent
+/// retries, e.g. through a `static`.
+///
+/// Its state advances with atomic operations instead of a lock, so hot paths sharing
+/// one policy don't contend on `next()`. As the state is shared, every delay taken by
+/// any retry loop grows the next one, until [`reset`](SyncExponentialBackoff::reset)
+/// is called. `Iterator` is implemented for `&SyncExponentialBackoff`.
+/// Delays saturate at `u64::MAX` milliseconds instead of overflowing.
+///
+/// ```rust
+/// use tokio_retry2::strategy::SyncExponentialBackoff;
+///
+/// static POLICY: SyncExponentialBackoff = SyncExponentialBackoff::from_millis(10).max_delay_millis(1000);
+///
+/// let delays: Vec<_> = (&POLICY).take(3).collect();
+/// POLICY.reset();
+/// ```
+#[derive(Debug)]
+pub struct SyncExponentialBackoff {
+    current: AtomicU64,
+    base: u64,
+    factor: u64,
+    max_delay: Option<Duration>,
+    min_delay: Option<Duration>,
+    /// Whether the last delay taken, by any retry loop, was capped at `max_delay`.
+    clamped: AtomicBool,
+}
+
+impl SyncExponentialBackoff {
+    /// Constructs a new shared exponential back-off strategy,
+    /// given a base duration in milliseconds.
+    ///
+    /// The resulting duration is calculated by taking the base to the `n`-th power,
+    /// where `n` denotes the number of delays taken since the last reset.
+    pub const fn from_millis(base: u64) -> SyncExponentialBackoff {
+        SyncExponentialBackoff {
+            current: AtomicU64::new(base),
+            base,
+            factor: 1u64,
+            max_delay: None,
+            min_delay: None,
+            clamped: AtomicBool::new(false),
+        }
+    }
+
+    /// A multiplicative factor that will be applied to the retry delay.
+    ///
+    /// For example, using a factor of `1000` will make each delay in units of seconds.
+    ///
+    /// Default factor is `1`.
+    pub const fn factor(mut self, factor: u64) -> SyncExponentialBackoff {
+        self.factor = factor;
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration`.
+    pub const fn max_delay(mut self, duration: Duration) -> SyncExponentialBackoff {
+        self.max_delay = Some(duration);
+        self
+    }
+
+    /// Apply a maximum delay. No single retry delay will be longer than this `Duration::from_millis`.
+    pub const fn max_delay_millis(mut self, duration: u64) -> SyncExponentialBackoff {
+        self.max_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay(mut self, duration: Duration) -> SyncExponentialBackoff {
+        self.min_delay = Some(duration);
+        self
+    }
+
+    /// Apply a minimum delay. No single retry delay will be shorter than this `Duration::from_millis`.
+    ///
+    /// `max_delay` takes precedence if it is shorter.
+    pub const fn min_delay_millis(mut self, duration: u64) -> SyncExponentialBackoff {
+        self.min_delay = Some(Duration::from_millis(duration));
+        self
+    }
+
+    /// Resets the shared state, so the next delay is the base one again.
+    pub fn reset(&self) {
+        self.current.store(self.base, Ordering::Relaxed);
+    }
+}
+
+impl Iterator for &SyncExponentialBackoff {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        let base = self.base;
+        let current = self
+            .current
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |current| {
+                Some(current.saturating_mul(base))
+            })
+            .unwrap_or_default();
+
+        // set delay duration by applying factor
+        let duration = Duration::from_millis(current.saturating_mul(self.factor));
+        let (duration, clamped) = bounded(duration, self.min_delay, self.max_delay);
+        self.clamped.store(clamped, Ordering::Relaxed);
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (usize::MAX, None)
+    }
+}
+
+impl Strategy for &SyncExponentialBackoff {
+    fn clamped(&self) -> bool {
+        self.clamped.load(Ordering::Relaxed)
+    }
+}
+
+/// Bounds `duration` by `min_delay` and `max_delay`, telling whether it was capped.
+fn bounded(
+    duration: Duration,
+    min_delay: Option<Duration>,
+    max_delay: Option<Duration>,
+) -> (Duration, bool) {
+    // check if we are under min delay
+    let duration = match min_delay {
+        Some(min_delay) if duration < min_delay => min_delay,
+        _ => duration,
+    };
+
+    // check if we reached max delay
+    match max_delay {
+        Some(max_delay) if duration > max_delay => {
+            #[cfg(feature = "tracing")]
+            tracing::warn!("`max_delay` for strategy reached");
+            (max_delay, true)
+        }
+        _ => (duration, false),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Arc;
+
+    #[test]
+    fn fixed_interval_applies_bounds() {
+        let s = SyncFixedInterval::from_millis(2)
+            .factor(1000)
+            .max_delay_millis(1500);
+
+        assert_eq!((&s).next(), Some(Duration::from_millis(1500)));
+        assert_eq!((&s).next(), Some(Duration::from_millis(1500)));
+    }
+
+    #[test]
+    fn exponential_backoff_is_shared() {
+        let s = SyncExponentialBackoff::from_millis(2).max_delay_millis(16);
+
+        assert_eq!((&s).next(), Some(Duration::from_millis(2)));
+        assert_eq!((&s).next(), Some(Duration::from_millis(4)));
+        assert_eq!(
+            (&s).take(3).collect::<Vec<_>>(),
+            [8, 16, 16].map(Duration::from_millis)
+        );
+
+        s.reset();
+        assert_eq!((&s).next(), Some(Duration::from_millis(2)));
+    }
+
+    #[test]
+    fn exponential_backoff_advances_from_many_threads() {
+        let s = Arc::new(SyncExponentialBackoff::from_millis(2));
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let s = s.clone();
+                std::thread::spawn(move || (&*s).take(100).count())
+            })
+            .collect();
+        let taken: usize = threads.into_iter().map(|t| t.join().unwrap()).sum();
+
+        assert_eq!(taken, 800);
+        assert_eq!((&*s).next(), Some(Duration::from_millis(u64::MAX)));
+    }
+}
@@ -0,0 +1,212 @@
+use core::fmt;
+use core::iter::FusedIterator;
+use core::time::Duration;
+
+use super::{Clock, Fused, FusedStrategy, MonotonicClock, Snapshot, Strategy, StrategySnapshot};
+use crate::notify::StopReason;
+
+/// The progress of a strategy retrying forever, passed to the heartbeat hook of
+/// [`UnboundedWithHeartbeat::unbounded_with_heartbeat`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct Heartbeat {
+    /// Retries made so far.
+    pub retries: u64,
+    /// Time elapsed since the strategy was wrapped.
+    pub elapsed: Duration,
+}
+
+/// Wraps a strategy meant to retry forever, e.g. a reconnect loop, requiring a
+/// heartbeat so it stays observable.
+pub trait UnboundedWithHeartbeat: Iterator<Item = Duration> {
+    /// Marks the strategy as intentionally unbounded, silencing the warning about
+    /// unbounded strategies, and calls `hook` at most once every `every` while
+    /// retrying, e.g. to log or report that the loop is still failing.
+    ///
+    /// The heartbeat is checked on every retry, so with delays longer than `every`,
+    /// it beats once per retry.
+    fn unbounded_with_heartbeat<F>(self, every: Duration, hook: F) -> UnlimitedRetries<Self, F>
+    where
+        Self: Sized,
+        F: FnMut(&Heartbeat),
+    {
+        self.unbounded_with_heartbeat_clock(every, hook, MonotonicClock::new())
+    }
+
+    /// Same as `unbounded_with_heartbeat`, but measures the time with `clock`.
+    fn unbounded_with_heartbeat_clock<F, C>(
+        self,
+        every: Duration,
+        hook: F,
+        clock: C,
+    ) -> UnlimitedRetries<Self, F, C>
+    where
+        Self: Sized,
+        F: FnMut(&Heartbeat),
+        C: Clock,
+    {
+        let start = clock.now();
+        UnlimitedRetries {
+            iter: self.fused(),
+            every,
+            hook,
+            start,
+            last_beat: start,
+            retries: 0,
+            clock,
+        }
+    }
+}
+
+impl<I> UnboundedWithHeartbeat for I where I: Iterator<Item = Duration> {}
+
+/// A strategy wrapper retrying forever with a heartbeat,
+/// created by [`UnboundedWithHeartbeat::unbounded_with_heartbeat`] function.
+///
+/// Its `size_hint` has a lower bound of `0` rather than reporting it as infinite, so
+/// it isn't reported as accidentally unbounded.
+#[derive(Clone)]
+pub struct UnlimitedRetries<I, F, C = MonotonicClock> {
+    iter: FusedStrategy<I>,
+    every: Duration,
+    hook: F,
+    start: Duration,
+    last_beat: Duration,
+    retries: u64,
+    clock: C,
+}
+
+impl<I, F, C> Iterator for UnlimitedRetries<I, F, C>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Heartbeat),
+    C: Clock,
+{
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let duration = self.iter.next()?;
+        if super::is_peeking() {
+            return Some(duration);
+        }
+        self.retries = self.retries.saturating_add(1);
+        let now = self.clock.now();
+        if now.saturating_sub(self.last_beat) >= self.every {
+            self.last_beat = now;
+            let heartbeat = Heartbeat {
+                retries: self.retries,
+                elapsed: now.saturating_sub(self.start),
+            };
+            #[cfg(feature = "tracing")]
+            tracing::info!(
+                retries = heartbeat.retries,
+                elapsed = ?heartbeat.elapsed,
+                "still retrying an unbounded strategy"
+            );
+            (self.hook)(&heartbeat);
+        }
+        Some(duration)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, self.iter.size_hint().1)
+    }
+}
+
+impl<I, F, C> FusedIterator for UnlimitedRetries<I, F, C>
+where
+    I: Iterator<Item = Duration>,
+    F: FnMut(&Heartbeat),
+    C: Clock,
+{
+}
+
+impl<I, F, C> Strategy for UnlimitedRetries<I, F, C>
+where
+    I: Strategy,
+    F: FnMut(&Heartbeat),
+    C: Clock,
+{
+    fn stop_reason(&self) -> Option<StopReason> {
+        self.iter.stop_reason()
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+impl<I, F, C> Snapshot for UnlimitedRetries<I, F, C>
+where
+    I: Iterator<Item = Duration> + Clone,
+    F: FnMut(&Heartbeat) + Clone,
+    C: Clock + Clone,
+{
+    fn snapshot(&self) -> StrategySnapshot {
+        StrategySnapshot::new(super::snapshot::peek(self))
+    }
+}
+
+impl<I: fmt::Debug, F, C> fmt::Debug for UnlimitedRetries<I, F, C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UnlimitedRetries")
+            .field("iter", &self.iter)
+            .field("every", &self.every)
+            .field("retries", &self.retries)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use crate::strategy::FixedInterval;
+
+    #[derive(Clone)]
+    struct ManualClock(Rc<Cell<Duration>>);
+
+    impl Clock for ManualClock {
+        fn now(&self) -> Duration {
+            self.0.get()
+        }
+    }
+
+    #[test]
+    fn beats_at_most_once_per_period() {
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let mut beats = Vec::new();
+        let mut s = FixedInterval::from_millis(10).unbounded_with_heartbeat_clock(
+            Duration::from_secs(60),
+            |heartbeat: &Heartbeat| beats.push(*heartbeat),
+            ManualClock(now.clone()),
+        );
+
+        assert_eq!(s.size_hint(), (0, None));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        now.set(Duration::from_secs(61));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        now.set(Duration::from_secs(90));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        now.set(Duration::from_secs(121));
+        assert_eq!(s.next(), Some(Duration::from_millis(10)));
+        drop(s);
+
+        assert_eq!(
+            beats,
+            vec![
+                Heartbeat {
+                    retries: 2,
+                    elapsed: Duration::from_secs(61),
+                },
+                Heartbeat {
+                    retries: 4,
+                    elapsed: Duration::from_secs(121),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,630 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{ready, Context, Poll};
+
+use futures_core::Stream;
+use pin_project::pin_project;
+use tokio::time::{sleep, Duration, Sleep};
+
+use crate::action::Action;
+use crate::delay::{self, DelayFn, DelayRules, Planned};
+use crate::error::Error as RetryError;
+use crate::strategy::{RetryBudget, Strategy};
+
+/// Extension trait adding retry behaviours to streams of `Result` items.
+pub trait RetryStreamExt<T, E>: Stream<Item = Result<T, E>> {
+    /// Re-polls the upstream after a backoff whenever it yields an `Err` item,
+    /// instead of passing the error downstream.
+    ///
+    /// `policy_factory` builds a fresh strategy for every failing item, so the
+    /// strategy caps the number of retries per item. Once it is exhausted the last
+    /// error is yielded and the stream carries on with the next item.
+    ///
+    /// The upstream must be able to produce further items after an `Err`, as is the
+    /// case for most gRPC and SSE streams.
+    fn retry_items<F, P>(self, policy_factory: F) -> RetryItems<Self, F, P>
+    where
+        Self: Sized,
+        F: FnMut() -> P,
+        P: IntoIterator<Item = Duration>,
+    {
+        RetryItems {
+            stream: self,
+            policy_factory,
+            strategy: None,
+            sleep: None,
+        }
+    }
+}
+
+impl<S, T, E> RetryStreamExt<T, E> for S where S: Stream<Item = Result<T, E>> {}
+
+/// Stream that retries failing items of the underlying stream,
+/// created by [`RetryStreamExt::retry_items`].
+#[pin_project]
+pub struct RetryItems<S, F, P>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    #[pin]
+    stream: S,
+    policy_factory: F,
+    strategy: Option<P::IntoIter>,
+    #[pin]
+    sleep: Option<Sleep>,
+}
+
+impl<S, F, P, T, E> Stream for RetryItems<S, F, P>
+where
+    S: Stream<Item = Result<T, E>>,
+    F: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleep) = this.sleep.as_mut().as_pin_mut() {
+                ready!(sleep.poll(cx));
+                this.sleep.set(None);
+            }
+
+            match ready!(this.stream.as_mut().poll_next(cx)) {
+                None => return Poll::Ready(None),
+                Some(Ok(item)) => {
+                    *this.strategy = None;
+                    return Poll::Ready(Some(Ok(item)));
+                }
+                Some(Err(err)) => {
+                    let policy_factory = &mut *this.policy_factory;
+                    let strategy = this
+                        .strategy
+                        .get_or_insert_with(|| policy_factory().into_iter());
+                    match delay::plain_delay(strategy, &err, None) {
+                        Some(duration) => this.sleep.set(Some(sleep(duration))),
+                        None => {
+                            #[cfg(feature = "tracing")]
+                            tracing::warn!("ending item retry: strategy reached its limit");
+                            *this.strategy = None;
+                            return Poll::Ready(Some(Err(err)));
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Extension trait turning a strategy into a stream ticking on its cadence.
+pub trait IntoDelayStream: IntoIterator<Item = Duration> {
+    /// Returns a stream yielding `()` after each successive delay of the strategy,
+    /// e.g. to log reminders or re-broadcast announcements on a back-off cadence.
+    /// The stream completes once the strategy is exhausted.
+    fn into_delay_stream(self) -> DelayStream<Self::IntoIter>
+    where
+        Self: Sized,
+    {
+        DelayStream {
+            strategy: self.into_iter(),
+            sleep: None,
+        }
+    }
+}
+
+impl<I> IntoDelayStream for I where I: IntoIterator<Item = Duration> {}
+
+/// Stream yielding after each delay of a strategy,
+/// created by [`IntoDelayStream::into_delay_stream`].
+#[pin_project]
+pub struct DelayStream<I> {
+    strategy: I,
+    #[pin]
+    sleep: Option<Sleep>,
+}
+
+impl<I> Stream for DelayStream<I>
+where
+    I: Iterator<Item = Duration>,
+{
+    type Item = ();
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        if this.sleep.is_none() {
+            match this.strategy.next() {
+                Some(duration) => this.sleep.set(Some(sleep(duration))),
+                None => return Poll::Ready(None),
+            }
+        }
+        if let Some(sleep) = this.sleep.as_mut().as_pin_mut() {
+            ready!(sleep.poll(cx));
+        }
+        this.sleep.set(None);
+        Poll::Ready(Some(()))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let (lower, upper) = self.strategy.size_hint();
+        // the pending sleep yields a tick of its own
+        let pending = usize::from(self.sleep.is_some());
+        (
+            lower.saturating_add(pending),
+            upper.and_then(|upper| upper.checked_add(pending)),
+        )
+    }
+}
+
+/// Outcome of a single attempt, yielded by [`Attempts`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct AttemptResult<T, E> {
+    /// The number of the attempt, starting at `1`.
+    pub attempt: u32,
+    /// What the attempt resolved with.
+    pub result: Result<T, E>,
+    /// The delay before the next attempt, or `None` if this was the last one.
+    pub next_delay: Option<Duration>,
+}
+
+/// Stream yielding the outcome of every attempt at an action,
+/// created by [`Retry::attempts`](crate::Retry::attempts).
+///
+/// Completes after the first success, a permanent error, or once the strategy is exhausted.
+#[pin_project]
+pub struct Attempts<I, A>
+where
+    A: Action,
+{
+    strategy: I,
+    action: A,
+    attempt: u32,
+    #[pin]
+    state: AttemptsState<A::Future>,
+    retry_after_jitter: Option<(f64, f64)>,
+    delay_fn: Option<(DelayFn<A::Error>, Duration)>,
+    retry_budget: Option<RetryBudget>,
+}
+
+#[pin_project(project = AttemptsStateProj)]
+enum AttemptsState<F> {
+    Running(#[pin] F),
+    Sleeping(#[pin] Sleep),
+    Done,
+}
+
+impl<I, A> Attempts<I, A>
+where
+    I: Strategy,
+    A: Action,
+{
+    pub(crate) fn new(strategy: I, mut action: A) -> Self {
+        Attempts {
+            strategy,
+            state: AttemptsState::Running(action.run()),
+            action,
+            attempt: 1,
+            retry_after_jitter: None,
+            delay_fn: None,
+            retry_budget: None,
+        }
+    }
+
+    /// See [`RetryIf::jitter_retry_after`](crate::RetryIf::jitter_retry_after).
+    #[cfg(feature = "jitter-std")]
+    pub fn jitter_retry_after(mut self, min: f64, max: f64) -> Self {
+        self.retry_after_jitter = Some((min, max));
+        self
+    }
+
+    /// See [`RetryIf::delay_fn`](crate::RetryIf::delay_fn).
+    pub fn delay_fn<F>(mut self, delay_fn: F, max: Duration) -> Self
+    where
+        F: FnMut(&A::Error, Duration) -> Duration + Send + Sync + 'static,
+    {
+        self.delay_fn = Some((Box::new(delay_fn), max));
+        self
+    }
+
+    /// See [`RetryIf::retry_budget`](crate::RetryIf::retry_budget).
+    pub fn retry_budget(mut self, budget: RetryBudget) -> Self {
+        self.retry_budget = Some(budget);
+        self
+    }
+}
+
+impl<I, A> Stream for Attempts<I, A>
+where
+    I: Strategy,
+    A: Action,
+{
+    type Item = AttemptResult<A::Item, A::Error>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let result = match this.state.as_mut().project() {
+                AttemptsStateProj::Done => return Poll::Ready(None),
+                AttemptsStateProj::Sleeping(sleep) => {
+                    ready!(sleep.poll(cx));
+                    *this.attempt += 1;
+                    let future = this.action.run();
+                    this.state.set(AttemptsState::Running(future));
+                    continue;
+                }
+                AttemptsStateProj::Running(future) => ready!(future.poll(cx)),
+            };
+
+            let attempt = *this.attempt;
+            let (result, next_delay) = match result {
+                Ok(item) => {
+                    if let Some(budget) = this.retry_budget {
+                        budget.record_success();
+                    }
+                    (Ok(item), None)
+                }
+                Err(RetryError::Permanent(err, ..)) => (Err(err), None),
+                Err(RetryError::Transient {
+                    err, retry_after, ..
+                }) => {
+                    let rules = DelayRules {
+                        retry_after_jitter: *this.retry_after_jitter,
+                        delay_fn: this
+                            .delay_fn
+                            .as_mut()
+                            .map(|(delay_fn, max)| (delay_fn, *max)),
+                        retry_budget: this.retry_budget.as_ref(),
+                        ..DelayRules::default()
+                    };
+                    let strategy = &mut *this.strategy;
+                    let next = || {
+                        let delay = strategy.next();
+                        Planned::of(strategy, delay)
+                    };
+                    let mut always = |_: &A::Error| true;
+                    let next_delay = delay::resolve(next, &err, retry_after, &mut always, rules)
+                        .next
+                        .ok();
+                    (Err(err), next_delay)
+                }
+            };
+            match next_delay {
+                Some(duration) => this.state.set(AttemptsState::Sleeping(sleep(duration))),
+                None => this.state.set(AttemptsState::Done),
+            }
+            return Poll::Ready(Some(AttemptResult {
+                attempt,
+                result,
+                next_delay,
+            }));
+        }
+    }
+}
+
+/// Subscribes to a stream with `connect`, and re-subscribes after a backoff whenever
+/// connecting fails or the subscription yields a transient error, e.g. for change
+/// streams or event subscriptions.
+///
+/// `resume_token` extracts a token, e.g. an offset or a sequence number, from every
+/// item passed downstream, and `connect` is given the token of the last one, or
+/// `None` for the first subscription, so that the new subscription resumes right
+/// after it, without gaps nor duplicates.
+///
+/// `strategy_factory` builds a fresh strategy whenever the subscription fails after
+/// yielding an item, so the strategy caps the number of reconnects in a row. Once
+/// it is exhausted, or on a permanent error, the error is yielded and the stream
+/// completes. The stream also completes along with a subscription.
+///
+/// ```rust,no_run
+/// use futures::{stream, Stream, StreamExt};
+/// use tokio_retry2::RetryError;
+/// use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+///
+/// struct Event {
+///     offset: u64,
+/// }
+///
+/// async fn subscribe(
+///     from: Option<u64>,
+/// ) -> Result<impl Stream<Item = Result<Event, RetryError<String>>>, RetryError<String>> {
+///     let start = from.map_or(0, |offset| offset + 1);
+///     Ok(stream::iter((start..).map(|offset| Ok(Event { offset }))))
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let events = tokio_retry2::stream::resuming(
+///     || ExponentialBackoff::from_millis(10).max_retries(5),
+///     subscribe,
+///     |event: &Event| event.offset,
+/// );
+/// let mut events = std::pin::pin!(events);
+/// while let Some(event) = events.next().await {
+///     println!("event {}", event.unwrap().offset);
+/// }
+/// # }
+/// ```
+pub fn resuming<G, P, C, Fut, S, R, K, T, E>(
+    strategy_factory: G,
+    mut connect: C,
+    resume_token: R,
+) -> Resuming<G, P, C, Fut, S, R, K>
+where
+    G: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: FnMut(Option<K>) -> Fut,
+    Fut: Future<Output = Result<S, RetryError<E>>>,
+    S: Stream<Item = Result<T, RetryError<E>>>,
+    R: FnMut(&T) -> K,
+    K: Clone,
+{
+    Resuming {
+        state: ResumingState::Connecting(connect(None)),
+        strategy_factory,
+        strategy: None,
+        connect,
+        resume_token,
+        token: None,
+    }
+}
+
+/// Stream re-subscribing where the previous subscription left off,
+/// created by [`resuming`].
+#[pin_project]
+pub struct Resuming<G, P, C, Fut, S, R, K>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    strategy_factory: G,
+    strategy: Option<P::IntoIter>,
+    connect: C,
+    resume_token: R,
+    token: Option<K>,
+    #[pin]
+    state: ResumingState<Fut, S>,
+}
+
+#[pin_project(project = ResumingStateProj)]
+enum ResumingState<Fut, S> {
+    Connecting(#[pin] Fut),
+    Streaming(#[pin] S),
+    Sleeping(#[pin] Sleep),
+    Done,
+}
+
+impl<G, P, C, Fut, S, R, K> Resuming<G, P, C, Fut, S, R, K>
+where
+    P: IntoIterator<Item = Duration>,
+{
+    /// The token of the last item passed downstream, which the next subscription
+    /// resumes from.
+    pub fn resume_token(&self) -> Option<&K> {
+        self.token.as_ref()
+    }
+}
+
+impl<G, P, C, Fut, S, R, K, T, E> Stream for Resuming<G, P, C, Fut, S, R, K>
+where
+    G: FnMut() -> P,
+    P: IntoIterator<Item = Duration>,
+    C: FnMut(Option<K>) -> Fut,
+    Fut: Future<Output = Result<S, RetryError<E>>>,
+    S: Stream<Item = Result<T, RetryError<E>>>,
+    R: FnMut(&T) -> K,
+    K: Clone,
+{
+    type Item = Result<T, E>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            let err = match this.state.as_mut().project() {
+                ResumingStateProj::Done => return Poll::Ready(None),
+                ResumingStateProj::Sleeping(sleep) => {
+                    ready!(sleep.poll(cx));
+                    let future = (this.connect)(this.token.clone());
+                    this.state.set(ResumingState::Connecting(future));
+                    continue;
+                }
+                ResumingStateProj::Connecting(future) => match ready!(future.poll(cx)) {
+                    Ok(stream) => {
+                        this.state.set(ResumingState::Streaming(stream));
+                        continue;
+                    }
+                    Err(err) => err,
+                },
+                ResumingStateProj::Streaming(stream) => match ready!(stream.poll_next(cx)) {
+                    None => {
+                        this.state.set(ResumingState::Done);
+                        return Poll::Ready(None);
+                    }
+                    Some(Ok(item)) => {
+                        *this.token = Some((this.resume_token)(&item));
+                        *this.strategy = None;
+                        return Poll::Ready(Some(Ok(item)));
+                    }
+                    Some(Err(err)) => err,
+                },
+            };
+
+            let (err, retry_after) = match err {
+                RetryError::Permanent(err, ..) => {
+                    this.state.set(ResumingState::Done);
+                    return Poll::Ready(Some(Err(err)));
+                }
+                RetryError::Transient {
+                    err, retry_after, ..
+                } => (err, retry_after),
+            };
+            let strategy_factory = &mut *this.strategy_factory;
+            let strategy = this
+                .strategy
+                .get_or_insert_with(|| strategy_factory().into_iter());
+            match delay::plain_delay(strategy, &err, retry_after) {
+                Some(duration) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(?duration, "subscription failed, resuming after a delay");
+                    this.state.set(ResumingState::Sleeping(sleep(duration)));
+                }
+                None => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!("ending subscription: strategy reached its limit");
+                    this.state.set(ResumingState::Done);
+                    return Poll::Ready(Some(Err(err)));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use futures::{stream, StreamExt};
+
+    use crate::strategy::FixedInterval;
+
+    #[tokio::test]
+    async fn delay_stream_ticks_after_each_delay() {
+        let start = tokio::time::Instant::now();
+        let ticks = FixedInterval::from_millis(10)
+            .take(3)
+            .into_delay_stream()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(ticks, vec![(), (), ()]);
+        assert!(start.elapsed() >= Duration::from_millis(30));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_stream_size_hint_counts_the_pending_sleep() {
+        let mut ticks = std::pin::pin!(FixedInterval::from_millis(10).take(3).into_delay_stream());
+        assert_eq!(ticks.size_hint(), (3, Some(3)));
+
+        let waker = futures::task::noop_waker();
+        let mut cx = Context::from_waker(&waker);
+        assert!(ticks.as_mut().poll_next(&mut cx).is_pending());
+        assert_eq!(ticks.size_hint(), (3, Some(3)));
+
+        assert_eq!(ticks.next().await, Some(()));
+        assert_eq!(ticks.size_hint(), (2, Some(2)));
+    }
+
+    #[tokio::test]
+    async fn retries_err_items_until_ok() {
+        let upstream = stream::iter(vec![Err(1), Err(2), Ok(3), Ok(4)]);
+        let items: Vec<Result<u64, u64>> = upstream
+            .retry_items(|| FixedInterval::from_millis(1).take(5))
+            .collect()
+            .await;
+
+        assert_eq!(items, vec![Ok(3), Ok(4)]);
+    }
+
+    #[tokio::test]
+    async fn yields_err_once_item_cap_is_reached() {
+        let upstream = stream::iter(vec![Err(1), Err(2), Err(3), Ok(4), Err(5), Ok(6)]);
+        let items: Vec<Result<u64, u64>> = upstream
+            .retry_items(|| FixedInterval::from_millis(1).take(2))
+            .collect()
+            .await;
+
+        assert_eq!(items, vec![Err(3), Ok(4), Ok(6)]);
+    }
+
+    #[tokio::test]
+    async fn attempts_yields_every_outcome() {
+        use std::future;
+        use std::sync::atomic::{AtomicU32, Ordering};
+
+        let counter = AtomicU32::new(0);
+        let attempts: Vec<_> =
+            crate::Retry::attempts(FixedInterval::from_millis(1).take(5), || {
+                let attempt = counter.fetch_add(1, Ordering::SeqCst);
+                if attempt < 2 {
+                    future::ready(RetryError::to_transient(attempt))
+                } else {
+                    future::ready(Ok(attempt))
+                }
+            })
+            .collect()
+            .await;
+
+        let results: Vec<_> = attempts.iter().map(|a| (a.attempt, a.result)).collect();
+        assert_eq!(results, vec![(1, Err(0)), (2, Err(1)), (3, Ok(2))]);
+        assert_eq!(attempts[0].next_delay, Some(Duration::from_millis(1)));
+        assert_eq!(attempts[2].next_delay, None);
+    }
+
+    #[tokio::test]
+    async fn attempts_completes_once_exhausted() {
+        let attempts: Vec<_> =
+            crate::Retry::attempts(FixedInterval::from_millis(1).take(1), || {
+                std::future::ready(RetryError::to_transient::<()>(42))
+            })
+            .collect()
+            .await;
+
+        assert_eq!(attempts.len(), 2);
+        assert_eq!(attempts[1].next_delay, None);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn attempts_resolve_delays_like_retry_if() {
+        use crate::strategy::RetryBudget;
+
+        let attempts: Vec<_> =
+            crate::Retry::attempts(FixedInterval::from_millis(10).take(5), || {
+                std::future::ready(RetryError::to_transient::<()>(3u64))
+            })
+            .delay_fn(|err, delay| delay * *err as u32, Duration::from_millis(25))
+            .retry_budget(RetryBudget::new(2))
+            .collect()
+            .await;
+
+        let delays: Vec<_> = attempts.iter().map(|a| a.next_delay).collect();
+        // capped by `delay_fn`, then stopped by the budget
+        let capped = Some(Duration::from_millis(25));
+        assert_eq!(delays, vec![capped, capped, None]);
+    }
+
+    #[tokio::test]
+    async fn resuming_reconnects_after_the_last_token() {
+        use std::sync::Mutex;
+
+        let connects = Mutex::new(Vec::new());
+        let items: Vec<Result<u64, &str>> = resuming(
+            || FixedInterval::from_millis(1).take(2),
+            |from: Option<u64>| {
+                connects.lock().unwrap().push(from);
+                let start = from.map_or(0, |offset| offset + 1);
+                // every subscription drops after two events
+                let events = (start..start + 2)
+                    .map(Ok)
+                    .chain([RetryError::to_transient("dropped")]);
+                std::future::ready(Ok(stream::iter(events)
+                    .take_while(move |event| std::future::ready(*event != Ok(5)))))
+            },
+            |event: &u64| *event,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(items, (0..5).map(Ok).collect::<Vec<_>>());
+        assert_eq!(*connects.lock().unwrap(), vec![None, Some(1), Some(3)]);
+    }
+
+    #[tokio::test]
+    async fn resuming_gives_up_once_exhausted() {
+        let items: Vec<Result<u64, &str>> = resuming(
+            || FixedInterval::from_millis(1).take(2),
+            |_| std::future::ready(Err::<stream::Empty<_>, _>(RetryError::transient("down"))),
+            |event: &u64| *event,
+        )
+        .collect()
+        .await;
+
+        assert_eq!(items, vec![Err("down")]);
+    }
+}
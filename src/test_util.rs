@@ -0,0 +1,185 @@
+//! Utilities for testing code that retries.
+use std::future::Future;
+use std::sync::Arc;
+
+use tokio::time::Duration;
+
+use crate::strategy::Strategy;
+
+type BoxedStrategy = Box<dyn Strategy + Send + Sync>;
+type StrategyFactory = Arc<dyn Fn() -> BoxedStrategy + Send + Sync>;
+
+tokio::task_local! {
+    static POLICY_OVERRIDE: StrategyFactory;
+}
+
+/// Runs `future` with every [`Retry`](crate::Retry) and [`RetryIf`](crate::RetryIf)
+/// spawned inside it driven by `policy` instead of the strategy it was spawned with.
+/// The other entry points resolving with a future run on `RetryIf`, so they are
+/// overridden too.
+///
+/// This allows forcing code under test that builds its own strategies onto a
+/// zero-delay policy, keeping integration tests fast and deterministic without
+/// injecting strategies through the code under test.
+///
+/// ```rust
+/// # use tokio_retry2::strategy::FixedInterval;
+/// # use tokio_retry2::test_util::with_policy_override;
+/// # async fn code_under_test() {}
+/// # #[tokio::main]
+/// # async fn main() {
+/// with_policy_override(FixedInterval::from_millis(0).take(3), async {
+///     code_under_test().await;
+/// })
+/// .await;
+/// # }
+/// ```
+pub async fn with_policy_override<P, F>(policy: P, future: F) -> F::Output
+where
+    P: IntoIterator<Item = Duration> + Clone + Send + Sync + 'static,
+    P::IntoIter: Strategy + Send + Sync + 'static,
+    F: Future,
+{
+    let factory: StrategyFactory = Arc::new(move || Box::new(policy.clone().into_iter()));
+    POLICY_OVERRIDE.scope(factory, future).await
+}
+
+/// Returns a fresh instance of the overriding policy, if any is in scope.
+pub(crate) fn policy_override() -> Option<BoxedStrategy> {
+    POLICY_OVERRIDE.try_with(|factory| factory()).ok()
+}
+
+/// An expected delay, matching durations within an inclusive range, for asserting on
+/// jittered strategies with [`assert_schedule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExpectedDelay {
+    min: Duration,
+    max: Duration,
+}
+
+impl ExpectedDelay {
+    /// Matches exactly `delay`.
+    pub const fn exactly(delay: Duration) -> ExpectedDelay {
+        ExpectedDelay {
+            min: delay,
+            max: delay,
+        }
+    }
+
+    /// Matches `delay`, give or take `tolerance`.
+    pub const fn around(delay: Duration, tolerance: Duration) -> ExpectedDelay {
+        ExpectedDelay {
+            min: delay.saturating_sub(tolerance),
+            max: delay.saturating_add(tolerance),
+        }
+    }
+
+    /// Matches any delay between `min` and `max`, both included, e.g. the range of a
+    /// `jitter` function.
+    pub const fn between(min: Duration, max: Duration) -> ExpectedDelay {
+        ExpectedDelay { min, max }
+    }
+
+    /// Whether `delay` matches.
+    pub fn matches(&self, delay: Duration) -> bool {
+        self.min <= delay && delay <= self.max
+    }
+}
+
+/// Asserts that `actual` is within `tolerance` of `expected`.
+///
+/// ```rust
+/// # #[cfg(feature = "jitter-std")] {
+/// # use std::time::Duration;
+/// # use tokio_retry2::strategy::jitter_range;
+/// # use tokio_retry2::test_util::assert_duration_close;
+/// let delay = jitter_range(0.9, 1.1)(Duration::from_millis(100));
+/// assert_duration_close(delay, Duration::from_millis(100), Duration::from_millis(10));
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_duration_close(actual: Duration, expected: Duration, tolerance: Duration) {
+    assert!(
+        ExpectedDelay::around(expected, tolerance).matches(actual),
+        "assertion failed: {actual:?} is not within {tolerance:?} of {expected:?}"
+    );
+}
+
+/// Asserts that the first delays of `strategy` match `expected`, one by one.
+///
+/// Only as many delays as expected are taken, so unbounded strategies can be checked.
+/// Fails if the strategy yields fewer delays.
+///
+/// ```rust
+/// # #[cfg(feature = "jitter-std")] {
+/// # use std::time::Duration;
+/// # use tokio_retry2::strategy::{jitter_range, ExponentialBackoff};
+/// # use tokio_retry2::test_util::{assert_schedule, ExpectedDelay};
+/// let strategy = ExponentialBackoff::from_millis(10).map(jitter_range(0.5, 1.5));
+/// assert_schedule(
+///     strategy,
+///     [
+///         ExpectedDelay::between(Duration::from_millis(5), Duration::from_millis(15)),
+///         ExpectedDelay::between(Duration::from_millis(50), Duration::from_millis(150)),
+///     ],
+/// );
+/// # }
+/// ```
+#[track_caller]
+pub fn assert_schedule<S, E>(strategy: S, expected: E)
+where
+    S: IntoIterator<Item = Duration>,
+    E: IntoIterator<Item = ExpectedDelay>,
+{
+    let mut strategy = strategy.into_iter();
+    for (index, expected) in expected.into_iter().enumerate() {
+        match strategy.next() {
+            Some(delay) => assert!(
+                expected.matches(delay),
+                "assertion failed: delay {index} is {delay:?}, expected between {:?} and {:?}",
+                expected.min,
+                expected.max
+            ),
+            None => panic!("assertion failed: the strategy stopped after {index} delays"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::strategy::{FixedInterval, MaxRetries};
+
+    #[test]
+    fn matches_schedules_within_tolerances() {
+        let strategy = FixedInterval::from_millis(100).max_retries(2);
+        assert_schedule(
+            strategy,
+            [
+                ExpectedDelay::exactly(Duration::from_millis(100)),
+                ExpectedDelay::around(Duration::from_millis(95), Duration::from_millis(5)),
+            ],
+        );
+        assert_duration_close(
+            Duration::from_millis(105),
+            Duration::from_millis(100),
+            Duration::from_millis(5),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "delay 1 is 100ms, expected between 10ms and 20ms")]
+    fn reports_the_mismatching_delay() {
+        let less = ExpectedDelay::between(Duration::from_millis(10), Duration::from_millis(20));
+        let any = ExpectedDelay::between(Duration::ZERO, Duration::MAX);
+        assert_schedule(FixedInterval::from_millis(100), [any, less]);
+    }
+
+    #[test]
+    #[should_panic(expected = "the strategy stopped after 1 delays")]
+    fn reports_short_schedules() {
+        let any = ExpectedDelay::between(Duration::ZERO, Duration::MAX);
+        assert_schedule(FixedInterval::from_millis(100).max_retries(1), [any, any]);
+    }
+}
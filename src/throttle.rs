@@ -0,0 +1,127 @@
+//! Retry throttling as specified by gRPC's service config.
+//!
+//! A [`GrpcRetryThrottle`] is shared by every call on a channel. Every failed attempt
+//! spends a token and every success refills `token_ratio` tokens, and retries are only
+//! allowed while more than half of `max_tokens` are left. Attach it to a policy with
+//! [`Builder::throttle`](crate::policy::Builder::throttle), which records failures, and
+//! report successes with [`GrpcRetryThrottle::record_success`].
+//!
+//! ```rust
+//! use tokio_retry2::policy::Builder;
+//! use tokio_retry2::strategy::ExponentialBackoff;
+//! use tokio_retry2::throttle::GrpcRetryThrottle;
+//!
+//! // `"retryThrottling": { "maxTokens": 10, "tokenRatio": 0.1 }`
+//! let throttle = GrpcRetryThrottle::new(10, 0.1);
+//! let policy = Builder::new(ExponentialBackoff::from_millis(10))
+//!     .max_retries(4)
+//!     .throttle(throttle.clone())
+//!     .build();
+//! ```
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Token counts are kept in thousandths, the precision gRPC requires for `tokenRatio`.
+const MILLI: u64 = 1000;
+
+/// The maximum `maxTokens` allowed by gRPC.
+const MAX_TOKENS: u32 = 1000;
+
+/// A gRPC retry throttle (`maxTokens`/`tokenRatio`), shared between clones.
+#[derive(Debug, Clone)]
+pub struct GrpcRetryThrottle {
+    inner: Arc<Inner>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    max_tokens: u64,
+    token_ratio: u64,
+    tokens: AtomicU64,
+}
+
+impl GrpcRetryThrottle {
+    /// Constructs a throttle with a full bucket of `max_tokens` tokens.
+    ///
+    /// As in gRPC, `max_tokens` is clamped to `1..=1000` and `token_ratio` is rounded
+    /// to three decimal places, with a minimum of `0.001`.
+    pub fn new(max_tokens: u32, token_ratio: f64) -> GrpcRetryThrottle {
+        let max_tokens = u64::from(max_tokens.clamp(1, MAX_TOKENS)) * MILLI;
+        let token_ratio = (token_ratio * MILLI as f64).round();
+        let token_ratio = if token_ratio >= 1.0 {
+            token_ratio as u64
+        } else {
+            1
+        };
+        GrpcRetryThrottle {
+            inner: Arc::new(Inner {
+                max_tokens,
+                token_ratio,
+                tokens: AtomicU64::new(max_tokens),
+            }),
+        }
+    }
+
+    /// Spends a token for a failed attempt.
+    pub fn record_failure(&self) {
+        let _ = self
+            .inner
+            .tokens
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                Some(tokens.saturating_sub(MILLI))
+            });
+    }
+
+    /// Refills `token_ratio` tokens for a successful call, up to `max_tokens`.
+    pub fn record_success(&self) {
+        let inner = &self.inner;
+        let _ = inner
+            .tokens
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |tokens| {
+                Some((tokens + inner.token_ratio).min(inner.max_tokens))
+            });
+    }
+
+    /// Returns `true` while more than half of `max_tokens` are left.
+    pub fn is_retry_allowed(&self) -> bool {
+        self.inner.tokens.load(Ordering::Relaxed) > self.inner.max_tokens / 2
+    }
+
+    /// The number of tokens left.
+    pub fn tokens(&self) -> f64 {
+        self.inner.tokens.load(Ordering::Relaxed) as f64 / MILLI as f64
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttles_below_half_of_max_tokens() {
+        let throttle = GrpcRetryThrottle::new(4, 0.5);
+
+        throttle.record_failure();
+        assert!(throttle.is_retry_allowed());
+        throttle.record_failure();
+        assert!(!throttle.is_retry_allowed());
+        assert_eq!(throttle.tokens(), 2.);
+
+        throttle.record_success();
+        assert!(throttle.is_retry_allowed());
+        assert_eq!(throttle.tokens(), 2.5);
+    }
+
+    #[test]
+    fn refills_up_to_max_tokens() {
+        let throttle = GrpcRetryThrottle::new(2, 1.2345);
+
+        throttle.record_failure();
+        throttle.record_success();
+        assert_eq!(throttle.tokens(), 2.);
+
+        let clone = throttle.clone();
+        clone.record_failure();
+        assert_eq!(throttle.tokens(), 1.);
+    }
+}
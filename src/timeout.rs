@@ -0,0 +1,366 @@
+use std::future::Future;
+use std::iter::IntoIterator;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::task::{ready, Context, Poll};
+
+use pin_project::pin_project;
+use tokio::time::Duration;
+
+use crate::error::{Error as RetryError, TimeoutError};
+use crate::notify::StopReason;
+use crate::strategy::{Clock, Strategy};
+use crate::RetryIf;
+
+use super::action::Action;
+
+/// The timer behind the per-attempt timeouts, the overall deadline and the sleeps of
+/// [`Retry::spawn_with_timeouts_on`](crate::Retry::spawn_with_timeouts_on), so they
+/// also run on runtimes other than tokio, e.g. async-std, smol or wasm.
+///
+/// Only that entry point is driven by a `Timer`: [`Retry`](crate::Retry),
+/// [`RetryIf`](crate::RetryIf) and the other retry loops sleep on `tokio::time`.
+///
+/// Its [`Clock`] measures the deadline, and must advance along with its sleeps.
+pub trait Timer: Clock {
+    /// The future completing once a sleep is over.
+    type Sleep: Future<Output = ()>;
+
+    /// Sleeps for `duration`.
+    fn sleep(&self, duration: Duration) -> Self::Sleep;
+}
+
+/// The default [`Timer`], backed by `tokio::time`, so it follows a paused tokio clock.
+#[derive(Debug, Clone, Copy)]
+pub struct TokioTimer {
+    origin: tokio::time::Instant,
+}
+
+impl TokioTimer {
+    /// Constructs a timer starting at zero now.
+    pub fn new() -> TokioTimer {
+        TokioTimer {
+            origin: tokio::time::Instant::now(),
+        }
+    }
+}
+
+impl Default for TokioTimer {
+    fn default() -> Self {
+        TokioTimer::new()
+    }
+}
+
+impl Clock for TokioTimer {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+impl Timer for TokioTimer {
+    type Sleep = tokio::time::Sleep;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        tokio::time::sleep(duration)
+    }
+}
+
+/// Future resolving with the output of `future`, or with `Err(())` once `sleep` is over.
+#[pin_project]
+struct Timeout<F, S> {
+    #[pin]
+    future: F,
+    #[pin]
+    sleep: S,
+}
+
+impl<F: Future, S: Future<Output = ()>> Future for Timeout<F, S> {
+    type Output = Result<F::Output, ()>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let this = self.project();
+        if let Poll::Ready(output) = this.future.poll(cx) {
+            return Poll::Ready(Ok(output));
+        }
+        this.sleep.poll(cx).map(Err)
+    }
+}
+
+fn timeout<K: Timer, F: Future>(timer: &K, duration: Duration, future: F) -> Timeout<F, K::Sleep> {
+    Timeout {
+        future,
+        sleep: timer.sleep(duration),
+    }
+}
+
+/// What [`Retry::spawn_with_timeouts_policy`](crate::Retry::spawn_with_timeouts_policy)
+/// does when a `retry_after` hint would delay the next attempt past the deadline.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RetryAfterVsDeadline {
+    /// Fails right away with [`TimeoutError::RetryAfterPastDeadline`], carrying the
+    /// hint so callers can propagate it. This is the behavior of
+    /// [`Retry::spawn_with_timeouts`](crate::Retry::spawn_with_timeouts).
+    #[default]
+    FailFast,
+    /// Disregards the hint and retries after the strategy delay, if the next attempt
+    /// still starts before the deadline.
+    UseStrategyDelay,
+}
+
+/// The limits every attempt and delay of a timed retry are checked against.
+#[derive(Clone, Copy)]
+struct Limits {
+    /// The overall deadline, on the clock of the timer.
+    deadline: Duration,
+    per_attempt: Duration,
+    on_retry_after: RetryAfterVsDeadline,
+}
+
+/// What a [`TimedAction`] and its [`UntilDeadline`] strategy tell each other.
+#[derive(Default)]
+struct Shared {
+    /// The delay to sleep before the next attempt, see [`Action::before_retry`].
+    delay: Option<Duration>,
+    /// The `retry_after` hint of the last attempt, unless disregarded.
+    retry_after: Option<Duration>,
+    /// The hint which stopped the loop, as it ends past the deadline.
+    past_deadline: Option<Duration>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// Runs every attempt of an action under a timeout bounded by the deadline, after
+/// sleeping the delay before it on its [`Timer`].
+struct TimedAction<A, K> {
+    action: Arc<Mutex<A>>,
+    timer: Arc<K>,
+    limits: Limits,
+    shared: Arc<Mutex<Shared>>,
+}
+
+impl<A: Action, K: Timer> Action for TimedAction<A, K> {
+    type Item = A::Item;
+    type Error = TimeoutError<A::Error>;
+    type Future = TimedAttempt<A, K>;
+
+    fn run(&mut self) -> Self::Future {
+        let delay = lock(&self.shared).delay.take();
+        TimedAttempt {
+            action: Arc::clone(&self.action),
+            timer: Arc::clone(&self.timer),
+            limits: self.limits,
+            shared: Arc::clone(&self.shared),
+            remaining: Duration::ZERO,
+            delay: delay.map(|delay| self.timer.sleep(delay)),
+            attempt: None,
+        }
+    }
+
+    fn before_retry(&mut self, delay: Duration) {
+        lock(&self.shared).delay = Some(delay);
+        lock(&self.action).before_retry(delay);
+    }
+}
+
+/// Future sleeping the delay, then running an attempt of a [`TimedAction`].
+#[pin_project]
+struct TimedAttempt<A: Action, K: Timer> {
+    action: Arc<Mutex<A>>,
+    timer: Arc<K>,
+    limits: Limits,
+    shared: Arc<Mutex<Shared>>,
+    /// The time left before the deadline when the attempt started.
+    remaining: Duration,
+    #[pin]
+    delay: Option<K::Sleep>,
+    #[pin]
+    attempt: Option<Timeout<A::Future, K::Sleep>>,
+}
+
+impl<A: Action, K: Timer> Future for TimedAttempt<A, K> {
+    type Output = Result<A::Item, RetryError<TimeoutError<A::Error>>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Self::Output> {
+        let mut this = self.project();
+        if let Some(delay) = this.delay.as_mut().as_pin_mut() {
+            ready!(delay.poll(cx));
+            this.delay.set(None);
+        }
+        if this.attempt.is_none() {
+            *this.remaining = this.limits.deadline.saturating_sub(this.timer.now());
+            let limit = this.limits.per_attempt.min(*this.remaining);
+            let attempt = lock(this.action).run();
+            this.attempt
+                .set(Some(timeout(this.timer.as_ref(), limit, attempt)));
+        }
+        let output = ready!(this
+            .attempt
+            .as_pin_mut()
+            .expect("the attempt was just started")
+            .poll(cx));
+
+        let mut shared = lock(this.shared);
+        shared.retry_after = None;
+        Poll::Ready(match output {
+            Ok(Ok(item)) => Ok(item),
+            Ok(Err(err)) => {
+                let mut err = err.map(TimeoutError::Action);
+                if let RetryError::Transient {
+                    retry_after: retry_after @ Some(_),
+                    ..
+                } = &mut err
+                {
+                    let fits = retry_after.is_some_and(|hint| {
+                        this.timer.now().saturating_add(hint) < this.limits.deadline
+                    });
+                    if !fits && this.limits.on_retry_after == RetryAfterVsDeadline::UseStrategyDelay
+                    {
+                        *retry_after = None;
+                    }
+                    shared.retry_after = *retry_after;
+                }
+                Err(err)
+            }
+            Err(_) if this.limits.per_attempt < *this.remaining => {
+                Err(RetryError::transient(TimeoutError::AttemptTimedOut))
+            }
+            Err(_) => Err(RetryError::permanent(TimeoutError::DeadlineExceeded)),
+        })
+    }
+}
+
+/// Strategy ending once the next attempt would start past the deadline.
+struct UntilDeadline<I, K> {
+    iter: I,
+    timer: Arc<K>,
+    limits: Limits,
+    shared: Arc<Mutex<Shared>>,
+    expired: bool,
+}
+
+impl<I: Iterator<Item = Duration>, K: Timer> Iterator for UntilDeadline<I, K> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Duration> {
+        if self.expired {
+            return None;
+        }
+        let delay = self.iter.next()?;
+        let now = self.timer.now();
+        let mut shared = lock(&self.shared);
+        // a hint replaces the delay, and only ends past the deadline when failing fast
+        let delay = match shared.retry_after {
+            Some(hint) if now.saturating_add(hint) >= self.limits.deadline => {
+                shared.past_deadline = Some(hint);
+                None
+            }
+            Some(hint) => Some(hint),
+            None => Some(delay),
+        };
+        let fits = delay.is_some_and(|delay| now.saturating_add(delay) < self.limits.deadline);
+        self.expired = !fits;
+        fits.then_some(delay).flatten()
+    }
+}
+
+impl<I: Strategy, K: Timer> Strategy for UntilDeadline<I, K> {
+    fn stop_reason(&self) -> Option<StopReason> {
+        if self.expired {
+            Some(StopReason::MaxDurationExceeded)
+        } else {
+            self.iter.stop_reason()
+        }
+    }
+
+    fn clamped(&self) -> bool {
+        self.iter.clamped()
+    }
+}
+
+/// Drives `action` with a per-attempt timeout and an overall deadline,
+/// see [`Retry::spawn_with_timeouts`](crate::Retry::spawn_with_timeouts).
+pub(crate) async fn retry_with_timeouts<T, A>(
+    strategy: T,
+    action: A,
+    per_attempt: Duration,
+    overall: Duration,
+    on_retry_after: RetryAfterVsDeadline,
+) -> Result<A::Item, TimeoutError<A::Error>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+{
+    retry_with_timeouts_on(
+        strategy,
+        action,
+        per_attempt,
+        overall,
+        on_retry_after,
+        TokioTimer::new(),
+    )
+    .await
+}
+
+/// Same as [`retry_with_timeouts`], with sleeps and timeouts driven by `timer`.
+///
+/// Runs on a [`RetryIf`], which leaves the sleeps to the [`TimedAction`] so they
+/// don't depend on tokio either.
+pub(crate) async fn retry_with_timeouts_on<T, A, K>(
+    strategy: T,
+    action: A,
+    per_attempt: Duration,
+    overall: Duration,
+    on_retry_after: RetryAfterVsDeadline,
+    timer: K,
+) -> Result<A::Item, TimeoutError<A::Error>>
+where
+    T: IntoIterator<Item = Duration>,
+    T::IntoIter: Strategy,
+    A: Action,
+    K: Timer,
+{
+    let limits = Limits {
+        deadline: timer.now().saturating_add(overall),
+        per_attempt,
+        on_retry_after,
+    };
+    let timer = Arc::new(timer);
+    let shared = Arc::new(Mutex::new(Shared::default()));
+    let strategy = UntilDeadline {
+        iter: strategy.into_iter(),
+        timer: Arc::clone(&timer),
+        limits,
+        shared: Arc::clone(&shared),
+        expired: false,
+    };
+    let action = TimedAction {
+        action: Arc::new(Mutex::new(action)),
+        timer,
+        limits,
+        shared: Arc::clone(&shared),
+    };
+    let result = RetryIf::spawn(
+        strategy,
+        action,
+        (|_| true) as fn(&TimeoutError<A::Error>) -> bool,
+        (|_, _| {}) as fn(&TimeoutError<A::Error>, Duration),
+    )
+    .action_sleeps()
+    .await;
+
+    let past_deadline = lock(&shared).past_deadline;
+    match (result, past_deadline) {
+        (Err(TimeoutError::Action(last_error)), Some(retry_after)) => {
+            Err(TimeoutError::RetryAfterPastDeadline {
+                last_error,
+                retry_after,
+            })
+        }
+        (result, _) => result,
+    }
+}
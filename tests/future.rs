@@ -1,3 +1,5 @@
+#![cfg(feature = "std")]
+
 use std::future;
 use std::iter::Take;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -5,7 +7,7 @@ use std::sync::Arc;
 use std::time::Duration;
 
 use tokio_retry2::strategy::ExponentialBackoff;
-use tokio_retry2::{Retry, RetryError, RetryIf};
+use tokio_retry2::{ErrorHistory, NotReady, Retry, RetryError, RetryIf, TimeoutError};
 
 #[tokio::test]
 async fn attempts_just_once() {
@@ -169,12 +171,1798 @@ async fn notify_retry_after_duration() {
     assert_eq!(res, Ok(()));
 }
 
-fn message_100ms(err: &u64, duration: Duration) {
-    let msg = format!("err: {}, duration: {:?}", err, duration);
-    assert_eq!(msg, "err: 42, duration: 100ms");
+#[tokio::test]
+async fn spawn_some_attempts_until_some() {
+    use tokio_retry2::strategy::FixedInterval;
+    let s = FixedInterval::from_millis(10);
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let future = Retry::spawn_some(s, move || {
+        let previous = cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready((previous >= 2).then_some(previous))
+    });
+    let res = future.await;
+
+    assert_eq!(res, Ok(2));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
 }
 
-fn message(err: &u64, duration: Duration) {
-    let msg = format!("err: {}, duration: {:?}", err, duration);
-    assert_eq!(msg, "err: 42, duration: 0ns");
+#[tokio::test]
+async fn spawn_some_fails_with_not_ready_when_exhausted() {
+    use tokio_retry2::strategy::FixedInterval;
+    let s = FixedInterval::from_millis(10).take(2);
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let future = Retry::spawn_some(s, move || {
+        cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(None::<u64>)
+    });
+    let res = future.await;
+
+    assert_eq!(res, Err(NotReady));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn sleeps_for_retry_after_hint() {
+    use tokio_retry2::strategy::FixedInterval;
+    let s = FixedInterval::from_millis(1).take(1);
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let start = std::time::Instant::now();
+    let future = Retry::spawn(s, move || {
+        let previous = cloned_counter.fetch_add(1, Ordering::SeqCst);
+        if previous < 1 {
+            future::ready(RetryError::to_retry_after(42, Duration::from_millis(100)))
+        } else {
+            future::ready(Ok::<(), RetryError<u64>>(()))
+        }
+    });
+    let res = future.await;
+
+    assert_eq!(res, Ok(()));
+    assert!(start.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+#[cfg(feature = "jitter-std")]
+async fn jitters_retry_after_hint() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+    let s = FixedInterval::from_millis(1).take(3);
+    let notified = Arc::new(Mutex::new(Vec::new()));
+    let cloned_notified = notified.clone();
+    let future = RetryIf::spawn(
+        s,
+        || {
+            future::ready(RetryError::to_retry_after::<()>(
+                42,
+                Duration::from_millis(20),
+            ))
+        },
+        |_: &u64| true,
+        move |_: &u64, d: Duration| cloned_notified.lock().unwrap().push(d),
+    )
+    .jitter_retry_after(0.5, 1.5);
+    let res = future.await;
+
+    assert_eq!(res, Err(42));
+    let notified = notified.lock().unwrap();
+    assert_eq!(notified.len(), 4);
+    assert!(notified
+        .iter()
+        .all(|d| *d >= Duration::from_millis(10) && *d <= Duration::from_millis(30)));
+}
+
+#[tokio::test]
+#[cfg(feature = "test-util")]
+async fn policy_override_replaces_strategy() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::test_util::with_policy_override;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let start = std::time::Instant::now();
+    let res = with_policy_override(FixedInterval::from_millis(0).take(2), async move {
+        Retry::spawn(FixedInterval::from_millis(10_000).take(5), move || {
+            cloned_counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(Err::<(), RetryError<u64>>(RetryError::transient(42)))
+        })
+        .await
+    })
+    .await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    assert!(start.elapsed() < Duration::from_secs(10));
+}
+
+#[tokio::test]
+#[cfg(feature = "test-util")]
+async fn policy_override_drives_the_entry_points_built_on_retry_if() {
+    use tokio_retry2::breaker::CircuitBreaker;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::test_util::with_policy_override;
+    use tokio_retry2::{
+        init_with_retry, poll_until_stable, spawn_mut, with_idempotency, with_reauth,
+        ActionMutFuture, IdempotencyStore,
+    };
+
+    struct Missing;
+
+    impl IdempotencyStore<()> for Missing {
+        async fn completed(&mut self) -> Option<()> {
+            None
+        }
+    }
+
+    let strategy = || FixedInterval::from_millis(0).take(5);
+    let attempts = AtomicUsize::new(0);
+    let failing = || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        future::ready(RetryError::to_transient::<()>(42u64))
+    };
+    let mut borrowed_attempts = 0;
+    with_policy_override(FixedInterval::from_millis(0).take(1), async {
+        let _ = Retry::spawn_notify_async(strategy(), failing, |_: &u64, _| async {}).await;
+        let _ = Retry::spawn_with_history(strategy(), failing, ErrorHistory::unbounded()).await;
+        let _ = Retry::spawn_with_report(strategy(), failing).await;
+        let _ = poll_until_stable(strategy(), failing, 2).await;
+        let per_attempt = Duration::from_secs(1);
+        let _ =
+            Retry::spawn_with_timeouts(strategy(), failing, per_attempt, per_attempt * 10).await;
+        let breaker = CircuitBreaker::new(100, Duration::from_secs(1));
+        let _ = Retry::spawn_with_breaker(strategy(), &breaker, failing).await;
+        let _ = with_reauth(strategy(), failing, |_| false, || future::ready(Ok(()))).await;
+        let _ = with_idempotency(strategy(), failing, Missing).await;
+        let _ = init_with_retry(strategy(), failing).await;
+        let _ = spawn_mut(
+            strategy(),
+            &mut borrowed_attempts,
+            |attempts: &mut usize| -> ActionMutFuture<'_, (), u64> {
+                *attempts += 1;
+                Box::pin(future::ready(RetryError::to_transient(42)))
+            },
+        )
+        .await;
+    })
+    .await;
+
+    // a single retry each, instead of the five of their strategy
+    assert_eq!(attempts.load(Ordering::SeqCst), 9 * 2);
+    assert_eq!(borrowed_attempts, 2);
+}
+
+#[tokio::test]
+async fn notify_attempt_receives_attempt_duration() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::{AttemptInfo, Notify};
+
+    struct Recorder(Arc<Mutex<Vec<Duration>>>);
+
+    impl Notify<u64> for Recorder {
+        fn notify(&mut self, _: &u64, _: Duration) {}
+
+        fn notify_attempt(&mut self, _: &u64, _: Duration, attempt: &AttemptInfo) {
+            self.0.lock().unwrap().push(attempt.attempt_duration);
+        }
+    }
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let future = RetryIf::spawn(
+        FixedInterval::from_millis(1).take(1),
+        || async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err::<(), RetryError<u64>>(RetryError::transient(42))
+        },
+        |_: &u64| true,
+        Recorder(recorded.clone()),
+    );
+    let res = future.await;
+
+    assert_eq!(res, Err(42));
+    let recorded = recorded.lock().unwrap();
+    assert_eq!(recorded.len(), 2);
+    assert!(recorded.iter().all(|d| *d >= Duration::from_millis(20)));
+}
+
+#[tokio::test]
+async fn notify_attempt_receives_delay_source() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::{ExponentialBackoff, MaxRetries};
+    use tokio_retry2::{AttemptInfo, DelaySource, Notify};
+
+    struct Recorder(Arc<Mutex<Vec<DelaySource>>>);
+
+    impl Notify<u64> for Recorder {
+        fn notify(&mut self, _: &u64, _: Duration) {}
+
+        fn notify_attempt(&mut self, _: &u64, _: Duration, attempt: &AttemptInfo) {
+            self.0.lock().unwrap().push(attempt.delay_source);
+        }
+    }
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let future = RetryIf::spawn(
+        ExponentialBackoff::from_millis(2)
+            .max_delay_millis(3)
+            .max_retries(3),
+        move || {
+            let previous = cloned_counter.fetch_add(1, Ordering::SeqCst);
+            if previous == 1 {
+                future::ready(RetryError::to_retry_after(42, Duration::from_millis(1)))
+            } else {
+                future::ready(RetryError::to_transient::<()>(42))
+            }
+        },
+        |_: &u64| true,
+        Recorder(recorded.clone()),
+    );
+    let res = future.await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(
+        *recorded.lock().unwrap(),
+        [
+            DelaySource::Strategy,
+            DelaySource::RetryAfter,
+            DelaySource::Clamped,
+            DelaySource::Strategy,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn spawn_with_timeouts_retries_timed_out_attempts() {
+    use tokio_retry2::strategy::FixedInterval;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let res = Retry::spawn_with_timeouts(
+        FixedInterval::from_millis(1).take(5),
+        move || {
+            let previous = cloned_counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if previous < 2 {
+                    tokio::time::sleep(Duration::from_secs(10)).await;
+                }
+                Ok::<u64, RetryError<u64>>(42)
+            }
+        },
+        Duration::from_millis(20),
+        Duration::from_secs(1),
+    )
+    .await;
+
+    assert_eq!(res, Ok(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn spawn_with_timeouts_stops_at_deadline() {
+    use tokio_retry2::strategy::FixedInterval;
+    let res = Retry::spawn_with_timeouts(
+        FixedInterval::from_millis(1),
+        || async {
+            tokio::time::sleep(Duration::from_secs(10)).await;
+            Ok::<u64, RetryError<u64>>(42)
+        },
+        Duration::from_secs(1),
+        Duration::from_millis(50),
+    )
+    .await;
+
+    assert_eq!(res, Err(TimeoutError::DeadlineExceeded));
+}
+
+/// A timer sleeping on threads, which works without any async runtime.
+struct ThreadTimer {
+    origin: std::time::Instant,
+    sleeps: Arc<AtomicUsize>,
+}
+
+impl tokio_retry2::strategy::Clock for ThreadTimer {
+    fn now(&self) -> Duration {
+        self.origin.elapsed()
+    }
+}
+
+impl tokio_retry2::Timer for ThreadTimer {
+    type Sleep = std::pin::Pin<Box<dyn std::future::Future<Output = ()> + Send>>;
+
+    fn sleep(&self, duration: Duration) -> Self::Sleep {
+        use futures::FutureExt;
+
+        self.sleeps.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            let _ = sender.send(());
+        });
+        Box::pin(receiver.map(|_| ()))
+    }
+}
+
+#[test]
+fn spawn_with_timeouts_on_runs_without_tokio() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let sleeps = Arc::new(AtomicUsize::new(0));
+    let counter = AtomicUsize::new(0);
+    let res = futures::executor::block_on(Retry::spawn_with_timeouts_on(
+        FixedInterval::from_millis(1).take(5),
+        || {
+            let previous = counter.fetch_add(1, Ordering::SeqCst);
+            async move {
+                if previous < 2 {
+                    future::pending::<()>().await;
+                }
+                Ok::<u64, RetryError<u64>>(42)
+            }
+        },
+        Duration::from_millis(20),
+        Duration::from_secs(1),
+        ThreadTimer {
+            origin: std::time::Instant::now(),
+            sleeps: sleeps.clone(),
+        },
+    ));
+
+    assert_eq!(res, Ok(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    // a timeout for every attempt, and a sleep before every retry
+    assert_eq!(sleeps.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn spawn_with_deadline_cancels_the_attempt_in_flight() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    struct Completed(Arc<AtomicUsize>);
+    impl Drop for Completed {
+        fn drop(&mut self) {
+            self.0.fetch_add(1, Ordering::SeqCst);
+        }
+    }
+
+    let dropped = Arc::new(AtomicUsize::new(0));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let (attempts, in_flight) = (counter.clone(), dropped.clone());
+    let start = std::time::Instant::now();
+    let res = Retry::spawn_with_deadline(
+        FixedInterval::from_millis(1),
+        Duration::from_millis(50),
+        move || {
+            let previous = attempts.fetch_add(1, Ordering::SeqCst);
+            let guard = Completed(in_flight.clone());
+            async move {
+                let _guard = guard;
+                match previous {
+                    0 => RetryError::to_transient(42),
+                    _ => {
+                        tokio::time::sleep(Duration::from_secs(10)).await;
+                        Ok::<u64, RetryError<u64>>(1)
+                    }
+                }
+            }
+        },
+    )
+    .await;
+
+    assert_eq!(res, Err(TimeoutError::DeadlineExceeded));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert_eq!(dropped.load(Ordering::SeqCst), 2);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn spawn_with_time_left_passes_the_time_left() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::AttemptContext;
+
+    let contexts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = contexts.clone();
+    let res = Retry::spawn_with_time_left(
+        FixedInterval::from_millis(20),
+        Duration::from_secs(1),
+        move |ctx: AttemptContext| {
+            recorder.lock().unwrap().push(ctx);
+            future::ready(if ctx.attempt < 3 {
+                RetryError::to_transient(42)
+            } else {
+                Ok::<u32, RetryError<u64>>(ctx.attempt)
+            })
+        },
+    )
+    .await;
+
+    assert_eq!(res, Ok(3));
+    let contexts = contexts.lock().unwrap();
+    assert_eq!(contexts.len(), 3);
+    assert!(contexts[0].remaining <= Duration::from_secs(1));
+    assert!(contexts[1].remaining <= contexts[0].remaining - Duration::from_millis(20));
+    assert!(contexts[2].remaining <= contexts[1].remaining - Duration::from_millis(20));
+}
+
+#[tokio::test(flavor = "current_thread", start_paused = true)]
+async fn interleaved_loops_see_their_own_last_delay() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::RetryContext;
+
+    let retry_every = |millis| {
+        Retry::spawn_with_context(
+            FixedInterval::from_millis(millis).take(3),
+            |ctx: RetryContext| {
+                future::ready(match ctx.last_delay {
+                    Some(delay) if ctx.attempt == 4 => Ok::<_, RetryError<()>>(delay),
+                    _ => RetryError::to_transient(()),
+                })
+            },
+        )
+    };
+
+    let (fast, slow) = tokio::join!(retry_every(10), retry_every(15));
+    assert_eq!(fast, Ok(Duration::from_millis(10)));
+    assert_eq!(slow, Ok(Duration::from_millis(15)));
+}
+
+#[tokio::test]
+async fn spawn_with_context_passes_the_attempt_details() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::RetryContext;
+
+    let contexts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = contexts.clone();
+    let res =
+        Retry::spawn_with_context(FixedInterval::from_millis(20), move |ctx: RetryContext| {
+            recorder.lock().unwrap().push(ctx);
+            future::ready(if ctx.attempt < 3 {
+                RetryError::to_transient(42)
+            } else {
+                Ok::<u32, RetryError<u64>>(ctx.attempt)
+            })
+        })
+        .await;
+
+    assert_eq!(res, Ok(3));
+    let contexts = contexts.lock().unwrap();
+    let attempts: Vec<_> = contexts.iter().map(|ctx| ctx.attempt).collect();
+    let delays: Vec<_> = contexts.iter().map(|ctx| ctx.last_delay).collect();
+    assert_eq!(attempts, vec![1, 2, 3]);
+    assert_eq!(
+        delays,
+        vec![
+            None,
+            Some(Duration::from_millis(20)),
+            Some(Duration::from_millis(20))
+        ]
+    );
+    assert_eq!(contexts[0].elapsed, Duration::ZERO);
+    assert!(contexts[2].elapsed >= Duration::from_millis(40));
+}
+
+#[tokio::test]
+async fn spawn_notify_async_awaits_the_hook_before_retrying() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+
+    let (sender, mut receiver) = tokio::sync::mpsc::unbounded_channel();
+    let res = Retry::spawn_notify_async(
+        FixedInterval::from_millis(1).max_retries(2),
+        || future::ready(RetryError::to_transient::<()>(42u64)),
+        |err: &u64, delay: Duration| {
+            let (sender, err) = (sender.clone(), *err);
+            async move {
+                tokio::task::yield_now().await;
+                sender.send((err, delay)).unwrap();
+            }
+        },
+    )
+    .await;
+    drop(sender);
+
+    assert_eq!(res, Err(42));
+    let mut notified = Vec::new();
+    while let Some(event) = receiver.recv().await {
+        notified.push(event);
+    }
+    assert_eq!(notified, vec![(42, Duration::from_millis(1)); 2]);
+}
+
+#[tokio::test]
+async fn spawn_with_timeouts_does_not_sleep_past_deadline() {
+    use tokio_retry2::strategy::FixedInterval;
+    let start = std::time::Instant::now();
+    let res = Retry::spawn_with_timeouts(
+        FixedInterval::from_millis(10_000),
+        || future::ready(RetryError::to_transient::<()>(42)),
+        Duration::from_millis(10),
+        Duration::from_millis(100),
+    )
+    .await;
+
+    assert_eq!(res, Err(TimeoutError::Action(42)));
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn spawn_with_timeouts_fails_fast_on_retry_after_past_deadline() {
+    use tokio_retry2::strategy::FixedInterval;
+    let start = std::time::Instant::now();
+    let res = Retry::spawn_with_timeouts(
+        FixedInterval::from_millis(1),
+        || {
+            future::ready(RetryError::to_retry_after::<()>(
+                42,
+                Duration::from_secs(60),
+            ))
+        },
+        Duration::from_millis(10),
+        Duration::from_secs(10),
+    )
+    .await;
+
+    assert_eq!(
+        res,
+        Err(TimeoutError::RetryAfterPastDeadline {
+            last_error: 42,
+            retry_after: Duration::from_secs(60),
+        })
+    );
+    assert_eq!(
+        res.unwrap_err().retry_after(),
+        Some(Duration::from_secs(60))
+    );
+    assert!(start.elapsed() < Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn spawn_with_timeouts_policy_can_use_strategy_delay() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::RetryAfterVsDeadline;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let res = Retry::spawn_with_timeouts_policy(
+        FixedInterval::from_millis(1),
+        move || {
+            if cloned_counter.fetch_add(1, Ordering::SeqCst) < 2 {
+                future::ready(RetryError::to_retry_after(42, Duration::from_secs(60)))
+            } else {
+                future::ready(Ok(7))
+            }
+        },
+        Duration::from_millis(10),
+        Duration::from_secs(10),
+        RetryAfterVsDeadline::UseStrategyDelay,
+    )
+    .await;
+
+    assert_eq!(res, Ok(7));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn spawn_with_history_samples_errors() {
+    use tokio_retry2::strategy::FixedInterval;
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let res = Retry::spawn_with_history(
+        FixedInterval::from_millis(0).take(9),
+        move || {
+            let previous = cloned_counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(RetryError::to_transient::<()>(previous))
+        },
+        ErrorHistory::sampled(2),
+    )
+    .await;
+
+    let history = res.unwrap_err();
+    assert_eq!(history.first(), &[0, 1]);
+    assert_eq!(history.last().copied().collect::<Vec<_>>(), vec![8, 9]);
+    assert_eq!(history.dropped(), 6);
+}
+
+#[tokio::test]
+async fn spawn_counted_returns_attempts_with_error() {
+    use tokio_retry2::strategy::FixedInterval;
+    let res = Retry::spawn_counted(FixedInterval::from_millis(1).take(2), || {
+        future::ready(RetryError::to_transient::<()>(42))
+    })
+    .await;
+
+    assert_eq!(res, Err((42, 3)));
+}
+
+#[tokio::test]
+async fn spawn_counted_counts_permanent_errors() {
+    let res = Retry::spawn_counted(ExponentialBackoff::from_millis(10).take(3), || {
+        future::ready(RetryError::to_permanent::<()>(42))
+    })
+    .await;
+
+    assert_eq!(res, Err((42, 1)));
+}
+
+#[tokio::test]
+async fn with_stop_reason_returns_why_the_loop_stopped() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries, RetryBudget};
+    use tokio_retry2::StopReason;
+
+    let transient = || future::ready(RetryError::to_transient::<()>(42));
+    let res = Retry::spawn(FixedInterval::from_millis(1).max_retries(2), transient)
+        .with_stop_reason()
+        .await;
+    assert_eq!(res, Err((42, StopReason::MaxRetriesExceeded)));
+
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(2), transient)
+        .retry_budget(RetryBudget::new(0))
+        .with_stop_reason()
+        .await;
+    assert_eq!(res, Err((42, StopReason::BudgetExhausted)));
+
+    let res = RetryIf::spawn(
+        FixedInterval::from_millis(1),
+        || future::ready(RetryError::to_permanent::<()>(7)),
+        |_: &u64| true,
+        |_: &u64, _| {},
+    )
+    .with_stop_reason()
+    .await;
+    assert_eq!(res, Err((7, StopReason::PermanentError)));
+
+    let res = Retry::spawn(FixedInterval::from_millis(1), || {
+        future::ready(Ok::<_, RetryError<()>>(1))
+    })
+    .with_stop_reason()
+    .await;
+    assert_eq!(res, Ok(1));
+}
+
+#[tokio::test]
+async fn strategies_report_through_the_strategy_trait() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries, Offset, Strategy};
+    use tokio_retry2::StopReason;
+
+    /// A user strategy stopping once the throttle it watches disallows retries.
+    struct Throttle(usize);
+
+    impl Iterator for Throttle {
+        type Item = Duration;
+
+        fn next(&mut self) -> Option<Duration> {
+            self.0 = self.0.checked_sub(1)?;
+            Some(Duration::from_millis(1))
+        }
+    }
+
+    impl Strategy for Throttle {
+        fn stop_reason(&self) -> Option<StopReason> {
+            Some(StopReason::Throttled)
+        }
+    }
+
+    let transient = || future::ready(RetryError::to_transient::<()>(42));
+    let res = Retry::spawn(Throttle(2), transient)
+        .with_stop_reason()
+        .await;
+    assert_eq!(res, Err((42, StopReason::Throttled)));
+
+    // forwarded by the wrappers of the crate, unless the user strategy stopped first
+    let res = Retry::spawn(Throttle(2).offset(Duration::ZERO).max_retries(5), transient)
+        .with_stop_reason()
+        .await;
+    assert_eq!(res, Err((42, StopReason::Throttled)));
+
+    // the adapters of `Iterator` can't see the strategy they wrap
+    let res = Retry::spawn(
+        FixedInterval::from_millis(1).max_retries(2).map(|d| d * 2),
+        transient,
+    )
+    .with_stop_reason()
+    .await;
+    assert_eq!(res, Err((42, StopReason::StrategyExhausted)));
+}
+
+#[tokio::test]
+async fn on_success_and_on_exhausted_report_the_outcome() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let succeeded = Arc::new(Mutex::new(None));
+    let counter = AtomicUsize::new(0);
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(3), || {
+        if counter.fetch_add(1, Ordering::SeqCst) < 2 {
+            future::ready(RetryError::to_transient(42u64))
+        } else {
+            future::ready(Ok(7))
+        }
+    })
+    .on_success({
+        let succeeded = succeeded.clone();
+        move |item: &u64, attempts, elapsed| {
+            *succeeded.lock().unwrap() = Some((*item, attempts, elapsed))
+        }
+    })
+    .on_exhausted(|_, _| panic!("the retry succeeded"))
+    .await;
+    assert_eq!(res, Ok(7));
+    let (item, attempts, elapsed) = succeeded.lock().unwrap().unwrap();
+    assert_eq!((item, attempts), (7, 3));
+    assert!(elapsed >= Duration::from_millis(2));
+
+    let exhausted = Arc::new(Mutex::new(None));
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(2), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .on_success(|_, _, _| panic!("the retry failed"))
+    .on_exhausted({
+        let exhausted = exhausted.clone();
+        move |err: &u64, attempts| *exhausted.lock().unwrap() = Some((*err, attempts))
+    })
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(*exhausted.lock().unwrap(), Some((42, 3)));
+}
+
+#[tokio::test]
+async fn every_loop_calls_on_success_and_on_exhausted() {
+    use std::sync::Mutex;
+    use tokio_retry2::breaker::CircuitBreaker;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::{
+        init_with_retry, poll_until_stable, spawn_mut, with_idempotency, with_reauth,
+        ActionMutFuture, IdempotencyStore,
+    };
+
+    struct Missing;
+
+    impl IdempotencyStore<()> for Missing {
+        async fn completed(&mut self) -> Option<()> {
+            None
+        }
+    }
+
+    type Log = Arc<Mutex<Vec<(&'static str, u32)>>>;
+
+    fn record<E>(log: &Log, name: &'static str) -> impl FnOnce(&E, u32) + Send + Sync {
+        let log = log.clone();
+        move |_, attempts| log.lock().unwrap().push((name, attempts))
+    }
+
+    let exhausted = Log::default();
+    let strategy = || FixedInterval::from_millis(0).take(2);
+    let failing = || future::ready(RetryError::to_transient::<()>(42u64));
+
+    let _ = Retry::spawn_notify_async(strategy(), failing, |_: &u64, _| async {})
+        .on_exhausted(record(&exhausted, "notify_async"))
+        .await;
+    let _ = Retry::spawn_with_history(strategy(), failing, ErrorHistory::unbounded())
+        .on_exhausted(record(&exhausted, "history"))
+        .await;
+    let _ = Retry::spawn_with_report(strategy(), failing)
+        .on_exhausted(record(&exhausted, "report"))
+        .await;
+    let _ = poll_until_stable(strategy(), failing, 2)
+        .on_exhausted(record(&exhausted, "stable"))
+        .await;
+    let breaker = CircuitBreaker::new(100, Duration::from_secs(1));
+    let _ = Retry::spawn_with_breaker(strategy(), &breaker, failing)
+        .on_exhausted(record(&exhausted, "breaker"))
+        .await;
+    let per_attempt = Duration::from_secs(1);
+    let _ = Retry::spawn_with_timeouts(strategy(), failing, per_attempt, per_attempt * 10)
+        .on_exhausted(record(&exhausted, "timeouts"))
+        .await;
+    let _ = with_idempotency(strategy(), failing, Missing)
+        .on_exhausted(record(&exhausted, "idempotency"))
+        .await;
+    let _ = with_reauth(strategy(), failing, |_| false, || future::ready(Ok(())))
+        .on_exhausted(record(&exhausted, "reauth"))
+        .await;
+    let _ = init_with_retry(strategy(), failing)
+        .on_exhausted(record(&exhausted, "startup"))
+        .await;
+    let mut target = ();
+    let _ = spawn_mut(
+        strategy(),
+        &mut target,
+        |_: &mut ()| -> ActionMutFuture<'_, (), u64> {
+            Box::pin(future::ready(RetryError::to_transient(42)))
+        },
+    )
+    .on_exhausted(record(&exhausted, "mutable"))
+    .await;
+
+    let names = [
+        "notify_async",
+        "history",
+        "report",
+        "stable",
+        "breaker",
+        "timeouts",
+        "idempotency",
+        "reauth",
+        "startup",
+        "mutable",
+    ];
+    assert_eq!(*exhausted.lock().unwrap(), names.map(|name| (name, 3)));
+
+    let succeeded = Arc::new(AtomicUsize::new(0));
+    let res = with_reauth(
+        strategy(),
+        || future::ready(Ok::<_, RetryError<()>>(1)),
+        |_| false,
+        || future::ready(Ok(())),
+    )
+    .on_success({
+        let succeeded = succeeded.clone();
+        move |_, attempts, _| succeeded.store(attempts as usize, Ordering::SeqCst)
+    })
+    .await;
+    assert_eq!(res, Ok(1));
+    assert_eq!(succeeded.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn condition_can_veto_retry_after_hint() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::{AttemptInfo, Condition, Notify};
+
+    /// Only honors hints for even errors.
+    struct EvenHints;
+
+    impl Condition<u64> for EvenHints {
+        fn should_retry(&mut self, _: &u64) -> bool {
+            true
+        }
+
+        fn retry_after(&mut self, err: &u64, retry_after: Option<Duration>) -> Option<Duration> {
+            retry_after.filter(|_| err.is_multiple_of(2))
+        }
+    }
+
+    struct Recorder(Arc<Mutex<Vec<Option<Duration>>>>);
+
+    impl Notify<u64> for Recorder {
+        fn notify(&mut self, _: &u64, _: Duration) {}
+
+        fn notify_attempt(&mut self, _: &u64, _: Duration, attempt: &AttemptInfo) {
+            self.0.lock().unwrap().push(attempt.retry_after);
+        }
+    }
+
+    let recorded = Arc::new(Mutex::new(Vec::new()));
+    let counter = AtomicUsize::new(0);
+    let res = RetryIf::spawn(
+        FixedInterval::from_millis(1).take(2),
+        || {
+            let err = counter.fetch_add(1, Ordering::SeqCst) as u64;
+            future::ready(RetryError::to_retry_after::<()>(
+                err,
+                Duration::from_millis(5),
+            ))
+        },
+        EvenHints,
+        Recorder(recorded.clone()),
+    )
+    .await;
+
+    assert_eq!(res, Err(2));
+    assert_eq!(
+        *recorded.lock().unwrap(),
+        vec![
+            Some(Duration::from_millis(5)),
+            None,
+            Some(Duration::from_millis(5))
+        ]
+    );
+}
+
+#[tokio::test]
+#[cfg(feature = "rt")]
+async fn spawn_scoped_is_cancelled_with_its_scope() {
+    use tokio::task::JoinSet;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let mut scope = JoinSet::new();
+    let cloned_counter = counter.clone();
+    Retry::spawn_scoped(
+        &mut scope,
+        FixedInterval::from_millis(1).take(2),
+        move || {
+            cloned_counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(RetryError::to_transient::<()>(42u64))
+        },
+    );
+    assert_eq!(scope.join_next().await.unwrap().unwrap(), Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+
+    let cloned_counter = counter.clone();
+    Retry::spawn_scoped(&mut scope, FixedInterval::from_millis(1000), move || {
+        cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(RetryError::to_transient::<()>(42u64))
+    });
+    tokio::time::sleep(Duration::from_millis(10)).await;
+    scope.shutdown().await;
+
+    assert!(scope.is_empty());
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+}
+
+struct StopRecorder(Arc<std::sync::Mutex<Vec<tokio_retry2::StopReason>>>);
+
+impl tokio_retry2::Notify<u64> for StopRecorder {
+    fn notify(&mut self, _: &u64, _: Duration) {}
+
+    fn notify_stop(&mut self, reason: tokio_retry2::StopReason) {
+        self.0.lock().unwrap().push(reason);
+    }
+}
+
+#[tokio::test]
+async fn notify_stop_reports_reason() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+    use tokio_retry2::StopReason;
+
+    let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let transient = || future::ready(RetryError::to_transient::<()>(42u64));
+
+    let _ = RetryIf::spawn(
+        FixedInterval::from_millis(1).max_retries(2),
+        transient,
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    )
+    .await;
+    let _ = RetryIf::spawn(
+        FixedInterval::from_millis(1).take(2),
+        transient,
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    )
+    .await;
+    let _ = RetryIf::spawn(
+        FixedInterval::from_millis(1),
+        transient,
+        |_: &u64| false,
+        StopRecorder(recorded.clone()),
+    )
+    .await;
+    let _ = RetryIf::spawn(
+        FixedInterval::from_millis(1).take(2),
+        || future::ready(RetryError::to_permanent::<()>(42u64)),
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    )
+    .await;
+    let _ = RetryIf::spawn(
+        FixedInterval::from_millis(1).take(2),
+        || future::ready(Ok::<(), RetryError<u64>>(())),
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    )
+    .await;
+
+    assert_eq!(
+        *recorded.lock().unwrap(),
+        vec![
+            StopReason::MaxRetriesExceeded,
+            StopReason::StrategyExhausted,
+            StopReason::ConditionRejected,
+            StopReason::PermanentError,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn notify_stop_reports_cancellation() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::StopReason;
+
+    let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let future = RetryIf::spawn(
+        FixedInterval::from_millis(1000),
+        || future::ready(RetryError::to_transient::<()>(42u64)),
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    );
+    let res = tokio::time::timeout(Duration::from_millis(10), future).await;
+
+    assert!(res.is_err());
+    assert_eq!(*recorded.lock().unwrap(), vec![StopReason::Cancelled]);
+}
+
+#[tokio::test]
+async fn retry_budget_is_shared_and_refilled_by_successes() {
+    use tokio_retry2::strategy::{FixedInterval, RetryBudget};
+    use tokio_retry2::StopReason;
+
+    let budget = RetryBudget::new(2).refill_per_successes(1);
+    let recorded = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let counter = Arc::new(AtomicUsize::new(0));
+    let transient = || {
+        let counter = counter.clone();
+        move || {
+            counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(RetryError::to_transient::<()>(42u64))
+        }
+    };
+
+    let res = RetryIf::spawn(
+        FixedInterval::from_millis(1),
+        transient(),
+        |_: &u64| true,
+        StopRecorder(recorded.clone()),
+    )
+    .retry_budget(budget.clone())
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+    assert_eq!(*recorded.lock().unwrap(), vec![StopReason::BudgetExhausted]);
+
+    // a success makes room for one more retry
+    let res = Retry::spawn(FixedInterval::from_millis(1), || {
+        future::ready(Ok::<(), RetryError<u64>>(()))
+    })
+    .retry_budget(budget.clone())
+    .await;
+    assert!(res.is_ok());
+    let _ = Retry::spawn(FixedInterval::from_millis(1), transient())
+        .retry_budget(budget.clone())
+        .await;
+    assert_eq!(counter.load(Ordering::SeqCst), 5);
+    assert_eq!(budget.metrics().successes, 1);
+}
+
+#[tokio::test]
+async fn spawn_with_delay_fn_computes_delays_from_errors() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+
+    let planned = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = planned.clone();
+    let started = std::time::Instant::now();
+    // the error asks for a 5s delay, capped at 30ms
+    let res = Retry::spawn_with_delay_fn(
+        FixedInterval::from_millis(1).max_retries(2),
+        || future::ready(RetryError::to_transient::<()>(5_000u64)),
+        move |err: &u64, delay| {
+            recorder.lock().unwrap().push(delay);
+            Duration::from_millis(*err)
+        },
+        Duration::from_millis(30),
+    )
+    .await;
+    let elapsed = started.elapsed();
+
+    assert_eq!(res, Err(5_000));
+    assert_eq!(*planned.lock().unwrap(), vec![Duration::from_millis(1); 2]);
+    assert!(elapsed >= Duration::from_millis(60));
+    assert!(elapsed < Duration::from_secs(2));
+}
+
+#[tokio::test(start_paused = true)]
+async fn delay_fn_is_not_capped_by_the_strategy() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+
+    let started = tokio::time::Instant::now();
+    // the strategy caps its own delays at 1s, before `map` scales them to 5s
+    let res = Retry::spawn_with_delay_fn(
+        FixedInterval::from_millis(500)
+            .max_delay(Duration::from_secs(1))
+            .map(|delay| delay * 10)
+            .max_retries(1),
+        || future::ready(RetryError::to_transient::<()>(())),
+        |_: &(), delay| delay,
+        Duration::from_secs(60),
+    )
+    .await;
+
+    assert_eq!(res, Err(()));
+    assert_eq!(started.elapsed(), Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn on_cancelled_receives_the_last_error() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let cancelled = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = cancelled.clone();
+    let future = Retry::spawn(FixedInterval::from_millis(1000), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .on_cancelled(move |err: Option<&u64>| recorder.lock().unwrap().push(err.copied()));
+    let res = tokio::time::timeout(Duration::from_millis(10), future).await;
+    assert!(res.is_err());
+
+    // the first attempt is still running
+    let recorder = cancelled.clone();
+    let future = Retry::spawn(FixedInterval::from_millis(1000), || {
+        future::pending::<Result<(), RetryError<u64>>>()
+    })
+    .on_cancelled(move |err: Option<&u64>| recorder.lock().unwrap().push(err.copied()));
+    let res = tokio::time::timeout(Duration::from_millis(10), future).await;
+    assert!(res.is_err());
+
+    assert_eq!(*cancelled.lock().unwrap(), vec![Some(42), None]);
+}
+
+#[tokio::test]
+async fn on_retry_reports_the_delays_slept() {
+    use tokio_retry2::policy::Builder;
+    use tokio_retry2::strategy::RetryBudget;
+
+    let retries = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = retries.clone();
+    let policy = Builder::fixed(5).max_retries(5);
+    let counter = AtomicUsize::new(0);
+    let res = Retry::spawn(&policy, || {
+        future::ready(match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => RetryError::to_retry_after::<()>(42u64, Duration::from_millis(1)),
+            _ => RetryError::to_transient(42u64),
+        })
+    })
+    // the third retry is refused by the budget
+    .retry_budget(RetryBudget::new(2))
+    .on_retry(move |retry, delay| recorder.lock().unwrap().push((retry, delay)))
+    .await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(
+        *retries.lock().unwrap(),
+        vec![(1, Duration::from_millis(1)), (2, Duration::from_millis(5))]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn on_success_receives_the_successful_result() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let succeeded = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let recorder = succeeded.clone();
+    let counter = Arc::new(AtomicUsize::new(0));
+    let res = Retry::spawn(FixedInterval::from_millis(5), move || {
+        let attempt = counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(if attempt < 2 {
+            RetryError::to_transient(())
+        } else {
+            Ok(format!("replica-{attempt}"))
+        })
+    })
+    .on_success(move |replica: &String, attempts, elapsed| {
+        recorder
+            .lock()
+            .unwrap()
+            .push((replica.clone(), attempts, elapsed))
+    })
+    .await;
+    assert_eq!(res.as_deref(), Ok("replica-2"));
+
+    let recorder = succeeded.clone();
+    let res = Retry::spawn(FixedInterval::from_millis(5), || {
+        future::ready(Ok::<_, RetryError<()>>("replica-0".to_owned()))
+    })
+    .on_success(move |replica: &String, attempts, elapsed| {
+        recorder
+            .lock()
+            .unwrap()
+            .push((replica.clone(), attempts, elapsed))
+    })
+    .await;
+    assert!(res.is_ok());
+
+    assert_eq!(
+        *succeeded.lock().unwrap(),
+        vec![
+            ("replica-2".to_owned(), 3, Duration::from_millis(10)),
+            ("replica-0".to_owned(), 1, Duration::ZERO)
+        ]
+    );
+}
+
+#[tokio::test(start_paused = true)]
+async fn with_hooks_times_the_loop_from_its_first_poll() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let succeeded = Arc::new(std::sync::Mutex::new(None));
+    let counter = AtomicUsize::new(0);
+    let retry = Retry::spawn_with_history(
+        FixedInterval::from_millis(5),
+        || {
+            future::ready(if counter.fetch_add(1, Ordering::SeqCst) < 1 {
+                RetryError::to_transient(42u64)
+            } else {
+                Ok(7)
+            })
+        },
+        ErrorHistory::unbounded(),
+    )
+    .on_success({
+        let succeeded = succeeded.clone();
+        move |item: &u64, attempts, elapsed| {
+            *succeeded.lock().unwrap() = Some((*item, attempts, elapsed))
+        }
+    });
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(retry.await.ok(), Some(7));
+    assert_eq!(
+        *succeeded.lock().unwrap(),
+        Some((7, 2, Duration::from_millis(5)))
+    );
+}
+
+#[tokio::test]
+async fn cancel_grace_lets_the_attempt_in_flight_complete() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let completed = Arc::new(AtomicUsize::new(0));
+    let action = |completed: Arc<AtomicUsize>| {
+        move || {
+            let completed = completed.clone();
+            async move {
+                tokio::time::sleep(Duration::from_millis(50)).await;
+                completed.fetch_add(1, Ordering::SeqCst);
+                Ok::<(), RetryError<u64>>(())
+            }
+        }
+    };
+
+    let future = Retry::spawn(FixedInterval::from_millis(10), action(completed.clone()))
+        .cancel_grace(Duration::from_secs(1));
+    assert!(tokio::time::timeout(Duration::from_millis(10), future)
+        .await
+        .is_err());
+    // without a grace period, the attempt is dropped mid-way
+    let future = Retry::spawn(FixedInterval::from_millis(10), action(completed.clone()));
+    assert!(tokio::time::timeout(Duration::from_millis(10), future)
+        .await
+        .is_err());
+
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(completed.load(Ordering::SeqCst), 1);
+}
+
+fn message_100ms(err: &u64, duration: Duration) {
+    let msg = format!("err: {}, duration: {:?}", err, duration);
+    assert_eq!(msg, "err: 42, duration: 100ms");
+}
+
+fn message(err: &u64, duration: Duration) {
+    let msg = format!("err: {}, duration: {:?}", err, duration);
+    assert_eq!(msg, "err: 42, duration: 0ns");
+}
+
+#[tokio::test]
+async fn first_attempt_policy_skips_empty_strategies() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::FirstAttemptPolicy;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let action = move || {
+        cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(RetryError::to_transient::<()>(42u64))
+    };
+
+    let skipped = Retry::spawn_with_first_attempt(
+        std::iter::empty(),
+        action.clone(),
+        FirstAttemptPolicy::OnlyIfStrategyNonEmpty,
+    );
+    assert!(skipped.is_none());
+    assert_eq!(counter.load(Ordering::SeqCst), 0);
+
+    let res = Retry::spawn_with_first_attempt(
+        std::iter::empty(),
+        action.clone(),
+        FirstAttemptPolicy::Always,
+    )
+    .unwrap()
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    let res = Retry::spawn_with_first_attempt(
+        FixedInterval::from_millis(1).take(2),
+        action,
+        FirstAttemptPolicy::OnlyIfStrategyNonEmpty,
+    )
+    .unwrap()
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn paced_retries_wait_for_a_slot() {
+    use tokio::time::Instant;
+    use tokio_retry2::pacer::Pacer;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let pacer = Pacer::from_interval(Duration::from_millis(30));
+    let start = Instant::now();
+    let retries = (0..3).map(|_| {
+        Retry::spawn(FixedInterval::from_millis(10).take(1), || {
+            future::ready(RetryError::to_transient::<()>(42u64))
+        })
+        .paced(pacer.clone())
+    });
+    futures::future::join_all(retries).await;
+
+    // the retries run 30ms apart once their 10ms back-off is over
+    assert!(start.elapsed() >= Duration::from_millis(70));
+}
+
+#[tokio::test]
+async fn load_gate_postpones_retries() {
+    use tokio::time::Instant;
+    use tokio_retry2::load_gate::{GateDecision, LoadGate};
+    use tokio_retry2::strategy::FixedInterval;
+
+    let checks = Arc::new(AtomicUsize::new(0));
+    let cloned = checks.clone();
+    let gate = LoadGate::new(move || {
+        // overloaded for the first two checks
+        if cloned.fetch_add(1, Ordering::SeqCst) < 2 {
+            GateDecision::Postpone(Duration::from_millis(30))
+        } else {
+            GateDecision::Proceed
+        }
+    });
+    let attempts = Arc::new(AtomicUsize::new(0));
+    let start = Instant::now();
+    let res = Retry::spawn(FixedInterval::from_millis(10).take(2), || {
+        attempts.fetch_add(1, Ordering::SeqCst);
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .load_gated(gate)
+    .await;
+
+    assert_eq!(res, Err(42));
+    // postponing doesn't count as an attempt
+    assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    assert_eq!(checks.load(Ordering::SeqCst), 4);
+    assert!(start.elapsed() >= Duration::from_millis(80));
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn zero_delay_retries_yield_once_out_of_budget() {
+    use tokio_retry2::strategy::FixedInterval;
+
+    let ticks = Arc::new(AtomicUsize::new(0));
+    let cloned_ticks = ticks.clone();
+    let ticker = tokio::spawn(async move {
+        loop {
+            cloned_ticks.fetch_add(1, Ordering::SeqCst);
+            tokio::task::yield_now().await;
+        }
+    });
+    tokio::task::yield_now().await;
+
+    let ticks_at_start = ticks.load(Ordering::SeqCst);
+    let res = Retry::spawn(FixedInterval::from_millis(0).take(1_000), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .await;
+    let ticks_during_retry = ticks.load(Ordering::SeqCst) - ticks_at_start;
+    ticker.abort();
+
+    assert_eq!(res, Err(42));
+    // the default budget of 128 units forces a yield every 128 attempts at most
+    assert!(ticks_during_retry >= 1_000 / 128);
+
+    // without a budget, nothing else runs until the retry is over
+    let ticks_at_start = ticks.load(Ordering::SeqCst);
+    let res = tokio::task::coop::unconstrained(Retry::spawn(
+        FixedInterval::from_millis(0).take(1_000),
+        || future::ready(RetryError::to_transient::<()>(42u64)),
+    ))
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(ticks.load(Ordering::SeqCst), ticks_at_start);
+}
+
+#[tokio::test]
+async fn zero_delay_retries_skip_the_timer() {
+    use tokio::time::Instant;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let start = Instant::now();
+    let res = Retry::spawn(FixedInterval::from_millis(0).take(1_000), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .await;
+
+    assert_eq!(res, Err(42));
+    assert!(start.elapsed() < Duration::from_millis(500));
+}
+
+#[tokio::test]
+async fn trigger_cuts_the_back_off_short() {
+    use tokio::sync::Notify;
+    use tokio::time::Instant;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let trigger = Arc::new(Notify::new());
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let start = Instant::now();
+    let retry = Retry::spawn(FixedInterval::from_millis(10_000).take(1), move || {
+        if cloned_counter.fetch_add(1, Ordering::SeqCst) == 0 {
+            future::ready(RetryError::to_transient(42u64))
+        } else {
+            future::ready(Ok(()))
+        }
+    })
+    .triggered_by(trigger.clone());
+
+    let cloned_trigger = trigger.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        cloned_trigger.notify_one();
+    });
+
+    assert_eq!(retry.await, Ok(()));
+    assert_eq!(counter.load(Ordering::SeqCst), 2);
+    assert!(start.elapsed() < Duration::from_secs(1));
+}
+
+#[tokio::test]
+async fn compensate_overrun_keeps_attempt_cadence() {
+    use tokio::time::Instant;
+    use tokio_retry2::strategy::FixedInterval;
+
+    let starts = Arc::new(std::sync::Mutex::new(Vec::new()));
+    let cloned_starts = starts.clone();
+    let res = Retry::spawn(FixedInterval::from_millis(100).take(2), move || {
+        cloned_starts.lock().unwrap().push(Instant::now());
+        async {
+            tokio::time::sleep(Duration::from_millis(80)).await;
+            RetryError::to_transient::<()>(42u64)
+        }
+    })
+    .compensate_overrun(Duration::from_millis(5))
+    .await;
+
+    assert_eq!(res, Err(42));
+    let starts = starts.lock().unwrap();
+    // attempts start about 100ms apart instead of 180ms
+    for pair in starts.windows(2) {
+        let gap = pair[1] - pair[0];
+        assert!(gap >= Duration::from_millis(95), "{gap:?}");
+        assert!(gap < Duration::from_millis(170), "{gap:?}");
+    }
+}
+
+#[tokio::test]
+async fn spawn_map_classifies_plain_errors() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::RetryClass;
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let res = Retry::spawn_map(
+        FixedInterval::from_millis(1).take(5),
+        move || {
+            let attempt = cloned_counter.fetch_add(1, Ordering::SeqCst);
+            future::ready(Err::<(), _>(attempt))
+        },
+        |attempt: &usize| {
+            if *attempt < 2 {
+                RetryClass::Transient
+            } else {
+                RetryClass::Permanent
+            }
+        },
+    )
+    .await;
+
+    assert_eq!(res, Err(2));
+    assert_eq!(counter.load(Ordering::SeqCst), 3);
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn attempts_run_in_spans_sharing_the_retry_id() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::AttemptSpan;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Metadata, Subscriber};
+
+    /// Records the `is_root`, `retry.id` and `attempt` of every `retry.attempt` span,
+    /// and the span current when the action runs.
+    #[derive(Default)]
+    struct SpanRecorder {
+        spans: Mutex<Vec<(bool, u64, u64)>>,
+        current: Mutex<Vec<u64>>,
+    }
+
+    struct Fields(u64, u64);
+
+    impl Visit for Fields {
+        fn record_u64(&mut self, field: &Field, value: u64) {
+            match field.name() {
+                "retry.id" => self.0 = value,
+                "attempt" => self.1 = value,
+                _ => {}
+            }
+        }
+
+        fn record_debug(&mut self, _: &Field, _: &dyn std::fmt::Debug) {}
+    }
+
+    impl Subscriber for &'static SpanRecorder {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut fields = Fields(0, 0);
+            span.record(&mut fields);
+            let mut spans = self.spans.lock().unwrap();
+            spans.push((span.is_root(), fields.0, fields.1));
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, _: &Event<'_>) {}
+
+        fn enter(&self, span: &Id) {
+            self.current.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    let recorder: &'static SpanRecorder = Box::leak(Box::default());
+    let _guard = tracing::subscriber::set_default(recorder);
+
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(2), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .attempt_span(AttemptSpan::Root)
+    .await;
+    assert_eq!(res, Err(42));
+
+    let spans = recorder.spans.lock().unwrap().clone();
+    assert_eq!(spans.len(), 3);
+    assert!(spans.iter().all(|(is_root, _, _)| *is_root));
+    assert!(spans.iter().all(|(_, retry_id, _)| *retry_id == spans[0].1));
+    assert_eq!(
+        spans
+            .iter()
+            .map(|(_, _, attempt)| *attempt)
+            .collect::<Vec<_>>(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(*recorder.current.lock().unwrap(), vec![1, 2, 3]);
+
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(0), || {
+        future::ready(RetryError::to_transient::<()>(42u64))
+    })
+    .await;
+    assert_eq!(res, Err(42));
+    assert_eq!(recorder.spans.lock().unwrap().len(), 3);
+}
+
+#[tokio::test]
+async fn spawn_fold_keeps_progress_across_attempts() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::Step;
+    let s = FixedInterval::from_millis(50).take(2);
+    let failures = Arc::new(AtomicUsize::new(0));
+    let cloned_failures = failures.clone();
+    let start = tokio::time::Instant::now();
+    let future = Retry::spawn_fold(s, (0, Vec::new()), move |(page, mut items)| {
+        // pages 2 and 4 fail once
+        let failed = (page == 2 && cloned_failures.load(Ordering::SeqCst) == 0)
+            || (page == 4 && cloned_failures.load(Ordering::SeqCst) == 1);
+        future::ready(if failed {
+            cloned_failures.fetch_add(1, Ordering::SeqCst);
+            Step::Err(RetryError::transient(page))
+        } else if page == 5 {
+            Step::Done(items)
+        } else {
+            items.push(page);
+            Step::Progress((page + 1, items))
+        })
+    });
+    let res = future.await;
+
+    assert_eq!(res, Ok(vec![0, 1, 2, 3, 4]));
+    assert_eq!(failures.load(Ordering::SeqCst), 2);
+    // only the two failed steps are followed by a delay
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(100), "{elapsed:?}");
+    assert!(elapsed < Duration::from_millis(150), "{elapsed:?}");
+}
+
+#[tokio::test]
+async fn watchdog_fires_once_past_threshold() {
+    use std::sync::Mutex;
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::Watchdog;
+    let alerts = Arc::new(Mutex::new(Vec::new()));
+    let cloned_alerts = alerts.clone();
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(5), || {
+        future::ready(RetryError::to_transient::<()>(42))
+    })
+    .watchdog(Watchdog::new().attempts(3), move |err, alert| {
+        cloned_alerts.lock().unwrap().push((*err, alert.attempts));
+    })
+    .await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(*alerts.lock().unwrap(), vec![(42, 3)]);
+}
+
+#[tokio::test]
+async fn watchdog_fires_on_elapsed_time() {
+    use tokio_retry2::strategy::FixedInterval;
+    use tokio_retry2::Watchdog;
+    let fired = Arc::new(AtomicUsize::new(0));
+    let cloned_fired = fired.clone();
+    let res = Retry::spawn(FixedInterval::from_millis(20).take(3), || {
+        future::ready(RetryError::to_transient::<()>(42))
+    })
+    .watchdog(
+        Watchdog::new().elapsed(Duration::from_millis(30)),
+        move |_, alert| {
+            assert!(alert.elapsed >= Duration::from_millis(30));
+            cloned_fired.fetch_add(1, Ordering::SeqCst);
+        },
+    )
+    .await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(fired.load(Ordering::SeqCst), 1);
+}
+
+#[cfg(feature = "governor")]
+#[tokio::test]
+async fn rate_limited_retries_wait_for_a_cell() {
+    use governor::{Quota, RateLimiter};
+    use tokio_retry2::strategy::FixedInterval;
+    let limiter = Arc::new(RateLimiter::direct(
+        Quota::with_period(Duration::from_millis(50)).unwrap(),
+    ));
+    let start = std::time::Instant::now();
+    // the first attempt takes the only cell
+    assert!(limiter.check().is_ok());
+    let res = Retry::spawn(FixedInterval::from_millis(1).take(2), || {
+        future::ready(RetryError::to_transient::<()>(42))
+    })
+    .rate_limited(limiter)
+    .await;
+
+    assert_eq!(res, Err(42));
+    // both retries waited for a cell, 50ms apart
+    assert!(start.elapsed() >= Duration::from_millis(95));
+}
+
+/// Records the level and message of every event.
+#[cfg(feature = "tracing")]
+#[derive(Default)]
+struct EventRecorder(std::sync::Mutex<Vec<(tracing::Level, String)>>);
+
+#[cfg(feature = "tracing")]
+impl EventRecorder {
+    /// Installs a recorder as the subscriber of the current thread.
+    fn install() -> (&'static EventRecorder, tracing::subscriber::DefaultGuard) {
+        let recorder: &'static EventRecorder = Box::leak(Box::default());
+        (recorder, tracing::subscriber::set_default(recorder))
+    }
+
+    fn count(&self, level: tracing::Level, message: &str) -> usize {
+        let events = self.0.lock().unwrap();
+        events
+            .iter()
+            .filter(|(event_level, event_message)| {
+                *event_level == level && event_message.contains(message)
+            })
+            .count()
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl tracing::Subscriber for &'static EventRecorder {
+    fn enabled(&self, _: &tracing::Metadata<'_>) -> bool {
+        true
+    }
+
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        struct Message(String);
+
+        impl tracing::field::Visit for Message {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+                if field.name() == "message" {
+                    self.0 = format!("{value:?}");
+                }
+            }
+        }
+
+        let mut message = Message(String::new());
+        event.record(&mut message);
+        self.0
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), message.0));
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn warns_about_unbounded_strategies_unless_unbounded_ok() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+    use tracing::Level;
+
+    let (recorder, _guard) = EventRecorder::install();
+    let flaky = || {
+        let counter = AtomicUsize::new(0);
+        move || match counter.fetch_add(1, Ordering::SeqCst) {
+            0 => future::ready(RetryError::to_transient(())),
+            _ => future::ready(Ok(())),
+        }
+    };
+
+    let res = Retry::spawn(FixedInterval::from_millis(1), flaky()).await;
+    assert_eq!(res, Ok(()));
+    assert_eq!(recorder.count(Level::WARN, "unbounded strategy"), 1);
+
+    let res = Retry::spawn(FixedInterval::from_millis(1), flaky())
+        .unbounded_ok()
+        .await;
+    assert_eq!(res, Ok(()));
+    let res = Retry::spawn(FixedInterval::from_millis(1).max_retries(3), flaky()).await;
+    assert_eq!(res, Ok(()));
+    assert_eq!(recorder.count(Level::WARN, "unbounded strategy"), 1);
+}
+
+#[cfg(feature = "tracing")]
+#[tokio::test]
+async fn only_limit_based_stops_warn() {
+    use tokio_retry2::strategy::{FixedInterval, MaxRetries};
+    use tracing::Level;
+
+    let (recorder, _guard) = EventRecorder::install();
+    let strategy = || FixedInterval::from_millis(1).max_retries(1);
+
+    let res = Retry::spawn(strategy(), || {
+        future::ready(RetryError::to_permanent::<()>(()))
+    })
+    .await;
+    assert_eq!(res, Err(()));
+    let cancelled = Retry::spawn(strategy(), || {
+        future::pending::<Result<(), RetryError<()>>>()
+    });
+    assert!(tokio::time::timeout(Duration::from_millis(5), cancelled)
+        .await
+        .is_err());
+    assert_eq!(recorder.count(Level::WARN, "ending retry"), 0);
+    assert_eq!(recorder.count(Level::DEBUG, "ending retry"), 2);
+
+    let res = Retry::spawn(strategy(), || {
+        future::ready(RetryError::to_transient::<()>(()))
+    })
+    .await;
+    assert_eq!(res, Err(()));
+    assert_eq!(recorder.count(Level::WARN, "ending retry"), 1);
 }
@@ -0,0 +1,39 @@
+#![cfg(feature = "std")]
+
+use std::future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use tokio_retry2::strategy::FixedInterval;
+use tokio_retry2::{retries, Retry, RetryError};
+
+#[tokio::test]
+async fn attempts_just_once_when_disabled() {
+    retries::disable();
+    assert!(!retries::is_enabled());
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let future = Retry::spawn(FixedInterval::from_millis(10).take(3), move || {
+        cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(Err::<(), RetryError<u64>>(RetryError::transient(42)))
+    });
+    let res = future.await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+    retries::enable();
+    assert!(retries::is_enabled());
+
+    let counter = Arc::new(AtomicUsize::new(0));
+    let cloned_counter = counter.clone();
+    let future = Retry::spawn(FixedInterval::from_millis(10).take(3), move || {
+        cloned_counter.fetch_add(1, Ordering::SeqCst);
+        future::ready(Err::<(), RetryError<u64>>(RetryError::transient(42)))
+    });
+    let res = future.await;
+
+    assert_eq!(res, Err(42));
+    assert_eq!(counter.load(Ordering::SeqCst), 4);
+}